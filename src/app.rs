@@ -1,496 +1,6095 @@
 use eframe::egui;
+use std::collections::HashMap;
 use uuid::Uuid;
 
-use crate::models::{Tool, DrawObject, StrokePoint, SelectionMode, SelectionHandle, WhiteboardState};
+use crate::models::{self, Guide, GuideAxis, ObjectProps, Tool, DrawObject, StrokePoint, SelectionMode, SelectionHandle, SelectionCombine, Snapshot, DocumentMetadata, ImageAdjustments, Layer, ColorStyle, UnitScale, Board, GridStyle, WhiteboardState};
 use crate::canvas;
 use crate::latex::LatexRenderer;
+use crate::images::{self, ImageCache};
 use crate::selection;
+use crate::guides;
 use crate::file_io;
+use crate::palette;
+use crate::clock;
+use crate::export::{self, ExportFraming};
+use crate::pdf_import;
+use crate::symbols::{self, Symbol};
+use crate::find;
+use crate::session::{PanelLayout, Session, Viewport, WindowLayout, SESSION_PATH};
+use crate::ocr::{self, OcrBackend, OcrJob, OcrLine};
+use crate::history::History;
+use crate::spatial::SpatialIndex;
+use crate::clipboard::ClipboardContents;
+use crate::templates::{self, Template, TemplateFill, TEMPLATES_PATH};
+use crate::hotkey::{self, GlobalHotkeys, HotkeyAction};
+use crate::loading::{self, DocumentLoad, ObjectFeed};
+use crate::memory::{self, MemoryUsage};
+use crate::profiler::Profiler;
+use crate::ops::{self, DocumentOp, ZOrder};
+use crate::i18n::{self, tr, trf, Language};
+use crate::recipes::{self, ExportRecipe, PostCommand, RecipeBackground, RecipeFormat, RecipeRegion};
+use crate::settings::{EraserMode, LatexQuality, PageOutline, PressureMapping, Settings, ToolbarDock, SETTINGS_PATH, SIZE_PRESET_NAMES};
+
+const HIT_TOLERANCE: f32 = 6.0;
+const SNAP_TOLERANCE: f32 = 8.0;
+const MAX_SAMPLE_GAP: f32 = 12.0;
+const DIM_OPACITY: f32 = 0.3;
+const HIDDEN_PREVIEW_OPACITY: f32 = 0.25;
+const CULL_MARGIN: f32 = 64.0;
+const RULER_SIZE: f32 = 20.0;
+const IDLE_AFTER_SECS: f64 = 60.0;
+const IDLE_REPAINT_SECS: u64 = 5;
+const MAINTENANCE_INTERVAL_SECS: f64 = 10.0;
+const LATEX_WARMUP_BUDGET_MS: u64 = 8;
+const LATEX_WARMUP_IN_FLIGHT: usize = 8;
+const TEXT_EDITING_KEYS: [egui::Key; 11] = [
+    egui::Key::Backspace,
+    egui::Key::Delete,
+    egui::Key::ArrowLeft,
+    egui::Key::ArrowRight,
+    egui::Key::Home,
+    egui::Key::End,
+    egui::Key::Enter,
+    egui::Key::Escape,
+    egui::Key::A,
+    egui::Key::Z,
+    egui::Key::Y,
+];
+const FLATTEN_SCALE: f32 = 2.0;
+const FLATTEN_MAX_PIXELS: f32 = 8192.0;
+const TOOL_HOLD_SECS: f64 = 0.3;
+const MIN_ZOOM: f32 = 0.02;
+const MAX_ZOOM: f32 = 10.0;
+const ZOOM_STEP: f32 = 1.25;
+const ZOOM_PRESETS: [f32; 6] = [0.05, 0.25, 0.5, 1.0, 2.0, 4.0];
+const OVERVIEW_ZOOM: f32 = 0.2;
+const PAN_MARGIN: f32 = 0.5;
+const FREE_SPACE_GAP: f32 = 40.0;
+const FREE_SPACE_ATTEMPTS: usize = 20;
+const LATEX_FONT_LABEL: &str = "LaTeX (math)";
+const PLAIN_TEXT_FONT: &str = "sans-serif";
+const OCR_LINE_SPACING: f32 = 1.4;
 
 pub struct WhiteboardApp {
     pub objects: Vec<DrawObject>,
-    pub undo_stack: Vec<Vec<DrawObject>>,
+    pub history: History,
+    pub boards: Vec<Board>,
+    pub active_board: usize,
+    pub board_histories: HashMap<Uuid, History>,
+    pub renaming_board: Option<usize>,
+    pub viewer: bool,
+    pub hotkeys: Option<GlobalHotkeys>,
+    pub hotkey_status: Option<String>,
+    pub hotkeys_dirty: bool,
+    pub quitting: bool,
+    pub document_load: Option<DocumentLoad>,
+    pub object_feed: Option<ObjectFeed>,
+    pub overlay: bool,
+    pub click_through: bool,
+    pub scratchpad: Option<[f32; 2]>,
+    pub scratchpad_requested: bool,
+    pub paste_count: u32,
+    pub v_press_seen: bool,
+    pub local_author: Uuid,
+    pub snapshots: Vec<Snapshot>,
+    pub metadata: DocumentMetadata,
     pub current_tool: Tool,
+    pub previous_tool: Tool,
+    pub held_tool: Option<(f64, Tool)>,
     pub brush_size: f32,
     pub current_color: egui::Color32,
+    pub auto_formula_color: bool,
     
     pub canvas_offset: egui::Vec2,
     pub canvas_zoom: f32,
+    pub canvas_rotation: f32,
     pub background_color: egui::Color32,
+    pub last_background_color: egui::Color32,
     pub show_grid: bool,
+    pub snap_to_grid: bool,
+    pub show_rulers: bool,
+    pub unit_scale: UnitScale,
+    pub guides: Vec<Guide>,
+    pub layers: Vec<Layer>,
+    pub active_layer: Option<Uuid>,
+    pub show_layers_panel: bool,
+    pub styles: Vec<ColorStyle>,
+    pub active_style: Option<Uuid>,
+    pub show_styles_panel: bool,
+    pub style_undo_pushed: bool,
+    pub page_background_undo_pushed: bool,
+    pub dragging_guide: Option<GuideAxis>,
+    pub show_guides: bool,
+    pub show_guides_panel: bool,
+    pub guide_input: f32,
     
     pub is_drawing: bool,
     pub current_stroke: Vec<StrokePoint>,
     pub draw_start_pos: Option<[f32; 2]>,
+    pub eraser_last_pos: Option<[f32; 2]>,
+    pub eraser_undo_pushed: bool,
     
     pub selected_objects: Vec<Uuid>,
     pub selection_start: Option<[f32; 2]>,
     pub selection_rect: Option<([f32; 2], [f32; 2])>,
+    pub context_menu_pos: Option<[f32; 2]>,
+    pub grab_panning: bool,
     pub selection_mode: SelectionMode,
+    pub selection_combine: SelectionCombine,
     pub selection_drag_start: Option<[f32; 2]>,
     pub selection_original_bounds: Option<([f32; 2], [f32; 2])>,
     pub selection_handle: Option<SelectionHandle>,
     pub selection_saved_objects: Vec<DrawObject>,
+    pub transform_readout: Option<String>,
+    pub show_hidden: bool,
+    pub show_reveal_panel: bool,
+    pub reveal_step_input: u32,
+    pub presenting: bool,
+    pub presentation_step: u32,
+    pub arrange_columns: usize,
+    pub arrange_spacing: f32,
     
     pub editing_text: Option<Uuid>,
     pub text_input: String,
     pub text_cursor_pos: usize,
     
     pub latex_renderer: LatexRenderer,
+    pub image_cache: ImageCache,
+    pub spatial_index: SpatialIndex,
+    pub latex_warmup_queue: Vec<(String, Option<String>, [u8; 4])>,
+    pub latex_warmup_total: usize,
+    pub current_font: Option<String>,
+    pub show_font_panel: bool,
+    pub font_file_path: String,
+    pub font_status: Option<String>,
+    pub show_symbol_picker: bool,
+    pub symbol_search: String,
+    pub show_find_panel: bool,
+    pub show_profiler: bool,
+    pub profiler: Profiler,
+    pub find_query: String,
+    pub replace_text: String,
+    pub find_whole_word: bool,
     
     pub show_latex_dialog: bool,
     pub latex_input: String,
     pub latex_placement_pos: [f32; 2],
+    pub placing_formula: bool,
+    pub cropping: Option<(Uuid, [f32; 2], [f32; 2])>,
+    pub crop_handle: Option<SelectionHandle>,
+    pub split_columns: u32,
+    pub split_rows: u32,
+    pub show_ocr_panel: bool,
+    pub adjusting_image: Option<Uuid>,
+    pub ocr_status: Option<String>,
+    pub ocr_job: Option<OcrJob>,
+    pub measuring_formulas: bool,
     pub show_toolbar: bool,
+    pub summon_palette: bool,
+    pub settings: Settings,
+    pub applied_high_contrast: Option<bool>,
     
     pub save_path: String,
     pub load_path: String,
+    pub document_path: Option<String>,
+    pub session_viewports: HashMap<String, Viewport>,
+    pub window_layout: Option<WindowLayout>,
+    
+    pub show_snapshots: bool,
+    pub snapshot_name: String,
+    pub timelapse_dir: String,
+    pub export_status: Option<String>,
+    pub saves_since_backup: u32,
+    pub backup_status: Option<String>,
+    
+    pub show_pdf_export: bool,
+    pub pdf_path: String,
+    pub pdf_scale: f32,
+    pub pdf_header: bool,
+    pub pdf_page_numbers: bool,
+    pub pdf_frame_order: Vec<Uuid>,
+    pub show_figure_export: bool,
+    pub export_names: HashMap<Uuid, String>,
+    pub show_named_export: bool,
+    pub named_export_dir: String,
+    pub named_export_png: bool,
+    pub named_export_svg: bool,
+    pub figure_path: String,
+    pub figure_scale: f32,
+    pub figure_recolor: bool,
+    pub figure_ink: egui::Color32,
+    pub figure_margin: u32,
+    pub show_pdf_import: bool,
+    pub pdf_import_path: String,
+    pub pdf_import_columns: usize,
+    pub show_image_folder_import: bool,
+    pub image_folder_path: String,
+    pub image_folder_columns: usize,
+    pub import_status: Option<String>,
+    
+    pub canvas_rect: egui::Rect,
+    pub pending_focus_cycle: i32,
     
     pub needs_repaint: bool,
+    pub last_input_time: f64,
+    pub last_maintenance_time: f64,
+    pub memory_usage: MemoryUsage,
+    pub memory_notice: Option<String>,
+    pub session_start: u64,
+    pub known_objects: std::collections::HashSet<Uuid>,
+    pub show_recent_panel: bool,
+    pub show_properties_panel: bool,
+    pub properties_undo_pushed: bool,
+    pub new_metadata_key: String,
+    pub new_metadata_value: String,
+    pub templates: Vec<Template>,
+    pub show_templates_panel: bool,
+    pub template_name: String,
+    pub template_fill: Option<TemplateFill>,
+    pub show_recipes_panel: bool,
+    pub recipe_status: Option<String>,
+    pub highlight_recent: bool,
+    pub recent_minutes: u32,
+    pub recent_this_session: bool,
+    pub recent_include_modified: bool,
+    pub idle: bool,
 }
 
 impl Default for WhiteboardApp {
     fn default() -> Self {
         Self {
             objects: Vec::new(),
-            undo_stack: Vec::new(),
+            history: History::default(),
+            boards: vec![Board::new("Board 1")],
+            active_board: 0,
+            board_histories: HashMap::new(),
+            renaming_board: None,
+            viewer: false,
+            hotkeys: None,
+            hotkey_status: None,
+            hotkeys_dirty: true,
+            quitting: false,
+            document_load: None,
+            object_feed: None,
+            overlay: false,
+            click_through: false,
+            scratchpad: None,
+            scratchpad_requested: false,
+            paste_count: 0,
+            v_press_seen: false,
+            local_author: Uuid::new_v4(),
+            snapshots: Vec::new(),
+            metadata: DocumentMetadata::default(),
             current_tool: Tool::Brush,
+            previous_tool: Tool::Eraser,
+            held_tool: None,
             brush_size: 2.0,
             current_color: egui::Color32::BLACK,
+            auto_formula_color: false,
             canvas_offset: egui::Vec2::ZERO,
             canvas_zoom: 1.0,
+            canvas_rotation: 0.0,
             background_color: egui::Color32::WHITE,
+            last_background_color: egui::Color32::WHITE,
             show_grid: true,
+            snap_to_grid: false,
+            show_rulers: false,
+            unit_scale: UnitScale::default(),
+            guides: Vec::new(),
+            layers: vec![Layer::new("Layer 1")],
+            active_layer: None,
+            show_layers_panel: false,
+            styles: Vec::new(),
+            active_style: None,
+            show_styles_panel: false,
+            style_undo_pushed: false,
+            page_background_undo_pushed: false,
+            dragging_guide: None,
+            show_guides: true,
+            show_guides_panel: false,
+            guide_input: 0.0,
             is_drawing: false,
             current_stroke: Vec::new(),
             draw_start_pos: None,
+            eraser_last_pos: None,
+            eraser_undo_pushed: false,
             selected_objects: Vec::new(),
             selection_start: None,
             selection_rect: None,
+            context_menu_pos: None,
+            grab_panning: false,
             selection_mode: SelectionMode::None,
+            selection_combine: SelectionCombine::Replace,
             selection_drag_start: None,
             selection_original_bounds: None,
             selection_handle: None,
             selection_saved_objects: Vec::new(),
+            transform_readout: None,
+            show_hidden: false,
+            show_reveal_panel: false,
+            reveal_step_input: 1,
+            presenting: false,
+            presentation_step: 0,
+            arrange_columns: 3,
+            arrange_spacing: 20.0,
             editing_text: None,
             text_input: String::new(),
             text_cursor_pos: 0,
             latex_renderer: LatexRenderer::new(),
+            image_cache: ImageCache::new(),
+            spatial_index: SpatialIndex::default(),
+            latex_warmup_queue: Vec::new(),
+            latex_warmup_total: 0,
+            current_font: None,
+            show_font_panel: false,
+            font_file_path: String::new(),
+            font_status: None,
+            show_symbol_picker: false,
+            symbol_search: String::new(),
+            show_find_panel: false,
+            show_profiler: false,
+            profiler: Profiler::default(),
+            find_query: String::new(),
+            replace_text: String::new(),
+            find_whole_word: true,
             show_latex_dialog: false,
             latex_input: String::new(),
             latex_placement_pos: [100.0, 100.0],
+            placing_formula: false,
+            cropping: None,
+            crop_handle: None,
+            split_columns: 2,
+            split_rows: 2,
+            show_ocr_panel: false,
+            adjusting_image: None,
+            ocr_status: None,
+            ocr_job: None,
+            measuring_formulas: false,
             show_toolbar: true,
+            summon_palette: false,
+            settings: Settings::default(),
+            applied_high_contrast: None,
             save_path: "whiteboard.json".to_string(),
             load_path: "whiteboard.json".to_string(),
+            document_path: None,
+            session_viewports: HashMap::new(),
+            window_layout: None,
+            show_snapshots: false,
+            snapshot_name: String::new(),
+            timelapse_dir: "timelapse".to_string(),
+            export_status: None,
+            saves_since_backup: 0,
+            backup_status: None,
+            show_pdf_export: false,
+            pdf_path: "whiteboard.pdf".to_string(),
+            pdf_scale: 2.0,
+            pdf_header: true,
+            pdf_page_numbers: true,
+            pdf_frame_order: Vec::new(),
+            show_figure_export: false,
+            export_names: HashMap::new(),
+            show_named_export: false,
+            named_export_dir: "figures".to_string(),
+            named_export_png: true,
+            named_export_svg: true,
+            figure_path: "figure.png".to_string(),
+            figure_scale: 3.0,
+            figure_recolor: true,
+            figure_ink: egui::Color32::BLACK,
+            figure_margin: 8,
+            show_pdf_import: false,
+            pdf_import_path: "document.pdf".to_string(),
+            pdf_import_columns: 3,
+            show_image_folder_import: false,
+            image_folder_path: String::new(),
+            image_folder_columns: 3,
+            import_status: None,
+            canvas_rect: egui::Rect::NOTHING,
+            pending_focus_cycle: 0,
             needs_repaint: true,
+            last_input_time: 0.0,
+            last_maintenance_time: 0.0,
+            memory_usage: MemoryUsage::default(),
+            memory_notice: None,
+            session_start: clock::now_secs(),
+            known_objects: std::collections::HashSet::new(),
+            show_recent_panel: false,
+            show_properties_panel: false,
+            properties_undo_pushed: false,
+            new_metadata_key: String::new(),
+            new_metadata_value: String::new(),
+            templates: templates::builtin_templates(),
+            show_templates_panel: false,
+            template_name: String::new(),
+            template_fill: None,
+            show_recipes_panel: false,
+            recipe_status: None,
+            highlight_recent: false,
+            recent_minutes: 30,
+            recent_this_session: true,
+            recent_include_modified: false,
+            idle: false,
         }
     }
 }
 
 impl WhiteboardApp {
-    fn push_undo(&mut self) {
-        if self.undo_stack.len() >= 50 {
-            self.undo_stack.remove(0);
+    pub fn new() -> Self {
+        let mut app = Self::default();
+        if let Ok(settings) = file_io::load_settings(SETTINGS_PATH) {
+            app.settings = settings;
+        }
+        i18n::set_language(app.settings.language);
+        if let Ok(templates) = file_io::load_templates(TEMPLATES_PATH) {
+            app.templates.extend(templates);
+        }
+        app.latex_renderer.set_scale_factor(app.settings.latex_quality.scale_factor());
+        for path in &app.settings.font_files {
+            if let Err(e) = app.latex_renderer.add_font_file(path) {
+                eprintln!("{}", e);
+            }
         }
-        self.undo_stack.push(self.objects.clone());
+        app
     }
 
-    fn undo(&mut self) {
-        if let Some(previous_state) = self.undo_stack.pop() {
-            self.objects = previous_state;
-            self.needs_repaint = true;
+    pub fn restore_session(&mut self, window: egui::ViewportBuilder) -> egui::ViewportBuilder {
+        if !self.settings.restore_session {
+            return window;
+        }
+        let Ok(session) = file_io::load_session(SESSION_PATH) else {
+            return window;
+        };
+
+        self.session_viewports = session.viewports;
+        if let Some(path) = session.document {
+            self.open_document(&path);
+        }
+        if let Some(panels) = session.panels {
+            self.show_toolbar = panels.toolbar;
+            self.show_snapshots = panels.snapshots;
+            self.show_guides_panel = panels.guides;
+            self.show_reveal_panel = panels.reveal_steps;
+            self.show_layers_panel = panels.layers;
+            self.show_rulers = panels.rulers;
+        }
+        let Some(layout) = session.window else {
+            return window;
+        };
+        let window = window.with_inner_size(layout.size).with_maximized(layout.maximized);
+        match layout.position {
+            Some(position) => window.with_position(position),
+            None => window,
         }
     }
 
-    fn handle_keyboard_shortcuts(&mut self, ctx: &egui::Context) {
+    fn save_session(&mut self) {
+        if let Some(path) = &self.document_path {
+            self.session_viewports.insert(path.clone(), self.current_viewport());
+        }
+        let session = Session {
+            document: self.document_path.clone(),
+            viewports: self.session_viewports.clone(),
+            window: self.window_layout,
+            panels: Some(PanelLayout {
+                toolbar: self.show_toolbar,
+                snapshots: self.show_snapshots,
+                guides: self.show_guides_panel,
+                reveal_steps: self.show_reveal_panel,
+                layers: self.show_layers_panel,
+                rulers: self.show_rulers,
+            }),
+        };
+        if let Err(e) = file_io::save_session(&session, SESSION_PATH) {
+            eprintln!("Error saving session: {}", e);
+        }
+    }
+
+    fn save_document(&mut self) {
+        let state = self.to_state();
+        if let Err(e) = file_io::save_to_file(&state, &self.save_path) {
+            eprintln!("Error saving: {}", e);
+            return;
+        }
+        self.document_path = Some(self.save_path.clone());
+        self.saves_since_backup += 1;
+        self.backup_if_due();
+    }
+
+    fn save_view_only_copy(&mut self) {
+        let path = std::path::Path::new(&self.save_path);
+        let stem = path.file_stem().map_or_else(|| "board".into(), |stem| stem.to_string_lossy());
+        let target = path.with_file_name(format!("{}-view.json", stem));
+        let mut state = self.to_state();
+        state.metadata.read_only = true;
+        match file_io::save_to_file(&state, &target.to_string_lossy()) {
+            Ok(()) => self.export_status = Some(trf("Wrote view-only copy to {}", &[&target.display()])),
+            Err(e) => eprintln!("Error saving view-only copy: {}", e),
+        }
+    }
+
+    pub fn apply_launch_args(&mut self, args: impl Iterator<Item = String>) {
+        for arg in args {
+            if arg == "--viewer" {
+                self.viewer = true;
+            } else if arg == "--scratchpad" {
+                self.scratchpad_requested = true;
+            } else if let Some(path) = arg.strip_prefix("--replay=") {
+                self.replay_ops(path);
+            } else if !arg.starts_with("--") {
+                self.open_document(&arg);
+            }
+        }
+    }
+
+    fn read_only(&self) -> bool {
+        self.viewer || self.metadata.read_only || self.loading()
+    }
+
+    fn loading(&self) -> bool {
+        self.document_load.is_some() || self.object_feed.is_some()
+    }
+
+    fn handle_viewer_shortcuts(&mut self, ctx: &egui::Context) {
+        if ctx.wants_keyboard_input() {
+            return;
+        }
         ctx.input(|i| {
-            if self.editing_text.is_none() {
-                if i.key_pressed(egui::Key::B) {
-                    self.current_tool = Tool::Brush;
-                    self.needs_repaint = true;
-                }
-                if i.key_pressed(egui::Key::L) {
-                    self.current_tool = Tool::Line;
-                    self.needs_repaint = true;
-                }
-                if i.key_pressed(egui::Key::C) {
-                    self.current_tool = Tool::Circle;
-                    self.needs_repaint = true;
-                }
-                if i.key_pressed(egui::Key::R) {
-                    self.current_tool = Tool::Square;
-                    self.needs_repaint = true;
-                }
-                if i.key_pressed(egui::Key::E) {
-                    self.current_tool = Tool::Eraser;
-                    self.needs_repaint = true;
-                }
-                if i.key_pressed(egui::Key::S) && !i.modifiers.ctrl {
-                    self.current_tool = Tool::Select;
-                    self.needs_repaint = true;
-                }
-                if i.key_pressed(egui::Key::T) {
-                    self.current_tool = Tool::Text;
-                    self.needs_repaint = true;
-                }
-                if i.key_pressed(egui::Key::Z) && i.modifiers.ctrl {
-                    self.undo();
-                }
-                if i.key_pressed(egui::Key::H) {
-                    self.show_toolbar = !self.show_toolbar;
-                    self.needs_repaint = true;
-                }
+            if i.key_pressed(egui::Key::Home) {
+                self.go_to_origin();
+            }
+            if i.key_pressed(egui::Key::F) && i.modifiers.command {
+                self.show_find_panel = !self.show_find_panel;
+            }
+            if i.key_pressed(egui::Key::H) {
+                self.show_toolbar = !self.show_toolbar;
+                self.needs_repaint = true;
+            }
+            if i.key_pressed(egui::Key::Escape) {
+                self.selected_objects.clear();
+                self.needs_repaint = true;
             }
         });
+        self.nudge_view(ctx);
     }
 
-    fn render_toolbar(&mut self, ctx: &egui::Context) {
+    fn render_viewer_toolbar(&mut self, ctx: &egui::Context) {
         if !self.show_toolbar {
             return;
         }
-
-        egui::TopBottomPanel::top("top_panel").show(ctx, |ui| {
-            ui.horizontal(|ui| {
-                ui.label("Tool:");
-                
-                if ui.selectable_label(self.current_tool == Tool::Brush, "Brush (B)").clicked() {
-                    self.current_tool = Tool::Brush;
-                    self.needs_repaint = true;
-                }
-                if ui.selectable_label(self.current_tool == Tool::Line, "Line (L)").clicked() {
-                    self.current_tool = Tool::Line;
-                    self.needs_repaint = true;
-                }
-                if ui.selectable_label(self.current_tool == Tool::Circle, "Circle (C)").clicked() {
-                    self.current_tool = Tool::Circle;
-                    self.needs_repaint = true;
-                }
-                if ui.selectable_label(self.current_tool == Tool::Square, "Square (R)").clicked() {
-                    self.current_tool = Tool::Square;
-                    self.needs_repaint = true;
-                }
-                if ui.selectable_label(self.current_tool == Tool::Eraser, "Eraser (E)").clicked() {
-                    self.current_tool = Tool::Eraser;
-                    self.needs_repaint = true;
-                }
-                if ui.selectable_label(self.current_tool == Tool::Select, "Select (S)").clicked() {
-                    self.current_tool = Tool::Select;
-                    self.needs_repaint = true;
-                }
-                if ui.selectable_label(self.current_tool == Tool::Text, "Text (T)").clicked() {
-                    self.current_tool = Tool::Text;
-                    self.needs_repaint = true;
+        egui::TopBottomPanel::top("viewer_panel").show(ctx, |ui| {
+            ui.horizontal_wrapped(|ui| {
+                ui.strong(tr("View only"));
+                if !self.metadata.title.is_empty() {
+                    ui.label(&self.metadata.title);
                 }
-            
                 ui.separator();
-                
-                ui.label("Brush Size:");
-                if ui.add(egui::Slider::new(&mut self.brush_size, 1.0..=20.0).text("px")).changed() {
-                    self.needs_repaint = true;
+                if ui.button(tr("Go to origin (Home)")).clicked() {
+                    self.go_to_origin();
                 }
-                
-                ui.separator();
-                
-                ui.label("Color:");
-                if egui::color_picker::color_edit_button_srgba(
-                    ui,
-                    &mut self.current_color,
-                    egui::color_picker::Alpha::Opaque,
-                ).changed() {
-                    self.needs_repaint = true;
+                if ui.selectable_label(self.show_find_panel, tr("Find (Ctrl+F)")).clicked() {
+                    self.show_find_panel = !self.show_find_panel;
                 }
-                
-                ui.separator();
-                
-                if ui.button("Undo (Ctrl+Z)").clicked() {
-                    self.undo();
+                if ui.button(tr("Start presentation")).on_hover_text(tr("Right arrow or Space reveals the next step, Esc exits")).clicked() {
+                    self.start_presentation();
                 }
-                
                 ui.separator();
-                
-                if ui.button("Save").clicked() {
-                    let state = WhiteboardState {
-                        objects: self.objects.clone(),
-                    };
-                    if let Err(e) = file_io::save_to_file(&state, &self.save_path) {
-                        eprintln!("Error saving: {}", e);
-                    }
-                }
-                
-                if ui.button("Load").clicked() {
-                    if let Ok(state) = file_io::load_from_file(&self.load_path) {
-                        self.objects = state.objects;
-                        self.needs_repaint = true;
-                    } else {
-                        eprintln!("Error loading file");
-                    }
+                ui.label(tr("Press H to toggle toolbar"));
+            });
+        });
+    }
+
+    fn backup_if_due(&mut self) {
+        let now = clock::now_secs();
+        let day = clock::date_stamp(now);
+        let every = self.settings.backup_every_saves;
+        let count_due = every > 0 && self.saves_since_backup >= every;
+        let daily_due = self.settings.backup_daily && !file_io::has_backup_for_day(&self.save_path, &self.settings.backup_dir, &day);
+        if !count_due && !daily_due {
+            return;
+        }
+        self.backup_status = Some(
+            match file_io::backup_document(&self.save_path, &self.settings.backup_dir, &day, &clock::time_stamp(now)) {
+                Ok(target) => {
+                    self.saves_since_backup = 0;
+                    trf("Backed up to {}", &[&target.display()])
                 }
-                
+                Err(e) => trf("Backup failed: {}", &[&e]),
+            },
+        );
+    }
+
+    fn backup_menu(&mut self, ui: &mut egui::Ui) {
+        ui.menu_button(tr("Backups"), |ui| {
+            let mut changed = false;
+            ui.horizontal(|ui| {
+                ui.label(tr("Folder:"));
+                changed |= ui.text_edit_singleline(&mut self.settings.backup_dir).lost_focus();
+            });
+            ui.horizontal(|ui| {
+                ui.label(tr("Every"));
+                changed |= ui.add(egui::DragValue::new(&mut self.settings.backup_every_saves).range(0..=1000))
+                    .on_hover_text(tr("0 turns off count-based backups"))
+                    .changed();
+                ui.label(tr("saves"));
+            });
+            changed |= ui.checkbox(&mut self.settings.backup_daily, tr("On the first save of each day")).changed();
+            ui.label(tr("Backups go into one dated subfolder per day and are never rotated away."));
+            if let Some(status) = &self.backup_status {
                 ui.separator();
-                
-                ui.label("Background:");
-                egui::ComboBox::from_id_salt("bg_preset")
-                    .selected_text("Preset")
-                    .show_ui(ui, |ui| {
-                        if ui.selectable_label(false, "White").clicked() {
-                            self.background_color = egui::Color32::WHITE;
-                            self.needs_repaint = true;
-                        }
-                        if ui.selectable_label(false, "Light Gray").clicked() {
-                            self.background_color = egui::Color32::from_rgb(240, 240, 240);
-                            self.needs_repaint = true;
-                        }
-                        if ui.selectable_label(false, "Dark Gray").clicked() {
-                            self.background_color = egui::Color32::from_rgb(40, 40, 40);
-                            self.needs_repaint = true;
+                ui.label(status);
+            }
+            if changed {
+                self.save_settings();
+            }
+        });
+    }
+
+    fn show_board_view(&mut self, background: Option<[u8; 4]>, camera: Option<Viewport>) {
+        if let Some([r, g, b, a]) = background {
+            self.background_color = egui::Color32::from_rgba_unmultiplied(r, g, b, a);
+            self.last_background_color = self.background_color;
+        }
+        if let Some(camera) = camera {
+            self.canvas_offset = egui::vec2(camera.offset[0], camera.offset[1]);
+            self.canvas_zoom = camera.zoom;
+            self.canvas_rotation = camera.rotation;
+        }
+    }
+
+    fn switch_board(&mut self, index: usize) {
+        if index == self.active_board || index >= self.boards.len() {
+            return;
+        }
+        self.editing_text = None;
+        let viewport = self.current_viewport();
+        let background = self.background_color.to_array();
+        let current = &mut self.boards[self.active_board];
+        current.objects = std::mem::take(&mut self.objects);
+        current.background = Some(background);
+        current.camera = Some(viewport);
+        let history = std::mem::take(&mut self.history);
+        self.board_histories.insert(current.id, history);
+
+        self.active_board = index;
+        let target = &mut self.boards[index];
+        self.objects = std::mem::take(&mut target.objects);
+        let (id, background, camera) = (target.id, target.background, target.camera);
+        self.history = self.board_histories.remove(&id).unwrap_or_else(|| {
+            let mut history = History::default();
+            history.reset(&self.objects, &self.layers, &self.styles);
+            history
+        });
+        self.show_board_view(background, camera);
+        self.sync_layers();
+        selection::sync_instances(&mut self.objects);
+        self.known_objects = self.objects.iter().map(|obj| obj.id()).collect();
+        self.selected_objects.clear();
+        self.measure_formulas();
+        self.queue_latex_warmup();
+        self.needs_repaint = true;
+    }
+
+    fn add_board(&mut self) {
+        let mut number = self.boards.len() + 1;
+        while self.boards.iter().any(|board| board.name == trf("Board {}", &[&number])) {
+            number += 1;
+        }
+        self.boards.push(Board::new(trf("Board {}", &[&number])));
+        self.switch_board(self.boards.len() - 1);
+        self.renaming_board = Some(self.active_board);
+    }
+
+    fn delete_board(&mut self, index: usize) {
+        if self.boards.len() <= 1 || index >= self.boards.len() {
+            return;
+        }
+        if index == self.active_board {
+            self.switch_board(if index == 0 { 1 } else { index - 1 });
+        }
+        let removed = self.boards.remove(index);
+        self.board_histories.remove(&removed.id);
+        if self.active_board > index {
+            self.active_board -= 1;
+        }
+        self.renaming_board = None;
+    }
+
+    fn render_board_tabs(&mut self, ctx: &egui::Context) {
+        if !self.show_toolbar || self.presenting {
+            return;
+        }
+        if self.loading() {
+            return;
+        }
+        let read_only = self.read_only();
+        let mut switch_to = None;
+        let mut delete = None;
+        let mut add = false;
+        egui::TopBottomPanel::bottom("board_tabs").show(ctx, |ui| {
+            ui.horizontal_wrapped(|ui| {
+                for index in 0..self.boards.len() {
+                    if self.renaming_board == Some(index) {
+                        let response = ui.add(egui::TextEdit::singleline(&mut self.boards[index].name).desired_width(120.0));
+                        if !response.has_focus() && !response.lost_focus() {
+                            response.request_focus();
                         }
-                        if ui.selectable_label(false, "Black").clicked() {
-                            self.background_color = egui::Color32::BLACK;
-                            self.needs_repaint = true;
+                        if response.lost_focus() {
+                            self.renaming_board = None;
                         }
-                        if ui.selectable_label(false, "Sepia").clicked() {
-                            self.background_color = egui::Color32::from_rgb(255, 245, 230);
-                            self.needs_repaint = true;
+                        continue;
+                    }
+                    let response = ui.selectable_label(index == self.active_board, &self.boards[index].name);
+                    if response.clicked() {
+                        switch_to = Some(index);
+                    }
+                    if read_only {
+                        continue;
+                    }
+                    if response.double_clicked() {
+                        self.renaming_board = Some(index);
+                    }
+                    response.context_menu(|ui| {
+                        if ui.button(tr("Rename")).clicked() {
+                            self.renaming_board = Some(index);
+                            ui.close_menu();
                         }
-                        if ui.selectable_label(false, "Dark Blue").clicked() {
-                            self.background_color = egui::Color32::from_rgb(20, 30, 40);
-                            self.needs_repaint = true;
+                        if ui.add_enabled(self.boards.len() > 1, egui::Button::new(tr("Delete board"))).clicked() {
+                            delete = Some(index);
+                            ui.close_menu();
                         }
                     });
-                
-                if egui::color_picker::color_edit_button_srgba(
-                    ui,
-                    &mut self.background_color,
-                    egui::color_picker::Alpha::Opaque,
-                ).changed() {
-                    self.needs_repaint = true;
-                }
-                
-                if ui.checkbox(&mut self.show_grid, "Grid").changed() {
-                    self.needs_repaint = true;
                 }
-                
-                ui.separator();
-                
-                ui.label(format!("Zoom: {:.0}%", self.canvas_zoom * 100.0));
-                
-                if ui.button("Clear All").clicked() {
-                    self.push_undo();
-                    self.objects.clear();
-                    self.needs_repaint = true;
+                if !read_only && ui.button("+").on_hover_text(tr("Add a board")).clicked() {
+                    add = true;
                 }
-                
-                ui.separator();
-                ui.label("Press H to toggle toolbar");
             });
         });
+
+        if let Some(index) = switch_to {
+            self.switch_board(index);
+        }
+        if let Some(index) = delete {
+            self.delete_board(index);
+        }
+        if add {
+            self.add_board();
+        }
     }
 
-    fn handle_text_editing(&mut self, ctx: &egui::Context) {
-        if let Some(editing_id) = self.editing_text {
-            ctx.input(|i| {
-                for event in &i.events {
-                    match event {
-                        egui::Event::Text(text) => {
-                            self.text_input.insert_str(self.text_cursor_pos, text);
-                            self.text_cursor_pos += text.len();
-                            self.needs_repaint = true;
-                        }
-                        egui::Event::Paste(text) => {
-                            self.text_input.insert_str(self.text_cursor_pos, text);
-                            self.text_cursor_pos += text.len();
-                            self.needs_repaint = true;
-                        }
-                        egui::Event::Key { key, pressed: true, modifiers: _, .. } => {
-                            match key {
-                                egui::Key::Backspace => {
-                                    if self.text_cursor_pos > 0 {
-                                        self.text_input.remove(self.text_cursor_pos - 1);
-                                        self.text_cursor_pos -= 1;
-                                        self.needs_repaint = true;
-                                    }
-                                }
-                                egui::Key::Delete => {
-                                    if self.text_cursor_pos < self.text_input.len() {
-                                        self.text_input.remove(self.text_cursor_pos);
-                                        self.needs_repaint = true;
-                                    }
-                                }
-                                egui::Key::ArrowLeft => {
-                                    if self.text_cursor_pos > 0 {
-                                        self.text_cursor_pos -= 1;
-                                        self.needs_repaint = true;
-                                    }
-                                }
-                                egui::Key::ArrowRight => {
-                                    if self.text_cursor_pos < self.text_input.len() {
-                                        self.text_cursor_pos += 1;
-                                        self.needs_repaint = true;
-                                    }
-                                }
-                                egui::Key::Enter => {
-                                    if let Some(DrawObject::LatexFormula { formula, cached_size, .. }) = 
-                                        self.objects.iter_mut().find(|o| o.id() == editing_id) {
-                                        *formula = self.text_input.clone();
-                                        *cached_size = None;
-                                    }
-                                    self.editing_text = None;
-                                    self.text_input.clear();
-                                    self.text_cursor_pos = 0;
-                                    self.needs_repaint = true;
-                                }
-                                egui::Key::Escape => {
-                                    self.editing_text = None;
-                                    self.text_input.clear();
-                                    self.text_cursor_pos = 0;
-                                    self.needs_repaint = true;
-                                }
-                                _ => {}
-                            }
-                        }
-                        _ => {}
-                    }
-                }
-            });
+    fn current_viewport(&self) -> Viewport {
+        Viewport {
+            offset: [self.canvas_offset.x, self.canvas_offset.y],
+            zoom: self.canvas_zoom,
+            rotation: self.canvas_rotation,
         }
     }
 
-    fn render_latex_dialog(&mut self, ctx: &egui::Context) {
-        if self.show_latex_dialog {
-            egui::Window::new("Add LaTeX Formula")
-                .collapsible(false)
-                .show(ctx, |ui| {
-                    ui.label("Enter LaTeX formula:");
-                    ui.text_edit_singleline(&mut self.latex_input);
-                    ui.horizontal(|ui| {
-                        if ui.button("Add").clicked() {
-                            let formula = DrawObject::LatexFormula {
-                                id: Uuid::new_v4(),
-                                pos: self.latex_placement_pos,
-                                formula: self.latex_input.clone(),
-                                color: [
-                                    self.current_color.r(),
-                                    self.current_color.g(),
-                                    self.current_color.b(),
-                                    self.current_color.a(),
-                                ],
-                                cached_size: None,
-                            };
-                            self.objects.push(formula);
-                            self.latex_input.clear();
-                            self.show_latex_dialog = false;
-                            self.needs_repaint = true;
-                        }
-                        if ui.button("Cancel").clicked() {
-                            self.show_latex_dialog = false;
-                        }
-                    });
-                });
+    fn open_document(&mut self, path: &str) {
+        if self.loading() {
+            return;
+        }
+        let large = std::fs::metadata(path).is_ok_and(|meta| meta.len() >= loading::LARGE_DOCUMENT_BYTES);
+        if large {
+            match DocumentLoad::start(path) {
+                Ok(load) => self.document_load = Some(load),
+                Err(e) => eprintln!("Error loading {}: {}", path, e),
+            }
+            self.needs_repaint = true;
+            return;
+        }
+        let state = match file_io::load_from_file(path) {
+            Ok(state) => state,
+            Err(e) => {
+                eprintln!("Error loading {}: {}", path, e);
+                return;
+            }
+        };
+        self.finish_open(path, state);
+    }
+
+    fn finish_open(&mut self, path: &str, state: WhiteboardState) {
+        if let Some(current) = &self.document_path {
+            self.session_viewports.insert(current.clone(), self.current_viewport());
         }
+        self.apply_state(state);
+        self.document_path = Some(path.to_string());
+        if let Some(viewport) = self.session_viewports.get(path) {
+            self.canvas_offset = egui::vec2(viewport.offset[0], viewport.offset[1]);
+            self.canvas_zoom = viewport.zoom;
+            self.canvas_rotation = viewport.rotation;
+        }
+    }
+
+    fn advance_document_load(&mut self) {
+        if let Some(result) = self.document_load.as_ref().and_then(DocumentLoad::poll) {
+            let path = self.document_load.take().map(|load| load.path).unwrap_or_default();
+            match result {
+                Ok(mut state) => {
+                    let objects = std::mem::take(&mut state.objects);
+                    self.finish_open(&path, state);
+                    let (min, max) = canvas::visible_canvas_bounds(self.canvas_rect, self.canvas_offset, self.canvas_zoom, self.canvas_rotation);
+                    let focus = [(min[0] + max[0]) / 2.0, (min[1] + max[1]) / 2.0];
+                    self.object_feed = Some(ObjectFeed::new(objects, focus));
+                }
+                Err(e) => eprintln!("Error loading {}: {}", path, e),
+            }
+        }
+        if let Some(feed) = &mut self.object_feed {
+            feed.feed(&mut self.objects, loading::OBJECTS_PER_FRAME);
+            if feed.is_done() {
+                self.object_feed = None;
+                self.settle_objects();
+            }
+        }
+        if self.loading() {
+            self.needs_repaint = true;
+        }
+    }
+
+    fn render_profiler(&mut self, ctx: &egui::Context) {
+        egui::Area::new(egui::Id::new("profiler"))
+            .pivot(egui::Align2::RIGHT_TOP)
+            .fixed_pos(self.canvas_rect.right_top() + egui::vec2(-12.0, 12.0))
+            .show(ctx, |ui| {
+                egui::Frame::popup(ui.style()).show(ui, |ui| {
+                    egui::Grid::new("profiler_rows").num_columns(2).show(ui, |ui| {
+                        for (label, value) in self.profiler.rows() {
+                            ui.label(label);
+                            ui.monospace(value);
+                            ui.end_row();
+                        }
+                    });
+                    ui.horizontal(|ui| {
+                        if ui.button(tr("Copy report")).on_hover_text(tr("Copy these numbers to paste into an issue")).clicked() {
+                            ctx.copy_text(self.profiler.report());
+                        }
+                        if ui.button(tr("Close")).clicked() {
+                            self.show_profiler = false;
+                        }
+                    });
+                });
+            });
+    }
+
+    fn render_memory_notice(&mut self, ctx: &egui::Context) {
+        let Some(notice) = &self.memory_notice else {
+            return;
+        };
+        let mut dismissed = false;
+        egui::Area::new(egui::Id::new("memory_notice"))
+            .pivot(egui::Align2::CENTER_BOTTOM)
+            .fixed_pos(self.canvas_rect.center_bottom() - egui::vec2(0.0, 12.0))
+            .show(ctx, |ui| {
+                egui::Frame::popup(ui.style()).show(ui, |ui| {
+                    ui.horizontal(|ui| {
+                        ui.label(notice);
+                        dismissed = ui.button(tr("OK")).clicked();
+                    });
+                });
+            });
+        if dismissed {
+            self.memory_notice = None;
+        }
+    }
+
+    fn render_load_progress(&self, ctx: &egui::Context) {
+        let (text, fraction) = if let Some(load) = &self.document_load {
+            let name = std::path::Path::new(&load.path).file_name().map_or_else(|| load.path.clone(), |name| name.to_string_lossy().into_owned());
+            (trf("Reading {}…", &[&name]), load.fraction())
+        } else if let Some(feed) = &self.object_feed {
+            (trf("Placing objects {} / {}", &[&feed.loaded(), &feed.total]), feed.loaded() as f32 / feed.total.max(1) as f32)
+        } else {
+            return;
+        };
+        egui::Area::new(egui::Id::new("load_progress"))
+            .pivot(egui::Align2::CENTER_TOP)
+            .fixed_pos(self.canvas_rect.center_top() + egui::vec2(0.0, 12.0))
+            .interactable(false)
+            .show(ctx, |ui| {
+                egui::Frame::popup(ui.style()).show(ui, |ui| {
+                    ui.label(text);
+                    ui.add(egui::ProgressBar::new(fraction).desired_width(240.0).show_percentage());
+                });
+            });
+    }
+
+    fn track_window_layout(&mut self, ctx: &egui::Context) {
+        if self.scratchpad.is_some() || self.overlay {
+            return;
+        }
+        let layout = ctx.input(|i| {
+            let info = i.viewport();
+            let maximized = info.maximized.unwrap_or(false);
+            let size = info.inner_rect.map(|rect| [rect.width(), rect.height()]);
+            let position = info.outer_rect.map(|rect| [rect.min.x, rect.min.y]);
+            size.map(|size| WindowLayout { position, size, maximized })
+        });
+        if let Some(layout) = layout {
+            match (&mut self.window_layout, layout.maximized) {
+                (Some(previous), true) => previous.maximized = true,
+                (previous, _) => *previous = Some(layout),
+            }
+        }
+    }
+
+    fn hotkey_active(&self, action: HotkeyAction) -> bool {
+        self.hotkeys.as_ref().is_some_and(|hotkeys| hotkeys.is_active(action))
+    }
+
+    fn quit(&mut self, ctx: &egui::Context) {
+        self.quitting = true;
+        ctx.send_viewport_cmd(egui::ViewportCommand::Close);
+    }
+
+    fn handle_global_hotkeys(&mut self, ctx: &egui::Context) {
+        if std::mem::take(&mut self.hotkeys_dirty) {
+            let wanted = [
+                (HotkeyAction::Summon, self.settings.stay_resident.then_some(&self.settings.quick_capture_hotkey)),
+                (HotkeyAction::ClickThrough, self.overlay.then_some(&self.settings.click_through_hotkey)),
+            ];
+            if self.hotkeys.is_none() && self.hotkey_status.is_none() && wanted.iter().any(|(_, text)| text.is_some()) {
+                match GlobalHotkeys::new(ctx) {
+                    Ok(hotkeys) => self.hotkeys = Some(hotkeys),
+                    Err(e) => self.hotkey_status = Some(e),
+                }
+            }
+            if let Some(hotkeys) = &mut self.hotkeys {
+                let errors: Vec<String> = wanted
+                    .into_iter()
+                    .filter_map(|(action, text)| hotkeys.set(action, text.map(String::as_str)).err())
+                    .collect();
+                self.hotkey_status = (!errors.is_empty()).then(|| errors.join("\n"));
+            }
+        }
+
+        if ctx.input_mut(|i| i.consume_key(egui::Modifiers::COMMAND, egui::Key::Q)) {
+            self.quit(ctx);
+        }
+        if !self.quitting && self.hotkey_active(HotkeyAction::Summon) && ctx.input(|i| i.viewport().close_requested()) {
+            self.save_session();
+            ctx.send_viewport_cmd(egui::ViewportCommand::CancelClose);
+            ctx.send_viewport_cmd(egui::ViewportCommand::Minimized(true));
+        }
+        let fired = self.hotkeys.as_ref().map(GlobalHotkeys::take_fired).unwrap_or_default();
+        for action in fired {
+            match action {
+                HotkeyAction::Summon => {
+                    ctx.send_viewport_cmd(egui::ViewportCommand::Minimized(false));
+                    ctx.send_viewport_cmd(egui::ViewportCommand::Visible(true));
+                    ctx.send_viewport_cmd(egui::ViewportCommand::Focus);
+                    if self.settings.scratchpad_on_summon && !self.overlay {
+                        self.scratchpad_requested = true;
+                    }
+                }
+                HotkeyAction::ClickThrough if self.overlay => self.set_click_through(ctx, !self.click_through),
+                HotkeyAction::ClickThrough => {}
+            }
+        }
+        if std::mem::take(&mut self.scratchpad_requested) && self.scratchpad.is_none() {
+            self.toggle_scratchpad(ctx);
+        }
+    }
+
+    fn toggle_overlay(&mut self, ctx: &egui::Context) {
+        if self.overlay {
+            self.set_click_through(ctx, false);
+            ctx.send_viewport_cmd(egui::ViewportCommand::Decorations(true));
+            ctx.send_viewport_cmd(egui::ViewportCommand::WindowLevel(egui::WindowLevel::Normal));
+            ctx.send_viewport_cmd(egui::ViewportCommand::Maximized(self.window_layout.as_ref().is_some_and(|layout| layout.maximized)));
+        } else {
+            if self.scratchpad.is_some() {
+                self.toggle_scratchpad(ctx);
+            }
+            ctx.send_viewport_cmd(egui::ViewportCommand::Decorations(false));
+            ctx.send_viewport_cmd(egui::ViewportCommand::WindowLevel(egui::WindowLevel::AlwaysOnTop));
+            ctx.send_viewport_cmd(egui::ViewportCommand::Maximized(true));
+        }
+        self.overlay = !self.overlay;
+        self.hotkeys_dirty = true;
+        self.needs_repaint = true;
+    }
+
+    fn set_click_through(&mut self, ctx: &egui::Context, enabled: bool) {
+        self.click_through = enabled;
+        ctx.send_viewport_cmd(egui::ViewportCommand::MousePassthrough(enabled));
+        self.needs_repaint = true;
+    }
+
+    fn toggle_scratchpad(&mut self, ctx: &egui::Context) {
+        match self.scratchpad.take() {
+            Some(size) => {
+                ctx.send_viewport_cmd(egui::ViewportCommand::WindowLevel(egui::WindowLevel::Normal));
+                ctx.send_viewport_cmd(egui::ViewportCommand::InnerSize(egui::vec2(size[0], size[1])));
+            }
+            None => {
+                let size = self.window_layout.as_ref().map_or([1280.0, 720.0], |layout| layout.size);
+                self.scratchpad = Some(size);
+                ctx.send_viewport_cmd(egui::ViewportCommand::WindowLevel(egui::WindowLevel::AlwaysOnTop));
+                ctx.send_viewport_cmd(egui::ViewportCommand::InnerSize(egui::vec2(hotkey::SCRATCHPAD_SIZE[0], hotkey::SCRATCHPAD_SIZE[1])));
+            }
+        }
+        self.needs_repaint = true;
+    }
+
+    fn push_undo(&mut self) {
+        self.history.record(&mut self.objects, &self.layers, &self.styles, clock::now_secs(), self.local_author);
+    }
+
+    fn current_page(&self) -> Option<Uuid> {
+        let selected = self.selected_objects
+            .first()
+            .and_then(|id| self.objects.iter().find(|obj| obj.id() == *id));
+        if let Some(obj) = selected {
+            return selection::page_of(&self.objects, obj);
+        }
+        let (view_min, view_max) = canvas::visible_canvas_bounds(self.canvas_rect, self.canvas_offset, self.canvas_zoom, self.canvas_rotation);
+        selection::page_at(&self.objects, [(view_min[0] + view_max[0]) / 2.0, (view_min[1] + view_max[1]) / 2.0])
+    }
+
+    fn undo(&mut self) {
+        let page = self.current_page();
+        if self.history.undo(&mut self.objects, &mut self.layers, &mut self.styles, page, self.local_author) {
+            self.after_history_step();
+        }
+    }
+
+    fn redo(&mut self) {
+        let page = self.current_page();
+        if self.history.redo(&mut self.objects, &mut self.layers, &mut self.styles, page, self.local_author) {
+            self.after_history_step();
+        }
+    }
+
+    fn after_history_step(&mut self) {
+        self.sync_layers();
+        self.measure_formulas();
+        let objects = &self.objects;
+        self.selected_objects.retain(|id| objects.iter().any(|obj| obj.id() == *id));
+        self.needs_repaint = true;
+    }
+
+    fn move_selection_to_page(&mut self, page: Uuid) {
+        let ids: Vec<Uuid> = self.objects
+            .iter()
+            .filter(|obj| self.selected_objects.contains(&obj.id()) && !matches!(obj, DrawObject::Frame { .. }))
+            .map(|obj| obj.id())
+            .collect();
+        let Some(DrawObject::Frame { min: target_min, .. }) = self.objects.iter().find(|obj| obj.id() == page) else {
+            return;
+        };
+        let Some((selection_min, _)) = selection::get_selection_bounds(&self.objects, &ids) else {
+            return;
+        };
+        let source_min = self.objects
+            .iter()
+            .find(|obj| obj.id() == ids[0])
+            .and_then(|obj| selection::page_of(&self.objects, obj))
+            .and_then(|source| match self.objects.iter().find(|obj| obj.id() == source) {
+                Some(DrawObject::Frame { min, .. }) => Some(*min),
+                _ => None,
+            });
+        let offset = match source_min {
+            Some(source_min) => [target_min[0] - source_min[0], target_min[1] - source_min[1]],
+            None => [target_min[0] + 20.0 - selection_min[0], target_min[1] + 20.0 - selection_min[1]],
+        };
+
+        self.push_undo();
+        self.apply_op(DocumentOp::Transform { ids: ids.clone(), scale: [1.0, 1.0], rotation: 0.0, translation: offset, pivot: [0.0, 0.0] });
+        self.selected_objects = ids;
+        self.scroll_object_into_view(page);
+        self.needs_repaint = true;
+    }
+
+    fn rotate_view_about(&mut self, pivot: egui::Pos2, angle: f32) {
+        let rot = egui::emath::Rot2::from_angle(angle);
+        self.canvas_offset = pivot.to_vec2() + rot * (self.canvas_offset - pivot.to_vec2());
+        self.canvas_rotation = (self.canvas_rotation + angle).rem_euclid(std::f32::consts::TAU);
+        self.needs_repaint = true;
+    }
+
+    fn zoom_view_about(&mut self, pivot: egui::Pos2, zoom: f32) {
+        let old_zoom = self.canvas_zoom;
+        self.canvas_zoom = zoom.clamp(MIN_ZOOM, MAX_ZOOM);
+        let zoom_ratio = self.canvas_zoom / old_zoom;
+        self.canvas_offset = pivot.to_vec2() + (self.canvas_offset - pivot.to_vec2()) * zoom_ratio;
+        self.needs_repaint = true;
+    }
+
+    fn render_zoom_controls(&mut self, ctx: &egui::Context) {
+        let left = if self.show_rulers { RULER_SIZE } else { 0.0 };
+        let center = self.canvas_rect.center();
+        egui::Area::new(egui::Id::new("zoom_controls"))
+            .pivot(egui::Align2::LEFT_BOTTOM)
+            .fixed_pos(self.canvas_rect.left_bottom() + egui::vec2(left + 12.0, -12.0))
+            .show(ctx, |ui| {
+                egui::Frame::popup(ui.style()).show(ui, |ui| {
+                    ui.horizontal(|ui| {
+                        if ui.button("−").on_hover_text(tr("Zoom out")).clicked() {
+                            self.zoom_view_about(center, self.canvas_zoom / ZOOM_STEP);
+                        }
+                        egui::ComboBox::from_id_salt("zoom_presets")
+                            .width(64.0)
+                            .selected_text(format!("{:.0}%", self.canvas_zoom * 100.0))
+                            .show_ui(ui, |ui| {
+                                for preset in ZOOM_PRESETS {
+                                    let selected = (self.canvas_zoom - preset).abs() < 0.001;
+                                    if ui.selectable_label(selected, format!("{:.0}%", preset * 100.0)).clicked() {
+                                        self.zoom_view_about(center, preset);
+                                    }
+                                }
+                            });
+                        if ui.button("+").on_hover_text(tr("Zoom in")).clicked() {
+                            self.zoom_view_about(center, self.canvas_zoom * ZOOM_STEP);
+                        }
+                        if ui.button("100%").on_hover_text(tr("Reset zoom")).clicked() {
+                            self.zoom_view_about(center, 1.0);
+                        }
+                    });
+                });
+            });
+    }
+
+    fn in_overview(&self) -> bool {
+        self.settings.overview_when_zoomed_out && self.canvas_zoom < OVERVIEW_ZOOM
+    }
+
+    fn render_overview_legend(&self, ctx: &egui::Context) {
+        if !self.in_overview() {
+            return;
+        }
+        egui::Area::new(egui::Id::new("overview_legend"))
+            .pivot(egui::Align2::RIGHT_BOTTOM)
+            .fixed_pos(self.canvas_rect.right_bottom() + egui::vec2(-12.0, -12.0))
+            .interactable(false)
+            .show(ctx, |ui| {
+                egui::Frame::popup(ui.style()).show(ui, |ui| {
+                    ui.label(egui::RichText::new(tr("Overview")).strong());
+                    for (label, [r, g, b]) in canvas::OVERVIEW_KINDS {
+                        ui.horizontal(|ui| {
+                            let (swatch, _) = ui.allocate_exact_size(egui::vec2(12.0, 12.0), egui::Sense::hover());
+                            ui.painter().rect_filled(swatch, 2.0, egui::Color32::from_rgb(r, g, b));
+                            ui.label(label);
+                        });
+                    }
+                });
+            });
+    }
+
+    fn to_state(&self) -> WhiteboardState {
+        WhiteboardState {
+            objects: self.objects.clone(),
+            snapshots: self.snapshots.clone(),
+            metadata: self.metadata.clone(),
+            guides: self.guides.clone(),
+            layers: self.layers.clone(),
+            styles: self.styles.clone(),
+            unit_scale: self.unit_scale.clone(),
+            export_names: self
+                .export_names
+                .iter()
+                .filter(|(id, _)| export::is_name_target(&self.objects, **id))
+                .map(|(id, name)| (*id, name.clone()))
+                .collect(),
+            boards: self.stashed_boards(),
+            active_board: self.active_board,
+        }
+    }
+
+    fn stashed_boards(&self) -> Vec<Board> {
+        let mut boards = self.boards.clone();
+        if let Some(board) = boards.get_mut(self.active_board) {
+            board.background = Some(self.background_color.to_array());
+            board.camera = Some(self.current_viewport());
+        }
+        boards
+    }
+
+    fn apply_state(&mut self, state: WhiteboardState) {
+        self.objects = state.objects;
+        self.snapshots = state.snapshots;
+        self.metadata = state.metadata;
+        self.guides = state.guides;
+        self.layers = state.layers;
+        self.styles = state.styles;
+        self.unit_scale = state.unit_scale;
+        self.export_names = state.export_names;
+        self.boards = state.boards;
+        if self.boards.is_empty() {
+            self.boards.push(Board::new("Board 1"));
+        }
+        self.active_board = state.active_board.min(self.boards.len() - 1);
+        self.board_histories.clear();
+        self.renaming_board = None;
+        let active = &mut self.boards[self.active_board];
+        active.objects.clear();
+        let (background, camera) = (active.background, active.camera);
+        self.show_board_view(background, camera);
+        self.active_style = None;
+        let board = self.board_id();
+        for snapshot in &mut self.snapshots {
+            snapshot.board.get_or_insert(board);
+            selection::sync_instances(&mut snapshot.objects);
+        }
+        self.settle_objects();
+    }
+
+    fn settle_objects(&mut self) {
+        selection::sync_instances(&mut self.objects);
+        self.sync_layers();
+        self.sync_scale_bars();
+        self.history.reset(&self.objects, &self.layers, &self.styles);
+        self.known_objects = self.objects.iter().map(|obj| obj.id()).collect();
+        self.selected_objects.clear();
+        self.measure_formulas();
+        self.queue_latex_warmup();
+        self.needs_repaint = true;
+    }
+
+    fn queue_latex_warmup(&mut self) {
+        let (view_min, view_max) = canvas::visible_canvas_bounds(self.canvas_rect, self.canvas_offset, self.canvas_zoom, self.canvas_rotation);
+        let view_center = [(view_min[0] + view_max[0]) / 2.0, (view_min[1] + view_max[1]) / 2.0];
+        let backgrounds = selection::page_backgrounds(&self.objects);
+        let mut formulas: Vec<_> = self.objects
+            .iter()
+            .filter_map(|obj| match obj {
+                DrawObject::LatexFormula { formula, color, auto_color, font, .. } if !formula.is_empty() => {
+                    let (min, max) = obj.bounds();
+                    let center = [(min[0] + max[0]) / 2.0, (min[1] + max[1]) / 2.0];
+                    let color = if *auto_color { palette::auto_ink(self.background_at(&backgrounds, center)) } else { *color };
+                    Some((models::distance(center, view_center), formula.clone(), font.clone(), color))
+                }
+                _ => None,
+            })
+            .collect();
+        formulas.sort_by(|a, b| a.0.total_cmp(&b.0));
+        let mut seen = std::collections::HashSet::new();
+        formulas.retain(|(_, formula, font, color)| seen.insert((formula.clone(), font.clone(), *color)));
+
+        self.latex_warmup_queue = formulas.into_iter().rev().map(|(_, formula, font, color)| (formula, font, color)).collect();
+        self.latex_warmup_total = self.latex_warmup_queue.len();
+    }
+
+    fn warm_latex_cache(&mut self, ctx: &egui::Context) {
+        if self.latex_warmup_total == 0 {
+            return;
+        }
+
+        let started = std::time::Instant::now();
+        let budget = std::time::Duration::from_millis(LATEX_WARMUP_BUDGET_MS);
+        while started.elapsed() < budget && self.latex_renderer.pending_count() < LATEX_WARMUP_IN_FLIGHT {
+            let Some((formula, font, color)) = self.latex_warmup_queue.pop() else {
+                break;
+            };
+            self.latex_renderer.get_or_create_texture(ctx, &formula, font.as_deref(), color);
+        }
+
+        let remaining = self.latex_warmup_queue.len() + self.latex_renderer.pending_count();
+        if remaining == 0 {
+            self.latex_warmup_total = 0;
+            return;
+        }
+        let done = self.latex_warmup_total.saturating_sub(remaining);
+        let progress = done as f32 / self.latex_warmup_total.max(1) as f32;
+        egui::Area::new(egui::Id::new("latex_warmup_progress"))
+            .anchor(egui::Align2::RIGHT_BOTTOM, egui::vec2(-12.0, -12.0))
+            .interactable(false)
+            .show(ctx, |ui| {
+                egui::Frame::popup(ui.style()).show(ui, |ui| {
+                    ui.add(
+                        egui::ProgressBar::new(progress)
+                            .desired_width(200.0)
+                            .text(trf("Rendering formulas {}/{}", &[&done, &self.latex_warmup_total])),
+                    );
+                });
+            });
+        self.needs_repaint = true;
+    }
+
+    fn measure_formulas(&mut self) {
+        self.measuring_formulas = true;
+    }
+
+    fn advance_measurements(&mut self, ctx: &egui::Context) {
+        if !self.measuring_formulas {
+            return;
+        }
+        let mut waiting = false;
+        let objects: Vec<DrawObject> = self
+            .objects
+            .iter()
+            .filter_map(|obj| match obj {
+                DrawObject::LatexFormula { formula, size: None, font, .. } => {
+                    let Some(measured) = self.latex_renderer.request_size(ctx, formula, font.as_deref()) else {
+                        waiting |= self.latex_renderer.is_measuring(formula, font.as_deref());
+                        return None;
+                    };
+                    let mut obj = obj.clone();
+                    if let DrawObject::LatexFormula { size, .. } = &mut obj {
+                        *size = Some(measured);
+                    }
+                    Some(obj)
+                }
+                _ => None,
+            })
+            .collect();
+        self.measuring_formulas = waiting;
+        if !objects.is_empty() {
+            self.apply_op(DocumentOp::Update { objects });
+        }
+    }
+
+    fn take_snapshot(&mut self) {
+        let name = if self.snapshot_name.trim().is_empty() {
+            trf("Snapshot {}", &[&(self.board_snapshots().count() + 1)])
+        } else {
+            self.snapshot_name.trim().to_string()
+        };
+        self.snapshots.push(Snapshot {
+            name,
+            timestamp: clock::now_secs(),
+            objects: self.objects.clone(),
+            board: Some(self.board_id()),
+        });
+        self.snapshot_name.clear();
+    }
+
+    fn board_id(&self) -> Uuid {
+        self.boards[self.active_board].id
+    }
+
+    fn board_snapshots(&self) -> impl Iterator<Item = (usize, &Snapshot)> {
+        let board = Some(self.board_id());
+        self.snapshots.iter().enumerate().filter(move |(_, snapshot)| snapshot.board == board)
+    }
+
+    fn restore_snapshot(&mut self, index: usize) {
+        if let Some(snapshot) = self.snapshots.get(index).filter(|snapshot| snapshot.board == Some(self.board_id())) {
+            let objects = snapshot.objects.clone();
+            self.known_objects.extend(objects.iter().map(|obj| obj.id()));
+            self.push_undo();
+            self.objects = objects;
+            self.selected_objects.clear();
+            self.measure_formulas();
+            self.needs_repaint = true;
+        }
+    }
+
+    fn export_timelapse(&mut self) {
+        let mut snapshots: Vec<&Snapshot> = self.board_snapshots().map(|(_, snapshot)| snapshot).collect();
+        snapshots.sort_by_key(|snapshot| snapshot.timestamp);
+        let mut layered: Vec<Vec<DrawObject>> = snapshots.iter().map(|s| export::layered(&s.objects, &self.layers)).collect();
+        layered.push(self.export_objects());
+        let frames: Vec<&[DrawObject]> = layered.iter().map(Vec::as_slice).collect();
+
+        let background = self.background_color.to_array();
+        self.export_status = Some(
+            match export::export_timelapse(&frames, &self.timelapse_dir, 1.0, background, &self.settings.export_framing, &mut self.latex_renderer) {
+                Ok(count) => trf("Wrote {} frames to {}", &[&count, &self.timelapse_dir]),
+                Err(e) => trf("Timelapse export failed: {}", &[&e]),
+            },
+        );
+    }
+
+    fn sync_pdf_frame_order(&mut self) {
+        let frame_ids: Vec<Uuid> = self.objects
+            .iter()
+            .filter(|o| matches!(o, DrawObject::Frame { .. }))
+            .map(|o| o.id())
+            .collect();
+        self.pdf_frame_order.retain(|id| frame_ids.contains(id));
+        for id in frame_ids {
+            if !self.pdf_frame_order.contains(&id) {
+                self.pdf_frame_order.push(id);
+            }
+        }
+    }
+
+    fn sort_pdf_frames_by_reading_order(&mut self) {
+        let objects = &self.objects;
+        let top_left = |id: &Uuid| objects.iter().find(|o| o.id() == *id).map(|o| o.bounds().0).unwrap_or([0.0, 0.0]);
+        self.pdf_frame_order.sort_by(|a, b| {
+            let (pa, pb) = (top_left(a), top_left(b));
+            pa[1].total_cmp(&pb[1]).then(pa[0].total_cmp(&pb[0]))
+        });
+    }
+
+    fn export_objects(&self) -> Vec<DrawObject> {
+        export::layered(&self.objects, &self.layers)
+    }
+
+    fn export_pdf(&mut self) {
+        let header = if self.pdf_header {
+            let mut parts = Vec::new();
+            if !self.metadata.title.is_empty() {
+                parts.push(self.metadata.title.clone());
+            }
+            if !self.metadata.author.is_empty() {
+                parts.push(self.metadata.author.clone());
+            }
+            (!parts.is_empty()).then(|| parts.join(" - "))
+        } else {
+            None
+        };
+        let options = export::PdfExportOptions {
+            scale: self.pdf_scale,
+            background: self.background_color.to_array(),
+            header,
+            page_numbers: self.pdf_page_numbers,
+            metadata: &self.metadata,
+        };
+        self.export_status = Some(
+            match export::export_frames_pdf(&self.export_objects(), &self.pdf_frame_order, &options, &self.pdf_path, &mut self.latex_renderer) {
+                Ok(count) => trf("Wrote {} pages to {}", &[&count, &self.pdf_path]),
+                Err(e) => trf("PDF export failed: {}", &[&e]),
+            },
+        );
+    }
+
+    fn render_pdf_export_panel(&mut self, ctx: &egui::Context) {
+        if !self.show_pdf_export {
+            return;
+        }
+        self.sync_pdf_frame_order();
+
+        let mut open = true;
+        let mut export = false;
+        let mut sort_reading = false;
+        let mut swap = None;
+        let mut renamed = None;
+        egui::Window::new(tr("Export PDF"))
+            .open(&mut open)
+            .default_width(320.0)
+            .show(ctx, |ui| {
+                egui::Grid::new("pdf_export_grid").num_columns(2).show(ui, |ui| {
+                    ui.label(tr("File:"));
+                    ui.text_edit_singleline(&mut self.pdf_path);
+                    ui.end_row();
+                    ui.label(tr("Title:"));
+                    ui.text_edit_singleline(&mut self.metadata.title);
+                    ui.end_row();
+                    ui.label(tr("Author:"));
+                    ui.text_edit_singleline(&mut self.metadata.author);
+                    ui.end_row();
+                    ui.label(tr("Resolution:"));
+                    ui.add(egui::Slider::new(&mut self.pdf_scale, 1.0..=4.0).suffix("x"));
+                    ui.end_row();
+                });
+                ui.checkbox(&mut self.pdf_header, tr("Header with title and author"));
+                ui.checkbox(&mut self.pdf_page_numbers, tr("Page numbers"));
+                ui.separator();
+
+                ui.horizontal(|ui| {
+                    ui.label(tr("Page order:"));
+                    if ui.button(tr("Sort by reading order")).clicked() {
+                        sort_reading = true;
+                    }
+                });
+                if self.pdf_frame_order.is_empty() {
+                    ui.label(tr("Draw frames with the Frame tool (K) to define pages."));
+                }
+                let count = self.pdf_frame_order.len();
+                for (index, id) in self.pdf_frame_order.iter().enumerate() {
+                    let Some(DrawObject::Frame { name, .. }) = self.objects.iter().find(|o| o.id() == *id) else {
+                        continue;
+                    };
+                    let mut name = name.clone();
+                    ui.horizontal(|ui| {
+                        ui.label(format!("{}.", index + 1));
+                        if ui.text_edit_singleline(&mut name).changed() {
+                            renamed = Some((*id, name.clone()));
+                        }
+                        if ui.add_enabled(index > 0, egui::Button::new("⬆")).clicked() {
+                            swap = Some((index, index - 1));
+                        }
+                        if ui.add_enabled(index + 1 < count, egui::Button::new("⬇")).clicked() {
+                            swap = Some((index, index + 1));
+                        }
+                    });
+                }
+                ui.separator();
+                if ui.add_enabled(count > 0, egui::Button::new(tr("Export"))).clicked() {
+                    export = true;
+                }
+                if let Some(status) = &self.export_status {
+                    ui.label(status);
+                }
+            });
+
+        if let Some((a, b)) = swap {
+            self.pdf_frame_order.swap(a, b);
+        }
+        if let Some((id, new_name)) = renamed {
+            self.update_objects(&[id], |obj| {
+                if let DrawObject::Frame { name, .. } = obj {
+                    *name = new_name.clone();
+                }
+            });
+        }
+        if sort_reading {
+            self.sort_pdf_frames_by_reading_order();
+        }
+        if export {
+            self.export_pdf();
+        }
+        self.show_pdf_export = open;
+    }
+
+    fn export_figure(&mut self) {
+        let mut objects = self.export_objects();
+        if !self.selected_objects.is_empty() {
+            objects.retain(|obj| self.selected_objects.contains(&obj.id()));
+        }
+        let options = export::FigureExportOptions {
+            scale: self.figure_scale,
+            ink: self.figure_recolor.then(|| self.figure_ink.to_array()),
+            margin: self.figure_margin,
+        };
+        self.export_status = Some(
+            match export::export_figure_png(&objects, &options, &self.figure_path, &mut self.latex_renderer) {
+                Ok([width, height]) => trf("Wrote {}x{} figure to {}", &[&width, &height, &self.figure_path]),
+                Err(e) => trf("Figure export failed: {}", &[&e]),
+            },
+        );
+    }
+
+    fn render_figure_export_panel(&mut self, ctx: &egui::Context) {
+        if !self.show_figure_export {
+            return;
+        }
+
+        let mut open = true;
+        let mut export = false;
+        egui::Window::new(tr("Export figure"))
+            .open(&mut open)
+            .default_width(300.0)
+            .show(ctx, |ui| {
+                ui.label(tr(if self.selected_objects.is_empty() {
+                    "Exports the whole board as a transparent PNG."
+                } else {
+                    "Exports the selection as a transparent PNG."
+                }));
+                egui::Grid::new("figure_export_grid").num_columns(2).show(ui, |ui| {
+                    ui.label(tr("File:"));
+                    ui.text_edit_singleline(&mut self.figure_path);
+                    ui.end_row();
+                    ui.label(tr("Resolution:"));
+                    ui.add(egui::Slider::new(&mut self.figure_scale, 1.0..=8.0).suffix("x"));
+                    ui.end_row();
+                    ui.label(tr("Margin:"));
+                    ui.add(egui::DragValue::new(&mut self.figure_margin).range(0..=200).suffix(" px"));
+                    ui.end_row();
+                });
+                ui.horizontal(|ui| {
+                    ui.checkbox(&mut self.figure_recolor, tr("Recolor ink to"));
+                    ui.add_enabled_ui(self.figure_recolor, |ui| {
+                        egui::color_picker::color_edit_button_srgba(ui, &mut self.figure_ink, egui::color_picker::Alpha::Opaque);
+                    });
+                });
+                ui.separator();
+                if ui.button(tr("Export")).clicked() {
+                    export = true;
+                }
+                if let Some(status) = &self.export_status {
+                    ui.label(status);
+                }
+            });
+
+        if export {
+            self.export_figure();
+        }
+        self.show_figure_export = open;
+    }
+
+    fn export_named(&mut self) {
+        let objects = &self.objects;
+        self.export_names.retain(|id, _| export::is_name_target(objects, *id));
+        let items = export::named_items(&self.export_objects(), &self.export_names, &self.settings.export_framing, self.figure_scale);
+        self.export_status = Some(
+            match export::export_named_items(
+                &items,
+                &self.named_export_dir,
+                self.figure_scale,
+                self.named_export_png,
+                self.named_export_svg,
+                &mut self.latex_renderer,
+            ) {
+                Ok(count) => trf("Wrote {} files to {}", &[&count, &self.named_export_dir]),
+                Err(e) => trf("Named export failed: {}", &[&e]),
+            },
+        );
+    }
+
+    fn render_named_export_panel(&mut self, ctx: &egui::Context) {
+        if !self.show_named_export {
+            return;
+        }
+
+        let mut open = true;
+        let mut export = false;
+        egui::Window::new(tr("Export named items"))
+            .open(&mut open)
+            .default_width(300.0)
+            .show(ctx, |ui| {
+                ui.label(tr("Name a frame or group from its context menu; each named item is written to <name>.png and/or <name>.svg."));
+                egui::Grid::new("named_export_grid").num_columns(2).show(ui, |ui| {
+                    ui.label(tr("Folder:"));
+                    ui.text_edit_singleline(&mut self.named_export_dir);
+                    ui.end_row();
+                    ui.label(tr("Resolution:"));
+                    ui.add(egui::Slider::new(&mut self.figure_scale, 1.0..=8.0).suffix("x"));
+                    ui.end_row();
+                    ui.label(tr("Formats:"));
+                    ui.horizontal(|ui| {
+                        ui.checkbox(&mut self.named_export_png, tr("PNG"));
+                        ui.checkbox(&mut self.named_export_svg, tr("SVG"));
+                    });
+                    ui.end_row();
+                });
+                self.export_framing_ui(ui);
+                ui.separator();
+                let items = export::named_items(&self.export_objects(), &self.export_names, &self.settings.export_framing, self.figure_scale);
+                if items.is_empty() {
+                    ui.label(tr("Nothing is named yet."));
+                }
+                for item in &items {
+                    if export::file_stem(&item.name) == item.stem {
+                        ui.label(&item.name);
+                    } else {
+                        ui.label(trf("{} (saved as {})", &[&item.name, &item.stem]));
+                    }
+                }
+                ui.separator();
+                if ui.add_enabled(!items.is_empty(), egui::Button::new(tr("Export all named items"))).clicked() {
+                    export = true;
+                }
+                if let Some(status) = &self.export_status {
+                    ui.label(status);
+                }
+            });
+
+        if export {
+            self.export_named();
+        }
+        self.show_named_export = open;
+    }
+
+    fn export_framing_ui(&mut self, ui: &mut egui::Ui) {
+        let framing = &mut self.settings.export_framing;
+        let mut changed = false;
+        egui::CollapsingHeader::new(tr("Margins")).id_salt("export_framing").show(ui, |ui| {
+            changed |= ui
+                .checkbox(&mut framing.trim, tr("Trim frames to their content"))
+                .on_hover_text(tr("Frame exports crop away empty space inside the frame"))
+                .changed();
+            ui.horizontal(|ui| {
+                ui.label(tr("Margin:"));
+                changed |= ui.add(egui::DragValue::new(&mut framing.margin).range(0.0..=500.0).suffix(" px")).changed();
+            });
+            ui.horizontal(|ui| {
+                ui.label(tr("Round size to:"));
+                egui::ComboBox::from_id_salt("export_round_to")
+                    .selected_text(format!("{} px", framing.round_to))
+                    .show_ui(ui, |ui| {
+                        for step in ExportFraming::ROUND_OPTIONS {
+                            changed |= ui.selectable_value(&mut framing.round_to, step, format!("{} px", step)).changed();
+                        }
+                    });
+            });
+        });
+        if changed {
+            self.save_settings();
+        }
+    }
+
+    fn export_name_key(&self) -> Option<Uuid> {
+        let selected: Vec<&DrawObject> = self.objects.iter().filter(|obj| self.selected_objects.contains(&obj.id())).collect();
+        match selected[..] {
+            [DrawObject::Frame { id, .. }] => Some(*id),
+            [first, ..] => first.props().group.filter(|group| selected.iter().all(|obj| obj.props().group == Some(*group))),
+            [] => None,
+        }
+    }
+
+    fn run_recipe(&mut self, ctx: &egui::Context, index: usize) {
+        let Some(recipe) = self.settings.export_recipes.get(index).cloned() else {
+            return;
+        };
+        let result = recipes::run(
+            &recipe,
+            &self.export_objects(),
+            &self.selected_objects,
+            self.background_color.to_array(),
+            &self.metadata,
+            &self.settings.export_framing,
+            &mut self.latex_renderer,
+        );
+        self.recipe_status = Some(match result {
+            Ok(path) => {
+                let after = match recipe.after {
+                    PostCommand::Nothing => Ok(()),
+                    PostCommand::Open => recipes::open_with_default_app(&path),
+                    PostCommand::CopyPath => {
+                        ctx.copy_text(path.display().to_string());
+                        Ok(())
+                    }
+                    PostCommand::Command => recipes::run_command(&recipe.command, &path),
+                };
+                match after {
+                    Ok(()) => trf("{}: wrote {}", &[&recipe.name, &path.display()]),
+                    Err(e) => trf("{}: wrote {}, but {}", &[&recipe.name, &path.display(), &e]),
+                }
+            }
+            Err(e) => trf("{} failed: {}", &[&recipe.name, &e]),
+        });
+    }
+
+    fn recipes_menu(&mut self, ui: &mut egui::Ui) {
+        ui.menu_button(tr("Recipes"), |ui| {
+            let mut run = None;
+            if self.settings.export_recipes.is_empty() {
+                ui.label(tr("No export recipes yet."));
+            }
+            for (index, recipe) in self.settings.export_recipes.iter().enumerate() {
+                let button = egui::Button::new(&recipe.name).shortcut_text(recipe.shortcut.trim());
+                if ui.add(button).clicked() {
+                    run = Some(index);
+                }
+            }
+            ui.separator();
+            if ui.button(tr("Edit recipes…")).clicked() {
+                self.show_recipes_panel = true;
+                ui.close_menu();
+            }
+            if let Some(status) = &self.recipe_status {
+                ui.label(status);
+            }
+            if let Some(index) = run {
+                self.run_recipe(ui.ctx(), index);
+            }
+        });
+    }
+
+    fn render_recipes_panel(&mut self, ctx: &egui::Context) {
+        if !self.show_recipes_panel {
+            return;
+        }
+
+        let mut open = true;
+        let mut changed = false;
+        let mut add = false;
+        let mut remove = None;
+        let mut run = None;
+        egui::Window::new(tr("Export recipes"))
+            .open(&mut open)
+            .default_width(360.0)
+            .show(ctx, |ui| {
+                ui.label(tr("In the file name, {frame}, {date} and {time} are filled in; ~ is your home folder. Commands get the file as {path}."));
+                self.export_framing_ui(ui);
+                for (index, recipe) in self.settings.export_recipes.iter_mut().enumerate() {
+                    egui::CollapsingHeader::new(recipe.name.clone())
+                        .id_salt(("recipe", index))
+                        .show(ui, |ui| {
+                            egui::Grid::new(("recipe_grid", index)).num_columns(2).show(ui, |ui| {
+                                ui.label(tr("Name:"));
+                                changed |= ui.text_edit_singleline(&mut recipe.name).lost_focus();
+                                ui.end_row();
+                                ui.label(tr("Format:"));
+                                egui::ComboBox::from_id_salt(("recipe_format", index))
+                                    .selected_text(tr(recipe.format.label()))
+                                    .show_ui(ui, |ui| {
+                                        for format in RecipeFormat::ALL {
+                                            changed |= ui.selectable_value(&mut recipe.format, format, tr(format.label())).changed();
+                                        }
+                                    });
+                                ui.end_row();
+                                ui.label(tr("Region:"));
+                                ui.horizontal(|ui| {
+                                    egui::ComboBox::from_id_salt(("recipe_region", index))
+                                        .selected_text(tr(recipe.region.label()))
+                                        .show_ui(ui, |ui| {
+                                            for region in RecipeRegion::ALL {
+                                                changed |= ui.selectable_value(&mut recipe.region, region, tr(region.label())).changed();
+                                            }
+                                        });
+                                    if recipe.region == RecipeRegion::Frame {
+                                        changed |= ui.add(egui::TextEdit::singleline(&mut recipe.frame).hint_text(tr("Frame name")).desired_width(120.0)).lost_focus();
+                                    }
+                                });
+                                ui.end_row();
+                                ui.label(tr("Background:"));
+                                ui.horizontal(|ui| {
+                                    egui::ComboBox::from_id_salt(("recipe_background", index))
+                                        .selected_text(tr(recipe.background.label()))
+                                        .show_ui(ui, |ui| {
+                                            for background in RecipeBackground::ALL {
+                                                changed |= ui.selectable_value(&mut recipe.background, background, tr(background.label())).changed();
+                                            }
+                                        });
+                                    if recipe.background == RecipeBackground::Color {
+                                        let [r, g, b, a] = recipe.background_color;
+                                        let mut color = egui::Color32::from_rgba_unmultiplied(r, g, b, a);
+                                        if egui::color_picker::color_edit_button_srgba(ui, &mut color, egui::color_picker::Alpha::Opaque).changed() {
+                                            recipe.background_color = color.to_array();
+                                            changed = true;
+                                        }
+                                    }
+                                });
+                                ui.end_row();
+                                ui.label(tr("Resolution:"));
+                                changed |= ui.add(egui::Slider::new(&mut recipe.scale, 1.0..=8.0).suffix("x")).changed();
+                                ui.end_row();
+                                ui.label(tr("File:"));
+                                changed |= ui.text_edit_singleline(&mut recipe.path).lost_focus();
+                                ui.end_row();
+                                ui.label(tr("Afterwards:"));
+                                egui::ComboBox::from_id_salt(("recipe_after", index))
+                                    .selected_text(tr(recipe.after.label()))
+                                    .show_ui(ui, |ui| {
+                                        for after in PostCommand::ALL {
+                                            changed |= ui.selectable_value(&mut recipe.after, after, tr(after.label())).changed();
+                                        }
+                                    });
+                                ui.end_row();
+                                if recipe.after == PostCommand::Command {
+                                    ui.label(tr("Command:"));
+                                    changed |= ui.text_edit_singleline(&mut recipe.command).lost_focus();
+                                    ui.end_row();
+                                }
+                                ui.label(tr("Shortcut:"));
+                                let shortcut = ui.add(egui::TextEdit::singleline(&mut recipe.shortcut).hint_text(tr("e.g. Ctrl+Alt+E")));
+                                changed |= shortcut.lost_focus();
+                                if !recipe.shortcut.trim().is_empty() && recipes::parse_shortcut(&recipe.shortcut).is_none() {
+                                    shortcut.on_hover_text(tr("Not a recognized shortcut"));
+                                }
+                                ui.end_row();
+                            });
+                            ui.horizontal(|ui| {
+                                if ui.button(tr("Run")).clicked() {
+                                    run = Some(index);
+                                }
+                                if ui.button("🗑").on_hover_text(tr("Delete recipe")).clicked() {
+                                    remove = Some(index);
+                                }
+                            });
+                        });
+                }
+                ui.separator();
+                if ui.button(tr("Add recipe")).clicked() {
+                    add = true;
+                }
+                if let Some(status) = &self.recipe_status {
+                    ui.label(status);
+                }
+            });
+
+        if add {
+            self.settings.export_recipes.push(ExportRecipe::default());
+            changed = true;
+        }
+        if let Some(index) = remove {
+            self.settings.export_recipes.remove(index);
+            changed = true;
+        }
+        if changed {
+            self.save_settings();
+        }
+        if let Some(index) = run {
+            self.run_recipe(ctx, index);
+        }
+        self.show_recipes_panel = open;
+    }
+
+    fn import_pdf(&mut self) {
+        let pages = match pdf_import::read_pdf_pages(&self.pdf_import_path) {
+            Ok(pages) => pages,
+            Err(e) => {
+                self.import_status = Some(trf("PDF import failed: {}", &[&e]));
+                return;
+            }
+        };
+
+        let page_count = pages.len();
+        let pages = pages
+            .into_iter()
+            .enumerate()
+            .map(|(index, page)| (trf("Page {}", &[&(index + 1)]), page.jpeg))
+            .collect();
+        let name = std::path::Path::new(&self.pdf_import_path)
+            .file_stem()
+            .map_or_else(|| "PDF".to_string(), |stem| stem.to_string_lossy().into_owned());
+        let mut background = Layer::new(trf("{} (background)", &[&name]));
+        background.locked = true;
+        let missing = self.place_page_grid(pages, self.pdf_import_columns, Some(background));
+        self.import_status = Some(if missing == 0 {
+            trf("Imported {} pages onto a locked background layer", &[&page_count])
+        } else {
+            trf("Imported {} pages; {} had no JPEG page image and were left blank", &[&page_count, &missing])
+        });
+    }
+
+    fn import_image_folder(&mut self) {
+        let pages = match images::folder_images(&self.image_folder_path) {
+            Ok(pages) => pages,
+            Err(e) => {
+                self.import_status = Some(trf("Image import failed: {}", &[&e]));
+                return;
+            }
+        };
+        let page_count = pages.len();
+        let pages = pages.into_iter().map(|image| (image.name, Some(image.data))).collect();
+        self.place_page_grid(pages, self.image_folder_columns, None);
+        self.import_status = Some(trf("Imported {} images", &[&page_count]));
+    }
+
+    fn place_page_grid(&mut self, pages: Vec<(String, Option<Vec<u8>>)>, columns: usize, background: Option<Layer>) -> usize {
+        let page_width = 800.0;
+        let gap = 80.0;
+        let columns = columns.max(1);
+        let sizes: Vec<[f32; 2]> = pages
+            .iter()
+            .map(|(_, data)| {
+                let [w, h] = data.as_deref().and_then(images::dimensions).unwrap_or([850, 1100]);
+                [page_width, page_width * h as f32 / w.max(1) as f32]
+            })
+            .collect();
+        let row_heights: Vec<f32> = sizes
+            .chunks(columns)
+            .map(|row| row.iter().map(|size| size[1]).fold(0.0, f32::max))
+            .collect();
+
+        let (view_min, _) = canvas::visible_canvas_bounds(self.canvas_rect, self.canvas_offset, self.canvas_zoom, self.canvas_rotation);
+        let origin = [view_min[0] + gap, view_min[1] + gap];
+
+        self.push_undo();
+        let image_props = ObjectProps { layer: background.as_ref().map(|layer| layer.id), ..ObjectProps::default() };
+        if let Some(layer) = background {
+            self.layers.insert(0, layer);
+        }
+        let mut added = Vec::new();
+        let mut missing = 0;
+        for (index, ((name, data), size)) in pages.into_iter().zip(sizes).enumerate() {
+            let (row, column) = (index / columns, index % columns);
+            let x = origin[0] + column as f32 * (page_width + gap);
+            let y = origin[1] + row_heights[..row].iter().map(|h| h + gap).sum::<f32>();
+            let min = [x, y];
+            let max = [x + size[0], y + size[1]];
+
+            if let Some(data) = data {
+                added.push(DrawObject::Image { id: Uuid::new_v4(), min, max, data, adjustments: ImageAdjustments::default(), props: image_props.clone() });
+            } else {
+                missing += 1;
+            }
+            added.push(DrawObject::Frame {
+                id: Uuid::new_v4(),
+                min,
+                max,
+                name,
+                background: None,
+                grid: None,
+                props: ObjectProps::default(),
+            });
+        }
+
+        self.selected_objects = added.iter().map(|obj| obj.id()).collect();
+        self.apply_op(DocumentOp::Add { objects: added });
+        self.set_tool(Tool::Select);
+        missing
+    }
+
+    fn render_pdf_import_panel(&mut self, ctx: &egui::Context) {
+        if !self.show_pdf_import {
+            return;
+        }
+
+        let mut open = true;
+        let mut import = false;
+        egui::Window::new(tr("Import scanned PDF"))
+            .open(&mut open)
+            .default_width(300.0)
+            .show(ctx, |ui| {
+                egui::Grid::new("pdf_import_grid").num_columns(2).show(ui, |ui| {
+                    ui.label(tr("File:"));
+                    ui.text_edit_singleline(&mut self.pdf_import_path);
+                    ui.end_row();
+                    ui.label(tr("Columns:"));
+                    ui.add(egui::DragValue::new(&mut self.pdf_import_columns).range(1..=10));
+                    ui.end_row();
+                });
+                ui.label(tr("Each page is placed in its own frame. Only scanned PDFs whose pages are JPEG images can be imported; text and vector pages are not rendered."));
+                if ui.button(tr("Import")).clicked() {
+                    import = true;
+                }
+                if let Some(status) = &self.import_status {
+                    ui.label(status);
+                }
+            });
+
+        if import {
+            self.import_pdf();
+        }
+        self.show_pdf_import = open;
+    }
+
+    fn render_image_folder_import_panel(&mut self, ctx: &egui::Context) {
+        if !self.show_image_folder_import {
+            return;
+        }
+
+        let mut open = true;
+        let mut import = false;
+        egui::Window::new(tr("Import image folder"))
+            .open(&mut open)
+            .default_width(300.0)
+            .show(ctx, |ui| {
+                egui::Grid::new("image_folder_import_grid").num_columns(2).show(ui, |ui| {
+                    ui.label(tr("Folder:"));
+                    ui.text_edit_singleline(&mut self.image_folder_path);
+                    ui.end_row();
+                    ui.label(tr("Columns:"));
+                    ui.add(egui::DragValue::new(&mut self.image_folder_columns).range(1..=10));
+                    ui.end_row();
+                });
+                ui.label(tr("Each image is placed in its own frame, labeled with its file name, in file name order."));
+                if ui.button(tr("Import")).clicked() {
+                    import = true;
+                }
+                if let Some(status) = &self.import_status {
+                    ui.label(status);
+                }
+            });
+
+        if import {
+            self.import_image_folder();
+        }
+        self.show_image_folder_import = open;
+    }
+
+    fn render_font_panel(&mut self, ctx: &egui::Context) {
+        if !self.show_font_panel {
+            return;
+        }
+
+        let mut open = true;
+        let mut load = false;
+        let mut remove = None;
+        egui::Window::new(tr("Fonts"))
+            .open(&mut open)
+            .default_width(300.0)
+            .show(ctx, |ui| {
+                ui.horizontal(|ui| {
+                    ui.label(tr("Font file:"));
+                    let input = ui.text_edit_singleline(&mut self.font_file_path);
+                    load = input.lost_focus() && ui.input(|i| i.key_pressed(egui::Key::Enter));
+                });
+                if ui.button(tr("Load")).clicked() {
+                    load = true;
+                }
+                if let Some(status) = &self.font_status {
+                    ui.label(status);
+                }
+                if !self.settings.font_files.is_empty() {
+                    ui.separator();
+                    ui.label(tr("Loaded at startup:"));
+                    for (index, path) in self.settings.font_files.iter().enumerate() {
+                        ui.horizontal(|ui| {
+                            ui.label(path);
+                            if ui.small_button(tr("Remove")).clicked() {
+                                remove = Some(index);
+                            }
+                        });
+                    }
+                }
+            });
+
+        if load {
+            self.load_font_file();
+        }
+        if let Some(index) = remove {
+            self.settings.font_files.remove(index);
+            self.save_settings();
+            self.font_status = Some(tr("Removed. The font stays available until restart.").to_string());
+        }
+        self.show_font_panel = open;
+    }
+
+    fn load_font_file(&mut self) {
+        let path = self.font_file_path.trim().to_string();
+        if path.is_empty() {
+            return;
+        }
+        match self.latex_renderer.add_font_file(&path) {
+            Ok(families) => {
+                self.font_status = Some(trf("Loaded {}", &[&families.join(", ")]));
+                if !self.settings.font_files.contains(&path) {
+                    self.settings.font_files.push(path);
+                    self.save_settings();
+                }
+                self.current_font = families.into_iter().next();
+                let ids: Vec<Uuid> = self
+                    .objects
+                    .iter()
+                    .filter(|obj| matches!(obj, DrawObject::LatexFormula { font: Some(_), .. }))
+                    .map(|obj| obj.id())
+                    .collect();
+                self.update_objects(&ids, |obj| {
+                    if let DrawObject::LatexFormula { size, .. } = obj {
+                        *size = None;
+                    }
+                });
+                self.measure_formulas();
+                self.queue_latex_warmup();
+                self.needs_repaint = true;
+            }
+            Err(e) => self.font_status = Some(e),
+        }
+    }
+
+    fn render_symbol_picker(&mut self, ctx: &egui::Context) {
+        if !self.show_symbol_picker {
+            return;
+        }
+
+        let mut open = true;
+        let mut picked = None;
+        egui::Window::new(tr("Symbols"))
+            .open(&mut open)
+            .default_width(320.0)
+            .show(ctx, |ui| {
+                ui.horizontal(|ui| {
+                    ui.label(tr("Search:"));
+                    ui.text_edit_singleline(&mut self.symbol_search);
+                });
+                ui.label(tr(if self.editing_text.is_some() {
+                    "Click a symbol to insert it into the text being edited."
+                } else {
+                    "Click a symbol to place it on the board."
+                }));
+                let query = self.symbol_search.trim().to_lowercase();
+                egui::ScrollArea::vertical().max_height(320.0).show(ui, |ui| {
+                    for category in &symbols::CATEGORIES {
+                        let matches: Vec<&Symbol> = category.symbols.iter().filter(|symbol| symbol.matches(&query)).collect();
+                        if matches.is_empty() {
+                            continue;
+                        }
+                        ui.label(category.name);
+                        ui.horizontal_wrapped(|ui| {
+                            for symbol in matches {
+                                let button = egui::Button::new(egui::RichText::new(symbol.glyph).size(18.0))
+                                    .min_size(egui::vec2(28.0, 28.0));
+                                let hover = match symbol.latex {
+                                    Some(latex) => format!("{} ({})", symbol.name, latex),
+                                    None => symbol.name.to_string(),
+                                };
+                                if ui.add(button).on_hover_text(hover).clicked() {
+                                    picked = Some(symbol);
+                                }
+                            }
+                        });
+                    }
+                });
+            });
+
+        if let Some(symbol) = picked {
+            self.insert_symbol(symbol);
+        }
+        self.show_symbol_picker = open;
+    }
+
+    fn insert_symbol(&mut self, symbol: &Symbol) {
+        if let Some(editing_id) = self.editing_text {
+            let latex_source = matches!(
+                self.objects.iter().find(|o| o.id() == editing_id),
+                Some(DrawObject::LatexFormula { font: None, .. })
+            );
+            let text = match symbol.latex {
+                Some(latex) if latex_source => format!("{} ", latex),
+                _ => symbol.glyph.to_string(),
+            };
+            self.text_input.insert_str(self.text_cursor_pos, &text);
+            self.text_cursor_pos += text.len();
+            self.needs_repaint = true;
+            return;
+        }
+
+        let (view_min, view_max) = canvas::visible_canvas_bounds(self.canvas_rect, self.canvas_offset, self.canvas_zoom, self.canvas_rotation);
+        let center = [(view_min[0] + view_max[0]) / 2.0, (view_min[1] + view_max[1]) / 2.0];
+        let font = self.current_font.clone().unwrap_or_else(|| PLAIN_TEXT_FONT.to_string());
+        let id = Uuid::new_v4();
+        let stamp = DrawObject::LatexFormula {
+            id,
+            pos: center,
+            formula: symbol.glyph.to_string(),
+            color: self.current_color.to_array(),
+            size: None,
+            scale: [1.0, 1.0],
+            auto_color: self.auto_formula_color,
+            font: Some(font),
+            pin_size: false,
+            props: self.ink_props(),
+        };
+        self.push_undo();
+        self.apply_op(DocumentOp::Add { objects: vec![stamp] });
+        self.measure_formulas();
+        self.selected_objects = vec![id];
+    }
+
+    fn find_replacements(&self) -> Vec<(Uuid, String, String, usize)> {
+        self.objects
+            .iter()
+            .filter_map(|obj| match obj {
+                DrawObject::LatexFormula { id, formula, .. } => {
+                    let count = find::match_ranges(formula, &self.find_query, self.find_whole_word).len();
+                    let replaced = find::replace_matches(formula, &self.find_query, &self.replace_text, self.find_whole_word)?;
+                    Some((*id, formula.clone(), replaced, count))
+                }
+                _ => None,
+            })
+            .collect()
+    }
+
+    fn render_find_panel(&mut self, ctx: &egui::Context) {
+        if !self.show_find_panel {
+            return;
+        }
+
+        let replacements = self.find_replacements();
+        let mut open = true;
+        let mut replace_all = false;
+        let mut select_all = false;
+        let mut focus = None;
+        let read_only = self.read_only();
+        egui::Window::new(tr(if read_only { "Find" } else { "Find and replace" }))
+            .open(&mut open)
+            .default_width(340.0)
+            .show(ctx, |ui| {
+                egui::Grid::new("find_grid").num_columns(2).show(ui, |ui| {
+                    ui.label(tr("Find:"));
+                    ui.text_edit_singleline(&mut self.find_query);
+                    ui.end_row();
+                    if !read_only {
+                        ui.label(tr("Replace with:"));
+                        ui.text_edit_singleline(&mut self.replace_text);
+                        ui.end_row();
+                    }
+                });
+                ui.checkbox(&mut self.find_whole_word, tr("Whole words only"))
+                    .on_hover_text(tr("Skip matches inside longer names, e.g. the x in \\max"));
+
+                let total: usize = replacements.iter().map(|(_, _, _, count)| count).sum();
+                if self.find_query.is_empty() {
+                    ui.label(tr("Searches formula sources and text objects."));
+                } else {
+                    ui.label(trf("{} matches in {} objects", &[&total, &replacements.len()]));
+                }
+
+                egui::ScrollArea::vertical().max_height(240.0).show(ui, |ui| {
+                    for (id, before, after, _) in &replacements {
+                        let preview = format!("{}  →  {}", before, after);
+                        if ui.selectable_label(self.selected_objects.contains(id), preview).clicked() {
+                            focus = Some(*id);
+                        }
+                    }
+                });
+
+                ui.horizontal(|ui| {
+                    if !read_only && ui.add_enabled(!replacements.is_empty(), egui::Button::new(tr("Replace all"))).clicked() {
+                        replace_all = true;
+                    }
+                    if ui.add_enabled(!replacements.is_empty(), egui::Button::new(tr("Select matches"))).clicked() {
+                        select_all = true;
+                    }
+                });
+            });
+
+        if let Some(id) = focus {
+            self.selected_objects = vec![id];
+            self.scroll_object_into_view(id);
+            self.needs_repaint = true;
+        }
+        if select_all {
+            self.selected_objects = replacements.iter().map(|(id, ..)| *id).collect();
+            self.needs_repaint = true;
+        }
+        if replace_all {
+            self.push_undo();
+            let objects = replacements
+                .into_iter()
+                .filter_map(|(id, _, replaced, _)| {
+                    let mut obj = self.objects.iter().find(|o| o.id() == id)?.clone();
+                    if let DrawObject::LatexFormula { formula, size, .. } = &mut obj {
+                        *size = None;
+                        *formula = replaced;
+                    }
+                    Some(obj)
+                })
+                .collect();
+            self.apply_op(DocumentOp::Update { objects });
+            self.measure_formulas();
+        }
+        self.show_find_panel = open;
+    }
+
+    fn previous_char_boundary(&self) -> usize {
+        self.text_input[..self.text_cursor_pos]
+            .chars()
+            .next_back()
+            .map_or(0, |c| self.text_cursor_pos - c.len_utf8())
+    }
+
+    fn set_selection_font(&mut self, font: Option<String>) {
+        self.push_undo();
+        let objects = self
+            .objects
+            .iter()
+            .filter(|obj| self.selected_objects.contains(&obj.id()))
+            .cloned()
+            .map(|mut obj| {
+                if let DrawObject::LatexFormula { size, font: formula_font, .. } = &mut obj {
+                    *formula_font = font.clone();
+                    *size = None;
+                }
+                obj
+            })
+            .collect();
+        self.apply_op(DocumentOp::Update { objects });
+        self.measure_formulas();
+    }
+
+    fn render_snapshots_panel(&mut self, ctx: &egui::Context) {
+        if !self.show_snapshots {
+            return;
+        }
+
+        let mut open = true;
+        let mut restore = None;
+        let mut delete = None;
+        let mut export_timelapse = false;
+        egui::Window::new(tr("Snapshots"))
+            .open(&mut open)
+            .default_width(280.0)
+            .show(ctx, |ui| {
+                ui.horizontal(|ui| {
+                    ui.add(egui::TextEdit::singleline(&mut self.snapshot_name).hint_text(tr("Snapshot name")));
+                    if ui.button(tr("Take snapshot")).clicked() {
+                        self.take_snapshot();
+                    }
+                });
+                ui.separator();
+                if self.board_snapshots().next().is_none() {
+                    ui.label(tr("No snapshots yet."));
+                }
+                ui.separator();
+                ui.horizontal(|ui| {
+                    ui.label(tr("Timelapse folder:"));
+                    ui.text_edit_singleline(&mut self.timelapse_dir);
+                });
+                self.export_framing_ui(ui);
+                if ui.button(tr("Export timelapse")).on_hover_text(tr("Writes one PNG per snapshot, oldest first, ending with the current board")).clicked() {
+                    export_timelapse = true;
+                }
+                if let Some(status) = &self.export_status {
+                    ui.label(status);
+                }
+                ui.separator();
+                egui::ScrollArea::vertical().max_height(300.0).show(ui, |ui| {
+                    let snapshots: Vec<(usize, &Snapshot)> = self.board_snapshots().collect();
+                    for (index, snapshot) in snapshots.into_iter().rev() {
+                        ui.horizontal(|ui| {
+                            ui.vertical(|ui| {
+                                ui.strong(&snapshot.name);
+                                ui.label(trf(
+                                    "{} · {} objects",
+                                    &[&clock::format_timestamp(snapshot.timestamp), &snapshot.objects.len()],
+                                ));
+                            });
+                            if ui.button(tr("Restore")).clicked() {
+                                restore = Some(index);
+                            }
+                            if ui.button(tr("Delete")).clicked() {
+                                delete = Some(index);
+                            }
+                        });
+                    }
+                });
+            });
+
+        if let Some(index) = restore {
+            self.restore_snapshot(index);
+        }
+        if let Some(index) = delete {
+            self.snapshots.remove(index);
+        }
+        if export_timelapse {
+            self.export_timelapse();
+        }
+        self.show_snapshots = open;
+    }
+
+    fn handle_keyboard_shortcuts(&mut self, ctx: &egui::Context) {
+        let typing = ctx.wants_keyboard_input();
+        if !typing {
+            let (copy, cut, paste, v_pressed, v_released) = ctx.input(|i| {
+                let mut clipboard = (false, false, None, false, false);
+                for event in &i.events {
+                    match event {
+                        egui::Event::Copy => clipboard.0 = true,
+                        egui::Event::Cut => clipboard.1 = true,
+                        egui::Event::Paste(text) => clipboard.2 = Some(text.clone()),
+                        egui::Event::Key { key: egui::Key::V, pressed: true, .. } => clipboard.3 = true,
+                        egui::Event::Key { key: egui::Key::V, pressed: false, .. } => clipboard.4 = true,
+                        _ => {}
+                    }
+                }
+                clipboard
+            });
+            if copy || cut {
+                self.copy_selection(ctx, cut);
+            }
+            let at = ctx.input(|i| i.pointer.hover_pos())
+                .filter(|pos| self.canvas_rect.contains(*pos))
+                .map(|pos| canvas::screen_to_canvas(pos, self.canvas_offset, self.canvas_zoom, self.canvas_rotation));
+            // The window layer drops Ctrl+V without an event when the clipboard holds no text,
+            // so a V release without a press or paste means an image paste.
+            self.v_press_seen |= v_pressed || paste.is_some();
+            if let Some(text) = paste {
+                self.paste_objects(&text, at);
+            }
+            if v_released {
+                if !self.v_press_seen {
+                    if let Ok(data) = images::clipboard_image() {
+                        let at = at.unwrap_or_else(|| self.drop_position(None));
+                        self.insert_images(vec![data], at);
+                    }
+                }
+                self.v_press_seen = false;
+            }
+        }
+        if !typing {
+            let triggered = self.settings.export_recipes
+                .iter()
+                .position(|recipe| {
+                    recipes::parse_shortcut(&recipe.shortcut).is_some_and(|shortcut| ctx.input_mut(|i| i.consume_shortcut(&shortcut)))
+                });
+            if let Some(index) = triggered {
+                self.run_recipe(ctx, index);
+            }
+        }
+        let hold_key = egui::Key::from_name(self.settings.eraser_hold_key.trim());
+        ctx.input(|i| {
+            if let Some((since, previous)) = self.held_tool {
+                if !hold_key.is_some_and(|key| i.key_down(key)) {
+                    self.held_tool = None;
+                    if i.time - since >= TOOL_HOLD_SECS && self.current_tool == Tool::Eraser {
+                        self.set_tool(previous);
+                    }
+                }
+            }
+            if !typing {
+                if let Some(key) = hold_key {
+                    if i.key_pressed(key) && self.held_tool.is_none() && i.modifiers.is_none() {
+                        self.held_tool = Some((i.time, self.current_tool));
+                        self.set_tool(Tool::Eraser);
+                    }
+                }
+                if i.key_pressed(egui::Key::B) {
+                    self.set_tool(Tool::Brush);
+                }
+                if i.key_pressed(egui::Key::L) {
+                    self.set_tool(Tool::Line);
+                }
+                if i.key_pressed(egui::Key::C) && !i.modifiers.command {
+                    self.set_tool(Tool::Circle);
+                }
+                if i.key_pressed(egui::Key::R) && i.modifiers.ctrl {
+                    self.repeat_last_object();
+                } else if i.key_pressed(egui::Key::R) {
+                    self.set_tool(Tool::Square);
+                }
+                if i.key_pressed(egui::Key::E) {
+                    self.set_tool(Tool::Eraser);
+                }
+                if i.key_pressed(egui::Key::S) && !i.modifiers.ctrl {
+                    self.set_tool(Tool::Select);
+                }
+                if i.key_pressed(egui::Key::A) && i.modifiers.command {
+                    self.select_all();
+                }
+                if i.key_pressed(egui::Key::Delete) || i.key_pressed(egui::Key::Backspace) {
+                    self.delete_selection();
+                }
+                if i.key_pressed(egui::Key::T) {
+                    self.set_tool(Tool::Text);
+                }
+                if i.key_pressed(egui::Key::K) {
+                    self.set_tool(Tool::Frame);
+                }
+                if i.key_pressed(egui::Key::I) {
+                    self.set_tool(Tool::Highlighter);
+                }
+                if i.key_pressed(egui::Key::X) && !i.modifiers.command {
+                    self.set_tool(self.previous_tool);
+                }
+                if i.key_pressed(egui::Key::N) {
+                    self.jump_to_free_space();
+                }
+                if i.key_pressed(egui::Key::Z) && i.modifiers.ctrl && !i.modifiers.shift {
+                    self.undo();
+                }
+                if (i.key_pressed(egui::Key::Z) && i.modifiers.ctrl && i.modifiers.shift)
+                    || (i.key_pressed(egui::Key::Y) && i.modifiers.ctrl)
+                {
+                    self.redo();
+                }
+                if i.key_pressed(egui::Key::Home) {
+                    self.go_to_origin();
+                }
+                if i.key_pressed(egui::Key::F) && i.modifiers.command {
+                    self.show_find_panel = !self.show_find_panel;
+                }
+                if i.key_pressed(egui::Key::M) && i.modifiers.command {
+                    self.open_latex_dialog(i.pointer.hover_pos());
+                }
+                for (key, step, jump) in [(egui::Key::CloseBracket, ZOrder::Forward, ZOrder::Front), (egui::Key::OpenBracket, ZOrder::Backward, ZOrder::Back)] {
+                    if i.key_pressed(key) && i.modifiers.command {
+                        self.reorder_selection(if i.modifiers.shift { jump } else { step });
+                    }
+                }
+                if i.key_pressed(egui::Key::G) && i.modifiers.command {
+                    if i.modifiers.shift {
+                        self.ungroup_selection();
+                    } else {
+                        self.group_selection();
+                    }
+                }
+                if i.key_pressed(egui::Key::D) && i.modifiers.command && i.modifiers.shift {
+                    self.linked_duplicate();
+                } else if i.key_pressed(egui::Key::D) && i.modifiers.command {
+                    self.duplicate_selection();
+                } else if i.key_pressed(egui::Key::D) && !i.modifiers.command {
+                    self.toggle_dim_selection();
+                }
+                for (index, key) in [egui::Key::Num1, egui::Key::Num2, egui::Key::Num3, egui::Key::Num4].into_iter().enumerate() {
+                    if i.key_pressed(key) && !i.modifiers.ctrl {
+                        self.apply_size_preset(index);
+                    }
+                }
+                if i.key_pressed(egui::Key::H) && i.modifiers.shift {
+                    self.set_selection_hidden(true);
+                } else if i.key_pressed(egui::Key::H) {
+                    self.show_toolbar = !self.show_toolbar;
+                    self.summon_palette = self.show_toolbar;
+                    self.needs_repaint = true;
+                }
+            }
+        });
+    }
+
+    fn handle_object_navigation(&mut self, ctx: &egui::Context) {
+        if self.editing_text.is_some() || self.cropping.is_some() || ctx.wants_keyboard_input() {
+            self.pending_focus_cycle = 0;
+            return;
+        }
+
+        if self.pending_focus_cycle != 0 && !self.objects.is_empty() {
+            let len = self.objects.len() as i32;
+            let current = self.selected_objects
+                .last()
+                .and_then(|id| self.objects.iter().position(|o| o.id() == *id));
+            let next = match current {
+                Some(index) => (index as i32 + self.pending_focus_cycle).rem_euclid(len),
+                None if self.pending_focus_cycle > 0 => 0,
+                None => len - 1,
+            } as usize;
+            let focused_id = self.objects[next].id();
+            self.selected_objects = vec![focused_id];
+            self.scroll_object_into_view(focused_id);
+            self.needs_repaint = true;
+        }
+        self.pending_focus_cycle = 0;
+
+        if self.selected_objects.is_empty() {
+            self.nudge_view(ctx);
+            return;
+        }
+
+        let continue_pressed = ctx.input_mut(|i| i.consume_key(egui::Modifiers::SHIFT, egui::Key::Enter));
+        if continue_pressed {
+            self.continue_derivation();
+            return;
+        }
+
+        let enter_pressed = ctx.input_mut(|i| i.consume_key(egui::Modifiers::NONE, egui::Key::Enter));
+        if enter_pressed && self.selected_objects.len() == 1 {
+            let id = self.selected_objects[0];
+            if let Some(DrawObject::LatexFormula { formula, .. }) = self.objects.iter().find(|o| o.id() == id) {
+                self.editing_text = Some(id);
+                self.text_input = formula.clone();
+                self.text_cursor_pos = formula.len();
+                self.needs_repaint = true;
+            }
+            return;
+        }
+
+        let (escape, arrows, modifiers) = ctx.input(|i| {
+            let arrows = [
+                i.key_pressed(egui::Key::ArrowLeft),
+                i.key_pressed(egui::Key::ArrowRight),
+                i.key_pressed(egui::Key::ArrowUp),
+                i.key_pressed(egui::Key::ArrowDown),
+            ];
+            (i.key_pressed(egui::Key::Escape), arrows, i.modifiers)
+        });
+
+        if escape {
+            self.selected_objects.clear();
+            self.needs_repaint = true;
+            return;
+        }
+
+        let [left, right, up, down] = arrows;
+        if !(left || right || up || down) {
+            return;
+        }
+        let Some(bounds) = selection::get_selection_bounds(&self.objects, &self.selected_objects) else {
+            return;
+        };
+        let center = [
+            (bounds.0[0] + bounds.1[0]) / 2.0,
+            (bounds.0[1] + bounds.1[1]) / 2.0,
+        ];
+
+        self.push_undo();
+        if modifiers.command {
+            let step = if modifiers.shift { 1.0_f32 } else { 15.0_f32 }.to_radians();
+            let rotation = if left { -step } else if right { step } else { 0.0 };
+            let scale = if up { 1.1 } else if down { 1.0 / 1.1 } else { 1.0 };
+            self.apply_op(DocumentOp::Transform { ids: self.selected_objects.clone(), scale: [scale, scale], rotation, translation: [0.0, 0.0], pivot: center });
+        } else {
+            let step = if modifiers.shift { 10.0 } else { 1.0 };
+            let screen_delta = egui::vec2(
+                (right as i32 - left as i32) as f32 * step,
+                (down as i32 - up as i32) as f32 * step,
+            );
+            let delta = egui::emath::Rot2::from_angle(-self.canvas_rotation) * screen_delta / self.canvas_zoom;
+            self.apply_op(DocumentOp::Transform { ids: self.selected_objects.clone(), scale: [1.0, 1.0], rotation: 0.0, translation: [delta.x, delta.y], pivot: center });
+        }
+        self.needs_repaint = true;
+    }
+
+    fn nudge_view(&mut self, ctx: &egui::Context) {
+        if ctx.wants_keyboard_input() {
+            return;
+        }
+        let (delta, shift) = ctx.input(|i| {
+            let delta = egui::vec2(
+                i.key_pressed(egui::Key::ArrowLeft) as i32 as f32 - i.key_pressed(egui::Key::ArrowRight) as i32 as f32,
+                i.key_pressed(egui::Key::ArrowUp) as i32 as f32 - i.key_pressed(egui::Key::ArrowDown) as i32 as f32,
+            );
+            (delta, i.modifiers.shift)
+        });
+        if delta != egui::Vec2::ZERO {
+            self.canvas_offset += delta * if shift { 200.0 } else { 50.0 };
+            self.needs_repaint = true;
+        }
+    }
+
+    fn scroll_object_into_view(&mut self, id: Uuid) {
+        let Some(obj) = self.objects.iter().find(|o| o.id() == id) else {
+            return;
+        };
+        let (min, max) = obj.bounds();
+        let center = [(min[0] + max[0]) / 2.0, (min[1] + max[1]) / 2.0];
+        let screen_center = canvas::canvas_to_screen(center, self.canvas_offset, self.canvas_zoom, self.canvas_rotation);
+        if self.canvas_rect.is_positive() && !self.canvas_rect.shrink(40.0).contains(screen_center) {
+            self.canvas_offset += self.canvas_rect.center() - screen_center;
+        }
+    }
+
+    fn selected_clones(&self) -> Vec<DrawObject> {
+        self.objects
+            .iter()
+            .filter(|obj| self.selected_objects.contains(&obj.id()))
+            .cloned()
+            .collect()
+    }
+
+    fn copy_selection(&mut self, ctx: &egui::Context, cut: bool) {
+        let objects = self.selected_clones();
+        if objects.is_empty() {
+            return;
+        }
+        match ClipboardContents::new(objects).to_text() {
+            Ok(text) => ctx.copy_text(text),
+            Err(e) => {
+                eprintln!("Error copying objects: {}", e);
+                return;
+            }
+        }
+        self.paste_count = 0;
+        if cut {
+            self.delete_selection();
+        }
+    }
+
+    fn paste_objects(&mut self, text: &str, at: Option<[f32; 2]>) {
+        let Some(contents) = ClipboardContents::from_text(text) else {
+            let files: Vec<Vec<u8>> = images::image_paths(text)
+                .into_iter()
+                .filter_map(|path| std::fs::read(&path).map_err(|e| eprintln!("Error reading {}: {}", path.display(), e)).ok())
+                .collect();
+            let files = if files.is_empty() { images::clipboard_image().into_iter().collect() } else { files };
+            let at = at.unwrap_or_else(|| self.drop_position(None));
+            self.insert_images(files, at);
+            return;
+        };
+        self.paste_count += 1;
+        let offset = match at.zip(contents.bounds()) {
+            Some((pos, (min, _))) => [pos[0] - min[0], pos[1] - min[1]],
+            None => {
+                let step = 20.0 * self.paste_count as f32;
+                [step, step]
+            }
+        };
+        self.insert_copies(contents.instantiate(offset));
+    }
+
+    fn duplicate_selection(&mut self) {
+        let objects = self.selected_clones();
+        if objects.is_empty() {
+            return;
+        }
+        self.insert_copies(ClipboardContents::new(objects).instantiate([20.0, 20.0]));
+    }
+
+    fn page_background_menu(&mut self, ui: &mut egui::Ui, page: Uuid) {
+        if !ui.ctx().input(|i| i.pointer.any_down()) {
+            self.page_background_undo_pushed = false;
+        }
+        let Some((mut background, mut grid)) = self.objects.iter().find_map(|obj| match obj {
+            DrawObject::Frame { id, background, grid, .. } if *id == page => Some((*background, *grid)),
+            _ => None,
+        }) else {
+            return;
+        };
+        let mut changed = false;
+        ui.horizontal(|ui| {
+            let mut overridden = background.is_some();
+            if ui.checkbox(&mut overridden, tr("Color")).changed() {
+                background = overridden.then(|| self.background_color.to_array());
+                changed = true;
+            }
+            if let Some(color) = &mut background {
+                let mut picked = egui::Color32::from_rgba_unmultiplied(color[0], color[1], color[2], color[3]);
+                if egui::color_picker::color_edit_button_srgba(ui, &mut picked, egui::color_picker::Alpha::Opaque).changed() {
+                    *color = picked.to_array();
+                    changed = true;
+                }
+            }
+        });
+        ui.label(tr("Grid:"));
+        changed |= ui.radio_value(&mut grid, None, tr("Same as board")).changed();
+        for style in GridStyle::ALL {
+            changed |= ui.radio_value(&mut grid, Some(style), tr(style.label())).changed();
+        }
+        if !changed {
+            return;
+        }
+        if !self.page_background_undo_pushed {
+            self.push_undo();
+            self.page_background_undo_pushed = true;
+        }
+        self.update_objects(&[page], |obj| {
+            if let DrawObject::Frame { background: frame_background, grid: frame_grid, .. } = obj {
+                *frame_background = background;
+                *frame_grid = grid;
+            }
+        });
+    }
+
+    fn duplicate_page(&mut self, page: Uuid, without_layer: Option<Uuid>) {
+        let Some((min, max, name)) = self.objects.iter().find_map(|obj| match obj {
+            DrawObject::Frame { id, min, max, name, .. } if *id == page => Some((*min, *max, name.clone())),
+            _ => None,
+        }) else {
+            return;
+        };
+        let objects: Vec<DrawObject> = self.objects
+            .iter()
+            .filter(|obj| selection::page_of(&self.objects, obj) == Some(page))
+            .filter(|obj| without_layer.is_none() || obj.props().layer != without_layer)
+            .cloned()
+            .collect();
+
+        let width = max[0] - min[0];
+        let gap = 40.0;
+        let mut offset = [width + gap, 0.0];
+        while self.objects.iter().any(|obj| match obj {
+            DrawObject::Frame { min: other_min, max: other_max, .. } => {
+                other_max[0] > min[0] + offset[0] && other_min[0] < max[0] + offset[0] && other_max[1] > min[1] && other_min[1] < max[1]
+            }
+            _ => false,
+        }) {
+            offset[0] += width + gap;
+        }
+
+        let mut copies = ClipboardContents::new(objects).instantiate(offset);
+        let suffix = match without_layer.and_then(|id| self.layers.iter().find(|layer| layer.id == id)) {
+            Some(layer) => format!(" {}", trf("(without {})", &[&layer.name])),
+            None => format!(" {}", tr("(copy)")),
+        };
+        for copy in &mut copies {
+            if let DrawObject::Frame { name: copy_name, .. } = copy {
+                *copy_name = format!("{}{}", name, suffix);
+            }
+        }
+        self.insert_copies(copies);
+    }
+
+    fn drop_position(&self, pointer: Option<egui::Pos2>) -> [f32; 2] {
+        let screen_pos = pointer
+            .filter(|pos| self.canvas_rect.contains(*pos))
+            .unwrap_or(self.canvas_rect.center());
+        canvas::screen_to_canvas(screen_pos, self.canvas_offset, self.canvas_zoom, self.canvas_rotation)
+    }
+
+    fn insert_images(&mut self, files: Vec<Vec<u8>>, at: [f32; 2]) {
+        let mut inserted = Vec::new();
+        for data in files {
+            let Some(size) = images::insert_size(&data) else {
+                eprintln!("Skipping a file that is not a readable image");
+                continue;
+            };
+            let step = 20.0 * inserted.len() as f32;
+            let min = [at[0] + step, at[1] + step];
+            let max = [min[0] + size[0], min[1] + size[1]];
+            inserted.push(DrawObject::Image { id: Uuid::new_v4(), min, max, data, adjustments: ImageAdjustments::default(), props: ObjectProps::default() });
+        }
+        if !inserted.is_empty() {
+            self.insert_copies(inserted);
+        }
+    }
+
+    fn paste_clipboard_image(&mut self, pointer: Option<egui::Pos2>) {
+        match images::clipboard_image() {
+            Ok(data) => {
+                let at = self.drop_position(pointer);
+                self.insert_images(vec![data], at);
+            }
+            Err(e) => eprintln!("Error pasting image: {}", e),
+        }
+    }
+
+    fn handle_dropped_files(&mut self, ctx: &egui::Context) {
+        let (hovering, dropped, pointer) = ctx.input(|i| (!i.raw.hovered_files.is_empty(), i.raw.dropped_files.clone(), i.pointer.hover_pos()));
+        if hovering {
+            let painter = ctx.layer_painter(egui::LayerId::new(egui::Order::Foreground, egui::Id::new("drop_hint")));
+            painter.rect_filled(self.canvas_rect, 0.0, egui::Color32::from_black_alpha(60));
+            painter.text(
+                self.canvas_rect.center(),
+                egui::Align2::CENTER_CENTER,
+                tr("Drop images to insert them"),
+                egui::FontId::proportional(20.0),
+                egui::Color32::WHITE,
+            );
+        }
+        if dropped.is_empty() {
+            return;
+        }
+        let files = dropped
+            .into_iter()
+            .filter_map(|file| match (file.bytes, file.path) {
+                (Some(bytes), _) => Some(bytes.to_vec()),
+                (None, Some(path)) => std::fs::read(&path).map_err(|e| eprintln!("Error reading {}: {}", path.display(), e)).ok(),
+                (None, None) => None,
+            })
+            .collect();
+        let at = self.drop_position(pointer);
+        self.insert_images(files, at);
+    }
+
+    fn insert_copies(&mut self, copies: Vec<DrawObject>) {
+        self.push_undo();
+        self.selected_objects = copies.iter().map(|obj| obj.id()).collect();
+        self.apply_op(DocumentOp::Add { objects: copies });
+        selection::sync_instances(&mut self.objects);
+        self.set_tool(Tool::Select);
+    }
+
+    fn repeat_last_object(&mut self) {
+        let Some(last) = self.objects.iter().rev().find(|obj| {
+            !matches!(obj, DrawObject::Frame { .. } | DrawObject::Image { .. })
+        }) else {
+            return;
+        };
+        let (min, max) = last.bounds();
+        let mut copy = last.with_new_id();
+        let id = copy.id();
+        let offset = [0.0, max[1] - min[1] + 20.0];
+        selection::transform_objects(std::slice::from_mut(&mut copy), &[id], [1.0, 1.0], 0.0, offset, [0.0, 0.0]);
+
+        self.push_undo();
+        self.apply_op(DocumentOp::Add { objects: vec![copy] });
+        self.selected_objects = vec![id];
+        self.scroll_object_into_view(id);
+    }
+
+    fn continue_derivation(&mut self) {
+        let [id] = self.selected_objects[..] else {
+            return;
+        };
+        let Some(source @ DrawObject::LatexFormula { .. }) = self.objects.iter().find(|o| o.id() == id) else {
+            return;
+        };
+        let (min, max) = source.bounds();
+        let mut copy = source.with_new_id();
+        let copy_id = copy.id();
+        let offset = [0.0, max[1] - min[1] + 10.0];
+        selection::transform_objects(std::slice::from_mut(&mut copy), &[copy_id], [1.0, 1.0], 0.0, offset, [0.0, 0.0]);
+        let DrawObject::LatexFormula { formula, .. } = &copy else {
+            return;
+        };
+        self.text_input = formula.clone();
+        self.text_cursor_pos = formula.len();
+
+        self.push_undo();
+        self.apply_op(DocumentOp::Add { objects: vec![copy] });
+        self.selected_objects = vec![copy_id];
+        self.editing_text = Some(copy_id);
+        self.scroll_object_into_view(copy_id);
+    }
+
+    fn set_selection_hidden(&mut self, hidden: bool) {
+        if self.selected_objects.is_empty() {
+            return;
+        }
+        self.push_undo();
+        let ids = self.selected_objects.clone();
+        self.update_objects(&ids, |obj| obj.props_mut().hidden = hidden);
+        if hidden && !self.show_hidden {
+            self.selected_objects.clear();
+        }
+        self.needs_repaint = true;
+    }
+
+    fn unhide_all(&mut self) {
+        if !self.objects.iter().any(|obj| obj.props().hidden) {
+            return;
+        }
+        self.push_undo();
+        let ids: Vec<Uuid> = self.objects.iter().filter(|obj| obj.props().hidden).map(|obj| obj.id()).collect();
+        self.update_objects(&ids, |obj| obj.props_mut().hidden = false);
+    }
+
+    fn group_selection(&mut self) {
+        if self.selected_objects.len() < 2 {
+            return;
+        }
+        self.push_undo();
+        self.apply_op(DocumentOp::Group { ids: self.selected_objects.clone(), group: Some(Uuid::new_v4()) });
+    }
+
+    fn ungroup_selection(&mut self) {
+        let grouped = self.objects
+            .iter()
+            .any(|obj| obj.props().group.is_some() && self.selected_objects.contains(&obj.id()));
+        if !grouped {
+            return;
+        }
+        self.push_undo();
+        self.apply_op(DocumentOp::Group { ids: self.selected_objects.clone(), group: None });
+    }
+
+    fn select_all(&mut self) {
+        self.selected_objects = self.objects
+            .iter()
+            .filter(|obj| (self.show_hidden || !obj.props().hidden) && self.layer_editable(obj) && self.revealed(obj))
+            .map(|obj| obj.id())
+            .collect();
+        self.needs_repaint = true;
+    }
+
+    fn delete_selection(&mut self) {
+        if self.selected_objects.is_empty() {
+            return;
+        }
+        self.push_undo();
+        let ids = std::mem::take(&mut self.selected_objects);
+        let orphans_before = selection::orphaned_instances(&self.objects);
+        self.apply_op(DocumentOp::Erase { ids });
+        let orphaned: Vec<Uuid> = selection::orphaned_instances(&self.objects).into_iter().filter(|id| !orphans_before.contains(id)).collect();
+        if !orphaned.is_empty() {
+            self.apply_op(DocumentOp::Erase { ids: orphaned });
+        }
+    }
+
+    fn remove_orphaned_instances(&mut self) {
+        let ids = selection::orphaned_instances(&self.objects);
+        if ids.is_empty() {
+            return;
+        }
+        self.push_undo();
+        self.selected_objects.retain(|id| !ids.contains(id));
+        self.apply_op(DocumentOp::Erase { ids });
+    }
+
+    fn reorder_selection(&mut self, order: ZOrder) {
+        if self.selected_objects.is_empty() {
+            return;
+        }
+        self.push_undo();
+        self.apply_op(DocumentOp::Reorder { ids: self.selected_objects.clone(), order });
+    }
+
+    fn apply_op(&mut self, op: DocumentOp) {
+        ops::apply(&mut self.objects, &op);
+        self.needs_repaint = true;
+    }
+
+    fn update_objects(&mut self, ids: &[Uuid], mut edit: impl FnMut(&mut DrawObject)) {
+        let objects: Vec<DrawObject> = self
+            .objects
+            .iter()
+            .filter(|obj| ids.contains(&obj.id()))
+            .cloned()
+            .map(|mut obj| {
+                edit(&mut obj);
+                obj
+            })
+            .collect();
+        if !objects.is_empty() {
+            self.apply_op(DocumentOp::Update { objects });
+        }
+    }
+    fn replay_ops(&mut self, path: &str) {
+        match ops::load_ops(path) {
+            Ok(ops) => {
+                self.push_undo();
+                for op in ops {
+                    self.apply_op(op);
+                }
+            }
+            Err(e) => eprintln!("Error replaying {}: {}", path, e),
+        }
+    }
+
+    fn click_select(&mut self, canvas_pos: [f32; 2], toggle: bool) {
+        let Some(id) = self.object_at(canvas_pos) else {
+            if !toggle {
+                self.selected_objects.clear();
+            }
+            self.needs_repaint = true;
+            return;
+        };
+        let group = self.objects.iter().find(|obj| obj.id() == id).and_then(|obj| obj.props().group);
+        let members: Vec<Uuid> = match group {
+            Some(group) => self.objects.iter().filter(|obj| obj.props().group == Some(group)).map(|obj| obj.id()).collect(),
+            None => vec![id],
+        };
+        if !toggle {
+            self.selected_objects = members;
+        } else if self.selected_objects.contains(&id) {
+            self.selected_objects.retain(|selected| !members.contains(selected));
+        } else {
+            for member in members {
+                if !self.selected_objects.contains(&member) {
+                    self.selected_objects.push(member);
+                }
+            }
+        }
+        self.needs_repaint = true;
+    }
+
+    fn expand_selection_to_groups(&mut self) {
+        let groups: Vec<Uuid> = self.objects
+            .iter()
+            .filter(|obj| self.selected_objects.contains(&obj.id()))
+            .filter_map(|obj| obj.props().group)
+            .collect();
+        if groups.is_empty() {
+            return;
+        }
+        for obj in &self.objects {
+            let id = obj.id();
+            if obj.props().group.is_some_and(|group| groups.contains(&group)) && !self.selected_objects.contains(&id) {
+                self.selected_objects.push(id);
+            }
+        }
+    }
+
+    fn linked_duplicate(&mut self) {
+        let Some((min, _)) = selection::get_selection_bounds(&self.objects, &self.selected_objects) else {
+            return;
+        };
+        let selected: Vec<&DrawObject> = self.objects
+            .iter()
+            .filter(|obj| self.selected_objects.contains(&obj.id()))
+            .collect();
+        let source = match selected.as_slice() {
+            [DrawObject::Instance { source, .. }] => Some(*source),
+            _ if selected.iter().any(|obj| matches!(obj, DrawObject::Instance { .. })) => return,
+            [first, ..] => {
+                let group = first.props().group;
+                if selected.iter().all(|obj| obj.props().group == group) {
+                    group
+                } else if selected.iter().all(|obj| obj.props().group.is_none()) {
+                    None
+                } else {
+                    return;
+                }
+            }
+            [] => return,
+        };
+
+        self.push_undo();
+        let source = match source {
+            Some(source) => source,
+            None => {
+                let group = Uuid::new_v4();
+                self.apply_op(DocumentOp::Group { ids: self.selected_objects.clone(), group: Some(group) });
+                group
+            }
+        };
+        let id = Uuid::new_v4();
+        self.apply_op(DocumentOp::Add {
+            objects: vec![DrawObject::Instance {
+                id,
+                source,
+                origin: [min[0] + 20.0, min[1] + 20.0],
+                scale: [1.0, 1.0],
+                rotation: 0.0,
+                members: Vec::new(),
+                synced: Vec::new(),
+                props: ObjectProps::default(),
+            }],
+        });
+        selection::sync_instances(&mut self.objects);
+        self.selected_objects = vec![id];
+    }
+
+    fn unlink_selected_instances(&mut self) {
+        let replacements: Vec<(Uuid, Vec<DrawObject>)> = self.objects
+            .iter()
+            .filter_map(|obj| match obj {
+                DrawObject::Instance { id, members, .. } if self.selected_objects.contains(id) => {
+                    let group = Uuid::new_v4();
+                    let copies = members
+                        .iter()
+                        .map(|member| {
+                            let mut copy = member.with_new_id();
+                            copy.props_mut().group = Some(group);
+                            copy
+                        })
+                        .collect();
+                    Some((*id, copies))
+                }
+                _ => None,
+            })
+            .collect();
+        if replacements.is_empty() {
+            return;
+        }
+        self.push_undo();
+        for (id, copies) in replacements {
+            self.selected_objects.retain(|selected| *selected != id);
+            self.selected_objects.extend(copies.iter().map(|copy: &DrawObject| copy.id()));
+            self.apply_op(DocumentOp::Replace { ids: vec![id], objects: copies });
+        }
+    }
+
+    fn arrange_selection_in_grid(&mut self) {
+        if self.selected_objects.len() < 2 {
+            return;
+        }
+        self.push_undo();
+        selection::arrange_in_grid(&mut self.objects, &self.selected_objects, self.arrange_columns, self.arrange_spacing);
+        self.needs_repaint = true;
+    }
+
+    fn flatten_selection(&mut self) {
+        let ink = palette::auto_ink(self.background_color);
+        let flattened: Vec<DrawObject> = self.objects
+            .iter()
+            .filter(|obj| {
+                self.selected_objects.contains(&obj.id())
+                    && !obj.props().hidden
+                    && !matches!(obj, DrawObject::Frame { .. })
+            })
+            .cloned()
+            .map(|mut obj| {
+                if let DrawObject::LatexFormula { color, auto_color: auto_color @ true, .. } = &mut obj {
+                    *color = ink;
+                    *auto_color = false;
+                }
+                obj
+            })
+            .collect();
+        let ids: Vec<Uuid> = flattened.iter().map(|obj| obj.id()).collect();
+        let Some((min, max)) = export::content_bounds(&flattened) else {
+            return;
+        };
+        let padding = flattened
+            .iter()
+            .filter_map(|obj| match obj {
+                DrawObject::Stroke { points, width, .. } => Some(models::stroke_max_width(points, *width)),
+                DrawObject::Line { width, .. } | DrawObject::Circle { width, .. } | DrawObject::Rectangle { width, .. } => Some(*width),
+                _ => None,
+            })
+            .fold(0.0, f32::max)
+            / 2.0
+            + 2.0;
+        let min = [min[0] - padding, min[1] - padding];
+        let max = [max[0] + padding, max[1] + padding];
+        let scale = FLATTEN_SCALE.min(FLATTEN_MAX_PIXELS / (max[0] - min[0]).max(max[1] - min[1]).max(1.0));
+
+        let data = match export::rasterize_png(&flattened, (min, max), scale, &mut self.latex_renderer) {
+            Ok(data) => data,
+            Err(e) => {
+                eprintln!("Error flattening selection: {}", e);
+                return;
+            }
+        };
+
+        self.push_undo();
+        let id = Uuid::new_v4();
+        let image = DrawObject::Image { id, min, max, data, adjustments: ImageAdjustments::default(), props: ObjectProps::default() };
+        self.apply_op(DocumentOp::Replace { ids, objects: vec![image] });
+        self.selected_objects = vec![id];
+    }
+
+    fn tidy_selection(&mut self) {
+        self.push_undo();
+        selection::tidy_handwriting(&mut self.objects, &self.selected_objects);
+        self.needs_repaint = true;
+    }
+
+    fn toggle_dim_selection(&mut self) {
+        if self.selected_objects.is_empty() {
+            return;
+        }
+        let all_dimmed = self.objects
+            .iter()
+            .filter(|obj| self.selected_objects.contains(&obj.id()))
+            .all(|obj| obj.props().opacity < 1.0);
+        let opacity = if all_dimmed { 1.0 } else { DIM_OPACITY };
+        self.set_selection_opacity(opacity);
+    }
+
+    fn set_selection_opacity(&mut self, opacity: f32) {
+        self.push_undo();
+        let ids = self.selected_objects.clone();
+        self.update_objects(&ids, |obj| obj.props_mut().opacity = opacity);
+    }
+
+    fn set_tool(&mut self, tool: Tool) {
+        if tool != self.current_tool {
+            self.previous_tool = self.current_tool;
+            self.current_tool = tool;
+        }
+        self.needs_repaint = true;
+    }
+
+    fn apply_size_preset(&mut self, index: usize) {
+        let size = self.settings.size_presets_for(self.current_tool)[index];
+        if self.current_tool == Tool::Highlighter {
+            self.settings.highlighter_width = size;
+            self.save_settings();
+        } else {
+            self.brush_size = size;
+        }
+        self.needs_repaint = true;
+    }
+
+    fn capture_size_preset(&mut self, index: usize) {
+        let mut presets = self.settings.size_presets_for(self.current_tool);
+        presets[index] = self.stroke_ink().1;
+        self.settings.size_presets.insert(self.current_tool, presets);
+        self.save_settings();
+    }
+
+    fn go_to_origin(&mut self) {
+        self.canvas_zoom = 1.0;
+        self.canvas_offset = if self.canvas_rect.is_positive() {
+            self.canvas_rect.center().to_vec2()
+        } else {
+            egui::Vec2::ZERO
+        };
+        self.needs_repaint = true;
+    }
+
+    fn center_view_on(&mut self, point: [f32; 2]) {
+        let screen = canvas::canvas_to_screen(point, self.canvas_offset, self.canvas_zoom, self.canvas_rotation);
+        self.canvas_offset += self.canvas_rect.center() - screen;
+        self.needs_repaint = true;
+    }
+
+    fn jump_to_free_space(&mut self) {
+        let (view_min, view_max) = canvas::visible_canvas_bounds(self.canvas_rect, self.canvas_offset, self.canvas_zoom, self.canvas_rotation);
+        let overlaps = |(min, max): ([f32; 2], [f32; 2]), (other_min, other_max): ([f32; 2], [f32; 2])| {
+            min[0] < other_max[0] && max[0] > other_min[0] && min[1] < other_max[1] && max[1] > other_min[1]
+        };
+        let visible: Vec<DrawObject> = self.objects
+            .iter()
+            .filter(|obj| !matches!(obj, DrawObject::Frame { .. }) && overlaps(obj.bounds(), (view_min, view_max)))
+            .cloned()
+            .collect();
+        let cluster = export::content_bounds(if visible.is_empty() { &self.objects } else { &visible });
+        let Some((cluster_min, cluster_max)) = cluster else {
+            return;
+        };
+
+        let size = [view_max[0] - view_min[0], view_max[1] - view_min[1]];
+        let gap = FREE_SPACE_GAP / self.canvas_zoom;
+        let occupied: Vec<_> = self.objects
+            .iter()
+            .filter(|obj| !matches!(obj, DrawObject::Frame { .. }))
+            .map(|obj| obj.bounds())
+            .collect();
+        let find_free = |mut min: [f32; 2], step_x: bool| {
+            for _ in 0..FREE_SPACE_ATTEMPTS {
+                let area = (min, [min[0] + size[0], min[1] + size[1]]);
+                let blocking = occupied
+                    .iter()
+                    .filter(|bounds| overlaps(**bounds, area))
+                    .map(|(_, max)| if step_x { max[0] } else { max[1] })
+                    .reduce(f32::max);
+                match blocking {
+                    None => return Some(min),
+                    Some(edge) if step_x => min[0] = edge + gap,
+                    Some(edge) => min[1] = edge + gap,
+                }
+            }
+            None
+        };
+
+        let view_center = [(view_min[0] + view_max[0]) / 2.0, (view_min[1] + view_max[1]) / 2.0];
+        let right = find_free([cluster_max[0] + gap, cluster_min[1]], true);
+        let below = find_free([cluster_min[0], cluster_max[1] + gap], false);
+        let target = [right, below]
+            .into_iter()
+            .flatten()
+            .map(|min| [min[0] + size[0] / 2.0, min[1] + size[1] / 2.0])
+            .min_by(|a, b| models::distance(*a, view_center).total_cmp(&models::distance(*b, view_center)));
+        if let Some(target) = target {
+            self.center_view_on(target);
+        }
+    }
+
+    fn limit_panning(&mut self) {
+        if !self.settings.limit_panning || !self.canvas_rect.is_positive() {
+            return;
+        }
+        let Some(content) = export::content_bounds(&self.objects) else {
+            return;
+        };
+        let (content_min, content_max) = export::union_bounds(Some(content), ([0.0, 0.0], [0.0, 0.0]));
+        let (view_min, view_max) = canvas::visible_canvas_bounds(self.canvas_rect, self.canvas_offset, self.canvas_zoom, self.canvas_rotation);
+        let reach = [
+            (view_max[0] - view_min[0]) * (0.5 + PAN_MARGIN),
+            (view_max[1] - view_min[1]) * (0.5 + PAN_MARGIN),
+        ];
+        let center = [(view_min[0] + view_max[0]) / 2.0, (view_min[1] + view_max[1]) / 2.0];
+        let allowed = [
+            center[0].clamp(content_min[0] - reach[0], content_max[0] + reach[0]),
+            center[1].clamp(content_min[1] - reach[1], content_max[1] + reach[1]),
+        ];
+        if allowed != center {
+            let from = canvas::canvas_to_screen(center, self.canvas_offset, self.canvas_zoom, self.canvas_rotation);
+            let to = canvas::canvas_to_screen(allowed, self.canvas_offset, self.canvas_zoom, self.canvas_rotation);
+            self.canvas_offset += from - to;
+        }
+    }
+
+    fn render_lost_content_guard(&mut self, ctx: &egui::Context, painter: &egui::Painter) {
+        let Some((content_min, content_max)) = export::content_bounds(&self.objects) else {
+            return;
+        };
+        let (view_min, view_max) = canvas::visible_canvas_bounds(self.canvas_rect, self.canvas_offset, self.canvas_zoom, self.canvas_rotation);
+        let overlaps = content_min[0] <= view_max[0] && content_max[0] >= view_min[0]
+            && content_min[1] <= view_max[1] && content_max[1] >= view_min[1];
+        if overlaps {
+            return;
+        }
+
+        let content_center = [(content_min[0] + content_max[0]) / 2.0, (content_min[1] + content_max[1]) / 2.0];
+        let rect = self.canvas_rect.shrink(48.0);
+        let center = rect.center();
+        let direction = (canvas::canvas_to_screen(content_center, self.canvas_offset, self.canvas_zoom, self.canvas_rotation) - center).normalized();
+        let reach_x = if direction.x.abs() > f32::EPSILON { rect.width() / 2.0 / direction.x.abs() } else { f32::INFINITY };
+        let reach_y = if direction.y.abs() > f32::EPSILON { rect.height() / 2.0 / direction.y.abs() } else { f32::INFINITY };
+        let tip = center + direction * reach_x.min(reach_y);
+        let color = egui::Color32::from_rgb(50, 100, 255);
+        painter.arrow(tip - direction * 36.0, direction * 36.0, egui::Stroke::new(4.0, color));
+
+        let label_pos = tip - direction * 60.0;
+        egui::Area::new(egui::Id::new("lost_content_guard"))
+            .pivot(egui::Align2::CENTER_CENTER)
+            .fixed_pos(label_pos)
+            .show(ctx, |ui| {
+                if ui.button(tr("Back to content")).clicked() {
+                    self.center_view_on(content_center);
+                }
+            });
+    }
+
+    fn save_settings(&self) {
+        if let Err(e) = file_io::save_settings(&self.settings, SETTINGS_PATH) {
+            eprintln!("Error saving settings: {}", e);
+        }
+    }
+
+    fn set_toolbar_dock(&mut self, dock: ToolbarDock) {
+        self.settings.toolbar_dock = dock;
+        self.summon_palette = true;
+        self.save_settings();
+        self.needs_repaint = true;
+    }
+
+    fn apply_visuals(&mut self, ctx: &egui::Context) {
+        if self.applied_high_contrast == Some(self.settings.high_contrast_ui) {
+            return;
+        }
+        if self.settings.high_contrast_ui {
+            ctx.set_visuals(palette::high_contrast_visuals(ctx.style().visuals.dark_mode));
+        } else {
+            ctx.set_visuals(if ctx.style().visuals.dark_mode { egui::Visuals::dark() } else { egui::Visuals::light() });
+        }
+        self.applied_high_contrast = Some(self.settings.high_contrast_ui);
+    }
+
+    fn palette_menu(&mut self, ui: &mut egui::Ui) {
+        ui.menu_button(tr("Palettes"), |ui| {
+            for palette in &palette::PALETTES {
+                ui.label(palette.name);
+                ui.horizontal_wrapped(|ui| {
+                    for (name, [r, g, b]) in palette.colors {
+                        let color = egui::Color32::from_rgb(*r, *g, *b);
+                        let swatch = egui::Button::new("")
+                            .fill(color)
+                            .min_size(egui::vec2(18.0, 18.0));
+                        if ui.add(swatch).on_hover_text(*name).clicked() {
+                            self.current_color = color;
+                            self.needs_repaint = true;
+                            ui.close_menu();
+                        }
+                    }
+                });
+            }
+            ui.separator();
+            ui.horizontal(|ui| {
+                ui.label(tr("Language:"));
+                egui::ComboBox::from_id_salt("language")
+                    .selected_text(self.settings.language.label())
+                    .show_ui(ui, |ui| {
+                        for language in Language::ALL {
+                            if ui.selectable_label(self.settings.language == language, language.label()).clicked() {
+                                self.settings.language = language;
+                                i18n::set_language(language);
+                                self.save_settings();
+                            }
+                        }
+                    });
+            });
+            if ui.checkbox(&mut self.settings.high_contrast_ui, tr("High-contrast interface")).changed() {
+                self.save_settings();
+                self.needs_repaint = true;
+            }
+            if ui.checkbox(&mut self.settings.warn_low_contrast, tr("Warn on low-contrast ink")).changed() {
+                self.save_settings();
+                self.needs_repaint = true;
+            }
+            ui.separator();
+            if ui.checkbox(&mut self.settings.remap_ink_on_background, tr("Remap ink when switching light/dark background")).changed() {
+                self.save_settings();
+            }
+            if ui.button(tr("Invert ink colors on board")).clicked() {
+                self.invert_ink(false, None);
+                ui.close_menu();
+            }
+            if ui.add_enabled(!self.selected_objects.is_empty(), egui::Button::new(tr("Invert ink colors in selection"))).clicked() {
+                self.invert_ink(true, None);
+                ui.close_menu();
+            }
+        });
+    }
+
+    fn invert_ink(&mut self, selection_only: bool, only_low_contrast_on: Option<egui::Color32>) {
+        self.push_undo();
+        let ids: Vec<Uuid> = if selection_only {
+            self.selected_objects.clone()
+        } else {
+            self.objects.iter().map(|obj| obj.id()).collect()
+        };
+        self.update_objects(&ids, |obj| {
+            let Some(color) = obj.color_mut() else {
+                return;
+            };
+            if let Some(background) = only_low_contrast_on {
+                let ink = egui::Color32::from_rgb(color[0], color[1], color[2]);
+                if palette::contrast_ratio(ink, background) >= palette::MIN_INK_CONTRAST {
+                    return;
+                }
+            }
+            *color = palette::invert_lightness(*color);
+            obj.props_mut().style = None;
+        });
+    }
+
+    fn handle_background_change(&mut self) {
+        if self.background_color == self.last_background_color {
+            return;
+        }
+        let flipped = palette::is_dark(self.background_color) != palette::is_dark(self.last_background_color);
+        self.last_background_color = self.background_color;
+        if !flipped || !self.settings.remap_ink_on_background {
+            return;
+        }
+
+        self.invert_ink(false, Some(self.background_color));
+        if palette::contrast_ratio(self.current_color, self.background_color) < palette::MIN_INK_CONTRAST {
+            let [r, g, b, a] = palette::invert_lightness(self.current_color.to_array());
+            self.current_color = egui::Color32::from_rgba_unmultiplied(r, g, b, a);
+        }
+    }
+
+    fn render_contrast_warning(&self, painter: &egui::Painter, rect: egui::Rect) {
+        if !self.settings.warn_low_contrast {
+            return;
+        }
+        let ratio = palette::contrast_ratio(self.current_color, self.background_color);
+        if ratio >= palette::MIN_INK_CONTRAST {
+            return;
+        }
+        let text = format!("⚠ {}", trf("Low ink contrast ({}:1) against the background", &[&format!("{:.1}", ratio)]));
+        let galley = painter.layout_no_wrap(text, egui::FontId::proportional(14.0), egui::Color32::BLACK);
+        let pos = rect.left_top() + egui::vec2(10.0, 10.0);
+        let frame = egui::Rect::from_min_size(pos, galley.size()).expand(6.0);
+        painter.rect_filled(frame, 4.0, egui::Color32::from_rgb(255, 220, 80));
+        painter.rect_stroke(frame, 4.0, egui::Stroke::new(1.0, egui::Color32::BLACK));
+        painter.galley(pos, galley, egui::Color32::BLACK);
+    }
+
+    fn render_toolbar(&mut self, ctx: &egui::Context) {
+        if !self.show_toolbar {
+            return;
+        }
+
+        match self.settings.toolbar_dock {
+            ToolbarDock::Top => {
+                egui::TopBottomPanel::top("top_panel").show(ctx, |ui| {
+                    ui.horizontal_wrapped(|ui| self.toolbar_contents(ui, ctx, false));
+                });
+            }
+            ToolbarDock::Bottom => {
+                egui::TopBottomPanel::bottom("bottom_panel").show(ctx, |ui| {
+                    ui.horizontal_wrapped(|ui| self.toolbar_contents(ui, ctx, false));
+                });
+            }
+            ToolbarDock::Left => {
+                egui::SidePanel::left("left_panel").show(ctx, |ui| {
+                    egui::ScrollArea::vertical().show(ui, |ui| self.toolbar_contents(ui, ctx, false));
+                });
+            }
+            ToolbarDock::Right => {
+                egui::SidePanel::right("right_panel").show(ctx, |ui| {
+                    egui::ScrollArea::vertical().show(ui, |ui| self.toolbar_contents(ui, ctx, false));
+                });
+            }
+            ToolbarDock::Floating => {
+                let mut window = egui::Window::new(tr("Tools"))
+                    .collapsible(true)
+                    .resizable(false)
+                    .title_bar(true);
+                if self.summon_palette {
+                    if let Some(pointer) = ctx.input(|i| i.pointer.latest_pos()) {
+                        window = window.current_pos(pointer + egui::vec2(24.0, 24.0));
+                    }
+                    self.summon_palette = false;
+                }
+                window.show(ctx, |ui| {
+                    self.toolbar_contents(ui, ctx, true);
+                    ui.separator();
+                    egui::ComboBox::from_id_salt("toolbar_dock_floating")
+                        .selected_text(tr(self.settings.toolbar_dock.label()))
+                        .show_ui(ui, |ui| {
+                            for dock in ToolbarDock::ALL {
+                                if ui.selectable_label(self.settings.toolbar_dock == dock, tr(dock.label())).clicked() {
+                                    self.set_toolbar_dock(dock);
+                                }
+                            }
+                        });
+                });
+            }
+        }
+    }
+
+    fn toolbar_contents(&mut self, ui: &mut egui::Ui, ctx: &egui::Context, compact: bool) {
+        ui.label(tr("Tool:"));
+        
+        if ui.selectable_label(self.current_tool == Tool::Brush, tr("Brush (B)")).clicked() {
+            self.set_tool(Tool::Brush);
+        }
+        if ui.selectable_label(self.current_tool == Tool::Line, tr("Line (L)")).clicked() {
+            self.set_tool(Tool::Line);
+        }
+        if ui.selectable_label(self.current_tool == Tool::Circle, tr("Circle (C)")).clicked() {
+            self.set_tool(Tool::Circle);
+        }
+        if ui.selectable_label(self.current_tool == Tool::Square, tr("Square (R)")).clicked() {
+            self.set_tool(Tool::Square);
+        }
+        if ui.selectable_label(self.current_tool == Tool::Eraser, tr("Eraser (E)")).clicked() {
+            self.set_tool(Tool::Eraser);
+        }
+        if ui.selectable_label(self.current_tool == Tool::Select, tr("Select (S)")).clicked() {
+            self.set_tool(Tool::Select);
+        }
+        if ui.selectable_label(self.current_tool == Tool::Text, tr("Text (T)")).clicked() {
+            self.set_tool(Tool::Text);
+        }
+        if ui.selectable_label(self.current_tool == Tool::Frame, tr("Frame (K)")).clicked() {
+            self.set_tool(Tool::Frame);
+        }
+        if ui.selectable_label(self.current_tool == Tool::Highlighter, tr("Highlighter (I)")).on_hover_text(tr("Wide translucent strokes drawn beneath the ink")).clicked() {
+            self.set_tool(Tool::Highlighter);
+        }
+        if ui.button("⇄").on_hover_text(trf("Switch back to {} (X)", &[&format!("{:?}", self.previous_tool)])).clicked() {
+            self.set_tool(self.previous_tool);
+        }
+        if ui.button(tr("Formula (Ctrl+M)")).on_hover_text(tr("Insert a LaTeX formula in the middle of the view")).clicked() {
+            self.open_latex_dialog(None);
+        }
+    
+        ui.separator();
+        
+        if self.current_tool == Tool::Eraser {
+            egui::ComboBox::from_id_salt("eraser_mode")
+                .selected_text(tr(self.settings.eraser_mode.label()))
+                .show_ui(ui, |ui| {
+                    for mode in EraserMode::ALL {
+                        if ui.selectable_label(self.settings.eraser_mode == mode, tr(mode.label())).clicked() {
+                            self.settings.eraser_mode = mode;
+                            self.save_settings();
+                        }
+                    }
+                });
+        }
+        let partial_eraser = self.current_tool == Tool::Eraser && self.settings.eraser_mode == EraserMode::Partial;
+        ui.label(tr(if partial_eraser { "Eraser Size:" } else { "Brush Size:" }));
+        if partial_eraser {
+            let slider = ui.add(egui::Slider::new(&mut self.settings.eraser_radius, 2.0..=60.0).text("px"));
+            if slider.drag_stopped() || (slider.changed() && !slider.dragged()) {
+                self.save_settings();
+            }
+        } else if self.current_tool == Tool::Highlighter {
+            let slider = ui.add(egui::Slider::new(&mut self.settings.highlighter_width, 4.0..=60.0).text("px"));
+            if slider.drag_stopped() || (slider.changed() && !slider.dragged()) {
+                self.save_settings();
+            }
+        } else if ui.add(egui::Slider::new(&mut self.brush_size, 1.0..=20.0).text("px")).changed() {
+            self.needs_repaint = true;
+        }
+        if !partial_eraser {
+            let presets = self.settings.size_presets_for(self.current_tool);
+            for (index, size) in presets.iter().enumerate() {
+                let button = ui
+                    .selectable_label(self.brush_size == *size, format!("{}", index + 1))
+                    .on_hover_text(trf("{} ({} px) - right-click to set to the current size", &[&tr(SIZE_PRESET_NAMES[index]), size]));
+                if button.clicked() {
+                    self.apply_size_preset(index);
+                }
+                if button.secondary_clicked() {
+                    self.capture_size_preset(index);
+                }
+            }
+        }
+        
+        ui.separator();
+        
+        ui.label(tr("Color:"));
+        if self.current_tool == Tool::Highlighter {
+            let [r, g, b, a] = self.settings.highlighter_color;
+            let mut color = egui::Color32::from_rgba_unmultiplied(r, g, b, a);
+            if egui::color_picker::color_edit_button_srgba(ui, &mut color, egui::color_picker::Alpha::OnlyBlend).changed() {
+                self.settings.highlighter_color = color.to_srgba_unmultiplied();
+                self.save_settings();
+            }
+        } else if egui::color_picker::color_edit_button_srgba(
+            ui,
+            &mut self.current_color,
+            egui::color_picker::Alpha::Opaque,
+        ).changed() {
+            self.needs_repaint = true;
+        }
+        self.palette_menu(ui);
+        if ui.selectable_label(self.show_styles_panel, tr("Styles"))
+            .on_hover_text(tr("Named colors that update every object using them"))
+            .clicked()
+        {
+            self.show_styles_panel = !self.show_styles_panel;
+        }
+        ui.checkbox(&mut self.auto_formula_color, tr("Auto formula color"))
+            .on_hover_text(tr("New formulas use dark ink on light backgrounds and light ink on dark ones"));
+        
+        ui.separator();
+        
+        if ui.button(tr("Undo (Ctrl+Z)")).clicked() {
+            self.undo();
+        }
+        if ui.add_enabled(self.history.can_redo(self.current_page(), self.local_author), egui::Button::new(tr("Redo (Ctrl+Y)"))).clicked() {
+            self.redo();
+        }
+        
+        if !compact {
+            ui.separator();
+        
+            if ui.button(tr("Save")).clicked() {
+                self.save_document();
+            }
+            if ui.button(tr("Save view-only copy"))
+                .on_hover_text(tr("Writes a copy that opens with editing disabled, for handing out"))
+                .clicked()
+            {
+                self.save_view_only_copy();
+            }
+            self.backup_menu(ui);
+        
+            if ui.button(tr("Load")).clicked() {
+                let path = self.load_path.clone();
+                self.open_document(&path);
+            }
+            
+            if ui.selectable_label(self.show_snapshots, tr("Snapshots")).clicked() {
+                self.show_snapshots = !self.show_snapshots;
+            }
+            
+            if ui.selectable_label(self.show_pdf_export, tr("Export PDF")).clicked() {
+                self.show_pdf_export = !self.show_pdf_export;
+            }
+            
+            if ui.selectable_label(self.show_figure_export, tr("Export figure"))
+                .on_hover_text(tr("Transparent, trimmed PNG with ink in a single color"))
+                .clicked()
+            {
+                self.show_figure_export = !self.show_figure_export;
+            }
+            
+            if ui.selectable_label(self.show_named_export, tr("Export named")).clicked() {
+                self.show_named_export = !self.show_named_export;
+            }
+
+            self.recipes_menu(ui);
+
+            if ui.selectable_label(self.show_pdf_import, tr("Import scanned PDF")).clicked() {
+                self.show_pdf_import = !self.show_pdf_import;
+            }
+            if ui.selectable_label(self.show_image_folder_import, tr("Import images")).clicked() {
+                self.show_image_folder_import = !self.show_image_folder_import;
+            }
+        
+            ui.separator();
+        
+            ui.label(tr("Background:"));
+            egui::ComboBox::from_id_salt("bg_preset")
+                .selected_text(tr("Preset"))
+                .show_ui(ui, |ui| {
+                    if ui.selectable_label(false, tr("White")).clicked() {
+                        self.background_color = egui::Color32::WHITE;
+                        self.needs_repaint = true;
+                    }
+                    if ui.selectable_label(false, tr("Light Gray")).clicked() {
+                        self.background_color = egui::Color32::from_rgb(240, 240, 240);
+                        self.needs_repaint = true;
+                    }
+                    if ui.selectable_label(false, tr("Dark Gray")).clicked() {
+                        self.background_color = egui::Color32::from_rgb(40, 40, 40);
+                        self.needs_repaint = true;
+                    }
+                    if ui.selectable_label(false, tr("Black")).clicked() {
+                        self.background_color = egui::Color32::BLACK;
+                        self.needs_repaint = true;
+                    }
+                    if ui.selectable_label(false, tr("Sepia")).clicked() {
+                        self.background_color = egui::Color32::from_rgb(255, 245, 230);
+                        self.needs_repaint = true;
+                    }
+                    if ui.selectable_label(false, tr("Dark Blue")).clicked() {
+                        self.background_color = egui::Color32::from_rgb(20, 30, 40);
+                        self.needs_repaint = true;
+                    }
+                });
+        
+            if egui::color_picker::color_edit_button_srgba(
+                ui,
+                &mut self.background_color,
+                egui::color_picker::Alpha::Opaque,
+            ).changed() {
+                self.needs_repaint = true;
+            }
+        
+            if ui.checkbox(&mut self.show_grid, tr("Grid")).changed() {
+                self.needs_repaint = true;
+            }
+            ui.add_enabled(self.show_grid, egui::Checkbox::new(&mut self.snap_to_grid, tr("Snap")))
+                .on_hover_text(tr("Snap shape corners and moved selections to the grid. Hold Shift while drawing a line to snap its angle to 15° steps."));
+            let hidden_count = self.objects.iter().filter(|obj| obj.props().hidden).count();
+            if ui.checkbox(&mut self.show_hidden, trf("Show hidden ({})", &[&hidden_count])).changed() {
+                if !self.show_hidden {
+                    let objects = &self.objects;
+                    self.selected_objects.retain(|id| objects.iter().any(|obj| obj.id() == *id && !obj.props().hidden));
+                }
+                self.needs_repaint = true;
+            }
+            if ui.checkbox(&mut self.show_rulers, tr("Rulers")).changed() {
+                self.needs_repaint = true;
+            }
+            ui.menu_button(tr("Scale"), |ui| self.scale_menu(ui));
+            if ui.selectable_label(self.show_guides_panel, tr("Guides")).clicked() {
+                self.show_guides_panel = !self.show_guides_panel;
+            }
+            if ui.selectable_label(self.show_layers_panel, tr("Layers")).clicked() {
+                self.show_layers_panel = !self.show_layers_panel;
+            }
+            if ui.selectable_label(self.show_properties_panel, tr("Properties")).clicked() {
+                self.show_properties_panel = !self.show_properties_panel;
+            }
+            if ui.selectable_label(self.show_templates_panel, tr("Templates")).clicked() {
+                self.show_templates_panel = !self.show_templates_panel;
+            }
+            if ui.selectable_label(self.show_recent_panel, tr("Recent")).clicked() {
+                self.show_recent_panel = !self.show_recent_panel;
+            }
+            if ui.selectable_label(self.show_reveal_panel, tr("Reveal steps")).clicked() {
+                self.show_reveal_panel = !self.show_reveal_panel;
+            }
+            ui.label(tr("Pages:"));
+            egui::ComboBox::from_id_salt("page_outline")
+                .selected_text(tr(self.settings.page_outline.label()))
+                .show_ui(ui, |ui| {
+                    for outline in PageOutline::ALL {
+                        if ui.selectable_label(self.settings.page_outline == outline, tr(outline.label())).clicked() {
+                            self.settings.page_outline = outline;
+                            self.save_settings();
+                            self.needs_repaint = true;
+                        }
+                    }
+                });
+        
+            ui.separator();
+        
+            ui.label(trf("Zoom: {}%", &[&format!("{:.0}", self.canvas_zoom * 100.0)]));
+        
+            ui.label(tr("Rotation:"));
+            let mut rotation_degrees = self.canvas_rotation.to_degrees();
+            if ui.add(egui::DragValue::new(&mut rotation_degrees).speed(1.0).range(0.0..=360.0).suffix("°")).changed() {
+                let pivot = ctx.screen_rect().center();
+                self.rotate_view_about(pivot, rotation_degrees.to_radians() - self.canvas_rotation);
+            }
+            if ui.button(tr("Reset")).clicked() {
+                let pivot = ctx.screen_rect().center();
+                self.rotate_view_about(pivot, -self.canvas_rotation);
+            }
+            if ui.button(tr("Go to origin")).on_hover_text(tr("Home")).clicked() {
+                self.go_to_origin();
+            }
+            if ui.button(tr("Free space")).on_hover_text(tr("Jump to empty space next to the content in view (N)")).clicked() {
+                self.jump_to_free_space();
+            }
+        
+            if ui.button(tr("Clear All")).clicked() {
+                self.push_undo();
+                self.apply_op(DocumentOp::Clear);
+            }
+        
+            ui.separator();
+            ui.label(tr("Dock:"));
+            egui::ComboBox::from_id_salt("toolbar_dock")
+                .selected_text(tr(self.settings.toolbar_dock.label()))
+                .show_ui(ui, |ui| {
+                    for dock in ToolbarDock::ALL {
+                        if ui.selectable_label(self.settings.toolbar_dock == dock, tr(dock.label())).clicked() {
+                            self.set_toolbar_dock(dock);
+                        }
+                    }
+                });
+            
+            if ui.checkbox(&mut self.settings.limit_panning, tr("Keep content in reach"))
+                .on_hover_text(tr("Stop panning once all content is more than half a screen out of view"))
+                .changed()
+            {
+                self.save_settings();
+            }
+            if ui.checkbox(&mut self.settings.overview_when_zoomed_out, tr("Overview when zoomed out"))
+                .on_hover_text(tr("Below 20% zoom, draw objects as blocks colored by type"))
+                .changed()
+            {
+                self.save_settings();
+            }
+            if ui.checkbox(&mut self.settings.restore_session, tr("Restore session on launch"))
+                .on_hover_text(tr("Reopen the last document, its view and the window layout at startup"))
+                .changed()
+            {
+                self.save_settings();
+            }
+            if ui.selectable_label(self.show_profiler, tr("Profiler"))
+                .on_hover_text(tr("Show frame timings, render counts and cache hit rates"))
+                .clicked()
+            {
+                self.show_profiler = !self.show_profiler;
+            }
+            ui.menu_button(tr("Memory"), |ui| {
+                self.memory_usage = self.measure_memory();
+                let usage = self.memory_usage;
+                egui::Grid::new("memory_usage").num_columns(2).show(ui, |ui| {
+                    for (label, bytes) in [("LaTeX", usage.latex), ("Images", usage.images), ("Undo history", usage.history), ("Counted against budget", usage.trimmable()), ("Document (never trimmed)", usage.document), ("Total", usage.total())] {
+                        ui.label(tr(label));
+                        ui.label(memory::format_bytes(bytes));
+                        ui.end_row();
+                    }
+                });
+                ui.horizontal(|ui| {
+                    ui.label(tr("Budget:"));
+                    let budget = ui
+                        .add(egui::DragValue::new(&mut self.settings.memory_budget_mb).range(64..=16384).suffix(" MB"))
+                        .on_hover_text(tr("Above this, unused LaTeX renders are dropped, offscreen images are shown at lower resolution and the oldest undo steps are discarded, keeping at least the last 20"));
+                    if budget.drag_stopped() || (budget.changed() && !budget.dragged()) {
+                        self.save_settings();
+                        self.enforce_memory_budget();
+                    }
+                });
+            });
+            ui.menu_button(tr("Quick capture"), |ui| {
+                let mut changed = ui
+                    .checkbox(&mut self.settings.stay_resident, tr("Stay resident"))
+                    .on_hover_text(tr("Closing the window minimizes it instead, and the hotkey brings it back from any app"))
+                    .changed();
+                ui.horizontal(|ui| {
+                    ui.label(tr("Hotkey:"));
+                    changed |= ui
+                        .add(egui::TextEdit::singleline(&mut self.settings.quick_capture_hotkey).desired_width(140.0))
+                        .on_hover_text(tr("For example ctrl+shift+space or alt+F9"))
+                        .lost_focus();
+                });
+                changed |= ui.checkbox(&mut self.settings.scratchpad_on_summon, tr("Open as a small always-on-top scratchpad")).changed();
+                if let Some(status) = &self.hotkey_status {
+                    ui.colored_label(egui::Color32::from_rgb(200, 60, 60), status);
+                }
+                if changed {
+                    self.hotkeys_dirty = true;
+                    self.save_settings();
+                }
+                ui.separator();
+                if ui.button(tr("Quit (Ctrl+Q)")).on_hover_text(tr("Close the app even while it stays resident")).clicked() {
+                    self.quit(ctx);
+                }
+            });
+            if ui.selectable_label(self.scratchpad.is_some(), tr("Scratchpad"))
+                .on_hover_text(tr("Shrink the window and keep it above other apps"))
+                .clicked()
+            {
+                self.toggle_scratchpad(ctx);
+            }
+            if ui.selectable_label(self.overlay, tr("Overlay"))
+                .on_hover_text(tr("Borderless, translucent and always on top, for annotating over other apps"))
+                .clicked()
+            {
+                self.toggle_overlay(ctx);
+            }
+            if self.overlay {
+                let missing = self.hotkey_status.clone().unwrap_or_else(|| tr("Needs a global hotkey to switch back").to_string());
+                if ui
+                    .add_enabled(self.hotkey_active(HotkeyAction::ClickThrough), egui::Button::new(tr("Click-through")))
+                    .on_hover_text(trf("Let clicks reach the app underneath. Press {} to take them back.", &[&self.settings.click_through_hotkey]))
+                    .on_disabled_hover_text(missing)
+                    .clicked()
+                {
+                    self.set_click_through(ctx, true);
+                }
+                ui.label(tr("Backdrop:"));
+                let tint = ui.add(egui::Slider::new(&mut self.settings.overlay_tint, 0.0..=0.8));
+                if tint.drag_stopped() || (tint.changed() && !tint.dragged()) {
+                    self.save_settings();
+                }
+                if ui.button(tr("Export ink")).on_hover_text(tr("Save only the ink, on a transparent background")).clicked() {
+                    self.show_figure_export = true;
+                }
+            }
+
+            ui.label(tr("Hold for eraser:"));
+            let hold_key = ui
+                .add(egui::TextEdit::singleline(&mut self.settings.eraser_hold_key).desired_width(40.0))
+                .on_hover_text(tr("Key that switches to the eraser while held and back when released. Tapping it keeps the eraser."));
+            if hold_key.lost_focus() {
+                if egui::Key::from_name(self.settings.eraser_hold_key.trim()).is_none() {
+                    self.settings.eraser_hold_key = Settings::default().eraser_hold_key;
+                }
+                self.save_settings();
+            }
+
+            ui.separator();
+            ui.label(tr("Ink prediction:"));
+            let prediction = ui
+                .add(egui::Slider::new(&mut self.settings.stroke_prediction_ms, 0.0..=50.0).suffix(" ms"))
+                .on_hover_text(tr("Extends the stroke being drawn along the pen's motion to hide input latency. 0 turns it off."));
+            if prediction.drag_stopped() || (prediction.changed() && !prediction.dragged()) {
+                self.save_settings();
+            }
+
+            ui.label(tr("Highlighter pressure:"));
+            egui::ComboBox::from_id_salt("highlighter_pressure")
+                .selected_text(tr(self.settings.highlighter_pressure.label()))
+                .show_ui(ui, |ui| {
+                    for mapping in PressureMapping::ALL {
+                        if ui.selectable_label(self.settings.highlighter_pressure == mapping, tr(mapping.label())).clicked() {
+                            self.settings.highlighter_pressure = mapping;
+                            self.save_settings();
+                        }
+                    }
+                })
+                .response
+                .on_hover_text(tr("What pen pressure controls while drawing with the highlighter"));
+            
+            ui.label(tr("Formula quality:"));
+            egui::ComboBox::from_id_salt("latex_quality")
+                .selected_text(tr(self.settings.latex_quality.label()))
+                .show_ui(ui, |ui| {
+                    for quality in LatexQuality::ALL {
+                        if ui.selectable_label(self.settings.latex_quality == quality, tr(quality.label())).clicked() {
+                            self.settings.latex_quality = quality;
+                            self.latex_renderer.set_scale_factor(quality.scale_factor());
+                            self.save_settings();
+                            self.queue_latex_warmup();
+                            self.needs_repaint = true;
+                        }
+                    }
+                })
+                .response
+                .on_hover_text(tr("Higher quality renders formulas sharper at the cost of memory and render time"));
+
+            ui.label(tr("Text font:"));
+            egui::ComboBox::from_id_salt("text_font")
+                .selected_text(self.current_font.as_deref().unwrap_or(tr(LATEX_FONT_LABEL)))
+                .show_ui(ui, |ui| {
+                    if ui.selectable_label(self.current_font.is_none(), tr(LATEX_FONT_LABEL)).clicked() {
+                        self.current_font = None;
+                    }
+                    for family in self.latex_renderer.font_families().to_vec() {
+                        if ui.selectable_label(self.current_font.as_ref() == Some(&family), &family).clicked() {
+                            self.current_font = Some(family);
+                        }
+                    }
+                })
+                .response
+                .on_hover_text(tr("Font used by the Text tool. LaTeX renders the text as math."));
+            if ui.selectable_label(self.show_font_panel, tr("Fonts")).clicked() {
+                self.show_font_panel = !self.show_font_panel;
+            }
+            if ui.selectable_label(self.show_symbol_picker, tr("Symbols")).clicked() {
+                self.show_symbol_picker = !self.show_symbol_picker;
+            }
+            if ui.selectable_label(self.show_find_panel, tr("Find (Ctrl+F)")).clicked() {
+                self.show_find_panel = !self.show_find_panel;
+            }
+            
+            ui.separator();
+            ui.label(tr("Press H to toggle toolbar"));
+        }
+    }
+
+    #[allow(clippy::collapsible_match)]
+    fn handle_text_editing(&mut self, ctx: &egui::Context) {
+        if ctx.wants_keyboard_input() {
+            return;
+        }
+        if let Some(editing_id) = self.editing_text {
+            let mut copied = None;
+            ctx.input_mut(|i| {
+                let events = std::mem::take(&mut i.events);
+                for event in events {
+                    match &event {
+                        egui::Event::Text(text) | egui::Event::Paste(text) => {
+                            self.text_input.insert_str(self.text_cursor_pos, text);
+                            self.text_cursor_pos += text.len();
+                            self.needs_repaint = true;
+                        }
+                        egui::Event::Copy => copied = Some(self.text_input.clone()),
+                        egui::Event::Cut => {
+                            copied = Some(std::mem::take(&mut self.text_input));
+                            self.text_cursor_pos = 0;
+                            self.needs_repaint = true;
+                        }
+                        egui::Event::Key { key, modifiers, .. } if (modifiers.command || modifiers.ctrl) && !TEXT_EDITING_KEYS.contains(key) => {
+                            i.events.push(event)
+                        }
+                        egui::Event::Key { pressed: false, .. } => {}
+                        egui::Event::Key { key, .. } => {
+                            match key {
+                                egui::Key::Backspace => {
+                                    if self.text_cursor_pos > 0 {
+                                        self.text_cursor_pos = self.previous_char_boundary();
+                                        self.text_input.remove(self.text_cursor_pos);
+                                        self.needs_repaint = true;
+                                    }
+                                }
+                                egui::Key::Delete => {
+                                    if self.text_cursor_pos < self.text_input.len() {
+                                        self.text_input.remove(self.text_cursor_pos);
+                                        self.needs_repaint = true;
+                                    }
+                                }
+                                egui::Key::ArrowLeft => {
+                                    if self.text_cursor_pos > 0 {
+                                        self.text_cursor_pos = self.previous_char_boundary();
+                                        self.needs_repaint = true;
+                                    }
+                                }
+                                egui::Key::ArrowRight => {
+                                    if self.text_cursor_pos < self.text_input.len() {
+                                        let next = self.text_input[self.text_cursor_pos..].chars().next().map_or(0, char::len_utf8);
+                                        self.text_cursor_pos += next;
+                                        self.needs_repaint = true;
+                                    }
+                                }
+                                egui::Key::Enter => {
+                                    if let Some(mut obj) = self.objects.iter().find(|o| o.id() == editing_id).cloned() {
+                                        if let DrawObject::LatexFormula { formula, size, .. } = &mut obj {
+                                            *formula = self.text_input.clone();
+                                            *size = None;
+                                        }
+                                        self.apply_op(DocumentOp::Update { objects: vec![obj] });
+                                        self.measure_formulas();
+                                    }
+                                    self.editing_text = None;
+                                    self.text_input.clear();
+                                    self.text_cursor_pos = 0;
+                                    self.needs_repaint = true;
+                                }
+                                egui::Key::Escape => {
+                                    self.editing_text = None;
+                                    self.text_input.clear();
+                                    self.text_cursor_pos = 0;
+                                    self.needs_repaint = true;
+                                }
+                                _ => {}
+                            }
+                        }
+                        _ => i.events.push(event),
+                    }
+                }
+            });
+            if let Some(text) = copied {
+                ctx.copy_text(text);
+            }
+        }
+    }
+
+    fn open_latex_dialog(&mut self, pointer_pos: Option<egui::Pos2>) {
+        let screen_pos = pointer_pos
+            .filter(|pos| self.canvas_rect.contains(*pos))
+            .unwrap_or(self.canvas_rect.center());
+        self.latex_placement_pos = canvas::screen_to_canvas(screen_pos, self.canvas_offset, self.canvas_zoom, self.canvas_rotation);
+        self.show_latex_dialog = true;
+        self.needs_repaint = true;
+    }
+
+    fn render_latex_dialog(&mut self, ctx: &egui::Context) {
+        if self.show_latex_dialog {
+            egui::Window::new(tr("Add LaTeX Formula"))
+                .collapsible(false)
+                .show(ctx, |ui| {
+                    ui.label(tr("Enter LaTeX formula:"));
+                    let input = ui.text_edit_singleline(&mut self.latex_input);
+                    if ui.memory(|m| m.focused().is_none()) {
+                        input.request_focus();
+                    }
+                    let submitted = input.lost_focus() && ui.input(|i| i.key_pressed(egui::Key::Enter));
+                    ui.horizontal(|ui| {
+                        if ui.button(tr("Add")).clicked() || submitted {
+                            self.add_pending_formula();
+                        }
+                        let can_place = !self.latex_input.trim().is_empty();
+                        if ui.add_enabled(can_place, egui::Button::new(tr("Pick position"))).on_hover_text(tr("Click on the canvas to place the formula")).clicked() {
+                            self.placing_formula = true;
+                            self.show_latex_dialog = false;
+                        }
+                        if ui.button(tr("Cancel")).clicked() {
+                            self.show_latex_dialog = false;
+                        }
+                    });
+                });
+        }
+    }
+
+    fn add_pending_formula(&mut self) {
+        let formula = DrawObject::LatexFormula {
+            id: Uuid::new_v4(),
+            pos: self.latex_placement_pos,
+            formula: self.latex_input.clone(),
+            color: [
+                self.current_color.r(),
+                self.current_color.g(),
+                self.current_color.b(),
+                self.current_color.a(),
+            ],
+            size: None,
+            scale: [1.0, 1.0],
+            auto_color: self.auto_formula_color,
+            font: None,
+            pin_size: false,
+            props: self.ink_props(),
+        };
+        self.push_undo();
+        self.apply_op(DocumentOp::Add { objects: vec![formula] });
+        self.measure_formulas();
+        self.latex_input.clear();
+        self.show_latex_dialog = false;
+        self.placing_formula = false;
+        self.needs_repaint = true;
+    }
+
+    fn handle_formula_placement(&mut self, ctx: &egui::Context, painter: &egui::Painter, response: &egui::Response) {
+        if ctx.input(|i| i.key_pressed(egui::Key::Escape)) {
+            self.placing_formula = false;
+            self.show_latex_dialog = true;
+            return;
+        }
+        let Some(hover_pos) = response.hover_pos() else {
+            return;
+        };
+        let canvas_pos = self.snap_to_guides(canvas::screen_to_canvas(hover_pos, self.canvas_offset, self.canvas_zoom, self.canvas_rotation));
+        if response.clicked() {
+            self.latex_placement_pos = canvas_pos;
+            self.add_pending_formula();
+            return;
+        }
+
+        let color = if self.auto_formula_color {
+            palette::auto_ink(self.background_color)
+        } else {
+            self.current_color.to_array()
+        };
+        let screen_pos = canvas::canvas_to_screen(canvas_pos, self.canvas_offset, self.canvas_zoom, self.canvas_rotation);
+        match self.latex_renderer.get_or_create_texture(ctx, &self.latex_input, None, color) {
+            Some(texture) => {
+                let [w, h] = self.latex_renderer.display_size(texture.size());
+                let rect = egui::Rect::from_min_size(screen_pos, egui::vec2(w, h) * self.canvas_zoom);
+                let mut mesh = egui::Mesh::with_texture(texture.id());
+                mesh.add_rect_with_uv(
+                    rect,
+                    egui::Rect::from_min_max(egui::pos2(0.0, 0.0), egui::pos2(1.0, 1.0)),
+                    egui::Color32::WHITE.gamma_multiply(0.5),
+                );
+                if self.canvas_rotation != 0.0 {
+                    mesh.rotate(egui::emath::Rot2::from_angle(self.canvas_rotation), screen_pos);
+                }
+                painter.add(mesh);
+            }
+            None => {
+                painter.text(screen_pos, egui::Align2::LEFT_TOP, &self.latex_input, egui::FontId::monospace(14.0), self.current_color.gamma_multiply(0.5));
+            }
+        }
+        self.needs_repaint = true;
+    }
+
+    fn start_crop(&mut self, id: Uuid) {
+        if let Some(DrawObject::Image { min, max, .. }) = self.objects.iter().find(|obj| obj.id() == id) {
+            self.cropping = Some((id, *min, *max));
+            self.crop_handle = None;
+            self.needs_repaint = true;
+        }
+    }
+
+    fn handle_crop(&mut self, ctx: &egui::Context, painter: &egui::Painter, response: &egui::Response) {
+        let Some((id, mut crop_min, mut crop_max)) = self.cropping else {
+            return;
+        };
+        let Some(DrawObject::Image { min, max, .. }) = self.objects.iter().find(|obj| obj.id() == id) else {
+            self.cropping = None;
+            return;
+        };
+        let (image_min, image_max) = (*min, *max);
+
+        let (apply, cancel) = ctx.input(|i| (i.key_pressed(egui::Key::Enter), i.key_pressed(egui::Key::Escape)));
+        if cancel {
+            self.cropping = None;
+            return;
+        }
+        if apply {
+            self.apply_crop();
+            return;
+        }
+
+        if let Some(pointer_pos) = response.interact_pointer_pos() {
+            let canvas_pos = canvas::screen_to_canvas(pointer_pos, self.canvas_offset, self.canvas_zoom, self.canvas_rotation);
+            if response.drag_started() {
+                self.crop_handle = selection::get_handle_at_pos(canvas_pos, (crop_min, crop_max), self.canvas_zoom)
+                    .filter(|handle| *handle != SelectionHandle::Rotate);
+            }
+            if let Some(handle) = self.crop_handle.filter(|_| response.dragged()) {
+                let min_size = 4.0;
+                let x = canvas_pos[0].clamp(image_min[0], image_max[0]);
+                let y = canvas_pos[1].clamp(image_min[1], image_max[1]);
+                if matches!(handle, SelectionHandle::Left | SelectionHandle::TopLeft | SelectionHandle::BottomLeft) {
+                    crop_min[0] = x.min(crop_max[0] - min_size);
+                }
+                if matches!(handle, SelectionHandle::Right | SelectionHandle::TopRight | SelectionHandle::BottomRight) {
+                    crop_max[0] = x.max(crop_min[0] + min_size);
+                }
+                if matches!(handle, SelectionHandle::Top | SelectionHandle::TopLeft | SelectionHandle::TopRight) {
+                    crop_min[1] = y.min(crop_max[1] - min_size);
+                }
+                if matches!(handle, SelectionHandle::Bottom | SelectionHandle::BottomLeft | SelectionHandle::BottomRight) {
+                    crop_max[1] = y.max(crop_min[1] + min_size);
+                }
+                self.cropping = Some((id, crop_min, crop_max));
+            }
+        }
+        if response.drag_stopped() {
+            self.crop_handle = None;
+        }
+
+        let to_screen = |min: [f32; 2], max: [f32; 2]| canvas::canvas_rect_to_screen(min, max, self.canvas_offset, self.canvas_zoom, self.canvas_rotation);
+        let shade = egui::Color32::from_black_alpha(140);
+        let outside = [
+            (image_min, [image_max[0], crop_min[1]]),
+            ([image_min[0], crop_max[1]], image_max),
+            ([image_min[0], crop_min[1]], [crop_min[0], crop_max[1]]),
+            ([crop_max[0], crop_min[1]], [image_max[0], crop_max[1]]),
+        ];
+        for (min, max) in outside {
+            if max[0] > min[0] && max[1] > min[1] {
+                painter.add(egui::Shape::convex_polygon(to_screen(min, max).to_vec(), shade, egui::Stroke::NONE));
+            }
+        }
+        let outline = egui::Stroke::new(1.5, egui::Color32::from_rgb(50, 100, 255));
+        painter.add(egui::Shape::closed_line(to_screen(crop_min, crop_max).to_vec(), outline));
+        let mid = [(crop_min[0] + crop_max[0]) / 2.0, (crop_min[1] + crop_max[1]) / 2.0];
+        let handles = [
+            crop_min,
+            [mid[0], crop_min[1]],
+            [crop_max[0], crop_min[1]],
+            [crop_max[0], mid[1]],
+            crop_max,
+            [mid[0], crop_max[1]],
+            [crop_min[0], crop_max[1]],
+            [crop_min[0], mid[1]],
+        ];
+        for handle in handles {
+            let pos = canvas::canvas_to_screen(handle, self.canvas_offset, self.canvas_zoom, self.canvas_rotation);
+            painter.rect_filled(egui::Rect::from_center_size(pos, egui::vec2(8.0, 8.0)), 1.0, egui::Color32::WHITE);
+            painter.rect_stroke(egui::Rect::from_center_size(pos, egui::vec2(8.0, 8.0)), 1.0, outline);
+        }
+
+        egui::Area::new(egui::Id::new("crop_status"))
+            .anchor(egui::Align2::CENTER_BOTTOM, egui::vec2(0.0, -12.0))
+            .show(ctx, |ui| {
+                egui::Frame::popup(ui.style()).show(ui, |ui| {
+                    ui.horizontal(|ui| {
+                        ui.label(tr("Drag the handles to crop."));
+                        if ui.button(tr("Apply (Enter)")).clicked() {
+                            self.apply_crop();
+                        }
+                        if ui.button(tr("Cancel (Esc)")).clicked() {
+                            self.cropping = None;
+                        }
+                    });
+                });
+            });
+        self.needs_repaint = true;
+    }
+
+    fn apply_crop(&mut self) {
+        let Some((id, crop_min, crop_max)) = self.cropping.take() else {
+            return;
+        };
+        let Some(DrawObject::Image { min, max, data, .. }) = self.objects.iter().find(|obj| obj.id() == id) else {
+            return;
+        };
+        if crop_min == *min && crop_max == *max {
+            return;
+        }
+        let size = [max[0] - min[0], max[1] - min[1]];
+        let from = [(crop_min[0] - min[0]) / size[0], (crop_min[1] - min[1]) / size[1]];
+        let to = [(crop_max[0] - min[0]) / size[0], (crop_max[1] - min[1]) / size[1]];
+        let cropped = match images::crop(data, from, to) {
+            Ok(cropped) => cropped,
+            Err(e) => {
+                eprintln!("Error cropping image: {}", e);
+                return;
+            }
+        };
+
+        self.push_undo();
+        self.update_objects(&[id], |obj| {
+            if let DrawObject::Image { min, max, data, .. } = obj {
+                *min = crop_min;
+                *max = crop_max;
+                *data = cropped.clone();
+            }
+        });
+    }
+
+    fn ocr_backend(&self) -> Box<dyn OcrBackend> {
+        Box::new(ocr::TesseractCli {
+            command: self.settings.ocr_command.clone(),
+            language: self.settings.ocr_language.clone(),
+        })
+    }
+
+    fn recognize_text(&mut self, id: Uuid) {
+        self.show_ocr_panel = true;
+        if self.ocr_job.is_some() {
+            return;
+        }
+        let Some(DrawObject::Image { data, .. }) = self.objects.iter().find(|obj| obj.id() == id) else {
+            return;
+        };
+        let data = data.clone();
+        self.ocr_job = Some(OcrJob::start(self.ocr_backend(), id, data));
+        self.ocr_status = Some(tr("Recognizing text…").to_string());
+    }
+
+    fn advance_ocr(&mut self) {
+        let Some(job) = &self.ocr_job else {
+            return;
+        };
+        let Some(result) = job.poll() else {
+            self.needs_repaint = true;
+            return;
+        };
+        let id = job.image;
+        self.ocr_job = None;
+        match result {
+            Ok(lines) => self.place_recognized_text(id, &lines),
+            Err(e) => self.ocr_status = Some(e),
+        }
+    }
+
+    fn place_recognized_text(&mut self, id: Uuid, lines: &[OcrLine]) {
+        let Some(DrawObject::Image { min, max, data, .. }) = self.objects.iter().find(|obj| obj.id() == id) else {
+            self.ocr_status = Some(tr("The image was removed before recognition finished").to_string());
+            return;
+        };
+        let (min, max) = (*min, *max);
+        let Some([width, height]) = images::dimensions(data) else {
+            self.ocr_status = Some(tr("Could not read the image").to_string());
+            return;
+        };
+        if lines.is_empty() {
+            self.ocr_status = Some(tr("No text found").to_string());
+            return;
+        }
+
+        let pixel_scale = [(max[0] - min[0]) / width as f32, (max[1] - min[1]) / height as f32];
+        let font = self.current_font.clone().unwrap_or_else(|| PLAIN_TEXT_FONT.to_string());
+        let mut created = Vec::new();
+        for line in lines {
+            let pos = [
+                min[0] + line.min[0] as f32 * pixel_scale[0],
+                min[1] + line.min[1] as f32 * pixel_scale[1],
+            ];
+            let line_height = (line.max[1] - line.min[1]) as f32 * pixel_scale[1];
+            let size = self.latex_renderer.measure(&line.text, Some(&font));
+            let factor = size.map_or(1.0, |size| line_height * OCR_LINE_SPACING / size[1]);
+            created.push(DrawObject::LatexFormula {
+                id: Uuid::new_v4(),
+                pos,
+                formula: line.text.clone(),
+                color: self.current_color.to_array(),
+                size,
+                scale: [factor, factor],
+                auto_color: self.auto_formula_color,
+                font: Some(font.clone()),
+                pin_size: false,
+                props: self.ink_props(),
+            });
+        }
+
+        self.push_undo();
+        self.selected_objects = created.iter().map(|obj| obj.id()).collect();
+        self.apply_op(DocumentOp::Add { objects: created });
+        self.ocr_status = Some(trf("Recognized {} lines", &[&lines.len()]));
+    }
+
+    fn render_ocr_panel(&mut self, ctx: &egui::Context) {
+        if !self.show_ocr_panel {
+            return;
+        }
+
+        let mut open = true;
+        let mut changed = false;
+        egui::Window::new(tr("Text recognition"))
+            .open(&mut open)
+            .default_width(300.0)
+            .show(ctx, |ui| {
+                egui::Grid::new("ocr_grid").num_columns(2).show(ui, |ui| {
+                    ui.label(tr("Command:"));
+                    changed |= ui.text_edit_singleline(&mut self.settings.ocr_command).lost_focus();
+                    ui.end_row();
+                    ui.label(tr("Language:"));
+                    changed |= ui.text_edit_singleline(&mut self.settings.ocr_language).lost_focus();
+                    ui.end_row();
+                });
+                ui.label(tr("Uses the tesseract command-line tool. Right-click an image and choose \"Recognize text\"."));
+                if let Some(status) = &self.ocr_status {
+                    ui.label(status);
+                }
+            });
+
+        if changed {
+            self.save_settings();
+        }
+        self.show_ocr_panel = open;
+    }
+
+    fn render_image_adjustments(&mut self, ui: &mut egui::Ui, id: Uuid) {
+        let Some(DrawObject::Image { adjustments, .. }) = self.objects.iter().find(|obj| obj.id() == id) else {
+            return;
+        };
+        let mut edited = *adjustments;
+        let mut interacted = false;
+        let mut finished = false;
+        let mut track = |response: egui::Response| {
+            interacted |= response.changed();
+            finished |= response.drag_stopped() || (response.changed() && !response.dragged());
+        };
+        track(ui.checkbox(&mut edited.invert, tr("Invert")));
+        track(ui.checkbox(&mut edited.grayscale, tr("Grayscale")));
+        track(ui.add(egui::Slider::new(&mut edited.brightness, -1.0..=1.0).text(tr("Brightness"))));
+        track(ui.add(egui::Slider::new(&mut edited.contrast, -1.0..=1.0).text(tr("Contrast"))));
+        track(
+            ui.add(egui::Slider::new(&mut edited.white_point, 0.3..=1.0).text(tr("White point")))
+                .on_hover_text(tr("Lower values turn light gray paper pure white")),
+        );
+        if ui.button(tr("Reset")).clicked() {
+            edited = ImageAdjustments::default();
+            interacted = true;
+            finished = true;
+        }
+
+        if !interacted {
+            return;
+        }
+        if self.adjusting_image != Some(id) {
+            self.push_undo();
+            self.adjusting_image = Some(id);
+        }
+        self.update_objects(&[id], |obj| {
+            if let DrawObject::Image { adjustments, .. } = obj {
+                *adjustments = edited;
+            }
+        });
+        if finished {
+            self.adjusting_image = None;
+        }
+    }
+
+    fn split_image(&mut self, id: Uuid) {
+        let Some(index) = self.objects.iter().position(|obj| obj.id() == id) else {
+            return;
+        };
+        let DrawObject::Image { min, max, data, adjustments, props, .. } = &self.objects[index] else {
+            return;
+        };
+        let (columns, rows) = (self.split_columns.max(1), self.split_rows.max(1));
+        let tiles = match images::split(data, columns, rows) {
+            Ok(tiles) => tiles,
+            Err(e) => {
+                eprintln!("Error splitting image: {}", e);
+                return;
+            }
+        };
+        let tile_size = [(max[0] - min[0]) / columns as f32, (max[1] - min[1]) / rows as f32];
+        let tiles: Vec<DrawObject> = tiles
+            .into_iter()
+            .enumerate()
+            .map(|(tile, data)| {
+                let (row, column) = (tile as u32 / columns, tile as u32 % columns);
+                let tile_min = [min[0] + column as f32 * tile_size[0], min[1] + row as f32 * tile_size[1]];
+                DrawObject::Image {
+                    id: Uuid::new_v4(),
+                    min: tile_min,
+                    max: [tile_min[0] + tile_size[0], tile_min[1] + tile_size[1]],
+                    data,
+                    adjustments: *adjustments,
+                    props: props.clone(),
+                }
+            })
+            .collect();
+
+        self.push_undo();
+        self.selected_objects = tiles.iter().map(|tile| tile.id()).collect();
+        self.apply_op(DocumentOp::Replace { ids: vec![id], objects: tiles });
+    }
+
+    fn background_at(&self, backgrounds: &[selection::PageBackground], point: [f32; 2]) -> egui::Color32 {
+        selection::background_in(backgrounds, point)
+            .map_or(self.background_color, |[r, g, b, a]| egui::Color32::from_rgba_unmultiplied(r, g, b, a))
+    }
+
+    fn render_frame_backgrounds(&self, painter: &egui::Painter) {
+        for page in selection::page_overrides(&self.objects) {
+            if let Some([r, g, b, a]) = page.background {
+                let corners = canvas::canvas_rect_to_screen(page.min, page.max, self.canvas_offset, self.canvas_zoom, self.canvas_rotation);
+                painter.add(egui::Shape::convex_polygon(
+                    corners.to_vec(),
+                    egui::Color32::from_rgba_unmultiplied(r, g, b, a),
+                    egui::Stroke::NONE,
+                ));
+            }
+        }
+    }
+
+    fn render_grid(&self, painter: &egui::Painter, rect: egui::Rect) {
+        let (min_canvas, max_canvas) = canvas::visible_canvas_bounds(rect, self.canvas_offset, self.canvas_zoom, self.canvas_rotation);
+        let overrides = selection::page_overrides(&self.objects);
+        let board_style = if self.show_grid { GridStyle::Dots } else { GridStyle::Blank };
+        let outside_overrides = |point: [f32; 2]| {
+            !overrides.iter().any(|page| page.contains(point))
+        };
+        self.draw_grid(painter, board_style, self.background_color, (min_canvas, max_canvas), &outside_overrides);
+
+        for page in &overrides {
+            let area = (
+                [page.min[0].max(min_canvas[0]), page.min[1].max(min_canvas[1])],
+                [page.max[0].min(max_canvas[0]), page.max[1].min(max_canvas[1])],
+            );
+            if area.0[0] > area.1[0] || area.0[1] > area.1[1] {
+                continue;
+            }
+            let background = page.background.map_or(self.background_color, |[r, g, b, a]| egui::Color32::from_rgba_unmultiplied(r, g, b, a));
+            self.draw_grid(painter, page.grid.unwrap_or(board_style), background, area, &|_| true);
+        }
+    }
+
+    fn draw_grid(&self, painter: &egui::Painter, style: GridStyle, background: egui::Color32, area: ([f32; 2], [f32; 2]), include: &dyn Fn([f32; 2]) -> bool) {
+        let grid_spacing = canvas::GRID_SPACING;
+        let dot_size = 2.0;
+        let dot_opacity = 30;
+        
+        let bg_brightness = (background.r() as u32 + 
+                            background.g() as u32 + 
+                            background.b() as u32) / 3;
+        let dot_color = if bg_brightness > 128 {
+            egui::Color32::from_rgba_premultiplied(100, 100, 100, dot_opacity)
+        } else {
+            egui::Color32::from_rgba_premultiplied(200, 200, 200, dot_opacity)
+        };
+        
+        let (min_canvas, max_canvas) = area;
+        
+        let start_x = (min_canvas[0] / grid_spacing).ceil() * grid_spacing;
+        let start_y = (min_canvas[1] / grid_spacing).ceil() * grid_spacing;
+        let end_x = (max_canvas[0] / grid_spacing).floor() * grid_spacing;
+        let end_y = (max_canvas[1] / grid_spacing).floor() * grid_spacing;
+        
+        match style {
+            GridStyle::Blank => {}
+            GridStyle::Dots => {
+                let mut x = start_x;
+                while x <= end_x {
+                    let mut y = start_y;
+                    while y <= end_y {
+                        if include([x, y]) {
+                            let screen_pos = canvas::canvas_to_screen([x, y], self.canvas_offset, self.canvas_zoom, self.canvas_rotation);
+                            painter.circle_filled(screen_pos, dot_size, dot_color);
+                        }
+                        y += grid_spacing;
+                    }
+                    x += grid_spacing;
+                }
+            }
+            GridStyle::Lines => {
+                let stroke = egui::Stroke::new(1.0, dot_color);
+                let to_screen = |pos: [f32; 2]| canvas::canvas_to_screen(pos, self.canvas_offset, self.canvas_zoom, self.canvas_rotation);
+                let mut x = start_x;
+                while x <= end_x {
+                    painter.line_segment([to_screen([x, min_canvas[1]]), to_screen([x, max_canvas[1]])], stroke);
+                    x += grid_spacing;
+                }
+                let mut y = start_y;
+                while y <= end_y {
+                    painter.line_segment([to_screen([min_canvas[0], y]), to_screen([max_canvas[0], y])], stroke);
+                    y += grid_spacing;
+                }
+            }
+        }
+    }
+
+    fn grid_snapping(&self) -> bool {
+        self.show_grid && self.snap_to_grid
+    }
+
+    fn snap_to_guides(&self, canvas_pos: [f32; 2]) -> [f32; 2] {
+        if !self.show_guides {
+            return canvas_pos;
+        }
+        let tolerance = SNAP_TOLERANCE / self.canvas_zoom;
+        [
+            guides::snap_value(canvas_pos[0], &self.guides, GuideAxis::Vertical, tolerance).unwrap_or(canvas_pos[0]),
+            guides::snap_value(canvas_pos[1], &self.guides, GuideAxis::Horizontal, tolerance).unwrap_or(canvas_pos[1]),
+        ]
+    }
+
+    fn render_guides_panel(&mut self, ctx: &egui::Context) {
+        if !self.show_guides_panel {
+            return;
+        }
+
+        let mut open = true;
+        let mut add = None;
+        let mut remove = None;
+        let mut clear = false;
+        egui::Window::new(tr("Guides"))
+            .open(&mut open)
+            .default_width(240.0)
+            .show(ctx, |ui| {
+                if ui.checkbox(&mut self.show_guides, tr("Show and snap to guides")).changed() {
+                    self.needs_repaint = true;
+                }
+                ui.horizontal(|ui| {
+                    ui.add(egui::DragValue::new(&mut self.guide_input).speed(1.0));
+                    if ui.button(tr("Add horizontal")).clicked() {
+                        add = Some(GuideAxis::Horizontal);
+                    }
+                    if ui.button(tr("Add vertical")).clicked() {
+                        add = Some(GuideAxis::Vertical);
+                    }
+                });
+                ui.separator();
+                if self.guides.is_empty() {
+                    ui.label(tr("Drag from a ruler or add a guide above."));
+                }
+                for (index, guide) in self.guides.iter_mut().enumerate() {
+                    ui.horizontal(|ui| {
+                        ui.label(match guide.axis {
+                            GuideAxis::Horizontal => "y =",
+                            GuideAxis::Vertical => "x =",
+                        });
+                        ui.add(egui::DragValue::new(&mut guide.position).speed(1.0));
+                        if ui.button(tr("Remove")).clicked() {
+                            remove = Some(index);
+                        }
+                    });
+                }
+                if !self.guides.is_empty() && ui.button(tr("Clear guides")).clicked() {
+                    clear = true;
+                }
+            });
+
+        if let Some(axis) = add {
+            self.guides.push(Guide { axis, position: self.guide_input });
+        }
+        if let Some(index) = remove {
+            self.guides.remove(index);
+        }
+        if clear {
+            self.guides.clear();
+        }
+        self.show_guides_panel = open;
+    }
+
+    fn max_reveal_step(&self) -> u32 {
+        self.objects.iter().filter_map(|obj| obj.props().reveal_step).max().unwrap_or(0)
+    }
+
+    fn revealed(&self, obj: &DrawObject) -> bool {
+        !self.presenting || obj.props().reveal_step.is_none_or(|step| step <= self.presentation_step)
+    }
+
+    fn set_selection_reveal_step(&mut self, step: Option<u32>) {
+        if self.selected_objects.is_empty() {
+            return;
+        }
+        self.push_undo();
+        let ids = self.selected_objects.clone();
+        self.update_objects(&ids, |obj| obj.props_mut().reveal_step = step);
+    }
+
+    fn start_presentation(&mut self) {
+        self.presenting = true;
+        self.presentation_step = 0;
+        self.selected_objects.clear();
+        self.editing_text = None;
+        self.needs_repaint = true;
+    }
+
+    fn render_reveal_panel(&mut self, ctx: &egui::Context) {
+        if !self.show_reveal_panel || self.presenting {
+            return;
+        }
+
+        let mut open = true;
+        let mut select_step = None;
+        egui::Window::new(tr("Reveal steps"))
+            .open(&mut open)
+            .default_width(260.0)
+            .show(ctx, |ui| {
+                ui.horizontal(|ui| {
+                    ui.label(tr("Step:"));
+                    ui.add(egui::DragValue::new(&mut self.reveal_step_input).range(1..=99));
+                    let has_selection = !self.selected_objects.is_empty();
+                    if ui.add_enabled(has_selection, egui::Button::new(tr("Assign selection"))).clicked() {
+                        self.set_selection_reveal_step(Some(self.reveal_step_input));
+                    }
+                    if ui.add_enabled(has_selection, egui::Button::new(tr("Clear"))).on_hover_text(tr("Always show the selected objects")).clicked() {
+                        self.set_selection_reveal_step(None);
+                    }
+                });
+                ui.separator();
+
+                let always = self.objects.iter().filter(|obj| obj.props().reveal_step.is_none()).count();
+                ui.label(trf("Always visible: {} objects", &[&always]));
+                for step in 1..=self.max_reveal_step() {
+                    let count = self.objects.iter().filter(|obj| obj.props().reveal_step == Some(step)).count();
+                    if count == 0 {
+                        continue;
+                    }
+                    ui.horizontal(|ui| {
+                        ui.label(trf("Step {}: {} objects", &[&step, &count]));
+                        if ui.button(tr("Select")).clicked() {
+                            select_step = Some(step);
+                        }
+                    });
+                }
+                ui.separator();
+                if ui.button(tr("Start presentation")).on_hover_text(tr("Right arrow or Space reveals the next step, Esc exits")).clicked() {
+                    self.start_presentation();
+                }
+            });
+
+        if let Some(step) = select_step {
+            self.selected_objects = self.objects
+                .iter()
+                .filter(|obj| obj.props().reveal_step == Some(step))
+                .map(|obj| obj.id())
+                .collect();
+            self.set_tool(Tool::Select);
+        }
+        self.show_reveal_panel = open;
+    }
+
+    fn handle_presentation(&mut self, ctx: &egui::Context) {
+        let (next, previous, exit) = ctx.input(|i| {
+            (
+                i.key_pressed(egui::Key::ArrowRight)
+                    || i.key_pressed(egui::Key::PageDown)
+                    || i.events.iter().any(|event| matches!(event, egui::Event::Key { key: egui::Key::Space, pressed: true, repeat: false, .. })),
+                i.key_pressed(egui::Key::ArrowLeft) || i.key_pressed(egui::Key::PageUp),
+                i.key_pressed(egui::Key::Escape),
+            )
+        });
+        let last_step = self.max_reveal_step();
+        if exit {
+            self.presenting = false;
+            self.needs_repaint = true;
+            return;
+        }
+        if next && self.presentation_step < last_step {
+            self.presentation_step += 1;
+            self.needs_repaint = true;
+        }
+        if previous && self.presentation_step > 0 {
+            self.presentation_step -= 1;
+            self.needs_repaint = true;
+        }
+
+        egui::Area::new(egui::Id::new("presentation_status"))
+            .anchor(egui::Align2::RIGHT_BOTTOM, egui::vec2(-12.0, -12.0))
+            .show(ctx, |ui| {
+                egui::Frame::popup(ui.style()).show(ui, |ui| {
+                    ui.horizontal(|ui| {
+                        if ui.add_enabled(self.presentation_step > 0, egui::Button::new("◀")).clicked() {
+                            self.presentation_step -= 1;
+                        }
+                        ui.label(trf("Step {} / {}", &[&self.presentation_step, &last_step]));
+                        if ui.add_enabled(self.presentation_step < last_step, egui::Button::new(tr("Next ▶"))).clicked() {
+                            self.presentation_step += 1;
+                        }
+                        if ui.button(tr("Exit")).clicked() {
+                            self.presenting = false;
+                        }
+                    });
+                });
+            });
+    }
+
+    fn render_guides(&self, painter: &egui::Painter, rect: egui::Rect) {
+        if !self.show_guides {
+            return;
+        }
+        let (min, max) = canvas::visible_canvas_bounds(rect, self.canvas_offset, self.canvas_zoom, self.canvas_rotation);
+        let stroke = egui::Stroke::new(1.0, egui::Color32::from_rgba_unmultiplied(0, 170, 220, 180));
+        for guide in &self.guides {
+            let (a, b) = match guide.axis {
+                GuideAxis::Horizontal => ([min[0], guide.position], [max[0], guide.position]),
+                GuideAxis::Vertical => ([guide.position, min[1]], [guide.position, max[1]]),
+            };
+            painter.line_segment(
+                [
+                    canvas::canvas_to_screen(a, self.canvas_offset, self.canvas_zoom, self.canvas_rotation),
+                    canvas::canvas_to_screen(b, self.canvas_offset, self.canvas_zoom, self.canvas_rotation),
+                ],
+                stroke,
+            );
+        }
+    }
+
+    fn render_rulers(&mut self, ui: &mut egui::Ui, painter: &egui::Painter, rect: egui::Rect) {
+        if !self.show_rulers || self.canvas_rotation != 0.0 {
+            return;
+        }
+
+        let top = egui::Rect::from_min_max(rect.min, egui::pos2(rect.max.x, rect.min.y + RULER_SIZE));
+        let left = egui::Rect::from_min_max(rect.min, egui::pos2(rect.min.x + RULER_SIZE, rect.max.y));
+        let background = ui.visuals().extreme_bg_color;
+        let text_color = ui.visuals().weak_text_color();
+        let tick = egui::Stroke::new(1.0, text_color);
+        painter.rect_filled(top, 0.0, background);
+        painter.rect_filled(left, 0.0, background);
+
+        let to_screen = |p: [f32; 2]| canvas::canvas_to_screen(p, self.canvas_offset, self.canvas_zoom, self.canvas_rotation);
+        let (min, max) = canvas::visible_canvas_bounds(rect, self.canvas_offset, self.canvas_zoom, self.canvas_rotation);
+        let step = guides::nice_step(60.0 / self.canvas_zoom);
+        let minor = step / 5.0;
+        let font = egui::FontId::proportional(9.0);
+
+        let mut x = (min[0] / minor).floor() * minor;
+        while x <= max[0] {
+            let sx = to_screen([x, 0.0]).x;
+            if sx > left.right() {
+                let major = (x / step).round() * step;
+                let is_major = (x - major).abs() < minor / 2.0;
+                let length = if is_major { 8.0 } else { 4.0 };
+                painter.line_segment([egui::pos2(sx, top.bottom() - length), egui::pos2(sx, top.bottom())], tick);
+                if is_major {
+                    painter.text(egui::pos2(sx + 2.0, top.top() + 1.0), egui::Align2::LEFT_TOP, format!("{}", major + 0.0), font.clone(), text_color);
+                }
+            }
+            x += minor;
+        }
+
+        let mut y = (min[1] / minor).floor() * minor;
+        while y <= max[1] {
+            let sy = to_screen([0.0, y]).y;
+            if sy > top.bottom() {
+                let major = (y / step).round() * step;
+                let is_major = (y - major).abs() < minor / 2.0;
+                let length = if is_major { 8.0 } else { 4.0 };
+                painter.line_segment([egui::pos2(left.right() - length, sy), egui::pos2(left.right(), sy)], tick);
+                if is_major {
+                    painter.text(egui::pos2(left.left() + 1.0, sy + 1.0), egui::Align2::LEFT_TOP, format!("{}", major + 0.0), font.clone(), text_color);
+                }
+            }
+            y += minor;
+        }
+        painter.rect_filled(top.intersect(left), 0.0, background);
+
+        let top_response = ui.interact(top, ui.id().with("ruler_top"), egui::Sense::drag());
+        let left_response = ui.interact(left, ui.id().with("ruler_left"), egui::Sense::drag());
+        if top_response.drag_started() {
+            self.dragging_guide = Some(GuideAxis::Horizontal);
+        }
+        if left_response.drag_started() {
+            self.dragging_guide = Some(GuideAxis::Vertical);
+        }
+
+        let Some(axis) = self.dragging_guide else {
+            return;
+        };
+        let Some(pointer) = top_response.interact_pointer_pos().or(left_response.interact_pointer_pos()) else {
+            self.dragging_guide = None;
+            return;
+        };
+        let canvas_pos = canvas::screen_to_canvas(pointer, self.canvas_offset, self.canvas_zoom, self.canvas_rotation);
+        let guide = Guide {
+            axis,
+            position: match axis {
+                GuideAxis::Horizontal => canvas_pos[1],
+                GuideAxis::Vertical => canvas_pos[0],
+            },
+        };
+        let over_ruler = top.contains(pointer) || left.contains(pointer);
+
+        if top_response.drag_stopped() || left_response.drag_stopped() {
+            if !over_ruler && rect.contains(pointer) {
+                self.guides.push(guide);
+            }
+            self.dragging_guide = None;
+        } else if !over_ruler {
+            let preview = egui::Stroke::new(1.0, egui::Color32::from_rgba_unmultiplied(0, 170, 220, 120));
+            match axis {
+                GuideAxis::Horizontal => painter.line_segment([egui::pos2(rect.left(), pointer.y), egui::pos2(rect.right(), pointer.y)], preview),
+                GuideAxis::Vertical => painter.line_segment([egui::pos2(pointer.x, rect.top()), egui::pos2(pointer.x, rect.bottom())], preview),
+            };
+        }
+        self.needs_repaint = true;
+    }
+
+    fn render_page_outlines(&self, painter: &egui::Painter, rect: egui::Rect) {
+        let Some([width, height]) = self.settings.page_outline.canvas_size() else {
+            return;
+        };
+        let (min, max) = canvas::visible_canvas_bounds(rect, self.canvas_offset, self.canvas_zoom, self.canvas_rotation);
+        let color = if self.background_color.r() as u32 + self.background_color.g() as u32 + self.background_color.b() as u32 > 384 {
+            egui::Color32::from_rgba_unmultiplied(120, 120, 150, 90)
+        } else {
+            egui::Color32::from_rgba_unmultiplied(180, 180, 210, 90)
+        };
+        let stroke = egui::Stroke::new(1.0, color);
+
+        let first_column = (min[0] / width).floor() as i64;
+        let last_column = (max[0] / width).ceil() as i64;
+        let first_row = (min[1] / height).floor() as i64;
+        let last_row = (max[1] / height).ceil() as i64;
+        if (last_column - first_column) * (last_row - first_row) > 2000 {
+            return;
+        }
+        for row in first_row..last_row {
+            for column in first_column..last_column {
+                let page_min = [column as f32 * width, row as f32 * height];
+                let page_max = [page_min[0] + width, page_min[1] + height];
+                let corners = canvas::canvas_rect_to_screen(page_min, page_max, self.canvas_offset, self.canvas_zoom, self.canvas_rotation);
+                painter.add(egui::Shape::closed_line(corners.to_vec(), stroke));
+            }
+        }
+    }
+
+    fn render_origin_marker(&self, painter: &egui::Painter) {
+        let origin = canvas::canvas_to_screen([0.0, 0.0], self.canvas_offset, self.canvas_zoom, self.canvas_rotation);
+        let color = if self.background_color.r() as u32 + self.background_color.g() as u32 + self.background_color.b() as u32 > 384 {
+            egui::Color32::from_rgba_unmultiplied(90, 90, 110, 110)
+        } else {
+            egui::Color32::from_rgba_unmultiplied(200, 200, 220, 110)
+        };
+        let rot = egui::emath::Rot2::from_angle(self.canvas_rotation);
+        let arm = 12.0;
+        for axis in [egui::vec2(1.0, 0.0), egui::vec2(0.0, 1.0)] {
+            let offset = rot * axis * arm;
+            painter.line_segment([origin - offset, origin + offset], egui::Stroke::new(1.0, color));
+        }
+        painter.circle_stroke(origin, 4.0, egui::Stroke::new(1.0, color));
+    }
+
+    fn scale_menu(&mut self, ui: &mut egui::Ui) {
+        ui.horizontal(|ui| {
+            ui.label("1");
+            ui.add(egui::TextEdit::singleline(&mut self.unit_scale.unit).desired_width(40.0));
+            ui.label("=");
+            ui.add(egui::DragValue::new(&mut self.unit_scale.canvas_units).range(0.01..=10000.0).speed(1.0));
+            ui.label(tr("canvas units"));
+        });
+        if ui.button(tr("Insert scale bar")).clicked() {
+            let center = canvas::screen_to_canvas(self.canvas_rect.center(), self.canvas_offset, self.canvas_zoom, self.canvas_rotation);
+            let length = self.unit_scale.nice_length(150.0 / self.canvas_zoom);
+            self.insert_copies(vec![DrawObject::ScaleBar {
+                id: Uuid::new_v4(),
+                pos: [center[0] - length / 2.0, center[1]],
+                length,
+                label: self.unit_scale.format(length),
+                color: self.current_color.to_array(),
+                props: self.ink_props(),
+            }]);
+            ui.close_menu();
+        }
+    }
+
+    fn sync_scale_bars(&mut self) {
+        let objects: Vec<DrawObject> = self
+            .objects
+            .iter()
+            .filter_map(|obj| match obj {
+                DrawObject::ScaleBar { length, label, .. } if *label != self.unit_scale.format(*length) => {
+                    let mut obj = obj.clone();
+                    if let DrawObject::ScaleBar { length, label, .. } = &mut obj {
+                        *label = self.unit_scale.format(*length);
+                    }
+                    Some(obj)
+                }
+                _ => None,
+            })
+            .collect();
+        if !objects.is_empty() {
+            self.apply_op(DocumentOp::Update { objects });
+        }
+    }
+
+    fn sync_layers(&mut self) {
+        if self.layers.is_empty() {
+            self.layers.push(Layer::new("Layer 1"));
+        }
+        let layers = &self.layers;
+        if !self.active_layer.is_some_and(|id| layers.iter().any(|layer| layer.id == id)) {
+            self.active_layer = layers.last().map(|layer| layer.id);
+        }
+        for obj in &mut self.objects {
+            if !obj.props().layer.is_some_and(|id| layers.iter().any(|layer| layer.id == id)) {
+                obj.props_mut().layer = self.active_layer;
+            }
+        }
+
+        let blocked: Vec<Uuid> = layers.iter().filter(|layer| !layer.visible || layer.locked).map(|layer| layer.id).collect();
+        if !blocked.is_empty() && !self.selected_objects.is_empty() {
+            let objects = &self.objects;
+            self.selected_objects.retain(|id| {
+                objects.iter().find(|obj| obj.id() == *id).is_some_and(|obj| !obj.props().layer.is_some_and(|layer| blocked.contains(&layer)))
+            });
+        }
+    }
+
+    fn layer_index(&self, obj: &DrawObject) -> usize {
+        obj.props()
+            .layer
+            .and_then(|id| self.layers.iter().position(|layer| layer.id == id))
+            .unwrap_or(0)
+    }
+
+    fn layer_editable(&self, obj: &DrawObject) -> bool {
+        let layer = &self.layers[self.layer_index(obj)];
+        layer.visible && !layer.locked
+    }
+
+    fn object_at(&self, canvas_pos: [f32; 2]) -> Option<Uuid> {
+        let started = std::time::Instant::now();
+        let tolerance = HIT_TOLERANCE / self.canvas_zoom;
+        let nearby = self.spatial_index.query(
+            [canvas_pos[0] - tolerance, canvas_pos[1] - tolerance],
+            [canvas_pos[0] + tolerance, canvas_pos[1] + tolerance],
+            self.objects.len(),
+        );
+        let mut candidates: Vec<(usize, &DrawObject)> = nearby
+            .into_iter()
+            .map(|index| &self.objects[index])
+            .filter(|obj| self.layer_editable(obj) && self.revealed(obj))
+            .map(|obj| (self.layer_index(obj), obj))
+            .collect();
+        candidates.sort_by_key(|(layer, _)| *layer);
+        let found = canvas::find_object_at(candidates.into_iter().map(|(_, obj)| obj), canvas_pos, tolerance, self.canvas_zoom);
+        self.profiler.add_hit_test(started.elapsed());
+        found
+    }
+
+    fn render_layers_panel(&mut self, ctx: &egui::Context) {
+        if !self.show_layers_panel {
+            return;
+        }
+
+        let mut open = true;
+        let mut add = false;
+        let mut remove = None;
+        let mut swap = None;
+        let mut move_selection = false;
+        let mut opacity_edit = None;
+        let mut changed = false;
+        egui::Window::new(tr("Layers"))
+            .open(&mut open)
+            .default_width(300.0)
+            .show(ctx, |ui| {
+                ui.horizontal(|ui| {
+                    if ui.button(tr("Add layer")).clicked() {
+                        add = true;
+                    }
+                    if ui.add_enabled(!self.selected_objects.is_empty(), egui::Button::new(tr("Move selection here")))
+                        .on_hover_text(tr("Move the selected objects to the active layer"))
+                        .clicked()
+                    {
+                        move_selection = true;
+                    }
+                });
+                ui.separator();
+                let count = self.layers.len();
+                for index in (0..count).rev() {
+                    let layer = &mut self.layers[index];
+                    ui.horizontal(|ui| {
+                        ui.radio_value(&mut self.active_layer, Some(layer.id), "").on_hover_text(tr("New objects go to the active layer"));
+                        changed |= ui.checkbox(&mut layer.visible, "👁").on_hover_text(tr("Visible")).changed();
+                        changed |= ui.checkbox(&mut layer.locked, "🔒").on_hover_text(tr("Locked")).changed();
+                        ui.add(egui::TextEdit::singleline(&mut layer.name).desired_width(90.0));
+                        let previous = layer.opacity;
+                        let opacity = ui.add(egui::Slider::new(&mut layer.opacity, 0.0..=1.0).show_value(false)).on_hover_text(tr("Opacity"));
+                        if opacity.drag_started() || (opacity.changed() && !opacity.dragged()) {
+                            opacity_edit = Some((index, previous));
+                        }
+                        changed |= opacity.changed();
+                        if ui.add_enabled(index + 1 < count, egui::Button::new("⬆")).clicked() {
+                            swap = Some((index, index + 1));
+                        }
+                        if ui.add_enabled(index > 0, egui::Button::new("⬇")).clicked() {
+                            swap = Some((index, index - 1));
+                        }
+                        if ui.add_enabled(count > 1, egui::Button::new("🗑"))
+                            .on_hover_text(tr("Remove the layer and move its objects to the layer below"))
+                            .clicked()
+                        {
+                            remove = Some(index);
+                        }
+                    });
+                }
+            });
+
+        if add {
+            let layer = Layer::new(trf("Layer {}", &[&(self.layers.len() + 1)]));
+            let above = self.active_layer
+                .and_then(|id| self.layers.iter().position(|layer| layer.id == id))
+                .map_or(self.layers.len(), |index| index + 1);
+            self.active_layer = Some(layer.id);
+            self.layers.insert(above, layer);
+        }
+        if let Some((a, b)) = swap {
+            self.layers.swap(a, b);
+            changed = true;
+        }
+        if let Some((index, previous)) = opacity_edit {
+            let opacity = std::mem::replace(&mut self.layers[index].opacity, previous);
+            self.push_undo();
+            self.layers[index].opacity = opacity;
+        }
+        if let Some(index) = remove {
+            self.push_undo();
+            let removed = self.layers.remove(index).id;
+            let target = self.layers[index.saturating_sub(1)].id;
+            let ids: Vec<Uuid> = self.objects.iter().filter(|obj| obj.props().layer == Some(removed)).map(|obj| obj.id()).collect();
+            self.update_objects(&ids, |obj| obj.props_mut().layer = Some(target));
+            changed = true;
+        }
+        if move_selection {
+            self.push_undo();
+            let ids = self.selected_objects.clone();
+            let layer = self.active_layer;
+            self.update_objects(&ids, |obj| obj.props_mut().layer = layer);
+            changed = true;
+        }
+        if changed {
+            self.needs_repaint = true;
+        }
+        self.show_layers_panel = open;
+    }
+
+    fn stamp_new_objects(&mut self) {
+        let now = clock::now_secs();
+        for obj in &mut self.objects {
+            if obj.props().created.is_none() && self.known_objects.insert(obj.id()) {
+                obj.props_mut().created = Some(now);
+            }
+        }
+    }
+
+    fn recent_since(&self) -> u64 {
+        if self.recent_this_session {
+            self.session_start
+        } else {
+            clock::now_secs().saturating_sub(self.recent_minutes as u64 * 60)
+        }
+    }
+
+    fn is_recent(&self, obj: &DrawObject, since: u64) -> bool {
+        let props = obj.props();
+        props.created.is_some_and(|created| created >= since)
+            || (self.recent_include_modified && props.modified.is_some_and(|modified| modified >= since))
+    }
+
+    fn render_recent_highlight(&self, painter: &egui::Painter) {
+        if !self.highlight_recent {
+            return;
+        }
+        let since = self.recent_since();
+        let color = egui::Color32::from_rgb(0, 158, 115);
+        for obj in self.objects.iter().filter(|obj| !obj.props().hidden && self.is_recent(obj, since)) {
+            let (min, max) = obj.bounds();
+            let pad = 4.0 / self.canvas_zoom;
+            let corners = canvas::canvas_rect_to_screen(
+                [min[0] - pad, min[1] - pad],
+                [max[0] + pad, max[1] + pad],
+                self.canvas_offset,
+                self.canvas_zoom,
+                self.canvas_rotation,
+            );
+            painter.add(egui::Shape::closed_line(corners.to_vec(), egui::Stroke::new(4.0, color.gamma_multiply(0.3))));
+            painter.add(egui::Shape::closed_line(corners.to_vec(), egui::Stroke::new(1.0, color)));
+        }
+    }
+
+    fn render_properties_panel(&mut self, ctx: &egui::Context) {
+        if !self.show_properties_panel {
+            return;
+        }
+        if !ctx.wants_keyboard_input() {
+            self.properties_undo_pushed = false;
+        }
+
+        let selected = match self.selected_objects[..] {
+            [id] => self.objects.iter().find(|obj| obj.id() == id),
+            _ => None,
+        };
+        let mut entries: Vec<(String, String)> = selected
+            .map(|obj| obj.props().metadata.iter().map(|(k, v)| (k.clone(), v.clone())).collect())
+            .unwrap_or_default();
+        entries.sort();
+        let mut open = true;
+        let mut changed = false;
+        let mut remove = None;
+        let mut add = false;
+        egui::Window::new(tr("Properties"))
+            .open(&mut open)
+            .default_width(300.0)
+            .show(ctx, |ui| {
+                let Some(obj) = selected else {
+                    ui.label(match self.selected_objects.len() {
+                        0 => tr("Select an object to see its properties.").to_string(),
+                        count => trf("{} objects selected. Select a single object to edit its metadata.", &[&count]),
+                    });
+                    return;
+                };
+                ui.strong(obj.type_name());
+                ui.label(obj.describe());
+                let props = obj.props();
+                if let Some(created) = props.created {
+                    ui.label(trf("Created {}", &[&clock::format_timestamp(created)]));
+                }
+                if let Some(modified) = props.modified {
+                    ui.label(trf("Modified {}", &[&clock::format_timestamp(modified)]));
+                }
+                ui.separator();
+                ui.label(tr("Metadata:"));
+                egui::Grid::new("metadata_grid").num_columns(3).show(ui, |ui| {
+                    for (index, (key, value)) in entries.iter_mut().enumerate() {
+                        ui.label(key.as_str());
+                        changed |= ui.text_edit_singleline(value).changed();
+                        if ui.small_button("🗑").on_hover_text(tr("Remove this entry")).clicked() {
+                            remove = Some(index);
+                        }
+                        ui.end_row();
+                    }
+                    ui.add(egui::TextEdit::singleline(&mut self.new_metadata_key).hint_text(tr("key")).desired_width(80.0));
+                    ui.add(egui::TextEdit::singleline(&mut self.new_metadata_value).hint_text(tr("value")));
+                    let key = self.new_metadata_key.trim();
+                    if ui.add_enabled(!key.is_empty(), egui::Button::new(tr("Add"))).clicked() {
+                        add = true;
+                    }
+                    ui.end_row();
+                });
+            });
+
+        if let Some(index) = remove {
+            entries.remove(index);
+            changed = true;
+        }
+        if add {
+            let key = self.new_metadata_key.trim().to_string();
+            match entries.iter_mut().find(|(existing, _)| *existing == key) {
+                Some((_, value)) => *value = std::mem::take(&mut self.new_metadata_value),
+                None => entries.push((key, std::mem::take(&mut self.new_metadata_value))),
+            }
+            self.new_metadata_key.clear();
+            changed = true;
+        }
+        if changed {
+            if let [id] = self.selected_objects[..] {
+                if !self.properties_undo_pushed || add || remove.is_some() {
+                    self.push_undo();
+                    self.properties_undo_pushed = true;
+                }
+                let metadata: HashMap<String, String> = entries.into_iter().collect();
+                self.update_objects(&[id], |obj| obj.props_mut().metadata = metadata.clone());
+            }
+        }
+        self.show_properties_panel = open;
+    }
+
+    fn save_templates(&self) {
+        let user: Vec<Template> = self.templates.iter().filter(|template| !template.builtin).cloned().collect();
+        if let Err(e) = file_io::save_templates(&user, TEMPLATES_PATH) {
+            eprintln!("Error saving templates: {}", e);
+        }
+    }
+
+    fn start_template(&mut self, index: usize) {
+        let Some(template) = self.templates.get(index).cloned() else {
+            return;
+        };
+        let center = canvas::screen_to_canvas(self.canvas_rect.center(), self.canvas_offset, self.canvas_zoom, self.canvas_rotation);
+        let fill = TemplateFill::new(template, center);
+        if fill.placeholders.is_empty() {
+            self.insert_template(&fill);
+        } else {
+            self.template_fill = Some(fill);
+        }
+    }
+
+    fn insert_template(&mut self, fill: &TemplateFill) {
+        let contents = ClipboardContents::new(fill.objects());
+        let offset = contents.bounds().map_or(fill.center, |(min, max)| {
+            [fill.center[0] - (min[0] + max[0]) / 2.0, fill.center[1] - (min[1] + max[1]) / 2.0]
+        });
+        self.insert_copies(contents.instantiate(offset));
+    }
+
+    fn render_templates_panel(&mut self, ctx: &egui::Context) {
+        if !self.show_templates_panel {
+            return;
+        }
+
+        let mut open = true;
+        let mut insert = None;
+        let mut remove = None;
+        let mut save = false;
+        let has_selection = !self.selected_objects.is_empty();
+        egui::Window::new(tr("Templates"))
+            .open(&mut open)
+            .default_width(300.0)
+            .show(ctx, |ui| {
+                for (index, template) in self.templates.iter().enumerate() {
+                    ui.horizontal(|ui| {
+                        ui.label(&template.name);
+                        let placeholders = template.placeholders();
+                        if !placeholders.is_empty() {
+                            ui.weak(trf("{} fields", &[&placeholders.len()])).on_hover_text(placeholders.join(", "));
+                        }
+                        if ui.button(tr("Insert")).clicked() {
+                            insert = Some(index);
+                        }
+                        if !template.builtin && ui.button("🗑").on_hover_text(tr("Delete template")).clicked() {
+                            remove = Some(index);
+                        }
+                    });
+                }
+                ui.separator();
+                ui.label(tr("Write {{Name}} in a text or formula to make it a field you fill in when inserting."));
+                ui.horizontal(|ui| {
+                    ui.add(egui::TextEdit::singleline(&mut self.template_name).hint_text(tr("Template name")).desired_width(160.0));
+                    let can_save = has_selection && !self.template_name.trim().is_empty();
+                    if ui.add_enabled(can_save, egui::Button::new(tr("Save selection"))).clicked() {
+                        save = true;
+                    }
+                });
+            });
+
+        if let Some(index) = insert {
+            self.start_template(index);
+        }
+        if let Some(index) = remove {
+            self.templates.remove(index);
+            self.save_templates();
+        }
+        if save {
+            let objects = self.selected_clones();
+            let name = std::mem::take(&mut self.template_name).trim().to_string();
+            self.templates.push(Template::from_objects(name, &objects));
+            self.save_templates();
+        }
+        self.show_templates_panel = open;
+    }
+
+    fn render_template_fill(&mut self, ctx: &egui::Context) {
+        let Some(fill) = &mut self.template_fill else {
+            return;
+        };
+
+        let mut open = true;
+        let mut finish = false;
+        egui::Window::new(trf("Insert {}", &[&fill.template.name]))
+            .open(&mut open)
+            .collapsible(false)
+            .show(ctx, |ui| {
+                ui.label(trf("{} ({} of {})", &[&fill.placeholders[fill.step], &(fill.step + 1), &fill.placeholders.len()]));
+                let input = ui.text_edit_singleline(&mut fill.values[fill.step]);
+                if ui.memory(|m| m.focused().is_none()) {
+                    input.request_focus();
+                }
+                let submitted = input.lost_focus() && ui.input(|i| i.key_pressed(egui::Key::Enter));
+                ui.weak(tr("Leave a field empty to keep its placeholder."));
+                ui.horizontal(|ui| {
+                    if ui.add_enabled(fill.step > 0, egui::Button::new(tr("Back"))).clicked() {
+                        fill.step -= 1;
+                    }
+                    let label = tr(if fill.is_last_step() { "Insert" } else { "Next" });
+                    if ui.button(label).clicked() || submitted {
+                        if fill.is_last_step() {
+                            finish = true;
+                        } else {
+                            fill.step += 1;
+                            input.request_focus();
+                        }
+                    }
+                });
+            });
+
+        if finish {
+            if let Some(fill) = self.template_fill.take() {
+                self.insert_template(&fill);
+            }
+        } else if !open {
+            self.template_fill = None;
+        }
+    }
+
+    fn render_recent_panel(&mut self, ctx: &egui::Context) {
+        if !self.show_recent_panel {
+            return;
+        }
+
+        let since = self.recent_since();
+        let recent: Vec<Uuid> = self.objects
+            .iter()
+            .filter(|obj| self.is_recent(obj, since))
+            .map(|obj| obj.id())
+            .collect();
+        let mut open = true;
+        let mut select = false;
+        egui::Window::new(tr("Recent changes"))
+            .open(&mut open)
+            .default_width(260.0)
+            .show(ctx, |ui| {
+                ui.checkbox(&mut self.highlight_recent, tr("Highlight on the board"));
+                ui.horizontal(|ui| {
+                    ui.radio_value(&mut self.recent_this_session, false, tr("Last"));
+                    ui.add_enabled(
+                        !self.recent_this_session,
+                        egui::DragValue::new(&mut self.recent_minutes).range(1..=1440).suffix(" min"),
+                    );
+                });
+                ui.radio_value(&mut self.recent_this_session, true, tr("This session"))
+                    .on_hover_text(trf("Since {}", &[&clock::format_timestamp(self.session_start)]));
+                ui.checkbox(&mut self.recent_include_modified, tr("Include modified objects"));
+                ui.separator();
+                ui.label(trf("{} objects", &[&recent.len()]));
+                if ui.add_enabled(!recent.is_empty(), egui::Button::new(tr("Select them"))).clicked() {
+                    select = true;
+                }
+            });
+
+        if select {
+            self.selected_objects = recent;
+            self.set_tool(Tool::Select);
+        }
+        self.show_recent_panel = open;
+    }
+
+    fn stroke_ink(&self) -> (egui::Color32, f32) {
+        if self.current_tool == Tool::Highlighter {
+            let [r, g, b, a] = self.settings.highlighter_color;
+            (egui::Color32::from_rgba_unmultiplied(r, g, b, a), self.settings.highlighter_width)
+        } else {
+            (self.current_color, self.brush_size)
+        }
+    }
+
+    fn ink_props(&self) -> ObjectProps {
+        let color = self.current_color.to_array();
+        ObjectProps {
+            style: self.active_style.filter(|id| self.styles.iter().any(|style| style.id == *id && style.color == color)),
+            ..ObjectProps::default()
+        }
+    }
+
+    fn apply_style_to_selection(&mut self, id: Uuid) {
+        let Some(color) = self.styles.iter().find(|style| style.id == id).map(|style| style.color) else {
+            return;
+        };
+        self.push_undo();
+        let ids = self.selected_objects.clone();
+        self.update_objects(&ids, |obj| {
+            if let DrawObject::LatexFormula { auto_color, .. } = obj {
+                *auto_color = false;
+            }
+            if let Some(obj_color) = obj.color_mut() {
+                *obj_color = color;
+                obj.props_mut().style = Some(id);
+            }
+        });
+    }
+
+    fn update_style_color(&mut self, id: Uuid, color: [u8; 4]) {
+        if !self.style_undo_pushed {
+            self.push_undo();
+            self.style_undo_pushed = true;
+        }
+        if let Some(style) = self.styles.iter_mut().find(|style| style.id == id) {
+            style.color = color;
+        }
+        let ids: Vec<Uuid> = self.objects.iter().filter(|obj| obj.props().style == Some(id)).map(|obj| obj.id()).collect();
+        self.update_objects(&ids, |obj| {
+            if let Some(obj_color) = obj.color_mut() {
+                *obj_color = color;
+            }
+        });
+        if self.active_style == Some(id) {
+            self.current_color = egui::Color32::from_rgba_unmultiplied(color[0], color[1], color[2], color[3]);
+        }
+        self.needs_repaint = true;
+    }
+
+    fn render_styles_panel(&mut self, ctx: &egui::Context) {
+        if !self.show_styles_panel {
+            return;
+        }
+        if !ctx.input(|i| i.pointer.any_down()) {
+            self.style_undo_pushed = false;
+        }
+
+        let mut open = true;
+        let mut add = false;
+        let mut recolored = None;
+        let mut renaming = false;
+        let mut use_style = None;
+        let mut apply = None;
+        let mut remove = None;
+        let has_selection = !self.selected_objects.is_empty();
+        egui::Window::new(tr("Styles"))
+            .open(&mut open)
+            .default_width(300.0)
+            .show(ctx, |ui| {
+                if ui.button(tr("Add style from current color")).clicked() {
+                    add = true;
+                }
+                ui.separator();
+                if self.styles.is_empty() {
+                    ui.label(tr("Styles name a color, such as \"definition red\". Changing a style recolors everything that uses it."));
+                }
+                for style in &mut self.styles {
+                    let objects = &self.objects;
+                    let uses = objects.iter().filter(|obj| obj.props().style == Some(style.id)).count();
+                    ui.horizontal(|ui| {
+                        let mut color = egui::Color32::from_rgba_unmultiplied(style.color[0], style.color[1], style.color[2], style.color[3]);
+                        if egui::color_picker::color_edit_button_srgba(ui, &mut color, egui::color_picker::Alpha::Opaque).changed() {
+                            recolored = Some((style.id, color.to_array()));
+                        }
+                        renaming |= ui.add(egui::TextEdit::singleline(&mut style.name).desired_width(110.0)).gained_focus();
+                        ui.label(format!("{}×", uses)).on_hover_text(tr("Objects using this style"));
+                        if ui.selectable_label(self.active_style == Some(style.id), tr("Use"))
+                            .on_hover_text(tr("Draw with this style"))
+                            .clicked()
+                        {
+                            use_style = Some(style.id);
+                        }
+                        if ui.add_enabled(has_selection, egui::Button::new(tr("Apply")))
+                            .on_hover_text(tr("Apply to the selection"))
+                            .clicked()
+                        {
+                            apply = Some(style.id);
+                        }
+                        if ui.button("🗑").on_hover_text(tr("Remove the style; objects keep their current color")).clicked() {
+                            remove = Some(style.id);
+                        }
+                    });
+                }
+            });
+
+        if add {
+            let style = ColorStyle {
+                id: Uuid::new_v4(),
+                name: trf("Style {}", &[&(self.styles.len() + 1)]),
+                color: self.current_color.to_array(),
+            };
+            self.push_undo();
+            self.active_style = Some(style.id);
+            self.styles.push(style);
+        }
+        if renaming {
+            self.push_undo();
+        }
+        if let Some((id, color)) = recolored {
+            self.update_style_color(id, color);
+        }
+        if let Some(id) = use_style {
+            if let Some(style) = self.styles.iter().find(|style| style.id == id) {
+                self.current_color = egui::Color32::from_rgba_unmultiplied(style.color[0], style.color[1], style.color[2], style.color[3]);
+                self.active_style = Some(id);
+            }
+        }
+        if let Some(id) = apply {
+            self.apply_style_to_selection(id);
+        }
+        if let Some(id) = remove {
+            self.push_undo();
+            self.styles.retain(|style| style.id != id);
+            let ids: Vec<Uuid> = self.objects.iter().filter(|obj| obj.props().style == Some(id)).map(|obj| obj.id()).collect();
+            self.update_objects(&ids, |obj| obj.props_mut().style = None);
+        }
+        self.show_styles_panel = open;
+    }
+
+    fn render_objects(&mut self, ctx: &egui::Context, painter: &egui::Painter) {
+        let reviewing_hidden = self.show_hidden && !self.presenting;
+        let (view_min, view_max) = canvas::visible_canvas_bounds(self.canvas_rect, self.canvas_offset, self.canvas_zoom, self.canvas_rotation);
+        let margin = CULL_MARGIN / self.canvas_zoom;
+        let (view_min, view_max) = ([view_min[0] - margin, view_min[1] - margin], [view_max[0] + margin, view_max[1] + margin]);
+        let in_view = self.spatial_index.query(view_min, view_max, self.objects.len());
+        let overlaps_view = |obj: &DrawObject| {
+            let (min, max) = obj.bounds();
+            max[0] >= view_min[0] && min[0] <= view_max[0] && max[1] >= view_min[1] && min[1] <= view_max[1]
+        };
+        let mut layered: Vec<(usize, &DrawObject)> = in_view
+            .into_iter()
+            .map(|index| &self.objects[index])
+            .filter(|obj| (reviewing_hidden || !obj.props().hidden) && self.revealed(obj))
+            .map(|obj| (self.layer_index(obj), obj))
+            .filter(|(layer, _)| self.layers[*layer].visible)
+            .collect();
+        layered.sort_by_key(|(layer, obj)| (*layer, !obj.props().highlight));
+        let visible: Vec<(&DrawObject, bool, f32)> = layered
+            .into_iter()
+            .flat_map(|(layer, obj)| {
+                let hidden = obj.props().hidden;
+                let layer_opacity = self.layers[layer].opacity;
+                let members: Vec<&DrawObject> = match obj {
+                    DrawObject::Instance { members, .. } => members.iter().filter(|member| overlaps_view(member)).collect(),
+                    _ => vec![obj],
+                };
+                members.into_iter().map(move |member| (member, hidden || member.props().hidden, layer_opacity))
+            })
+            .filter(|(_, hidden, _)| reviewing_hidden || !hidden)
+            .map(|(obj, hidden, layer_opacity)| (obj, hidden, if hidden { layer_opacity * HIDDEN_PREVIEW_OPACITY } else { layer_opacity }))
+            .collect();
+        self.profiler.current.objects_drawn = visible.len();
+        self.profiler.current.objects_total = self.objects.len();
+
+        if self.in_overview() {
+            for (obj, _, opacity) in visible {
+                canvas::render_overview_block(painter, obj, obj.props().opacity * opacity, self.canvas_offset, self.canvas_zoom, self.canvas_rotation);
+            }
+            return;
+        }
+
+        let backgrounds = selection::page_backgrounds(&self.objects);
+        let latex_formulas: Vec<_> = visible
+            .iter()
+            .filter_map(|(obj, _, opacity)| {
+                if let DrawObject::LatexFormula { id, pos, formula, color, scale, auto_color, font, pin_size, props, .. } = obj {
+                    if !formula.is_empty() {
+                        let (min, max) = obj.bounds();
+                        let center = [(min[0] + max[0]) / 2.0, (min[1] + max[1]) / 2.0];
+                        let color = if *auto_color { palette::auto_ink(self.background_at(&backgrounds, center)) } else { *color };
+                        let scale = if *pin_size { [scale[0] / self.canvas_zoom, scale[1] / self.canvas_zoom] } else { *scale };
+                        return Some((*id, *pos, formula.clone(), font.clone(), color, scale, props.opacity * opacity));
+                    }
+                }
+                None
+            })
+            .collect();
+
+        for (obj, _, opacity) in visible {
+            match obj {
+                DrawObject::LatexFormula { .. } => {}
+                DrawObject::Image { id, min, max, data, adjustments, props } => {
+                    if let Some(texture) = self.image_cache.get_or_create_texture(ctx, *id, data, adjustments) {
+                        let corners = canvas::canvas_rect_to_screen(*min, *max, self.canvas_offset, self.canvas_zoom, self.canvas_rotation);
+                        painter.add(canvas::textured_quad(texture.id(), corners, props.opacity * opacity));
+                    }
+                }
+                _ if opacity < 1.0 => {
+                    let mut preview = obj.clone();
+                    preview.props_mut().opacity *= opacity;
+                    canvas::render_object(painter, &preview, self.canvas_offset, self.canvas_zoom, self.canvas_rotation);
+                }
+                _ => canvas::render_object(painter, obj, self.canvas_offset, self.canvas_zoom, self.canvas_rotation),
+            }
+        }
+
+        for (id, pos, formula, font, color, scale, opacity) in latex_formulas {
+            if let Some(texture) = self.latex_renderer.get_or_create_texture(ctx, &formula, font.as_deref(), color) {
+                let screen_pos = canvas::canvas_to_screen(pos, self.canvas_offset, self.canvas_zoom, self.canvas_rotation);
+                let natural_size = match self.objects.iter_mut().find(|o| o.id() == id) {
+                    Some(DrawObject::LatexFormula { size: stored_size, .. }) => {
+                        *stored_size.get_or_insert_with(|| self.latex_renderer.display_size(texture.size()))
+                    }
+                    _ => self.latex_renderer.display_size(texture.size()),
+                };
+                let size = egui::vec2(natural_size[0] * scale[0], natural_size[1] * scale[1]) * self.canvas_zoom;
+                
+                let rect = egui::Rect::from_min_size(screen_pos, size);
+                let mut mesh = egui::Mesh::with_texture(texture.id());
+                mesh.add_rect_with_uv(
+                    rect,
+                    egui::Rect::from_min_max(egui::pos2(0.0, 0.0), egui::pos2(1.0, 1.0)),
+                    egui::Color32::WHITE.gamma_multiply(opacity),
+                );
+                if self.canvas_rotation != 0.0 {
+                    mesh.rotate(egui::emath::Rot2::from_angle(self.canvas_rotation), screen_pos);
+                }
+                painter.add(mesh);
+            } else if self.latex_renderer.is_pending(&formula, font.as_deref(), color) {
+                let natural_size = match self.objects.iter().find(|o| o.id() == id) {
+                    Some(DrawObject::LatexFormula { size: Some(size), .. }) => *size,
+                    _ => [100.0, 40.0],
+                };
+                let max = [pos[0] + natural_size[0] * scale[0], pos[1] + natural_size[1] * scale[1]];
+                let corners = canvas::canvas_rect_to_screen(pos, max, self.canvas_offset, self.canvas_zoom, self.canvas_rotation);
+                let ink = egui::Color32::from_rgba_unmultiplied(color[0], color[1], color[2], color[3]).gamma_multiply(0.3 * opacity);
+                painter.add(egui::Shape::convex_polygon(corners.to_vec(), ink.gamma_multiply(0.3), egui::Stroke::new(1.0, ink)));
+                painter.text(
+                    egui::pos2((corners[0].x + corners[2].x) / 2.0, (corners[0].y + corners[2].y) / 2.0),
+                    egui::Align2::CENTER_CENTER,
+                    "…",
+                    egui::FontId::proportional(14.0),
+                    ink,
+                );
+            }
+        }
+    }
+
+    fn update_idle_state(&mut self, ctx: &egui::Context) {
+        let (now, active) = ctx.input(|i| {
+            (i.time, !i.events.is_empty() || i.pointer.is_moving() || i.pointer.any_down() || i.raw_scroll_delta != egui::Vec2::ZERO)
+        });
+        if active {
+            self.last_input_time = now;
+        }
+        self.idle = now - self.last_input_time > IDLE_AFTER_SECS;
+
+        if !self.idle && now - self.last_maintenance_time > MAINTENANCE_INTERVAL_SECS {
+            self.last_maintenance_time = now;
+            self.run_maintenance();
+        }
+    }
+
+    fn run_maintenance(&mut self) {
+        let live_images: std::collections::HashSet<Uuid> = self.objects
+            .iter()
+            .chain(self.history.objects())
+            .flat_map(|obj| match obj {
+                DrawObject::Instance { members, .. } => members.iter().collect(),
+                other => vec![other],
+            })
+            .filter(|obj| matches!(obj, DrawObject::Image { .. }))
+            .map(|obj| obj.id())
+            .collect();
+        self.image_cache.retain(&live_images);
+        self.enforce_memory_budget();
+    }
+
+    fn measure_memory(&self) -> MemoryUsage {
+        MemoryUsage {
+            latex: self.latex_renderer.memory_bytes(),
+            images: self.image_cache.memory_bytes(),
+            history: self.history.memory_bytes(),
+            document: self.objects.iter().map(memory::object_bytes).sum(),
+        }
+    }
+
+    fn enforce_memory_budget(&mut self) {
+        let budget = self.settings.memory_budget_mb as usize * memory::MEGABYTE;
+        let mut usage = self.measure_memory();
+        if usage.trimmable() > budget {
+            usage.latex -= self.latex_renderer.trim(usage.trimmable() - budget).min(usage.latex);
+        }
+        if usage.trimmable() > budget {
+            let objects = &self.objects;
+            let freed = self.image_cache.downsample(usage.trimmable() - budget, |id| {
+                objects
+                    .iter()
+                    .flat_map(|obj| match obj {
+                        DrawObject::Instance { members, .. } => members.iter().collect(),
+                        other => vec![other],
+                    })
+                    .find_map(|obj| match obj {
+                        DrawObject::Image { id: image_id, data, adjustments, .. } if *image_id == id => Some((data.as_slice(), adjustments)),
+                        _ => None,
+                    })
+            });
+            usage.images -= freed.min(usage.images);
+        }
+        if usage.trimmable() > budget {
+            let (freed, dropped) = self.history.trim(usage.trimmable() - budget);
+            usage.history -= freed.min(usage.history);
+            if dropped > 0 {
+                self.memory_notice = Some(trf("Dropped the {} oldest undo steps to stay within the memory budget", &[&dropped]));
+            }
+        }
+        self.memory_usage = usage;
+    }
+
+    fn render_stroke_prediction(&self, ctx: &egui::Context, painter: &egui::Painter) {
+        if self.settings.stroke_prediction_ms <= 0.0 {
+            return;
+        }
+        let Some(last) = self.current_stroke.last() else {
+            return;
+        };
+        let velocity = ctx.input(|i| i.pointer.velocity());
+        let mut lead = velocity * self.settings.stroke_prediction_ms / 1000.0;
+        if lead.length() > 60.0 {
+            lead = lead.normalized() * 60.0;
+        }
+        if lead.length() < 1.0 {
+            return;
+        }
+
+        let start = canvas::canvas_to_screen(last.pos, self.canvas_offset, self.canvas_zoom, self.canvas_rotation);
+        let (color, base_width) = self.stroke_ink();
+        let width = last.width.unwrap_or(base_width);
+        painter.line_segment(
+            [start, start + lead],
+            egui::Stroke::new(width * self.canvas_zoom, color.gamma_multiply(0.6)),
+        );
     }
 
-    fn render_grid(&self, painter: &egui::Painter, rect: egui::Rect) {
-        if !self.show_grid {
+    fn render_transform_readout(&self, painter: &egui::Painter, response: &egui::Response) {
+        let Some(readout) = &self.transform_readout else {
             return;
-        }
+        };
+        let Some(pointer_pos) = response.interact_pointer_pos().or(response.hover_pos()) else {
+            return;
+        };
+        let galley = painter.layout_no_wrap(readout.clone(), egui::FontId::monospace(12.0), egui::Color32::WHITE);
+        let text_pos = pointer_pos + egui::vec2(16.0, 16.0);
+        let background = egui::Rect::from_min_size(text_pos, galley.size()).expand(4.0);
+        painter.rect_filled(background, 3.0, egui::Color32::from_rgba_unmultiplied(30, 30, 30, 200));
+        painter.galley(text_pos, galley, egui::Color32::WHITE);
+    }
 
-        let grid_spacing = 50.0;
-        let dot_size = 2.0;
-        let dot_opacity = 30;
-        
-        let bg_brightness = (self.background_color.r() as u32 + 
-                            self.background_color.g() as u32 + 
-                            self.background_color.b() as u32) / 3;
-        let dot_color = if bg_brightness > 128 {
-            egui::Color32::from_rgba_premultiplied(100, 100, 100, dot_opacity)
-        } else {
-            egui::Color32::from_rgba_premultiplied(200, 200, 200, dot_opacity)
+    fn render_hover_highlight(&self, painter: &egui::Painter, response: &egui::Response) {
+        let color = match self.current_tool {
+            Tool::Select if self.selection_mode == SelectionMode::None => egui::Color32::from_rgb(50, 100, 255),
+            Tool::Eraser => egui::Color32::from_rgb(230, 60, 60),
+            _ => return,
         };
-        
-        let min_canvas = canvas::screen_to_canvas(rect.min, self.canvas_offset, self.canvas_zoom);
-        let max_canvas = canvas::screen_to_canvas(rect.max, self.canvas_offset, self.canvas_zoom);
-        
-        let start_x = (min_canvas[0] / grid_spacing).floor() * grid_spacing;
-        let start_y = (min_canvas[1] / grid_spacing).floor() * grid_spacing;
-        let end_x = (max_canvas[0] / grid_spacing).ceil() * grid_spacing;
-        let end_y = (max_canvas[1] / grid_spacing).ceil() * grid_spacing;
-        
-        let mut x = start_x;
-        while x <= end_x {
-            let mut y = start_y;
-            while y <= end_y {
-                let screen_pos = canvas::canvas_to_screen([x, y], self.canvas_offset, self.canvas_zoom);
-                painter.circle_filled(screen_pos, dot_size, dot_color);
-                y += grid_spacing;
-            }
-            x += grid_spacing;
+        let Some(hover_pos) = response.hover_pos() else {
+            return;
+        };
+        if self.current_tool == Tool::Eraser && self.settings.eraser_mode == EraserMode::Partial {
+            painter.circle_stroke(hover_pos, self.settings.eraser_radius, egui::Stroke::new(1.5, color));
+            return;
+        }
+        let canvas_pos = canvas::screen_to_canvas(hover_pos, self.canvas_offset, self.canvas_zoom, self.canvas_rotation);
+        let Some(id) = self.object_at(canvas_pos) else {
+            return;
+        };
+        if self.current_tool == Tool::Select && self.selected_objects.contains(&id) {
+            return;
         }
+        let Some(obj) = self.objects.iter().find(|o| o.id() == id) else {
+            return;
+        };
+
+        let (min, max) = obj.bounds();
+        let pad = 3.0 / self.canvas_zoom;
+        let corners = canvas::canvas_rect_to_screen(
+            [min[0] - pad, min[1] - pad],
+            [max[0] + pad, max[1] + pad],
+            self.canvas_offset,
+            self.canvas_zoom,
+            self.canvas_rotation,
+        );
+        painter.add(egui::Shape::closed_line(corners.to_vec(), egui::Stroke::new(5.0, color.gamma_multiply(0.25))));
+        painter.add(egui::Shape::closed_line(corners.to_vec(), egui::Stroke::new(1.0, color.gamma_multiply(0.8))));
     }
 
-    fn render_objects(&mut self, ctx: &egui::Context, painter: &egui::Painter) {
-        let latex_formulas: Vec<(Uuid, [f32; 2], String, [u8; 4])> = self.objects
-            .iter()
-            .filter_map(|obj| {
-                if let DrawObject::LatexFormula { id, pos, formula, color, .. } = obj {
-                    if !formula.is_empty() {
-                        return Some((*id, *pos, formula.clone(), *color));
-                    }
-                }
-                None
-            })
-            .collect();
+    fn publish_accessibility_tree(&self, ctx: &egui::Context, response: &egui::Response) {
+        let object_count = self.objects.len();
+        response.widget_info(|| {
+            egui::WidgetInfo::labeled(
+                egui::WidgetType::Other,
+                true,
+                trf("Whiteboard canvas with {} objects", &[&object_count]),
+            )
+        });
 
-        for obj in &self.objects {
-            if !matches!(obj, DrawObject::LatexFormula { .. }) {
-                canvas::render_object(painter, obj, self.canvas_offset, self.canvas_zoom);
+        ctx.with_accessibility_parent(response.id, || {
+            for obj in self.objects.iter().filter(|obj| !obj.props().hidden) {
+                let (min, max) = obj.bounds();
+                let screen_rect = egui::Rect::from_points(&canvas::canvas_rect_to_screen(
+                    min,
+                    max,
+                    self.canvas_offset,
+                    self.canvas_zoom,
+                    self.canvas_rotation,
+                ));
+                let selected = self.selected_objects.contains(&obj.id());
+                ctx.accesskit_node_builder(response.id.with(obj.id()), |builder| {
+                    builder.set_role(egui::accesskit::Role::GraphicsObject);
+                    builder.set_role_description(obj.type_name());
+                    builder.set_name(obj.describe());
+                    builder.set_selected(selected);
+                    builder.set_bounds(egui::accesskit::Rect {
+                        x0: screen_rect.min.x.into(),
+                        y0: screen_rect.min.y.into(),
+                        x1: screen_rect.max.x.into(),
+                        y1: screen_rect.max.y.into(),
+                    });
+                });
             }
-        }
+        });
+    }
 
-        for (id, pos, formula, color) in latex_formulas {
-            if let Some(texture) = self.latex_renderer.get_or_create_texture(ctx, &formula, color) {
-                let screen_pos = canvas::canvas_to_screen(pos, self.canvas_offset, self.canvas_zoom);
-                let size = texture.size_vec2() * self.canvas_zoom;
-                
-                let canvas_size = [size.x / self.canvas_zoom, size.y / self.canvas_zoom];
-                if let Some(DrawObject::LatexFormula { cached_size, .. }) = self.objects.iter_mut().find(|o| o.id() == id) {
-                    *cached_size = Some(canvas_size);
-                }
-                
-                let rect = egui::Rect::from_min_size(screen_pos, size);
-                painter.image(
-                    texture.id(),
-                    rect,
-                    egui::Rect::from_min_max(egui::pos2(0.0, 0.0), egui::pos2(1.0, 1.0)),
-                    egui::Color32::WHITE,
-                );
-            }
+    fn stroke_point(&self, response: &egui::Response, canvas_pos: [f32; 2]) -> StrokePoint {
+        if self.current_tool != Tool::Highlighter {
+            return StrokePoint::new(canvas_pos);
+        }
+        let force = response.ctx.input(|i| {
+            i.events.iter().rev().find_map(|event| match event {
+                egui::Event::Touch { force, .. } => *force,
+                _ => None,
+            })
+        });
+        let Some(force) = force else {
+            return StrokePoint::new(canvas_pos);
+        };
+        let weight = 0.25 + 0.75 * force.clamp(0.0, 1.0);
+        let (width, opacity) = match self.settings.highlighter_pressure {
+            PressureMapping::Width => (weight, None),
+            PressureMapping::Opacity => (1.0, Some(weight)),
+            PressureMapping::Both => (weight, Some(weight)),
+        };
+        StrokePoint {
+            pos: canvas_pos,
+            width: Some(self.stroke_ink().1 * width),
+            opacity,
         }
     }
 
     fn handle_brush_tool(&mut self, response: &egui::Response, canvas_pos: [f32; 2]) {
         if response.drag_started() {
             self.is_drawing = true;
-            self.current_stroke = vec![StrokePoint { pos: canvas_pos }];
+            self.current_stroke = vec![self.stroke_point(response, canvas_pos)];
             self.needs_repaint = true;
         }
 
         if self.is_drawing && response.dragged() {
-            self.current_stroke.push(StrokePoint { pos: canvas_pos });
+            let (straight, samples) = response.ctx.input(|i| {
+                let samples: Vec<egui::Pos2> = i.events
+                    .iter()
+                    .filter_map(|event| match event {
+                        egui::Event::PointerMoved(pos) => Some(*pos),
+                        _ => None,
+                    })
+                    .collect();
+                (i.modifiers.shift, samples)
+            });
+            let mut sample_positions: Vec<[f32; 2]> = samples
+                .into_iter()
+                .map(|pos| canvas::screen_to_canvas(pos, self.canvas_offset, self.canvas_zoom, self.canvas_rotation))
+                .collect();
+            if straight || sample_positions.is_empty() {
+                sample_positions = vec![canvas_pos];
+            }
+            if straight {
+                self.current_stroke.truncate(1);
+            }
+            for pos in sample_positions {
+                let point = self.stroke_point(response, pos);
+                canvas::push_densified(&mut self.current_stroke, point, MAX_SAMPLE_GAP / self.canvas_zoom);
+            }
             self.needs_repaint = true;
         }
 
         if response.drag_stopped() && self.is_drawing {
             if self.current_stroke.len() > 1 {
                 self.push_undo();
-                let color = self.current_color.to_array();
+                let (color, width) = self.stroke_ink();
                 let smoothed_points = canvas::smooth_stroke(&self.current_stroke);
+                let props = if self.current_tool == Tool::Highlighter {
+                    ObjectProps { highlight: true, ..ObjectProps::default() }
+                } else {
+                    self.ink_props()
+                };
                 let stroke = DrawObject::Stroke {
                     id: Uuid::new_v4(),
                     points: smoothed_points,
-                    color,
-                    width: self.brush_size,
+                    color: color.to_srgba_unmultiplied(),
+                    width,
+                    props,
                 };
-                self.objects.push(stroke);
+                self.apply_op(DocumentOp::Add { objects: vec![stroke] });
             }
             self.is_drawing = false;
             self.current_stroke.clear();
@@ -510,7 +6109,9 @@ impl WhiteboardApp {
                 let color = self.current_color;
                 match self.current_tool {
                     Tool::Line => {
-                        let start = canvas::canvas_to_screen(start_pos, self.canvas_offset, self.canvas_zoom);
+                        let length = ((canvas_pos[0] - start_pos[0]).powi(2) + (canvas_pos[1] - start_pos[1]).powi(2)).sqrt();
+                        self.transform_readout = Some(self.unit_scale.format(length));
+                        let start = canvas::canvas_to_screen(start_pos, self.canvas_offset, self.canvas_zoom, self.canvas_rotation);
                         let end = pointer_pos;
                         painter.line_segment(
                             [start, end],
@@ -518,8 +6119,9 @@ impl WhiteboardApp {
                         );
                     }
                     Tool::Circle => {
-                        let start = canvas::canvas_to_screen(start_pos, self.canvas_offset, self.canvas_zoom);
+                        let start = canvas::canvas_to_screen(start_pos, self.canvas_offset, self.canvas_zoom, self.canvas_rotation);
                         let radius = start.distance(pointer_pos);
+                        self.transform_readout = Some(format!("r = {}", self.unit_scale.format(radius / self.canvas_zoom)));
                         painter.circle_stroke(
                             start,
                             radius,
@@ -527,13 +6129,27 @@ impl WhiteboardApp {
                         );
                     }
                     Tool::Square => {
-                        let start = canvas::canvas_to_screen(start_pos, self.canvas_offset, self.canvas_zoom);
-                        let rect = egui::Rect::from_two_pos(start, pointer_pos);
-                        painter.rect_stroke(
-                            rect,
-                            0.0,
+                        let min = [start_pos[0].min(canvas_pos[0]), start_pos[1].min(canvas_pos[1])];
+                        let max = [start_pos[0].max(canvas_pos[0]), start_pos[1].max(canvas_pos[1])];
+                        self.transform_readout = Some(format!(
+                            "{} × {}",
+                            self.unit_scale.format(max[0] - min[0]),
+                            self.unit_scale.format(max[1] - min[1]),
+                        ));
+                        let corners = canvas::canvas_rect_to_screen(min, max, self.canvas_offset, self.canvas_zoom, self.canvas_rotation);
+                        painter.add(egui::Shape::closed_line(
+                            corners.to_vec(),
                             egui::Stroke::new(self.brush_size * self.canvas_zoom, color),
-                        );
+                        ));
+                    }
+                    Tool::Frame => {
+                        let min = [start_pos[0].min(canvas_pos[0]), start_pos[1].min(canvas_pos[1])];
+                        let max = [start_pos[0].max(canvas_pos[0]), start_pos[1].max(canvas_pos[1])];
+                        let corners = canvas::canvas_rect_to_screen(min, max, self.canvas_offset, self.canvas_zoom, self.canvas_rotation);
+                        painter.add(egui::Shape::closed_line(
+                            corners.to_vec(),
+                            egui::Stroke::new(1.0, egui::Color32::from_rgb(120, 120, 140)),
+                        ));
                     }
                     _ => {}
                 }
@@ -541,6 +6157,7 @@ impl WhiteboardApp {
         }
 
         if response.drag_stopped() {
+            self.transform_readout = None;
             if let Some(start_pos) = self.draw_start_pos {
                 self.push_undo();
                 let color_array = self.current_color.to_array();
@@ -552,8 +6169,9 @@ impl WhiteboardApp {
                             end: canvas_pos,
                             color: color_array,
                             width: self.brush_size,
+                            props: self.ink_props(),
                         };
-                        self.objects.push(line);
+                        self.apply_op(DocumentOp::Add { objects: vec![line] });
                     }
                     Tool::Circle => {
                         let dx = canvas_pos[0] - start_pos[0];
@@ -566,8 +6184,9 @@ impl WhiteboardApp {
                             color: color_array,
                             width: self.brush_size,
                             filled: false,
+                            props: self.ink_props(),
                         };
-                        self.objects.push(circle);
+                        self.apply_op(DocumentOp::Add { objects: vec![circle] });
                     }
                     Tool::Square => {
                         let min = [
@@ -585,8 +6204,25 @@ impl WhiteboardApp {
                             color: color_array,
                             width: self.brush_size,
                             filled: false,
+                            props: self.ink_props(),
+                        };
+                        self.apply_op(DocumentOp::Add { objects: vec![rect] });
+                    }
+                    Tool::Frame => {
+                        let frame_count = self.objects
+                            .iter()
+                            .filter(|o| matches!(o, DrawObject::Frame { .. }))
+                            .count();
+                        let frame = DrawObject::Frame {
+                            id: Uuid::new_v4(),
+                            min: [start_pos[0].min(canvas_pos[0]), start_pos[1].min(canvas_pos[1])],
+                            max: [start_pos[0].max(canvas_pos[0]), start_pos[1].max(canvas_pos[1])],
+                            name: trf("Frame {}", &[&(frame_count + 1)]),
+                            background: None,
+                            grid: None,
+                            props: ObjectProps::default(),
                         };
-                        self.objects.push(rect);
+                        self.apply_op(DocumentOp::Add { objects: vec![frame] });
                     }
                     _ => {}
                 }
@@ -597,18 +6233,86 @@ impl WhiteboardApp {
     }
 
     fn handle_eraser_tool(&mut self, response: &egui::Response, canvas_pos: [f32; 2]) {
-        if response.drag_started() || response.dragged() {
-            if let Some(obj_id) = canvas::find_object_at(&self.objects, canvas_pos) {
-                self.push_undo();
-                self.objects.retain(|obj| obj.id() != obj_id);
-                self.needs_repaint = true;
+        let erasing = response.is_pointer_button_down_on() && response.ctx.input(|i| i.pointer.primary_down());
+        if erasing {
+            if self.eraser_last_pos.is_none() {
+                self.eraser_undo_pushed = false;
+            }
+            let from = self.eraser_last_pos.unwrap_or(canvas_pos);
+            self.eraser_last_pos = Some(canvas_pos);
+            if self.settings.eraser_mode == EraserMode::Partial {
+                self.erase_partial(from, canvas_pos);
+                return;
+            }
+            let tolerance = HIT_TOLERANCE / self.canvas_zoom;
+            let nearby = self.spatial_index.query(
+                [from[0].min(canvas_pos[0]) - tolerance, from[1].min(canvas_pos[1]) - tolerance],
+                [from[0].max(canvas_pos[0]) + tolerance, from[1].max(canvas_pos[1]) + tolerance],
+                self.objects.len(),
+            );
+            let hit: Vec<Uuid> = nearby
+                .into_iter()
+                .map(|index| &self.objects[index])
+                .filter(|obj| !obj.props().hidden && self.layer_editable(obj) && self.revealed(obj) && obj.hit_test_segment(from, canvas_pos, tolerance))
+                .map(|obj| obj.id())
+                .collect();
+            if !hit.is_empty() {
+                if !self.eraser_undo_pushed {
+                    self.push_undo();
+                    self.eraser_undo_pushed = true;
+                }
+                self.apply_op(DocumentOp::Erase { ids: hit });
             }
+        } else {
+            self.eraser_last_pos = None;
+        }
+    }
+
+    fn erase_partial(&mut self, from: [f32; 2], to: [f32; 2]) {
+        let radius = self.settings.eraser_radius / self.canvas_zoom;
+        let nearby = self.spatial_index.query(
+            [from[0].min(to[0]) - radius, from[1].min(to[1]) - radius],
+            [from[0].max(to[0]) + radius, from[1].max(to[1]) + radius],
+            self.objects.len(),
+        );
+        let replacements: Vec<(Uuid, Vec<DrawObject>)> = nearby
+            .into_iter()
+            .filter(|&index| {
+                let obj = &self.objects[index];
+                !obj.props().hidden && self.layer_editable(obj) && self.revealed(obj)
+            })
+            .filter_map(|index| self.objects[index].erase_along(from, to, radius).map(|pieces| (self.objects[index].id(), pieces)))
+            .collect();
+        if replacements.is_empty() {
+            return;
+        }
+        if !self.eraser_undo_pushed {
+            self.push_undo();
+            self.eraser_undo_pushed = true;
+        }
+        for (id, pieces) in replacements {
+            self.apply_op(DocumentOp::Replace { ids: vec![id], objects: pieces });
         }
     }
 
     fn handle_select_tool(&mut self, response: &egui::Response, canvas_pos: [f32; 2]) {
+        if response.clicked() {
+            let toggle = response.ctx.input(|i| i.modifiers.shift);
+            self.click_select(canvas_pos, toggle);
+        }
+
         if response.drag_started() {
-            if let Some(bounds) = selection::get_selection_bounds(&self.objects, &self.selected_objects) {
+            let modifiers = response.ctx.input(|i| i.modifiers);
+            self.selection_combine = if modifiers.alt {
+                SelectionCombine::Subtract
+            } else if modifiers.shift {
+                SelectionCombine::Add
+            } else {
+                SelectionCombine::Replace
+            };
+            let combining = self.selection_combine != SelectionCombine::Replace;
+            
+            if let Some(bounds) = selection::get_view_bounds(&self.objects, &self.selected_objects, self.canvas_zoom) {
                 if let Some(handle) = selection::get_handle_at_pos(canvas_pos, bounds, self.canvas_zoom) {
                     self.selection_handle = Some(handle);
                     self.selection_drag_start = Some(canvas_pos);
@@ -626,13 +6330,20 @@ impl WhiteboardApp {
                     self.needs_repaint = true;
                 } else {
                     let (min, max) = bounds;
-                    if canvas_pos[0] >= min[0] && canvas_pos[0] <= max[0] &&
+                    if !combining && canvas_pos[0] >= min[0] && canvas_pos[0] <= max[0] &&
                        canvas_pos[1] >= min[1] && canvas_pos[1] <= max[1] {
                         self.selection_mode = SelectionMode::Moving;
                         self.selection_drag_start = Some(canvas_pos);
+                        self.selection_original_bounds = Some(bounds);
+                        self.selection_saved_objects = self.selected_objects
+                            .iter()
+                            .filter_map(|id| self.objects.iter().find(|o| o.id() == *id).cloned())
+                            .collect();
                         self.needs_repaint = true;
                     } else {
-                        self.selected_objects.clear();
+                        if !combining {
+                            self.selected_objects.clear();
+                        }
                         self.selection_start = Some(canvas_pos);
                         self.selection_mode = SelectionMode::Selecting;
                         self.needs_repaint = true;
@@ -654,21 +6365,32 @@ impl WhiteboardApp {
                     }
                 }
                 SelectionMode::Moving => {
-                    if let Some(drag_start) = self.selection_drag_start {
-                        let delta = [
+                    if let (Some(drag_start), Some(bounds)) = (self.selection_drag_start, self.selection_original_bounds) {
+                        let mut delta = [
                             canvas_pos[0] - drag_start[0],
                             canvas_pos[1] - drag_start[1],
                         ];
-                        
-                        if let Some(bounds) = selection::get_selection_bounds(&self.objects, &self.selected_objects) {
-                            let center = [
-                                (bounds.0[0] + bounds.1[0]) / 2.0,
-                                (bounds.0[1] + bounds.1[1]) / 2.0,
-                            ];
-                            selection::transform_objects(&mut self.objects, &self.selected_objects, [1.0, 1.0], 0.0, delta, center);
+                        if self.show_guides && !self.guides.is_empty() {
+                            delta = guides::snap_bounds_delta(bounds, delta, &self.guides, SNAP_TOLERANCE / self.canvas_zoom);
+                        }
+                        if self.grid_snapping() {
+                            delta = canvas::snap_delta_to_grid(bounds.0, delta, canvas::GRID_SPACING);
                         }
                         
-                        self.selection_drag_start = Some(canvas_pos);
+                        self.apply_op(DocumentOp::Update { objects: self.selection_saved_objects.clone() });
+                        
+                        let center = [
+                            (bounds.0[0] + bounds.1[0]) / 2.0,
+                            (bounds.0[1] + bounds.1[1]) / 2.0,
+                        ];
+                        self.apply_op(DocumentOp::Transform { ids: self.selected_objects.clone(), scale: [1.0, 1.0], rotation: 0.0, translation: delta, pivot: center });
+                        self.transform_readout = Some(format!(
+                            "Δx {:.1}  Δy {:.1}  ({}, {})",
+                            delta[0],
+                            delta[1],
+                            self.unit_scale.format(delta[0]),
+                            self.unit_scale.format(delta[1]),
+                        ));
                         self.needs_repaint = true;
                     }
                 }
@@ -684,56 +6406,70 @@ impl WhiteboardApp {
                         let orig_width = orig_bounds.1[0] - orig_bounds.0[0];
                         let orig_height = orig_bounds.1[1] - orig_bounds.0[1];
                         
+                        let modifiers = response.ctx.input(|i| i.modifiers);
+                        let extent = |pos: f32, center: f32, min: f32, max: f32, toward_max: bool| {
+                            let size = if modifiers.alt {
+                                2.0 * (pos - center).abs()
+                            } else if toward_max {
+                                pos - min
+                            } else {
+                                max - pos
+                            };
+                            size.max(10.0)
+                        };
+                        
                         let mut new_width = orig_width;
                         let mut new_height = orig_height;
                         
-                        match handle {
-                            SelectionHandle::Left | SelectionHandle::Right => {
-                                new_width = if matches!(handle, SelectionHandle::Right) {
-                                    (canvas_pos[0] - orig_bounds.0[0]).max(10.0)
-                                } else {
-                                    (orig_bounds.1[0] - canvas_pos[0]).max(10.0)
-                                };
-                            }
-                            SelectionHandle::Top | SelectionHandle::Bottom => {
-                                new_height = if matches!(handle, SelectionHandle::Bottom) {
-                                    (canvas_pos[1] - orig_bounds.0[1]).max(10.0)
-                                } else {
-                                    (orig_bounds.1[1] - canvas_pos[1]).max(10.0)
-                                };
-                            }
-                            SelectionHandle::TopLeft | SelectionHandle::TopRight | 
-                            SelectionHandle::BottomLeft | SelectionHandle::BottomRight => {
-                                let (ref_x, ref_y) = match handle {
-                                    SelectionHandle::TopLeft => (orig_bounds.1[0], orig_bounds.1[1]),
-                                    SelectionHandle::TopRight => (orig_bounds.0[0], orig_bounds.1[1]),
-                                    SelectionHandle::BottomLeft => (orig_bounds.1[0], orig_bounds.0[1]),
-                                    SelectionHandle::BottomRight => (orig_bounds.0[0], orig_bounds.0[1]),
-                                    _ => (center[0], center[1]),
-                                };
-                                new_width = (canvas_pos[0] - ref_x).abs().max(10.0);
-                                new_height = (canvas_pos[1] - ref_y).abs().max(10.0);
-                                
-                                let aspect = orig_width / orig_height;
-                                if new_width / new_height > aspect {
-                                    new_height = new_width / aspect;
-                                } else {
-                                    new_width = new_height * aspect;
-                                }
-                            }
-                            _ => {}
+                        let (horizontal, vertical) = match handle {
+                            SelectionHandle::Left => (Some(false), None),
+                            SelectionHandle::Right => (Some(true), None),
+                            SelectionHandle::Top => (None, Some(false)),
+                            SelectionHandle::Bottom => (None, Some(true)),
+                            SelectionHandle::TopLeft => (Some(false), Some(false)),
+                            SelectionHandle::TopRight => (Some(true), Some(false)),
+                            SelectionHandle::BottomLeft => (Some(false), Some(true)),
+                            SelectionHandle::BottomRight => (Some(true), Some(true)),
+                            SelectionHandle::Rotate => (None, None),
+                        };
+                        if let Some(toward_max) = horizontal {
+                            new_width = extent(canvas_pos[0], center[0], orig_bounds.0[0], orig_bounds.1[0], toward_max);
+                        }
+                        if let Some(toward_max) = vertical {
+                            new_height = extent(canvas_pos[1], center[1], orig_bounds.0[1], orig_bounds.1[1], toward_max);
+                        }
+                        
+                        if modifiers.shift {
+                            let factor = match (horizontal, vertical) {
+                                (Some(_), Some(_)) => (new_width / orig_width).max(new_height / orig_height),
+                                (Some(_), None) => new_width / orig_width,
+                                _ => new_height / orig_height,
+                            };
+                            new_width = orig_width * factor;
+                            new_height = orig_height * factor;
                         }
                         
                         let scale_x = new_width / orig_width;
                         let scale_y = new_height / orig_height;
+                        let anchor_for = |toward_max: Option<bool>, min: f32, max: f32, center: f32| match toward_max {
+                            _ if modifiers.alt => center,
+                            Some(true) => min,
+                            Some(false) => max,
+                            None => center,
+                        };
+                        let anchor = [
+                            anchor_for(horizontal, orig_bounds.0[0], orig_bounds.1[0], center[0]),
+                            anchor_for(vertical, orig_bounds.0[1], orig_bounds.1[1], center[1]),
+                        ];
                         
-                        for saved_obj in &self.selection_saved_objects {
-                            if let Some(current_obj) = self.objects.iter_mut().find(|o| o.id() == saved_obj.id()) {
-                                *current_obj = saved_obj.clone();
-                            }
-                        }
+                        self.apply_op(DocumentOp::Update { objects: self.selection_saved_objects.clone() });
                         
-                        selection::transform_objects(&mut self.objects, &self.selected_objects, [scale_x, scale_y], 0.0, [0.0, 0.0], center);
+                        self.apply_op(DocumentOp::Transform { ids: self.selected_objects.clone(), scale: [scale_x, scale_y], rotation: 0.0, translation: [0.0, 0.0], pivot: anchor });
+                        self.transform_readout = Some(if (scale_x - scale_y).abs() < 0.0005 {
+                            format!("{:.1}%", scale_x * 100.0)
+                        } else {
+                            format!("{:.1}% × {:.1}%", scale_x * 100.0, scale_y * 100.0)
+                        });
                         self.needs_repaint = true;
                     }
                 }
@@ -750,13 +6486,16 @@ impl WhiteboardApp {
                         let current_angle = (canvas_pos[1] - center[1]).atan2(canvas_pos[0] - center[0]);
                         let rotation = current_angle - start_angle;
                         
-                        for saved_obj in &self.selection_saved_objects {
-                            if let Some(current_obj) = self.objects.iter_mut().find(|o| o.id() == saved_obj.id()) {
-                                *current_obj = saved_obj.clone();
-                            }
-                        }
+                        self.apply_op(DocumentOp::Update { objects: self.selection_saved_objects.clone() });
                         
-                        selection::transform_objects(&mut self.objects, &self.selected_objects, [1.0, 1.0], rotation, [0.0, 0.0], center);
+                        self.apply_op(DocumentOp::Transform { ids: self.selected_objects.clone(), scale: [1.0, 1.0], rotation, translation: [0.0, 0.0], pivot: center });
+                        let mut degrees = rotation.to_degrees();
+                        if degrees > 180.0 {
+                            degrees -= 360.0;
+                        } else if degrees < -180.0 {
+                            degrees += 360.0;
+                        }
+                        self.transform_readout = Some(format!("{:.1}°", degrees));
                         self.needs_repaint = true;
                     }
                 }
@@ -772,14 +6511,29 @@ impl WhiteboardApp {
                     let min_y = start[1].min(end[1]);
                     let max_y = start[1].max(end[1]);
                     
-                    self.selected_objects.clear();
-                    for obj in &self.objects {
-                        let (obj_min, obj_max) = obj.bounds();
+                    if self.selection_combine == SelectionCombine::Replace {
+                        self.selected_objects.clear();
+                    }
+                    let candidates: Vec<_> = self.spatial_index
+                        .query([min_x, min_y], [max_x, max_y], self.objects.len())
+                        .into_iter()
+                        .map(|index| &self.objects[index])
+                        .filter(|obj| (self.show_hidden || !obj.props().hidden) && self.layer_editable(obj) && self.revealed(obj))
+                        .map(|obj| (obj.id(), obj.bounds()))
+                        .collect();
+                    for (id, (obj_min, obj_max)) in candidates {
                         if obj_min[0] >= min_x && obj_max[0] <= max_x &&
                            obj_min[1] >= min_y && obj_max[1] <= max_y {
-                            self.selected_objects.push(obj.id());
+                            if self.selection_combine == SelectionCombine::Subtract {
+                                self.selected_objects.retain(|selected| *selected != id);
+                            } else if !self.selected_objects.contains(&id) {
+                                self.selected_objects.push(id);
+                            }
                         }
                     }
+                    if self.selection_combine != SelectionCombine::Subtract {
+                        self.expand_selection_to_groups();
+                    }
                 }
                 
                 self.selection_rect = None;
@@ -790,13 +6544,14 @@ impl WhiteboardApp {
             self.selection_drag_start = None;
             self.selection_handle = None;
             self.selection_saved_objects.clear();
+            self.transform_readout = None;
             self.needs_repaint = true;
         }
     }
 
     fn handle_text_tool(&mut self, response: &egui::Response, canvas_pos: [f32; 2]) {
         if response.clicked() {
-            let clicked_existing = if let Some(obj_id) = canvas::find_object_at(&self.objects, canvas_pos) {
+            let clicked_existing = if let Some(obj_id) = self.object_at(canvas_pos) {
                 if let Some(DrawObject::LatexFormula { formula, .. }) = self.objects.iter().find(|o| o.id() == obj_id) {
                     self.editing_text = Some(obj_id);
                     self.text_input = formula.clone();
@@ -823,148 +6578,230 @@ impl WhiteboardApp {
                         self.current_color.b(),
                         self.current_color.a(),
                     ],
-                    cached_size: None,
+                    size: None,
+                    scale: [1.0, 1.0],
+                    auto_color: self.auto_formula_color,
+                    font: self.current_font.clone(),
+                    pin_size: false,
+                    props: self.ink_props(),
                 };
-                self.objects.push(formula);
+                self.apply_op(DocumentOp::Add { objects: vec![formula] });
                 self.editing_text = Some(new_id);
                 self.text_input.clear();
                 self.text_cursor_pos = 0;
-                self.needs_repaint = true;
             }
         }
     }
 
     fn render_canvas(&mut self, ctx: &egui::Context) {
-        egui::CentralPanel::default().show(ctx, |ui| {
+        let panel_frame = if self.overlay { egui::Frame::none() } else { egui::Frame::central_panel(&ctx.style()) };
+        egui::CentralPanel::default().frame(panel_frame).show(ctx, |ui| {
             let (response, painter) = ui.allocate_painter(
                 ui.available_size(),
                 egui::Sense::click_and_drag(),
             );
 
-            painter.rect_filled(response.rect, 0.0, self.background_color);
+            self.canvas_rect = response.rect;
+            if self.overlay {
+                painter.rect_filled(response.rect, 0.0, self.background_color.gamma_multiply(self.settings.overlay_tint));
+            } else {
+                painter.rect_filled(response.rect, 0.0, self.background_color);
+            }
             
-            self.render_grid(&painter, response.rect);
+            self.render_frame_backgrounds(&painter);
+            if !self.overlay {
+                self.render_grid(&painter, response.rect);
+                self.render_page_outlines(&painter, response.rect);
+                self.render_origin_marker(&painter);
+            }
 
             if response.hovered() {
-                let scroll_delta = ui.input(|i| i.smooth_scroll_delta.y);
-                if scroll_delta != 0.0 {
+                let (scroll_delta, alt_held) = ui.input(|i| (i.smooth_scroll_delta.y, i.modifiers.alt));
+                if scroll_delta != 0.0 && alt_held {
+                    let pivot = response.hover_pos().unwrap_or(response.rect.center());
+                    self.rotate_view_about(pivot, scroll_delta * 0.002);
+                } else if scroll_delta != 0.0 {
                     let zoom_factor = 1.0 + scroll_delta * 0.001;
-                    let old_zoom = self.canvas_zoom;
-                    self.canvas_zoom = (self.canvas_zoom * zoom_factor).clamp(0.1, 10.0);
-                    
-                    if let Some(hover_pos) = response.hover_pos() {
-                        let zoom_ratio = self.canvas_zoom / old_zoom;
-                        self.canvas_offset = hover_pos.to_vec2() + (self.canvas_offset - hover_pos.to_vec2()) * zoom_ratio;
-                    }
-                    self.needs_repaint = true;
+                    let pivot = response.hover_pos().unwrap_or(response.rect.center());
+                    self.zoom_view_about(pivot, self.canvas_zoom * zoom_factor);
                 }
             }
 
-            if response.dragged_by(egui::PointerButton::Middle) {
+            if response.secondary_clicked() {
+                self.context_menu_pos = response
+                    .interact_pointer_pos()
+                    .map(|pos| canvas::screen_to_canvas(pos, self.canvas_offset, self.canvas_zoom, self.canvas_rotation));
+            }
+
+            let space_held = !self.presenting && self.editing_text.is_none() && !ctx.wants_keyboard_input() && ctx.input(|i| i.key_down(egui::Key::Space));
+            if response.drag_started_by(egui::PointerButton::Primary) {
+                self.grab_panning = space_held;
+            }
+            let grabbing = self.grab_panning && (response.dragged_by(egui::PointerButton::Primary) || response.drag_stopped_by(egui::PointerButton::Primary));
+            let touch = ctx.multi_touch().filter(|_| response.contains_pointer());
+            let panning = response.dragged_by(egui::PointerButton::Middle)
+                || response.drag_stopped_by(egui::PointerButton::Middle)
+                || grabbing
+                || touch.is_some();
+            if response.dragged_by(egui::PointerButton::Middle) || (grabbing && response.dragged()) {
                 self.canvas_offset += response.drag_delta();
                 self.needs_repaint = true;
             }
+            if let Some(touch) = touch {
+                self.canvas_offset += touch.translation_delta;
+                if touch.zoom_delta != 1.0 {
+                    self.zoom_view_about(response.hover_pos().unwrap_or(touch.start_pos), self.canvas_zoom * touch.zoom_delta);
+                }
+                self.needs_repaint = true;
+            }
+            if response.drag_stopped() {
+                self.grab_panning = false;
+            }
+            if (space_held || grabbing) && response.hovered() {
+                ctx.set_cursor_icon(if grabbing { egui::CursorIcon::Grabbing } else { egui::CursorIcon::Grab });
+            }
 
+            let started = std::time::Instant::now();
+            self.spatial_index.update(&self.objects);
+            self.profiler.current.index_ms = started.elapsed().as_secs_f32() * 1000.0;
+            let started = std::time::Instant::now();
             self.render_objects(ctx, &painter);
+            self.profiler.current.render_ms = started.elapsed().as_secs_f32() * 1000.0;
+            self.render_guides(&painter, response.rect);
+            self.render_hover_highlight(&painter, &response);
+            self.render_recent_highlight(&painter);
+            self.publish_accessibility_tree(ctx, &response);
+            self.render_contrast_warning(&painter, response.rect);
+            self.render_lost_content_guard(ctx, &painter);
+            self.render_overview_legend(ctx);
+
+            if !response.is_pointer_button_down_on() {
+                self.eraser_last_pos = None;
+            }
 
-            if let Some(pointer_pos) = response.interact_pointer_pos() {
-                let canvas_pos = canvas::screen_to_canvas(pointer_pos, self.canvas_offset, self.canvas_zoom);
+            if self.read_only() {
+                if response.dragged_by(egui::PointerButton::Primary) && !panning {
+                    self.canvas_offset += response.drag_delta();
+                    self.needs_repaint = true;
+                }
+            } else if self.placing_formula {
+                self.handle_formula_placement(ctx, &painter, &response);
+            } else if self.cropping.is_some() {
+                self.handle_crop(ctx, &painter, &response);
+            } else if let Some(pointer_pos) = response.interact_pointer_pos().filter(|_| !panning) {
+                let mut pointer_pos = pointer_pos;
+                let mut canvas_pos = canvas::screen_to_canvas(pointer_pos, self.canvas_offset, self.canvas_zoom, self.canvas_rotation);
+                let inking_while_typing = self.current_tool == Tool::Text && self.editing_text.is_some() && (response.dragged() || self.is_drawing);
+                if matches!(self.current_tool, Tool::Line | Tool::Circle | Tool::Square | Tool::Frame | Tool::Text) && !inking_while_typing {
+                    canvas_pos = self.snap_to_guides(canvas_pos);
+                    if self.grid_snapping() {
+                        canvas_pos = canvas::snap_to_grid(canvas_pos, canvas::GRID_SPACING);
+                    }
+                    if self.current_tool == Tool::Line && ctx.input(|i| i.modifiers.shift) {
+                        if let Some(start) = self.draw_start_pos.filter(|_| !response.drag_started()) {
+                            canvas_pos = canvas::snap_angle(start, canvas_pos, canvas::ANGLE_SNAP_DEGREES);
+                        }
+                    }
+                    pointer_pos = canvas::canvas_to_screen(canvas_pos, self.canvas_offset, self.canvas_zoom, self.canvas_rotation);
+                }
 
                 match self.current_tool {
-                    Tool::Brush => self.handle_brush_tool(&response, canvas_pos),
-                    Tool::Line | Tool::Circle | Tool::Square => self.handle_shape_tool(&response, pointer_pos, canvas_pos, &painter),
+                    Tool::Brush | Tool::Highlighter => self.handle_brush_tool(&response, canvas_pos),
+                    Tool::Line | Tool::Circle | Tool::Square | Tool::Frame => self.handle_shape_tool(&response, pointer_pos, canvas_pos, &painter),
                     Tool::Eraser => self.handle_eraser_tool(&response, canvas_pos),
                     Tool::Select => self.handle_select_tool(&response, canvas_pos),
+                    Tool::Text if inking_while_typing => self.handle_brush_tool(&response, canvas_pos),
                     Tool::Text => self.handle_text_tool(&response, canvas_pos),
                 }
             }
 
             if self.is_drawing && self.current_stroke.len() > 1 {
-                for i in 0..self.current_stroke.len() - 1 {
-                    let start = canvas::canvas_to_screen(self.current_stroke[i].pos, self.canvas_offset, self.canvas_zoom);
-                    let end = canvas::canvas_to_screen(self.current_stroke[i + 1].pos, self.canvas_offset, self.canvas_zoom);
-                    painter.line_segment(
-                        [start, end],
-                        egui::Stroke::new(self.brush_size * self.canvas_zoom, self.current_color),
-                    );
+                let (color, base_width) = self.stroke_ink();
+                if self.current_tool == Tool::Highlighter {
+                    for (range, width, opacity) in canvas::stroke_runs(&self.current_stroke, base_width) {
+                        let points: Vec<egui::Pos2> = self.current_stroke[range]
+                            .iter()
+                            .map(|p| canvas::canvas_to_screen(p.pos, self.canvas_offset, self.canvas_zoom, self.canvas_rotation))
+                            .collect();
+                        painter.add(egui::Shape::line(points, egui::Stroke::new(width * self.canvas_zoom, color.gamma_multiply(opacity))));
+                    }
+                } else {
+                    for i in 0..self.current_stroke.len() - 1 {
+                        let start = canvas::canvas_to_screen(self.current_stroke[i].pos, self.canvas_offset, self.canvas_zoom, self.canvas_rotation);
+                        let end = canvas::canvas_to_screen(self.current_stroke[i + 1].pos, self.canvas_offset, self.canvas_zoom, self.canvas_rotation);
+                        let (width, opacity) = canvas::segment_ink(&self.current_stroke[i], &self.current_stroke[i + 1], base_width);
+                        painter.line_segment(
+                            [start, end],
+                            egui::Stroke::new(width * self.canvas_zoom, color.gamma_multiply(opacity)),
+                        );
+                    }
                 }
+                self.render_stroke_prediction(ctx, &painter);
             }
 
             if let Some((start, end)) = self.selection_rect {
-                let screen_start = canvas::canvas_to_screen(start, self.canvas_offset, self.canvas_zoom);
-                let screen_end = canvas::canvas_to_screen(end, self.canvas_offset, self.canvas_zoom);
-                let rect = egui::Rect::from_two_pos(screen_start, screen_end);
-                painter.rect_stroke(
-                    rect,
-                    0.0,
-                    egui::Stroke::new(1.0, egui::Color32::from_rgb(100, 150, 255)),
-                );
-                painter.rect_filled(
-                    rect,
-                    0.0,
+                let min = [start[0].min(end[0]), start[1].min(end[1])];
+                let max = [start[0].max(end[0]), start[1].max(end[1])];
+                let corners = canvas::canvas_rect_to_screen(min, max, self.canvas_offset, self.canvas_zoom, self.canvas_rotation);
+                painter.add(egui::Shape::convex_polygon(
+                    corners.to_vec(),
                     egui::Color32::from_rgba_premultiplied(100, 150, 255, 20),
-                );
+                    egui::Stroke::new(1.0, egui::Color32::from_rgb(100, 150, 255)),
+                ));
             }
 
             if !self.selected_objects.is_empty() && self.selection_mode != SelectionMode::Selecting {
-                if let Some((min, max)) = selection::get_selection_bounds(&self.objects, &self.selected_objects) {
-                    let screen_min = canvas::canvas_to_screen(min, self.canvas_offset, self.canvas_zoom);
-                    let screen_max = canvas::canvas_to_screen(max, self.canvas_offset, self.canvas_zoom);
-                    let rect = egui::Rect::from_two_pos(screen_min, screen_max);
+                if let Some((min, max)) = selection::get_view_bounds(&self.objects, &self.selected_objects, self.canvas_zoom) {
+                    let to_screen = |p: [f32; 2]| canvas::canvas_to_screen(p, self.canvas_offset, self.canvas_zoom, self.canvas_rotation);
+                    let corners = canvas::canvas_rect_to_screen(min, max, self.canvas_offset, self.canvas_zoom, self.canvas_rotation);
                     
-                    painter.rect_stroke(
-                        rect,
-                        0.0,
+                    painter.add(egui::Shape::closed_line(
+                        corners.to_vec(),
                         egui::Stroke::new(2.0, egui::Color32::from_rgb(50, 100, 255)),
-                    );
+                    ));
                     
                     let handle_size = 8.0;
-                    let mid_x = (screen_min.x + screen_max.x) / 2.0;
-                    let mid_y = (screen_min.y + screen_max.y) / 2.0;
+                    let mid_x = (min[0] + max[0]) / 2.0;
+                    let mid_y = (min[1] + max[1]) / 2.0;
                     
                     let handles = vec![
-                        (screen_min.x, screen_min.y),
-                        (screen_max.x, screen_min.y),
-                        (screen_min.x, screen_max.y),
-                        (screen_max.x, screen_max.y),
-                        (mid_x, screen_min.y),
-                        (mid_x, screen_max.y),
-                        (screen_min.x, mid_y),
-                        (screen_max.x, mid_y),
+                        [min[0], min[1]],
+                        [max[0], min[1]],
+                        [min[0], max[1]],
+                        [max[0], max[1]],
+                        [mid_x, min[1]],
+                        [mid_x, max[1]],
+                        [min[0], mid_y],
+                        [max[0], mid_y],
                     ];
                     
-                    for (x, y) in handles {
-                        painter.rect_filled(
-                            egui::Rect::from_center_size(
-                                egui::pos2(x, y),
-                                egui::vec2(handle_size, handle_size),
-                            ),
-                            0.0,
-                            egui::Color32::WHITE,
+                    for handle in handles {
+                        let handle_rect = egui::Rect::from_center_size(
+                            to_screen(handle),
+                            egui::vec2(handle_size, handle_size),
                         );
+                        painter.rect_filled(handle_rect, 0.0, egui::Color32::WHITE);
                         painter.rect_stroke(
-                            egui::Rect::from_center_size(
-                                egui::pos2(x, y),
-                                egui::vec2(handle_size, handle_size),
-                            ),
+                            handle_rect,
                             0.0,
                             egui::Stroke::new(1.0, egui::Color32::from_rgb(50, 100, 255)),
                         );
                     }
                     
-                    let rotate_y = screen_min.y - 30.0;
+                    let top_mid = to_screen([mid_x, min[1]]);
+                    let rotate_pos = to_screen([mid_x, min[1] - 30.0 / self.canvas_zoom]);
                     painter.circle_filled(
-                        egui::pos2(mid_x, rotate_y),
+                        rotate_pos,
                         5.0,
                         egui::Color32::WHITE,
                     );
                     painter.circle_stroke(
-                        egui::pos2(mid_x, rotate_y),
+                        rotate_pos,
                         5.0,
                         egui::Stroke::new(1.0, egui::Color32::from_rgb(50, 100, 255)),
                     );
                     painter.line_segment(
-                        [egui::pos2(mid_x, screen_min.y), egui::pos2(mid_x, rotate_y)],
+                        [top_mid, rotate_pos],
                         egui::Stroke::new(1.0, egui::Color32::from_rgb(50, 100, 255)),
                     );
                 }
@@ -972,7 +6809,7 @@ impl WhiteboardApp {
             
             if let Some(editing_id) = self.editing_text {
                 if let Some(DrawObject::LatexFormula { pos, .. }) = self.objects.iter().find(|o| o.id() == editing_id) {
-                    let screen_pos = canvas::canvas_to_screen(*pos, self.canvas_offset, self.canvas_zoom);
+                    let screen_pos = canvas::canvas_to_screen(*pos, self.canvas_offset, self.canvas_zoom, self.canvas_rotation);
                         
                     let text_width = (self.text_input.len().max(10) as f32) * 8.0;
                     let text_height = 30.0;
@@ -1012,23 +6849,379 @@ impl WhiteboardApp {
                     }
                 }
             }
+
+            self.render_transform_readout(&painter, &response);
+            self.render_rulers(ui, &painter, response.rect);
+
+            response.context_menu(|ui| {
+                if self.read_only() {
+                    if ui.button(tr("Go to origin (Home)")).clicked() {
+                        self.go_to_origin();
+                        ui.close_menu();
+                    }
+                    return;
+                }
+                if ui.button(tr("Repeat last object (Ctrl+R)")).clicked() {
+                    self.repeat_last_object();
+                    ui.close_menu();
+                }
+                if ui.button(tr("Paste image")).clicked() {
+                    self.paste_clipboard_image(ui.ctx().input(|i| i.pointer.interact_pos()));
+                    ui.close_menu();
+                }
+                if ui.button(tr("Go to origin (Home)")).clicked() {
+                    self.go_to_origin();
+                    ui.close_menu();
+                }
+                if self.objects.iter().any(|obj| obj.props().hidden) && ui.button(tr("Unhide all")).clicked() {
+                    self.unhide_all();
+                    ui.close_menu();
+                }
+                let orphans = selection::orphaned_instances(&self.objects).len();
+                if orphans > 0
+                    && ui
+                        .button(trf("Remove orphaned instances ({})", &[&orphans]))
+                        .on_hover_text(tr("Linked instances whose original group no longer exists"))
+                        .clicked()
+                {
+                    self.remove_orphaned_instances();
+                    ui.close_menu();
+                }
+                if let Some(page) = self.context_menu_pos.and_then(|pos| selection::page_at(&self.objects, pos)) {
+                    ui.separator();
+                    if ui.button(tr("Duplicate page")).clicked() {
+                        self.duplicate_page(page, None);
+                        ui.close_menu();
+                    }
+                    ui.menu_button(tr("Page background"), |ui| self.page_background_menu(ui, page));
+                    ui.menu_button(tr("Duplicate page without layer"), |ui| {
+                        for layer in self.layers.clone().iter().rev() {
+                            if ui.button(&layer.name).clicked() {
+                                self.duplicate_page(page, Some(layer.id));
+                                ui.close_menu();
+                            }
+                        }
+                    });
+                }
+                if !self.selected_objects.is_empty() {
+                    ui.separator();
+                    if let Some(key) = self.export_name_key() {
+                        ui.horizontal(|ui| {
+                            ui.label(tr("Export name:"));
+                            let name = self.export_names.entry(key).or_default();
+                            ui.add(egui::TextEdit::singleline(name).hint_text(tr("fig-name")).desired_width(120.0));
+                            if name.trim().is_empty() {
+                                self.export_names.remove(&key);
+                            }
+                        });
+                    }
+                    let single_formula = matches!(
+                        self.selected_objects[..],
+                        [id] if matches!(self.objects.iter().find(|o| o.id() == id), Some(DrawObject::LatexFormula { .. }))
+                    );
+                    if ui.button(tr("Duplicate (Ctrl+D)")).clicked() {
+                        self.duplicate_selection();
+                        ui.close_menu();
+                    }
+                    if single_formula && ui.button(tr("Continue derivation (Shift+Enter)")).clicked() {
+                        self.continue_derivation();
+                        ui.close_menu();
+                    }
+                    let selected_formulas: Vec<bool> = self.objects
+                        .iter()
+                        .filter(|obj| self.selected_objects.contains(&obj.id()))
+                        .filter_map(|obj| match obj {
+                            DrawObject::LatexFormula { auto_color, .. } => Some(*auto_color),
+                            _ => None,
+                        })
+                        .collect();
+                    let pinned_formulas: Vec<bool> = self.objects
+                        .iter()
+                        .filter(|obj| self.selected_objects.contains(&obj.id()))
+                        .filter_map(|obj| match obj {
+                            DrawObject::LatexFormula { pin_size, .. } => Some(*pin_size),
+                            _ => None,
+                        })
+                        .collect();
+                    if !selected_formulas.is_empty() {
+                        let mut pin_size = pinned_formulas.iter().all(|pinned| *pinned);
+                        if ui.checkbox(&mut pin_size, tr("Pin size to screen"))
+                            .on_hover_text(tr("Keep the same size on screen at any zoom, like an axis label"))
+                            .clicked()
+                        {
+                            self.push_undo();
+                            let ids = self.selected_objects.clone();
+                            self.update_objects(&ids, |obj| {
+                                if let DrawObject::LatexFormula { pin_size: pinned, .. } = obj {
+                                    *pinned = pin_size;
+                                }
+                            });
+                        }
+                        let mut auto_color = selected_formulas.iter().all(|auto| *auto);
+                        if ui.checkbox(&mut auto_color, tr("Automatic formula color")).clicked() {
+                            self.push_undo();
+                            let ids = self.selected_objects.clone();
+                            self.update_objects(&ids, |obj| {
+                                if let DrawObject::LatexFormula { auto_color: formula_auto, .. } = obj {
+                                    *formula_auto = auto_color;
+                                }
+                            });
+                        }
+                        ui.menu_button(tr("Font"), |ui| {
+                            egui::ScrollArea::vertical().max_height(300.0).show(ui, |ui| {
+                                if ui.button(tr(LATEX_FONT_LABEL)).clicked() {
+                                    self.set_selection_font(None);
+                                    ui.close_menu();
+                                }
+                                for family in self.latex_renderer.font_families().to_vec() {
+                                    if ui.button(&family).clicked() {
+                                        self.set_selection_font(Some(family));
+                                        ui.close_menu();
+                                    }
+                                }
+                            });
+                        });
+                    }
+                    let any_hidden = self.objects
+                        .iter()
+                        .any(|obj| obj.props().hidden && self.selected_objects.contains(&obj.id()));
+                    if any_hidden && ui.button(tr("Unhide selection")).clicked() {
+                        self.set_selection_hidden(false);
+                        ui.close_menu();
+                    }
+                    if ui.button(tr("Hide selection (Shift+H)")).clicked() {
+                        self.set_selection_hidden(true);
+                        ui.close_menu();
+                    }
+                    if ui.button(tr("Dim / undim selection (D)")).clicked() {
+                        self.toggle_dim_selection();
+                        ui.close_menu();
+                    }
+                    let mut opacity = self.objects
+                        .iter()
+                        .find(|obj| self.selected_objects.contains(&obj.id()))
+                        .map(|obj| obj.props().opacity)
+                        .unwrap_or(1.0);
+                    ui.horizontal(|ui| {
+                        ui.label(tr("Opacity:"));
+                        let slider = ui.add(egui::Slider::new(&mut opacity, 0.05..=1.0));
+                        if slider.drag_started() || (slider.changed() && !slider.dragged()) {
+                            self.push_undo();
+                        }
+                        if slider.changed() {
+                            let ids = self.selected_objects.clone();
+                            self.update_objects(&ids, |obj| obj.props_mut().opacity = opacity);
+                        }
+                    });
+                }
+                if !self.selected_objects.is_empty() {
+                    ui.separator();
+                    if self.selected_objects.len() > 1 && ui.button(tr("Group (Ctrl+G)")).clicked() {
+                        self.group_selection();
+                        ui.close_menu();
+                    }
+                    let selected = || self.objects.iter().filter(|obj| self.selected_objects.contains(&obj.id()));
+                    let any_grouped = selected().any(|obj| obj.props().group.is_some());
+                    let any_instance = selected().any(|obj| matches!(obj, DrawObject::Instance { .. }));
+                    let movable = selected().any(|obj| !matches!(obj, DrawObject::Frame { .. }));
+                    if any_grouped && ui.button(tr("Ungroup (Ctrl+Shift+G)")).clicked() {
+                        self.ungroup_selection();
+                        ui.close_menu();
+                    }
+                    if ui.button(tr("Linked duplicate (Ctrl+Shift+D)")).on_hover_text(tr("A copy that follows every edit made to the original group")).clicked() {
+                        self.linked_duplicate();
+                        ui.close_menu();
+                    }
+                    let single_image = match self.selected_objects[..] {
+                        [id] if matches!(self.objects.iter().find(|obj| obj.id() == id), Some(DrawObject::Image { .. })) => Some(id),
+                        _ => None,
+                    };
+                    if let Some(id) = single_image {
+                        if ui.button(tr("Crop image")).clicked() {
+                            self.start_crop(id);
+                            ui.close_menu();
+                        }
+                        ui.horizontal(|ui| {
+                            ui.label(tr("Tiles:"));
+                            ui.add(egui::DragValue::new(&mut self.split_columns).range(1..=10));
+                            ui.label("×");
+                            ui.add(egui::DragValue::new(&mut self.split_rows).range(1..=10));
+                        });
+                        if ui.button(tr("Split into tiles")).clicked() {
+                            self.split_image(id);
+                            ui.close_menu();
+                        }
+                        if ui.add_enabled(self.ocr_job.is_none(), egui::Button::new(tr("Recognize text (OCR)"))).clicked() {
+                            self.recognize_text(id);
+                            ui.close_menu();
+                        }
+                        ui.menu_button(tr("Adjust colors"), |ui| self.render_image_adjustments(ui, id));
+                    }
+                    if ui.button(tr("Flatten to image")).on_hover_text(tr("Replace the selection with a single picture of it")).clicked() {
+                        self.flatten_selection();
+                        ui.close_menu();
+                    }
+                    if any_instance && ui.button(tr("Unlink instance")).clicked() {
+                        self.unlink_selected_instances();
+                        ui.close_menu();
+                    }
+                    let pages: Vec<(Uuid, String)> = self.objects
+                        .iter()
+                        .filter_map(|obj| match obj {
+                            DrawObject::Frame { id, name, .. } => Some((*id, name.clone())),
+                            _ => None,
+                        })
+                        .collect();
+                    if movable && !pages.is_empty() {
+                        ui.menu_button(tr("Move selection to page…"), |ui| {
+                            for (id, name) in pages {
+                                if ui.button(name).clicked() {
+                                    self.move_selection_to_page(id);
+                                    ui.close_menu();
+                                }
+                            }
+                        });
+                    }
+                    ui.menu_button(tr("Arrange"), |ui| {
+                        for order in ZOrder::ALL {
+                            if ui.button(tr(order.label())).clicked() {
+                                self.reorder_selection(order);
+                                ui.close_menu();
+                            }
+                        }
+                    });
+                }
+                if self.selected_objects.len() > 1 {
+                    ui.separator();
+                    ui.horizontal(|ui| {
+                        ui.label(tr("Columns:"));
+                        ui.add(egui::DragValue::new(&mut self.arrange_columns).range(1..=20));
+                        ui.label(tr("Spacing:"));
+                        ui.add(egui::DragValue::new(&mut self.arrange_spacing).range(0.0..=500.0));
+                    });
+                    if ui.button(tr("Arrange as grid")).clicked() {
+                        self.arrange_selection_in_grid();
+                        ui.close_menu();
+                    }
+                    let strokes = self.objects
+                        .iter()
+                        .filter(|obj| matches!(obj, DrawObject::Stroke { .. }) && self.selected_objects.contains(&obj.id()))
+                        .count();
+                    if strokes > 1 && ui.button(tr("Tidy handwriting")).on_hover_text(tr("Align the baselines of handwritten lines and even out letter spacing")).clicked() {
+                        self.tidy_selection();
+                        ui.close_menu();
+                    }
+                }
+            });
         });
     }
 }
 
 impl eframe::App for WhiteboardApp {
-    fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
-        self.handle_keyboard_shortcuts(ctx);
-        self.render_toolbar(ctx);
-        self.handle_text_editing(ctx);
-        self.render_latex_dialog(ctx);
+    fn clear_color(&self, _visuals: &egui::Visuals) -> [f32; 4] {
+        [0.0; 4]
+    }
+
+    fn on_exit(&mut self, _gl: Option<&eframe::glow::Context>) {
+        self.save_session();
+    }
+
+    fn update(&mut self, ctx: &egui::Context, frame: &mut eframe::Frame) {
+        let update_started = std::time::Instant::now();
+        self.update_idle_state(ctx);
+        self.handle_global_hotkeys(ctx);
+        self.advance_document_load();
+        self.advance_ocr();
+        self.advance_measurements(ctx);
+        self.track_window_layout(ctx);
+        if !self.loading() {
+            selection::sync_instances(&mut self.objects);
+            self.sync_layers();
+            self.sync_scale_bars();
+            self.stamp_new_objects();
+        }
+        self.apply_visuals(ctx);
+        self.handle_background_change();
+        let read_only = self.read_only();
+        if !self.presenting {
+            if read_only {
+                self.handle_viewer_shortcuts(ctx);
+            } else {
+                self.handle_text_editing(ctx);
+                self.handle_keyboard_shortcuts(ctx);
+            }
+        }
+        if self.presenting {
+            self.handle_presentation(ctx);
+        } else if read_only {
+            self.render_viewer_toolbar(ctx);
+        } else {
+            self.handle_object_navigation(ctx);
+            self.render_toolbar(ctx);
+        }
+        if !read_only {
+            self.handle_dropped_files(ctx);
+            self.render_latex_dialog(ctx);
+            self.render_snapshots_panel(ctx);
+            self.render_pdf_export_panel(ctx);
+            self.render_figure_export_panel(ctx);
+            self.render_named_export_panel(ctx);
+            self.render_recipes_panel(ctx);
+            self.render_pdf_import_panel(ctx);
+            self.render_image_folder_import_panel(ctx);
+            self.render_guides_panel(ctx);
+            self.render_layers_panel(ctx);
+            self.render_styles_panel(ctx);
+            self.render_recent_panel(ctx);
+            self.render_properties_panel(ctx);
+            self.render_templates_panel(ctx);
+            self.render_template_fill(ctx);
+            self.render_reveal_panel(ctx);
+            self.render_font_panel(ctx);
+            self.render_symbol_picker(ctx);
+            self.render_ocr_panel(ctx);
+        }
+        self.render_board_tabs(ctx);
+        self.render_find_panel(ctx);
+        self.latex_renderer.next_frame();
+        self.image_cache.next_frame();
         self.render_canvas(ctx);
+        self.limit_panning();
+        self.render_zoom_controls(ctx);
+        self.render_load_progress(ctx);
+        self.render_memory_notice(ctx);
+        self.warm_latex_cache(ctx);
+        self.profiler.current.latex_cache = self.latex_renderer.frame_hits();
+        self.profiler.current.image_cache = self.image_cache.frame_hits();
+        self.profiler.finish_frame(update_started.elapsed(), frame.info().cpu_usage);
+        if self.show_profiler {
+            self.render_profiler(ctx);
+        }
 
         if self.needs_repaint || self.is_drawing || self.draw_start_pos.is_some() || 
            !self.selected_objects.is_empty() || self.selection_mode != SelectionMode::None || 
            self.editing_text.is_some() {
-            ctx.request_repaint();
+            if self.idle {
+                ctx.request_repaint_after(std::time::Duration::from_secs(IDLE_REPAINT_SECS));
+            } else {
+                ctx.request_repaint();
+            }
             self.needs_repaint = false;
         }
     }
+
+    fn raw_input_hook(&mut self, ctx: &egui::Context, raw_input: &mut egui::RawInput) {
+        if self.editing_text.is_some() || ctx.memory(|m| m.focused().is_some()) {
+            return;
+        }
+        raw_input.events.retain(|event| match event {
+            egui::Event::Key { key: egui::Key::Tab, pressed, modifiers, .. } => {
+                if *pressed {
+                    self.pending_focus_cycle += if modifiers.shift { -1 } else { 1 };
+                }
+                false
+            }
+            _ => true,
+        });
+    }
 }
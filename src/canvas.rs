@@ -15,7 +15,8 @@ pub fn smooth_stroke(points: &[StrokePoint]) -> Vec<StrokePoint> {
         let p2 = points[i + 1].pos;
         let p3 = if i + 2 < points.len() { points[i + 2].pos } else { points[i + 1].pos };
 
-        let segments = 5;
+        let length = ((p2[0] - p1[0]).powi(2) + (p2[1] - p1[1]).powi(2)).sqrt();
+        let segments = ((length / 2.0).ceil() as usize).clamp(1, 16);
         for t in 0..segments {
             let t = t as f32 / segments as f32;
             let t2 = t * t;
@@ -34,7 +35,11 @@ pub fn smooth_stroke(points: &[StrokePoint]) -> Vec<StrokePoint> {
                 (-p0[1] + 3.0 * p1[1] - 3.0 * p2[1] + p3[1]) * t3
             );
 
-            smoothed.push(StrokePoint { pos: [x, y] });
+            smoothed.push(StrokePoint {
+                pos: [x, y],
+                width: lerp_optional(points[i].width, points[i + 1].width, t),
+                opacity: lerp_optional(points[i].opacity, points[i + 1].opacity, t),
+            });
         }
     }
 
@@ -42,44 +47,163 @@ pub fn smooth_stroke(points: &[StrokePoint]) -> Vec<StrokePoint> {
     smoothed
 }
 
-pub fn screen_to_canvas(screen_pos: egui::Pos2, canvas_offset: egui::Vec2, canvas_zoom: f32) -> [f32; 2] {
-    let canvas_pos = (screen_pos.to_vec2() - canvas_offset) / canvas_zoom;
+fn lerp_optional(a: Option<f32>, b: Option<f32>, t: f32) -> Option<f32> {
+    match (a, b) {
+        (Some(a), Some(b)) => Some(a + (b - a) * t),
+        _ => None,
+    }
+}
+
+pub fn push_densified(points: &mut Vec<StrokePoint>, next: StrokePoint, max_gap: f32) {
+    if let Some(last) = points.last().cloned() {
+        let dx = next.pos[0] - last.pos[0];
+        let dy = next.pos[1] - last.pos[1];
+        let steps = ((dx * dx + dy * dy).sqrt() / max_gap).ceil() as usize;
+        for step in 1..steps.min(256) {
+            let t = step as f32 / steps as f32;
+            points.push(StrokePoint {
+                pos: [last.pos[0] + dx * t, last.pos[1] + dy * t],
+                width: lerp_optional(last.width, next.width, t),
+                opacity: lerp_optional(last.opacity, next.opacity, t),
+            });
+        }
+    }
+    points.push(next);
+}
+
+pub const GRID_SPACING: f32 = 50.0;
+pub const ANGLE_SNAP_DEGREES: f32 = 15.0;
+
+pub fn snap_to_grid(pos: [f32; 2], spacing: f32) -> [f32; 2] {
+    [(pos[0] / spacing).round() * spacing, (pos[1] / spacing).round() * spacing]
+}
+
+pub fn snap_delta_to_grid(origin: [f32; 2], delta: [f32; 2], spacing: f32) -> [f32; 2] {
+    let snapped = snap_to_grid([origin[0] + delta[0], origin[1] + delta[1]], spacing);
+    [snapped[0] - origin[0], snapped[1] - origin[1]]
+}
+
+pub fn snap_angle(origin: [f32; 2], pos: [f32; 2], step_degrees: f32) -> [f32; 2] {
+    let (dx, dy) = (pos[0] - origin[0], pos[1] - origin[1]);
+    let length = (dx * dx + dy * dy).sqrt();
+    let step = step_degrees.to_radians();
+    let angle = (dy.atan2(dx) / step).round() * step;
+    [origin[0] + length * angle.cos(), origin[1] + length * angle.sin()]
+}
+
+pub fn screen_to_canvas(screen_pos: egui::Pos2, canvas_offset: egui::Vec2, canvas_zoom: f32, canvas_rotation: f32) -> [f32; 2] {
+    let rotated = screen_pos.to_vec2() - canvas_offset;
+    let canvas_pos = egui::emath::Rot2::from_angle(-canvas_rotation) * rotated / canvas_zoom;
     [canvas_pos.x, canvas_pos.y]
 }
 
-pub fn canvas_to_screen(canvas_pos: [f32; 2], canvas_offset: egui::Vec2, canvas_zoom: f32) -> egui::Pos2 {
-    let screen_vec = egui::Vec2::new(canvas_pos[0], canvas_pos[1]) * canvas_zoom + canvas_offset;
+pub fn canvas_to_screen(canvas_pos: [f32; 2], canvas_offset: egui::Vec2, canvas_zoom: f32, canvas_rotation: f32) -> egui::Pos2 {
+    let scaled = egui::Vec2::new(canvas_pos[0], canvas_pos[1]) * canvas_zoom;
+    let screen_vec = egui::emath::Rot2::from_angle(canvas_rotation) * scaled + canvas_offset;
     egui::Pos2::new(screen_vec.x, screen_vec.y)
 }
 
-pub fn render_object(painter: &egui::Painter, obj: &DrawObject, canvas_offset: egui::Vec2, canvas_zoom: f32) {
+pub fn canvas_rect_to_screen(min: [f32; 2], max: [f32; 2], canvas_offset: egui::Vec2, canvas_zoom: f32, canvas_rotation: f32) -> [egui::Pos2; 4] {
+    [
+        canvas_to_screen([min[0], min[1]], canvas_offset, canvas_zoom, canvas_rotation),
+        canvas_to_screen([max[0], min[1]], canvas_offset, canvas_zoom, canvas_rotation),
+        canvas_to_screen([max[0], max[1]], canvas_offset, canvas_zoom, canvas_rotation),
+        canvas_to_screen([min[0], max[1]], canvas_offset, canvas_zoom, canvas_rotation),
+    ]
+}
+
+pub fn visible_canvas_bounds(rect: egui::Rect, canvas_offset: egui::Vec2, canvas_zoom: f32, canvas_rotation: f32) -> ([f32; 2], [f32; 2]) {
+    let corners = [rect.left_top(), rect.right_top(), rect.right_bottom(), rect.left_bottom()];
+    let mut min = [f32::MAX, f32::MAX];
+    let mut max = [f32::MIN, f32::MIN];
+    for corner in corners {
+        let p = screen_to_canvas(corner, canvas_offset, canvas_zoom, canvas_rotation);
+        min[0] = min[0].min(p[0]);
+        min[1] = min[1].min(p[1]);
+        max[0] = max[0].max(p[0]);
+        max[1] = max[1].max(p[1]);
+    }
+    (min, max)
+}
+
+pub fn segment_ink(a: &StrokePoint, b: &StrokePoint, base_width: f32) -> (f32, f32) {
+    let width = lerp_optional(a.width, b.width, 0.5);
+    match (lerp_optional(a.opacity, b.opacity, 0.5), width) {
+        (Some(opacity), width) => (width.unwrap_or(base_width), opacity.clamp(0.0, 1.0)),
+        (None, Some(width)) => (width, (base_width / width).clamp(0.25, 1.0)),
+        (None, None) => (base_width, 1.0),
+    }
+}
+
+pub fn stroke_runs(points: &[StrokePoint], base_width: f32) -> Vec<(std::ops::Range<usize>, f32, f32)> {
+    let quantize = |(width, opacity): (f32, f32)| ((width * 4.0).round() / 4.0, (opacity * 20.0).round() / 20.0);
+    let mut runs: Vec<(std::ops::Range<usize>, f32, f32)> = Vec::new();
+    for (i, pair) in points.windows(2).enumerate() {
+        let (width, opacity) = quantize(segment_ink(&pair[0], &pair[1], base_width));
+        match runs.last_mut() {
+            Some((range, w, o)) if *w == width && *o == opacity => range.end = i + 2,
+            _ => runs.push((i..i + 2, width, opacity)),
+        }
+    }
+    runs
+}
+
+pub fn ink_color(color: [u8; 4], opacity: f32) -> egui::Color32 {
+    let alpha = (color[3] as f32 * opacity.clamp(0.0, 1.0)).round() as u8;
+    egui::Color32::from_rgba_unmultiplied(color[0], color[1], color[2], alpha)
+}
+
+pub fn textured_quad(texture_id: egui::TextureId, corners: [egui::Pos2; 4], opacity: f32) -> egui::Mesh {
+    let mut mesh = egui::Mesh::with_texture(texture_id);
+    let uvs = [egui::pos2(0.0, 0.0), egui::pos2(1.0, 0.0), egui::pos2(1.0, 1.0), egui::pos2(0.0, 1.0)];
+    for (pos, uv) in corners.into_iter().zip(uvs) {
+        mesh.vertices.push(egui::epaint::Vertex { pos, uv, color: egui::Color32::WHITE.gamma_multiply(opacity) });
+    }
+    mesh.add_triangle(0, 1, 2);
+    mesh.add_triangle(0, 2, 3);
+    mesh
+}
+
+pub fn render_object(painter: &egui::Painter, obj: &DrawObject, canvas_offset: egui::Vec2, canvas_zoom: f32, canvas_rotation: f32) {
     match obj {
         DrawObject::Stroke { points, color, width, .. } => {
             if points.len() < 2 {
                 return;
             }
-            let color = egui::Color32::from_rgba_unmultiplied(color[0], color[1], color[2], color[3]);
+            let color = ink_color(*color, obj.props().opacity);
+            if obj.props().highlight {
+                for (range, segment_width, segment_opacity) in stroke_runs(points, *width) {
+                    let screen: Vec<egui::Pos2> = points[range].iter().map(|p| canvas_to_screen(p.pos, canvas_offset, canvas_zoom, canvas_rotation)).collect();
+                    painter.add(egui::Shape::line(screen, egui::Stroke::new(segment_width * canvas_zoom, color.gamma_multiply(segment_opacity))));
+                }
+                return;
+            }
+            let clip = painter.clip_rect();
             for i in 0..points.len() - 1 {
-                let start = canvas_to_screen(points[i].pos, canvas_offset, canvas_zoom);
-                let end = canvas_to_screen(points[i + 1].pos, canvas_offset, canvas_zoom);
+                let start = canvas_to_screen(points[i].pos, canvas_offset, canvas_zoom, canvas_rotation);
+                let end = canvas_to_screen(points[i + 1].pos, canvas_offset, canvas_zoom, canvas_rotation);
+                let (segment_width, segment_opacity) = segment_ink(&points[i], &points[i + 1], *width);
+                if !clip.intersects(egui::Rect::from_two_pos(start, end).expand(segment_width * canvas_zoom)) {
+                    continue;
+                }
                 painter.line_segment(
                     [start, end],
-                    egui::Stroke::new(*width * canvas_zoom, color),
+                    egui::Stroke::new(segment_width * canvas_zoom, color.gamma_multiply(segment_opacity)),
                 );
             }
         }
         DrawObject::Line { start, end, color, width, .. } => {
-            let color = egui::Color32::from_rgba_unmultiplied(color[0], color[1], color[2], color[3]);
-            let screen_start = canvas_to_screen(*start, canvas_offset, canvas_zoom);
-            let screen_end = canvas_to_screen(*end, canvas_offset, canvas_zoom);
+            let color = ink_color(*color, obj.props().opacity);
+            let screen_start = canvas_to_screen(*start, canvas_offset, canvas_zoom, canvas_rotation);
+            let screen_end = canvas_to_screen(*end, canvas_offset, canvas_zoom, canvas_rotation);
             painter.line_segment(
                 [screen_start, screen_end],
                 egui::Stroke::new(*width * canvas_zoom, color),
             );
         }
         DrawObject::Circle { center, radius, color, width, filled, .. } => {
-            let color = egui::Color32::from_rgba_unmultiplied(color[0], color[1], color[2], color[3]);
-            let screen_center = canvas_to_screen(*center, canvas_offset, canvas_zoom);
+            let color = ink_color(*color, obj.props().opacity);
+            let screen_center = canvas_to_screen(*center, canvas_offset, canvas_zoom, canvas_rotation);
             let screen_radius = radius * canvas_zoom;
             if *filled {
                 painter.circle_filled(screen_center, screen_radius, color);
@@ -92,31 +216,120 @@ pub fn render_object(painter: &egui::Painter, obj: &DrawObject, canvas_offset: e
             }
         }
         DrawObject::Rectangle { min, max, color, width, filled, .. } => {
-            let color = egui::Color32::from_rgba_unmultiplied(color[0], color[1], color[2], color[3]);
-            let screen_min = canvas_to_screen(*min, canvas_offset, canvas_zoom);
-            let screen_max = canvas_to_screen(*max, canvas_offset, canvas_zoom);
-            let rect = egui::Rect::from_two_pos(screen_min, screen_max);
+            let color = ink_color(*color, obj.props().opacity);
+            let corners = canvas_rect_to_screen(*min, *max, canvas_offset, canvas_zoom, canvas_rotation);
             if *filled {
-                painter.rect_filled(rect, 0.0, color);
+                painter.add(egui::Shape::convex_polygon(corners.to_vec(), color, egui::Stroke::NONE));
             } else {
-                painter.rect_stroke(
-                    rect,
-                    0.0,
+                painter.add(egui::Shape::closed_line(
+                    corners.to_vec(),
                     egui::Stroke::new(*width * canvas_zoom, color),
-                );
+                ));
             }
         }
-        DrawObject::LatexFormula { .. } => {
+        DrawObject::LatexFormula { .. } | DrawObject::Image { .. } => {
             //hi future me don't delete this
         }
+        DrawObject::ScaleBar { pos, length, label, color, .. } => {
+            let color = ink_color(*color, obj.props().opacity);
+            for (start, end) in crate::models::scale_bar_segments(*pos, *length) {
+                painter.line_segment(
+                    [
+                        canvas_to_screen(start, canvas_offset, canvas_zoom, canvas_rotation),
+                        canvas_to_screen(end, canvas_offset, canvas_zoom, canvas_rotation),
+                    ],
+                    egui::Stroke::new(2.0 * canvas_zoom, color),
+                );
+            }
+            painter.text(
+                canvas_to_screen([pos[0] + length / 2.0, pos[1] + 4.0], canvas_offset, canvas_zoom, canvas_rotation),
+                egui::Align2::CENTER_TOP,
+                label,
+                egui::FontId::proportional(14.0 * canvas_zoom),
+                color,
+            );
+        }
+        DrawObject::Instance { members, .. } => {
+            for member in members {
+                render_object(painter, member, canvas_offset, canvas_zoom, canvas_rotation);
+            }
+        }
+        DrawObject::Frame { min, max, name, props, .. } => {
+            let color = egui::Color32::from_rgb(120, 120, 140).gamma_multiply(props.opacity);
+            let corners = canvas_rect_to_screen(*min, *max, canvas_offset, canvas_zoom, canvas_rotation);
+            for i in 0..4 {
+                painter.extend(egui::Shape::dashed_line(
+                    &[corners[i], corners[(i + 1) % 4]],
+                    egui::Stroke::new(1.0, color),
+                    6.0,
+                    4.0,
+                ));
+            }
+            painter.text(
+                corners[0] - egui::vec2(0.0, 4.0),
+                egui::Align2::LEFT_BOTTOM,
+                name,
+                egui::FontId::proportional(12.0),
+                color,
+            );
+        }
     }
 }
 
-pub fn find_object_at(objects: &[DrawObject], canvas_pos: [f32; 2]) -> Option<uuid::Uuid> {
-    for obj in objects.iter().rev() {
-        if obj.contains_point(canvas_pos) {
-            return Some(obj.id());
-        }
+pub const OVERVIEW_KINDS: [(&str, [u8; 3]); 4] = [
+    ("Ink", [0, 114, 178]),
+    ("Shapes", [0, 158, 115]),
+    ("Formulas", [213, 94, 0]),
+    ("Images", [204, 121, 167]),
+];
+
+pub fn overview_kind(obj: &DrawObject) -> Option<usize> {
+    match obj {
+        DrawObject::Stroke { .. } | DrawObject::Line { .. } => Some(0),
+        DrawObject::Circle { .. } | DrawObject::Rectangle { .. } | DrawObject::ScaleBar { .. } => Some(1),
+        DrawObject::LatexFormula { .. } => Some(2),
+        DrawObject::Image { .. } => Some(3),
+        DrawObject::Frame { .. } | DrawObject::Instance { .. } => None,
     }
-    None
+}
+
+pub fn render_overview_block(painter: &egui::Painter, obj: &DrawObject, opacity: f32, canvas_offset: egui::Vec2, canvas_zoom: f32, canvas_rotation: f32) {
+    let Some(kind) = overview_kind(obj) else {
+        match obj {
+            DrawObject::Instance { members, .. } => {
+                for member in members {
+                    render_overview_block(painter, member, opacity, canvas_offset, canvas_zoom, canvas_rotation);
+                }
+            }
+            _ => render_object(painter, obj, canvas_offset, canvas_zoom, canvas_rotation),
+        }
+        return;
+    };
+    let (min, max) = obj.bounds();
+    let pad = 1.0 / canvas_zoom;
+    let corners = canvas_rect_to_screen(
+        [min[0] - pad, min[1] - pad],
+        [max[0] + pad, max[1] + pad],
+        canvas_offset,
+        canvas_zoom,
+        canvas_rotation,
+    );
+    let [r, g, b] = OVERVIEW_KINDS[kind].1;
+    let color = ink_color([r, g, b, 200], opacity);
+    painter.add(egui::Shape::convex_polygon(corners.to_vec(), color, egui::Stroke::NONE));
+}
+
+pub fn find_object_at<'a>(objects: impl DoubleEndedIterator<Item = &'a DrawObject>, canvas_pos: [f32; 2], tolerance: f32, canvas_zoom: f32) -> Option<uuid::Uuid> {
+    objects
+        .rev()
+        .find(|obj| {
+            !obj.props().hidden && match obj {
+                DrawObject::LatexFormula { pin_size: true, .. } => {
+                    let (min, max) = obj.view_bounds(canvas_zoom);
+                    canvas_pos[0] >= min[0] && canvas_pos[0] <= max[0] && canvas_pos[1] >= min[1] && canvas_pos[1] <= max[1]
+                }
+                _ => obj.hit_test(canvas_pos, tolerance),
+            }
+        })
+        .map(|obj| obj.id())
 }
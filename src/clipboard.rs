@@ -0,0 +1,55 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use uuid::Uuid;
+
+use crate::models::DrawObject;
+use crate::selection;
+
+const CLIPBOARD_FORMAT: &str = "infinitex/objects";
+
+#[derive(Serialize, Deserialize)]
+pub struct ClipboardContents {
+    format: String,
+    pub objects: Vec<DrawObject>,
+}
+
+impl ClipboardContents {
+    pub fn new(objects: Vec<DrawObject>) -> Self {
+        Self { format: CLIPBOARD_FORMAT.to_string(), objects }
+    }
+
+    pub fn to_text(&self) -> Result<String, serde_json::Error> {
+        serde_json::to_string(self)
+    }
+
+    pub fn from_text(text: &str) -> Option<Self> {
+        let contents: Self = serde_json::from_str(text.trim()).ok()?;
+        (contents.format == CLIPBOARD_FORMAT && !contents.objects.is_empty()).then_some(contents)
+    }
+
+    pub fn bounds(&self) -> Option<([f32; 2], [f32; 2])> {
+        let ids: Vec<Uuid> = self.objects.iter().map(|obj| obj.id()).collect();
+        selection::get_selection_bounds(&self.objects, &ids)
+    }
+
+    pub fn instantiate(&self, offset: [f32; 2]) -> Vec<DrawObject> {
+        let mut groups: HashMap<Uuid, Uuid> = HashMap::new();
+        let mut copies: Vec<DrawObject> = self.objects.iter().map(DrawObject::with_new_id).collect();
+        for copy in &mut copies {
+            if let Some(group) = copy.props().group {
+                let renamed = *groups.entry(group).or_insert_with(Uuid::new_v4);
+                copy.props_mut().group = Some(renamed);
+            }
+        }
+        for copy in &mut copies {
+            if let DrawObject::Instance { source, .. } = copy {
+                if let Some(renamed) = groups.get(source) {
+                    *source = *renamed;
+                }
+            }
+        }
+        let ids: Vec<Uuid> = copies.iter().map(|obj| obj.id()).collect();
+        selection::transform_objects(&mut copies, &ids, [1.0, 1.0], 0.0, offset, [0.0, 0.0]);
+        copies
+    }
+}
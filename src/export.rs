@@ -0,0 +1,586 @@
+use std::collections::{HashMap, HashSet};
+use std::path::Path;
+
+use eframe::egui;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::latex::LatexRenderer;
+use crate::models::{DocumentMetadata, DrawObject, Layer};
+use crate::pdf::{self, PdfPage};
+
+pub fn union_bounds(a: Option<([f32; 2], [f32; 2])>, b: ([f32; 2], [f32; 2])) -> ([f32; 2], [f32; 2]) {
+    match a {
+        None => b,
+        Some((min, max)) => (
+            [min[0].min(b.0[0]), min[1].min(b.0[1])],
+            [max[0].max(b.1[0]), max[1].max(b.1[1])],
+        ),
+    }
+}
+
+pub fn layered(objects: &[DrawObject], layers: &[Layer]) -> Vec<DrawObject> {
+    let layer_of = |obj: &DrawObject| obj.props().layer.and_then(|id| layers.iter().position(|layer| layer.id == id));
+    let mut visible: Vec<(usize, &DrawObject)> = objects
+        .iter()
+        .filter(|obj| layer_of(obj).is_none_or(|index| layers[index].visible))
+        .map(|obj| (layer_of(obj).unwrap_or(0), obj))
+        .collect();
+    visible.sort_by_key(|(layer, _)| *layer);
+    visible.into_iter().map(|(_, obj)| obj.clone()).collect()
+}
+
+pub fn content_bounds(objects: &[DrawObject]) -> Option<([f32; 2], [f32; 2])> {
+    objects
+        .iter()
+        .filter(|obj| !obj.props().hidden)
+        .fold(None, |acc, obj| Some(union_bounds(acc, obj.bounds())))
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ExportFraming {
+    pub trim: bool,
+    pub margin: f32,
+    pub round_to: u32,
+}
+
+impl Default for ExportFraming {
+    fn default() -> Self {
+        Self { trim: true, margin: 20.0, round_to: 1 }
+    }
+}
+
+impl ExportFraming {
+    pub const ROUND_OPTIONS: [u32; 5] = [1, 2, 8, 16, 64];
+
+    pub fn around(&self, (min, max): ([f32; 2], [f32; 2]), scale: f32) -> ([f32; 2], [f32; 2]) {
+        let scale = scale.max(f32::EPSILON);
+        let pad = self.margin.max(0.0) + 1.0 / scale;
+        let (mut min, mut max) = ([min[0] - pad, min[1] - pad], [max[0] + pad, max[1] + pad]);
+        let step = self.round_to.max(1) as f32;
+        for axis in 0..2 {
+            let pixels = (max[axis] - min[axis]) * scale;
+            let extra = ((pixels / step).ceil() * step - pixels) / scale / 2.0;
+            min[axis] -= extra;
+            max[axis] += extra;
+        }
+        (min, max)
+    }
+
+    pub fn frame(&self, objects: &[DrawObject], frame: ([f32; 2], [f32; 2]), scale: f32) -> ([f32; 2], [f32; 2]) {
+        if !self.trim {
+            return frame;
+        }
+        let (min, max) = frame;
+        let content = objects
+            .iter()
+            .filter(|obj| !obj.props().hidden && !matches!(obj, DrawObject::Frame { min: fmin, max: fmax, .. } if *fmin == min && *fmax == max))
+            .map(|obj| obj.bounds())
+            .filter(|(omin, omax)| omax[0] >= min[0] && omin[0] <= max[0] && omax[1] >= min[1] && omin[1] <= max[1])
+            .fold(None, |acc, b| Some(union_bounds(acc, b)));
+        let Some((cmin, cmax)) = content else {
+            return frame;
+        };
+        let (tmin, tmax) = self.around(
+            ([cmin[0].max(min[0]), cmin[1].max(min[1])], [cmax[0].min(max[0]), cmax[1].min(max[1])]),
+            scale,
+        );
+        ([tmin[0].max(min[0]), tmin[1].max(min[1])], [tmax[0].min(max[0]), tmax[1].min(max[1])])
+    }
+}
+
+fn paint_for(color: [u8; 4], opacity: f32) -> tiny_skia::Paint<'static> {
+    let mut paint = tiny_skia::Paint::default();
+    let alpha = (color[3] as f32 * opacity.clamp(0.0, 1.0)).round() as u8;
+    paint.set_color_rgba8(color[0], color[1], color[2], alpha);
+    paint.anti_alias = true;
+    paint
+}
+
+fn stroke_for(width: f32) -> tiny_skia::Stroke {
+    tiny_skia::Stroke {
+        width,
+        line_cap: tiny_skia::LineCap::Round,
+        line_join: tiny_skia::LineJoin::Round,
+        ..Default::default()
+    }
+}
+
+pub fn render_to_pixmap(
+    objects: &[DrawObject],
+    bounds: ([f32; 2], [f32; 2]),
+    scale: f32,
+    background: Option<[u8; 4]>,
+    latex_renderer: &mut LatexRenderer,
+) -> Result<tiny_skia::Pixmap, Box<dyn std::error::Error>> {
+    let (min, max) = bounds;
+    let width = ((max[0] - min[0]) * scale - 1e-3).ceil().max(1.0) as u32;
+    let height = ((max[1] - min[1]) * scale - 1e-3).ceil().max(1.0) as u32;
+    let mut pixmap = tiny_skia::Pixmap::new(width, height)
+        .ok_or("Export area is too large")?;
+
+    if let Some(bg) = background {
+        pixmap.fill(tiny_skia::Color::from_rgba8(bg[0], bg[1], bg[2], bg[3]));
+    }
+
+    let transform = tiny_skia::Transform::from_translate(-min[0], -min[1]).post_scale(scale, scale);
+
+    for obj in objects.iter().filter(|obj| !obj.props().hidden) {
+        if let DrawObject::Frame { min, max, background: Some(color), .. } = obj {
+            if let Some(rect) = tiny_skia::Rect::from_ltrb(min[0], min[1], max[0].max(min[0] + 0.01), max[1].max(min[1] + 0.01)) {
+                pixmap.fill_rect(rect, &paint_for(*color, 1.0), transform, None);
+            }
+        }
+    }
+
+    let to_color = |bg: [u8; 4]| egui::Color32::from_rgba_unmultiplied(bg[0], bg[1], bg[2], bg[3]);
+    let background = background.map_or(egui::Color32::WHITE, to_color);
+    let backgrounds = crate::selection::page_backgrounds(objects);
+    let mut ordered: Vec<&DrawObject> = objects.iter().filter(|obj| !obj.props().hidden).collect();
+    ordered.sort_by_key(|obj| !obj.props().highlight);
+    for obj in ordered {
+        let (min, max) = obj.bounds();
+        let center = [(min[0] + max[0]) / 2.0, (min[1] + max[1]) / 2.0];
+        let local_background = crate::selection::background_in(&backgrounds, center).map_or(background, to_color);
+        draw_object(&mut pixmap, obj, transform, local_background, latex_renderer);
+    }
+
+    Ok(pixmap)
+}
+
+fn draw_object(
+    pixmap: &mut tiny_skia::Pixmap,
+    obj: &DrawObject,
+    transform: tiny_skia::Transform,
+    background: egui::Color32,
+    latex_renderer: &mut LatexRenderer,
+) {
+    let opacity = obj.props().opacity;
+    match obj {
+        DrawObject::Stroke { points, color, width, .. } => {
+            if points.len() < 2 {
+                return;
+            }
+            if points.iter().any(|p| p.width.is_some() || p.opacity.is_some()) {
+                for pair in points.windows(2) {
+                    let (segment_width, segment_opacity) = crate::canvas::segment_ink(&pair[0], &pair[1], *width);
+                    let mut pb = tiny_skia::PathBuilder::new();
+                    pb.move_to(pair[0].pos[0], pair[0].pos[1]);
+                    pb.line_to(pair[1].pos[0], pair[1].pos[1]);
+                    if let Some(path) = pb.finish() {
+                        pixmap.stroke_path(&path, &paint_for(*color, opacity * segment_opacity), &stroke_for(segment_width), transform, None);
+                    }
+                }
+                return;
+            }
+            let mut pb = tiny_skia::PathBuilder::new();
+            pb.move_to(points[0].pos[0], points[0].pos[1]);
+            for p in &points[1..] {
+                pb.line_to(p.pos[0], p.pos[1]);
+            }
+            if let Some(path) = pb.finish() {
+                pixmap.stroke_path(&path, &paint_for(*color, opacity), &stroke_for(*width), transform, None);
+            }
+        }
+        DrawObject::Line { start, end, color, width, .. } => {
+            let mut pb = tiny_skia::PathBuilder::new();
+            pb.move_to(start[0], start[1]);
+            pb.line_to(end[0], end[1]);
+            if let Some(path) = pb.finish() {
+                pixmap.stroke_path(&path, &paint_for(*color, opacity), &stroke_for(*width), transform, None);
+            }
+        }
+        DrawObject::Circle { center, radius, color, width, filled, .. } => {
+            if let Some(path) = tiny_skia::PathBuilder::from_circle(center[0], center[1], radius.max(0.01)) {
+                if *filled {
+                    pixmap.fill_path(&path, &paint_for(*color, opacity), tiny_skia::FillRule::Winding, transform, None);
+                } else {
+                    pixmap.stroke_path(&path, &paint_for(*color, opacity), &stroke_for(*width), transform, None);
+                }
+            }
+        }
+        DrawObject::Rectangle { min, max, color, width, filled, .. } => {
+            if let Some(rect) = tiny_skia::Rect::from_ltrb(min[0], min[1], max[0].max(min[0] + 0.01), max[1].max(min[1] + 0.01)) {
+                let path = tiny_skia::PathBuilder::from_rect(rect);
+                if *filled {
+                    pixmap.fill_path(&path, &paint_for(*color, opacity), tiny_skia::FillRule::Winding, transform, None);
+                } else {
+                    pixmap.stroke_path(&path, &paint_for(*color, opacity), &stroke_for(*width), transform, None);
+                }
+            }
+        }
+        DrawObject::LatexFormula { pos, formula, color, size: formula_size, scale, auto_color, font, .. } => {
+            if formula.is_empty() {
+                return;
+            }
+            let color = if *auto_color { crate::palette::auto_ink(background) } else { *color };
+            let Ok(image) = latex_renderer.render_to_image(formula, font.as_deref(), color) else {
+                return;
+            };
+            let [w, h] = image.size;
+            let data: Vec<u8> = image.pixels.iter().flat_map(|c| c.to_array()).collect();
+            let Some(size) = tiny_skia::IntSize::from_wh(w as u32, h as u32) else {
+                return;
+            };
+            let Some(formula_pixmap) = tiny_skia::Pixmap::from_vec(data, size) else {
+                return;
+            };
+            let canvas_size = formula_size.unwrap_or(latex_renderer.display_size(image.size));
+            let image_transform = transform
+                .pre_translate(pos[0], pos[1])
+                .pre_scale(canvas_size[0] * scale[0] / w as f32, canvas_size[1] * scale[1] / h as f32);
+            pixmap.draw_pixmap(
+                0,
+                0,
+                formula_pixmap.as_ref(),
+                &tiny_skia::PixmapPaint {
+                    opacity,
+                    quality: tiny_skia::FilterQuality::Bilinear,
+                    ..Default::default()
+                },
+                image_transform,
+                None,
+            );
+        }
+        DrawObject::ScaleBar { pos, length, label, color, .. } => {
+            let mut pb = tiny_skia::PathBuilder::new();
+            for (start, end) in crate::models::scale_bar_segments(*pos, *length) {
+                pb.move_to(start[0], start[1]);
+                pb.line_to(end[0], end[1]);
+            }
+            if let Some(path) = pb.finish() {
+                pixmap.stroke_path(&path, &paint_for(*color, opacity), &stroke_for(2.0), transform, None);
+            }
+            let Ok(image) = latex_renderer.render_to_image(&format!("\\text{{{}}}", label), None, *color) else {
+                return;
+            };
+            let [w, h] = image.size;
+            let data: Vec<u8> = image.pixels.iter().flat_map(|c| c.to_array()).collect();
+            let Some(size) = tiny_skia::IntSize::from_wh(w as u32, h as u32) else {
+                return;
+            };
+            let Some(label_pixmap) = tiny_skia::Pixmap::from_vec(data, size) else {
+                return;
+            };
+            let [label_width, label_height] = latex_renderer.display_size(image.size);
+            let fit = (crate::models::SCALE_BAR_LABEL_HEIGHT - 6.0) / label_height.max(1.0);
+            let label_transform = transform
+                .pre_translate(pos[0] + length / 2.0 - label_width * fit / 2.0, pos[1] + 4.0)
+                .pre_scale(label_width * fit / w as f32, label_height * fit / h as f32);
+            pixmap.draw_pixmap(
+                0,
+                0,
+                label_pixmap.as_ref(),
+                &tiny_skia::PixmapPaint {
+                    opacity,
+                    quality: tiny_skia::FilterQuality::Bilinear,
+                    ..Default::default()
+                },
+                label_transform,
+                None,
+            );
+        }
+        DrawObject::Frame { .. } => {}
+        DrawObject::Instance { members, .. } => {
+            for member in members.iter().filter(|member| !member.props().hidden) {
+                draw_object(pixmap, member, transform, background, latex_renderer);
+            }
+        }
+        DrawObject::Image { min, max, data, adjustments, .. } => {
+            let Ok(mut rgba) = crate::images::decode(data) else {
+                return;
+            };
+            crate::images::adjust(&mut rgba, adjustments);
+            let (w, h) = rgba.dimensions();
+            let premultiplied: Vec<u8> = rgba
+                .pixels()
+                .flat_map(|p| {
+                    let [r, g, b, a] = p.0;
+                    let premultiply = |c: u8| ((c as u16 * a as u16 + 127) / 255) as u8;
+                    [premultiply(r), premultiply(g), premultiply(b), a]
+                })
+                .collect();
+            let Some(size) = tiny_skia::IntSize::from_wh(w, h) else {
+                return;
+            };
+            let Some(image_pixmap) = tiny_skia::Pixmap::from_vec(premultiplied, size) else {
+                return;
+            };
+            let image_transform = transform
+                .pre_translate(min[0], min[1])
+                .pre_scale((max[0] - min[0]) / w as f32, (max[1] - min[1]) / h as f32);
+            pixmap.draw_pixmap(
+                0,
+                0,
+                image_pixmap.as_ref(),
+                &tiny_skia::PixmapPaint {
+                    opacity,
+                    quality: tiny_skia::FilterQuality::Bilinear,
+                    ..Default::default()
+                },
+                image_transform,
+                None,
+            );
+        }
+    }
+}
+
+pub fn export_timelapse(
+    frames: &[&[DrawObject]],
+    dir: &str,
+    scale: f32,
+    background: [u8; 4],
+    framing: &ExportFraming,
+    latex_renderer: &mut LatexRenderer,
+) -> Result<usize, Box<dyn std::error::Error>> {
+    let content = frames
+        .iter()
+        .filter_map(|frame| content_bounds(frame))
+        .fold(None, |acc, b| Some(union_bounds(acc, b)))
+        .ok_or("Nothing to export")?;
+    let bounds = framing.around(content, scale);
+
+    std::fs::create_dir_all(dir)?;
+    for (index, frame) in frames.iter().enumerate() {
+        let pixmap = render_to_pixmap(frame, bounds, scale, Some(background), latex_renderer)?;
+        let path = Path::new(dir).join(format!("frame_{:04}.png", index + 1));
+        pixmap.save_png(path)?;
+    }
+    Ok(frames.len())
+}
+
+pub fn rasterize_png(
+    objects: &[DrawObject],
+    bounds: ([f32; 2], [f32; 2]),
+    scale: f32,
+    latex_renderer: &mut LatexRenderer,
+) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+    let pixmap = render_to_pixmap(objects, bounds, scale, None, latex_renderer)?;
+    Ok(pixmap.encode_png()?)
+}
+
+pub struct FigureExportOptions {
+    pub scale: f32,
+    pub ink: Option<[u8; 4]>,
+    pub margin: u32,
+}
+
+fn recolor_ink(obj: &mut DrawObject, ink: [u8; 4]) {
+    match obj {
+        DrawObject::LatexFormula { color, auto_color, .. } => {
+            *color = ink;
+            *auto_color = false;
+        }
+        DrawObject::Instance { members, .. } => {
+            for member in members {
+                recolor_ink(member, ink);
+            }
+        }
+        _ => {
+            if let Some(color) = obj.color_mut() {
+                *color = [ink[0], ink[1], ink[2], color[3]];
+            }
+        }
+    }
+}
+
+fn trim_transparent(pixmap: &tiny_skia::Pixmap, margin: u32) -> Option<tiny_skia::Pixmap> {
+    let width = pixmap.width();
+    let mut min = [u32::MAX, u32::MAX];
+    let mut max = [0, 0];
+    for (index, pixel) in pixmap.pixels().iter().enumerate() {
+        if pixel.alpha() == 0 {
+            continue;
+        }
+        let (x, y) = (index as u32 % width, index as u32 / width);
+        min = [min[0].min(x), min[1].min(y)];
+        max = [max[0].max(x), max[1].max(y)];
+    }
+    if min[0] > max[0] {
+        return None;
+    }
+
+    let size = [max[0] - min[0] + 1 + 2 * margin, max[1] - min[1] + 1 + 2 * margin];
+    let mut trimmed = tiny_skia::Pixmap::new(size[0], size[1])?;
+    trimmed.draw_pixmap(
+        margin as i32 - min[0] as i32,
+        margin as i32 - min[1] as i32,
+        pixmap.as_ref(),
+        &tiny_skia::PixmapPaint::default(),
+        tiny_skia::Transform::identity(),
+        None,
+    );
+    Some(trimmed)
+}
+
+pub fn export_figure_png(
+    objects: &[DrawObject],
+    options: &FigureExportOptions,
+    path: &str,
+    latex_renderer: &mut LatexRenderer,
+) -> Result<[u32; 2], Box<dyn std::error::Error>> {
+    let mut objects: Vec<DrawObject> = objects
+        .iter()
+        .filter(|obj| !obj.props().hidden && !matches!(obj, DrawObject::Frame { .. }))
+        .cloned()
+        .collect();
+    if let Some(ink) = options.ink {
+        for obj in &mut objects {
+            recolor_ink(obj, ink);
+        }
+    }
+    let (min, max) = content_bounds(&objects).ok_or("Nothing to export")?;
+    let padding = 20.0;
+    let bounds = ([min[0] - padding, min[1] - padding], [max[0] + padding, max[1] + padding]);
+
+    let pixmap = render_to_pixmap(&objects, bounds, options.scale, None, latex_renderer)?;
+    let trimmed = trim_transparent(&pixmap, options.margin).ok_or("Nothing visible to export")?;
+    trimmed.save_png(path)?;
+    Ok([trimmed.width(), trimmed.height()])
+}
+
+pub struct NamedItem {
+    pub name: String,
+    pub stem: String,
+    pub objects: Vec<DrawObject>,
+    pub bounds: ([f32; 2], [f32; 2]),
+}
+
+pub fn named_items(objects: &[DrawObject], names: &HashMap<Uuid, String>, framing: &ExportFraming, scale: f32) -> Vec<NamedItem> {
+    let mut names: Vec<(&Uuid, &String)> = names.iter().filter(|(_, name)| !name.trim().is_empty()).collect();
+    names.sort_by(|a, b| (a.1.trim(), a.0).cmp(&(b.1.trim(), b.0)));
+    let mut items: Vec<NamedItem> = names
+        .into_iter()
+        .filter_map(|(id, name)| {
+            let frame = objects.iter().find_map(|obj| match obj {
+                DrawObject::Frame { id: frame_id, min, max, .. } if frame_id == id => Some((*min, *max)),
+                _ => None,
+            });
+            let (objects, bounds) = match frame {
+                Some((min, max)) => {
+                    let inside: Vec<DrawObject> = objects
+                        .iter()
+                        .filter(|obj| {
+                            let (omin, omax) = obj.bounds();
+                            omax[0] >= min[0] && omin[0] <= max[0] && omax[1] >= min[1] && omin[1] <= max[1]
+                        })
+                        .cloned()
+                        .collect();
+                    let bounds = framing.frame(&inside, (min, max), scale);
+                    (inside, bounds)
+                }
+                None => {
+                    let members: Vec<DrawObject> = objects.iter().filter(|obj| obj.props().group == Some(*id)).cloned().collect();
+                    let bounds = framing.around(content_bounds(&members)?, scale);
+                    (members, bounds)
+                }
+            };
+            Some(NamedItem { name: name.trim().to_string(), stem: String::new(), objects, bounds })
+        })
+        .collect();
+    let mut used = HashSet::new();
+    for item in &mut items {
+        let base = file_stem(&item.name);
+        let mut stem = base.clone();
+        let mut suffix = 2;
+        while !used.insert(stem.to_lowercase()) {
+            stem = format!("{}-{}", base, suffix);
+            suffix += 1;
+        }
+        item.stem = stem;
+    }
+    items
+}
+
+pub fn is_name_target(objects: &[DrawObject], id: Uuid) -> bool {
+    objects.iter().any(|obj| (obj.id() == id && matches!(obj, DrawObject::Frame { .. })) || obj.props().group == Some(id))
+}
+
+pub fn file_stem(name: &str) -> String {
+    let stem: String = name.chars().map(|c| if c.is_alphanumeric() || matches!(c, '-' | '_' | '.') { c } else { '_' }).collect();
+    match stem.strip_prefix('.') {
+        Some(rest) => format!("_{}", rest),
+        None if stem.is_empty() => "_".to_string(),
+        None => stem,
+    }
+}
+
+pub fn export_named_items(
+    items: &[NamedItem],
+    dir: &str,
+    scale: f32,
+    png: bool,
+    svg: bool,
+    latex_renderer: &mut LatexRenderer,
+) -> Result<usize, Box<dyn std::error::Error>> {
+    if items.is_empty() {
+        return Err("No named frames or groups".into());
+    }
+    std::fs::create_dir_all(dir)?;
+    let mut written = 0;
+    for item in items {
+        if png {
+            let pixmap = render_to_pixmap(&item.objects, item.bounds, scale, None, latex_renderer)?;
+            pixmap.save_png(Path::new(dir).join(format!("{}.png", item.stem)))?;
+            written += 1;
+        }
+        if svg {
+            let document = crate::svg::render_svg(&item.objects, item.bounds, latex_renderer);
+            std::fs::write(Path::new(dir).join(format!("{}.svg", item.stem)), document)?;
+            written += 1;
+        }
+    }
+    Ok(written)
+}
+
+pub struct PdfExportOptions<'a> {
+    pub scale: f32,
+    pub background: [u8; 4],
+    pub header: Option<String>,
+    pub page_numbers: bool,
+    pub metadata: &'a DocumentMetadata,
+}
+
+pub fn export_frames_pdf(
+    objects: &[DrawObject],
+    frame_ids: &[Uuid],
+    options: &PdfExportOptions,
+    path: &str,
+    latex_renderer: &mut LatexRenderer,
+) -> Result<usize, Box<dyn std::error::Error>> {
+    let frames: Vec<([f32; 2], [f32; 2])> = frame_ids
+        .iter()
+        .filter_map(|id| objects.iter().find(|o| o.id() == *id))
+        .filter_map(|obj| match obj {
+            DrawObject::Frame { min, max, .. } => Some((*min, *max)),
+            _ => None,
+        })
+        .collect();
+    if frames.is_empty() {
+        return Err("No frames to export".into());
+    }
+
+    let mut pages = Vec::with_capacity(frames.len());
+    for (index, (min, max)) in frames.iter().enumerate() {
+        let visible: Vec<DrawObject> = objects
+            .iter()
+            .filter(|obj| {
+                let (omin, omax) = obj.bounds();
+                omax[0] >= min[0] && omin[0] <= max[0] && omax[1] >= min[1] && omin[1] <= max[1]
+            })
+            .cloned()
+            .collect();
+        let pixmap = render_to_pixmap(&visible, (*min, *max), options.scale, Some(options.background), latex_renderer)?;
+        pages.push(PdfPage {
+            jpeg: pdf::encode_jpeg(&pixmap, 90)?,
+            pixel_size: [pixmap.width(), pixmap.height()],
+            image_size_pt: [(max[0] - min[0]) * 0.75, (max[1] - min[1]) * 0.75],
+            header: options.header.clone(),
+            footer: options.page_numbers.then(|| format!("{} / {}", index + 1, frames.len())),
+        });
+    }
+
+    pdf::write_pdf(&pages, &options.metadata.title, &options.metadata.author, path)?;
+    Ok(pages.len())
+}
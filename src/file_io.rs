@@ -1,5 +1,9 @@
 use std::fs;
+use std::path::{Path, PathBuf};
 use crate::models::WhiteboardState;
+use crate::session::Session;
+use crate::settings::Settings;
+use crate::templates::Template;
 
 pub fn save_to_file(state: &WhiteboardState, path: &str) -> Result<(), Box<dyn std::error::Error>> {
     let json = serde_json::to_string_pretty(state)?;
@@ -12,3 +16,65 @@ pub fn load_from_file(path: &str) -> Result<WhiteboardState, Box<dyn std::error:
     let state: WhiteboardState = serde_json::from_str(&json)?;
     Ok(state)
 }
+
+fn backup_stem(path: &str) -> String {
+    Path::new(path)
+        .file_stem()
+        .map_or_else(|| "board".to_string(), |stem| stem.to_string_lossy().into_owned())
+}
+
+pub fn has_backup_for_day(path: &str, backup_dir: &str, day: &str) -> bool {
+    let prefix = format!("{}-", backup_stem(path));
+    fs::read_dir(Path::new(backup_dir).join(day)).is_ok_and(|entries| {
+        entries
+            .flatten()
+            .any(|entry| entry.file_name().to_string_lossy().starts_with(&prefix))
+    })
+}
+
+pub fn backup_document(path: &str, backup_dir: &str, day: &str, time: &str) -> Result<PathBuf, Box<dyn std::error::Error>> {
+    let folder = Path::new(backup_dir).join(day);
+    fs::create_dir_all(&folder)?;
+    let extension = Path::new(path)
+        .extension()
+        .map_or_else(|| "json".to_string(), |ext| ext.to_string_lossy().into_owned());
+    let target = folder.join(format!("{}-{}.{}", backup_stem(path), time, extension));
+    fs::copy(path, &target)?;
+    Ok(target)
+}
+
+pub fn save_settings(settings: &Settings, path: &str) -> Result<(), Box<dyn std::error::Error>> {
+    let json = serde_json::to_string_pretty(settings)?;
+    fs::write(path, json)?;
+    Ok(())
+}
+
+pub fn load_settings(path: &str) -> Result<Settings, Box<dyn std::error::Error>> {
+    let json = fs::read_to_string(path)?;
+    let settings: Settings = serde_json::from_str(&json)?;
+    Ok(settings)
+}
+
+pub fn save_templates(templates: &[Template], path: &str) -> Result<(), Box<dyn std::error::Error>> {
+    let json = serde_json::to_string_pretty(templates)?;
+    fs::write(path, json)?;
+    Ok(())
+}
+
+pub fn load_templates(path: &str) -> Result<Vec<Template>, Box<dyn std::error::Error>> {
+    let json = fs::read_to_string(path)?;
+    let templates: Vec<Template> = serde_json::from_str(&json)?;
+    Ok(templates)
+}
+
+pub fn save_session(session: &Session, path: &str) -> Result<(), Box<dyn std::error::Error>> {
+    let json = serde_json::to_string_pretty(session)?;
+    fs::write(path, json)?;
+    Ok(())
+}
+
+pub fn load_session(path: &str) -> Result<Session, Box<dyn std::error::Error>> {
+    let json = fs::read_to_string(path)?;
+    let session: Session = serde_json::from_str(&json)?;
+    Ok(session)
+}
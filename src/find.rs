@@ -0,0 +1,32 @@
+pub fn match_ranges(text: &str, needle: &str, whole_word: bool) -> Vec<(usize, usize)> {
+    if needle.is_empty() {
+        return Vec::new();
+    }
+    text.match_indices(needle)
+        .map(|(start, found)| (start, start + found.len()))
+        .filter(|&(start, end)| {
+            let starts_word = match text[..start].chars().next_back() {
+                Some('\\') => false,
+                Some(c) if c.is_alphabetic() => needle.starts_with('\\'),
+                _ => true,
+            };
+            !whole_word || (starts_word && !text[end..].chars().next().is_some_and(char::is_alphabetic))
+        })
+        .collect()
+}
+
+pub fn replace_matches(text: &str, needle: &str, replacement: &str, whole_word: bool) -> Option<String> {
+    let ranges = match_ranges(text, needle, whole_word);
+    if ranges.is_empty() {
+        return None;
+    }
+    let mut result = String::with_capacity(text.len());
+    let mut last = 0;
+    for (start, end) in ranges {
+        result.push_str(&text[last..start]);
+        result.push_str(replacement);
+        last = end;
+    }
+    result.push_str(&text[last..]);
+    Some(result)
+}
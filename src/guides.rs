@@ -0,0 +1,39 @@
+use crate::models::{Guide, GuideAxis};
+
+pub fn nice_step(min_step: f32) -> f32 {
+    let magnitude = 10f32.powf(min_step.log10().floor());
+    for factor in [1.0, 2.0, 5.0, 10.0] {
+        if magnitude * factor >= min_step {
+            return magnitude * factor;
+        }
+    }
+    magnitude * 10.0
+}
+
+pub fn snap_value(value: f32, guides: &[Guide], axis: GuideAxis, tolerance: f32) -> Option<f32> {
+    guides
+        .iter()
+        .filter(|guide| guide.axis == axis)
+        .map(|guide| guide.position)
+        .filter(|position| (position - value).abs() <= tolerance)
+        .min_by(|a, b| (a - value).abs().total_cmp(&(b - value).abs()))
+}
+
+pub fn snap_bounds_delta(bounds: ([f32; 2], [f32; 2]), delta: [f32; 2], guides: &[Guide], tolerance: f32) -> [f32; 2] {
+    let (min, max) = bounds;
+    let mut snapped = delta;
+    for (index, axis) in [(0, GuideAxis::Vertical), (1, GuideAxis::Horizontal)] {
+        let candidates = [min[index], (min[index] + max[index]) / 2.0, max[index]];
+        let best = candidates
+            .iter()
+            .filter_map(|edge| {
+                let moved = edge + delta[index];
+                snap_value(moved, guides, axis, tolerance).map(|target| target - moved)
+            })
+            .min_by(|a, b| a.abs().total_cmp(&b.abs()));
+        if let Some(adjust) = best {
+            snapped[index] += adjust;
+        }
+    }
+    snapped
+}
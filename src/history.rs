@@ -0,0 +1,316 @@
+use std::collections::HashMap;
+
+use crate::memory;
+use crate::models::{ColorStyle, DrawObject, Layer};
+use crate::selection;
+use uuid::Uuid;
+
+const MAX_HISTORY: usize = 200;
+pub const MIN_UNDO_DEPTH: usize = 20;
+
+#[derive(Debug, Clone)]
+struct ObjectChange {
+    id: Uuid,
+    before: Option<(usize, DrawObject)>,
+    after: Option<(usize, DrawObject)>,
+}
+
+#[derive(Debug, Clone)]
+pub struct Command {
+    author: Uuid,
+    changes: Vec<ObjectChange>,
+    order: Option<(Vec<Uuid>, Vec<Uuid>)>,
+    layers: Option<(Vec<Layer>, Vec<Layer>)>,
+    styles: Option<(Vec<ColorStyle>, Vec<ColorStyle>)>,
+    pages: Vec<Option<Uuid>>,
+}
+
+fn list_change<T: Clone + PartialEq>(before: &[T], after: &[T]) -> Option<(Vec<T>, Vec<T>)> {
+    (before != after).then(|| (before.to_vec(), after.to_vec()))
+}
+
+fn apply_list<T: Clone + PartialEq>(change: &Option<(Vec<T>, Vec<T>)>, list: &mut Vec<T>, forward: bool, skip_conflicts: bool) {
+    if let Some((before, after)) = change {
+        let (expected, target) = if forward { (before, after) } else { (after, before) };
+        if !skip_conflicts || list == expected {
+            *list = target.clone();
+        }
+    }
+}
+
+impl Command {
+    fn diff(before: &[DrawObject], after: &[DrawObject], lists_before: (&[Layer], &[ColorStyle]), lists_after: (&[Layer], &[ColorStyle]), author: Uuid) -> Option<Command> {
+        let before_by_id: HashMap<Uuid, (usize, &DrawObject)> = before.iter().enumerate().map(|(i, obj)| (obj.id(), (i, obj))).collect();
+        let after_by_id: HashMap<Uuid, (usize, &DrawObject)> = after.iter().enumerate().map(|(i, obj)| (obj.id(), (i, obj))).collect();
+
+        let mut changes = Vec::new();
+        let mut pages = Vec::new();
+        for obj in before.iter().chain(after.iter().filter(|obj| !before_by_id.contains_key(&obj.id()))) {
+            let id = obj.id();
+            let old = before_by_id.get(&id).copied();
+            let new = after_by_id.get(&id).copied();
+            if old.map(|(_, obj)| obj) == new.map(|(_, obj)| obj) {
+                continue;
+            }
+            for (objects, side) in [(before, old), (after, new)] {
+                if let Some((_, obj)) = side {
+                    let page = selection::page_of(objects, obj);
+                    if !pages.contains(&page) {
+                        pages.push(page);
+                    }
+                }
+            }
+            changes.push(ObjectChange {
+                id,
+                before: old.map(|(i, obj)| (i, obj.clone())),
+                after: new.map(|(i, obj)| (i, obj.clone())),
+            });
+        }
+
+        let kept_before = before.iter().map(|obj| obj.id()).filter(|id| after_by_id.contains_key(id));
+        let kept_after = after.iter().map(|obj| obj.id()).filter(|id| before_by_id.contains_key(id));
+        let order = if kept_before.eq(kept_after) {
+            None
+        } else {
+            let ids = |objects: &[DrawObject]| objects.iter().map(|obj| obj.id()).collect::<Vec<_>>();
+            for obj in before.iter().chain(after) {
+                let page = selection::page_of(before, obj);
+                if !pages.contains(&page) {
+                    pages.push(page);
+                }
+            }
+            Some((ids(before), ids(after)))
+        };
+
+        let layers = list_change(lists_before.0, lists_after.0);
+        let styles = list_change(lists_before.1, lists_after.1);
+
+        if changes.is_empty() && order.is_none() && layers.is_none() && styles.is_none() {
+            return None;
+        }
+        Some(Command { author, changes, order, layers, styles, pages })
+    }
+
+    fn applies_to(&self, page: Option<Uuid>) -> bool {
+        self.pages.contains(&page) || self.layers.is_some() || self.styles.is_some()
+    }
+
+    fn overlaps(&self, other: &Command) -> bool {
+        self.pages.iter().any(|page| other.pages.contains(page))
+            || ((self.layers.is_some() || self.styles.is_some()) && (other.layers.is_some() || other.styles.is_some()))
+    }
+
+    fn apply_lists(&self, layers: &mut Vec<Layer>, styles: &mut Vec<ColorStyle>, forward: bool, skip_conflicts: bool) {
+        apply_list(&self.layers, layers, forward, skip_conflicts);
+        apply_list(&self.styles, styles, forward, skip_conflicts);
+    }
+
+    fn apply(&self, objects: &mut Vec<DrawObject>, forward: bool, skip_conflicts: bool) {
+        let mut inserts = Vec::new();
+        let mut removed = Vec::new();
+        for change in &self.changes {
+            let (expected, target) = if forward { (&change.before, &change.after) } else { (&change.after, &change.before) };
+            if skip_conflicts && objects.iter().find(|o| o.id() == change.id) != expected.as_ref().map(|(_, obj)| obj) {
+                continue;
+            }
+            match target {
+                Some((index, obj)) => match objects.iter().position(|o| o.id() == change.id) {
+                    Some(position) => objects[position] = obj.clone(),
+                    None => inserts.push((*index, obj)),
+                },
+                None => removed.push(change.id),
+            }
+        }
+        objects.retain(|obj| !removed.contains(&obj.id()));
+        inserts.sort_by_key(|(index, _)| *index);
+        for (index, obj) in inserts {
+            objects.insert(index.min(objects.len()), obj.clone());
+        }
+
+        if let Some((before, after)) = &self.order {
+            let (expected, order) = if forward { (before, after) } else { (after, before) };
+            if skip_conflicts && !objects.iter().map(|obj| obj.id()).eq(expected.iter().copied()) {
+                return;
+            }
+            let rank: HashMap<Uuid, usize> = order.iter().enumerate().map(|(i, id)| (*id, i)).collect();
+            objects.sort_by_key(|obj| rank.get(&obj.id()).copied().unwrap_or(usize::MAX));
+        }
+    }
+
+    fn stamp_modified(&mut self, objects: &mut [DrawObject], time: u64) {
+        for change in &mut self.changes {
+            if let Some((index, obj)) = &mut change.after {
+                obj.props_mut().modified = Some(time);
+                if let Some(current) = objects.get_mut(*index) {
+                    current.props_mut().modified = Some(time);
+                }
+            }
+        }
+    }
+
+    fn objects(&self) -> impl Iterator<Item = &DrawObject> {
+        self.changes
+            .iter()
+            .flat_map(|change| change.before.iter().chain(&change.after))
+            .map(|(_, obj)| obj)
+    }
+}
+
+#[derive(Default)]
+pub struct History {
+    baseline: Vec<DrawObject>,
+    layer_baseline: Vec<Layer>,
+    style_baseline: Vec<ColorStyle>,
+    recording: Option<(u64, Uuid)>,
+    undo: Vec<Command>,
+    redo: Vec<Command>,
+}
+
+impl History {
+    pub fn reset(&mut self, objects: &[DrawObject], layers: &[Layer], styles: &[ColorStyle]) {
+        self.baseline = objects.to_vec();
+        self.layer_baseline = layers.to_vec();
+        self.style_baseline = styles.to_vec();
+        self.recording = None;
+        self.undo.clear();
+        self.redo.clear();
+    }
+
+    pub fn record(&mut self, objects: &mut [DrawObject], layers: &[Layer], styles: &[ColorStyle], time: u64, author: Uuid) {
+        self.settle(objects, layers, styles);
+        self.recording = Some((time, author));
+    }
+
+    fn settle(&mut self, objects: &mut [DrawObject], layers: &[Layer], styles: &[ColorStyle]) {
+        let recording = self.recording.take();
+        let author = recording.map_or(Uuid::nil(), |(_, author)| author);
+        let baseline_lists = (self.layer_baseline.as_slice(), self.style_baseline.as_slice());
+        let Some(mut command) = Command::diff(&self.baseline, objects, baseline_lists, (layers, styles), author) else {
+            return;
+        };
+        if let Some((time, _)) = recording {
+            command.stamp_modified(objects, time);
+        }
+        command.apply(&mut self.baseline, true, false);
+        command.apply_lists(&mut self.layer_baseline, &mut self.style_baseline, true, false);
+        if recording.is_some() {
+            if self.undo.len() >= MAX_HISTORY {
+                self.undo.remove(0);
+            }
+            self.redo.retain(|undone| !undone.overlaps(&command));
+            self.undo.push(command);
+        }
+    }
+
+    pub fn undo(&mut self, objects: &mut Vec<DrawObject>, layers: &mut Vec<Layer>, styles: &mut Vec<ColorStyle>, page: Option<Uuid>, author: Uuid) -> bool {
+        self.settle(objects, layers, styles);
+        let Some(index) = self.undo.iter().rposition(|command| command.author == author && command.applies_to(page)) else {
+            return false;
+        };
+        let latest = index + 1 == self.undo.len();
+        let command = self.undo.remove(index);
+        command.apply(objects, false, !latest);
+        command.apply(&mut self.baseline, false, !latest);
+        command.apply_lists(layers, styles, false, !latest);
+        command.apply_lists(&mut self.layer_baseline, &mut self.style_baseline, false, !latest);
+        self.redo.push(command);
+        true
+    }
+
+    pub fn redo(&mut self, objects: &mut Vec<DrawObject>, layers: &mut Vec<Layer>, styles: &mut Vec<ColorStyle>, page: Option<Uuid>, author: Uuid) -> bool {
+        self.settle(objects, layers, styles);
+        let Some(index) = self.redo.iter().rposition(|command| command.author == author && command.applies_to(page)) else {
+            return false;
+        };
+        let command = self.redo.remove(index);
+        command.apply(objects, true, true);
+        command.apply(&mut self.baseline, true, true);
+        command.apply_lists(layers, styles, true, true);
+        command.apply_lists(&mut self.layer_baseline, &mut self.style_baseline, true, true);
+        self.undo.push(command);
+        true
+    }
+
+    pub fn can_redo(&self, page: Option<Uuid>, author: Uuid) -> bool {
+        self.redo.iter().any(|command| command.author == author && command.applies_to(page))
+    }
+
+    pub fn memory_bytes(&self) -> usize {
+        self.objects().map(memory::object_bytes).sum()
+    }
+
+    pub fn trim(&mut self, excess: usize) -> (usize, usize) {
+        let mut freed = 0;
+        let mut dropped = 0;
+        while freed < excess && self.undo.len() > MIN_UNDO_DEPTH {
+            freed += self.undo.remove(0).objects().map(memory::object_bytes).sum::<usize>();
+            dropped += 1;
+        }
+        (freed, dropped)
+    }
+
+    pub fn objects(&self) -> impl Iterator<Item = &DrawObject> {
+        self.undo.iter().chain(&self.redo).flat_map(Command::objects)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::ObjectProps;
+
+    fn rectangle(id: Uuid) -> DrawObject {
+        DrawObject::Rectangle {
+            id,
+            min: [0.0, 0.0],
+            max: [10.0, 10.0],
+            color: [0, 0, 0, 255],
+            width: 1.0,
+            filled: false,
+            props: ObjectProps::default(),
+        }
+    }
+
+    fn edit(objects: &mut [DrawObject], change: impl FnOnce(&mut [u8; 4], &mut f32)) {
+        if let DrawObject::Rectangle { color, width, .. } = &mut objects[0] {
+            change(color, width);
+        }
+    }
+
+    fn state(objects: &[DrawObject]) -> ([u8; 4], f32) {
+        match &objects[0] {
+            DrawObject::Rectangle { color, width, .. } => (*color, *width),
+            _ => unreachable!(),
+        }
+    }
+
+    #[test]
+    fn undo_and_redo_are_per_author() {
+        let alice = Uuid::from_u128(1);
+        let bob = Uuid::from_u128(2);
+        let mut objects = vec![rectangle(Uuid::from_u128(10))];
+        let (mut layers, mut styles) = (Vec::new(), Vec::new());
+        let mut history = History::default();
+        history.reset(&objects, &layers, &styles);
+
+        history.record(&mut objects, &layers, &styles, 1, alice);
+        edit(&mut objects, |color, _| *color = [255, 0, 0, 255]);
+        history.record(&mut objects, &layers, &styles, 2, bob);
+        edit(&mut objects, |_, width| *width = 5.0);
+
+        assert!(history.undo(&mut objects, &mut layers, &mut styles, None, bob));
+        assert_eq!(state(&objects), ([255, 0, 0, 255], 1.0));
+        assert!(!history.can_redo(None, alice));
+        assert!(!history.redo(&mut objects, &mut layers, &mut styles, None, alice));
+        assert!(history.can_redo(None, bob));
+
+        assert!(history.undo(&mut objects, &mut layers, &mut styles, None, alice));
+        assert_eq!(state(&objects), ([0, 0, 0, 255], 1.0));
+
+        assert!(history.redo(&mut objects, &mut layers, &mut styles, None, bob));
+        assert_eq!(state(&objects), ([0, 0, 0, 255], 1.0), "bob's redo must not replay over alice's undone edit");
+        assert!(history.redo(&mut objects, &mut layers, &mut styles, None, alice));
+        assert_eq!(state(&objects), ([255, 0, 0, 255], 1.0));
+        assert!(!history.can_redo(None, alice));
+    }
+}
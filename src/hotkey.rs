@@ -0,0 +1,77 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+use eframe::egui;
+use global_hotkey::hotkey::HotKey;
+use global_hotkey::{GlobalHotKeyEvent, GlobalHotKeyManager, HotKeyState};
+
+pub const DEFAULT_HOTKEY: &str = "ctrl+shift+space";
+pub const DEFAULT_CLICK_THROUGH_HOTKEY: &str = "ctrl+shift+o";
+pub const SCRATCHPAD_SIZE: [f32; 2] = [520.0, 380.0];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum HotkeyAction {
+    Summon,
+    ClickThrough,
+}
+
+pub struct GlobalHotkeys {
+    manager: GlobalHotKeyManager,
+    registered: HashMap<HotkeyAction, HotKey>,
+    actions: Arc<Mutex<HashMap<u32, HotkeyAction>>>,
+    fired: Arc<Mutex<Vec<HotkeyAction>>>,
+}
+
+impl GlobalHotkeys {
+    pub fn new(ctx: &egui::Context) -> Result<Self, String> {
+        let manager = GlobalHotKeyManager::new().map_err(|e| format!("Global hotkeys are unavailable: {}", e))?;
+        let actions: Arc<Mutex<HashMap<u32, HotkeyAction>>> = Arc::default();
+        let fired: Arc<Mutex<Vec<HotkeyAction>>> = Arc::default();
+        let (lookup, queue, ctx) = (actions.clone(), fired.clone(), ctx.clone());
+        GlobalHotKeyEvent::set_event_handler(Some(move |event: GlobalHotKeyEvent| {
+            if event.state != HotKeyState::Pressed {
+                return;
+            }
+            if let Some(action) = lookup.lock().ok().and_then(|actions| actions.get(&event.id).copied()) {
+                if let Ok(mut queue) = queue.lock() {
+                    queue.push(action);
+                }
+                ctx.request_repaint();
+            }
+        }));
+        Ok(Self { manager, registered: HashMap::new(), actions, fired })
+    }
+
+    pub fn set(&mut self, action: HotkeyAction, text: Option<&str>) -> Result<(), String> {
+        let hotkey = match text {
+            Some(text) => Some(text.trim().parse::<HotKey>().map_err(|e| format!("\"{}\" is not a valid hotkey: {}", text.trim(), e))?),
+            None => None,
+        };
+        if self.registered.get(&action).copied() == hotkey {
+            return Ok(());
+        }
+        if let Some(old) = self.registered.remove(&action) {
+            let _ = self.manager.unregister(old);
+            if let Ok(mut actions) = self.actions.lock() {
+                actions.remove(&old.id());
+            }
+        }
+        let Some(hotkey) = hotkey else {
+            return Ok(());
+        };
+        self.manager.register(hotkey).map_err(|e| format!("Could not register {}: {}", hotkey.into_string(), e))?;
+        self.registered.insert(action, hotkey);
+        if let Ok(mut actions) = self.actions.lock() {
+            actions.insert(hotkey.id(), action);
+        }
+        Ok(())
+    }
+
+    pub fn is_active(&self, action: HotkeyAction) -> bool {
+        self.registered.contains_key(&action)
+    }
+
+    pub fn take_fired(&self) -> Vec<HotkeyAction> {
+        self.fired.lock().map(|mut fired| std::mem::take(&mut *fired)).unwrap_or_default()
+    }
+}
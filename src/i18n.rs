@@ -0,0 +1,82 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU8, Ordering};
+use std::sync::OnceLock;
+
+const GERMAN: &str = include_str!("../locales/de.txt");
+
+static LANGUAGE: AtomicU8 = AtomicU8::new(0);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum Language {
+    #[default]
+    English,
+    German,
+}
+
+impl Language {
+    pub const ALL: [Language; 2] = [Language::English, Language::German];
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            Language::English => "English",
+            Language::German => "Deutsch",
+        }
+    }
+
+    fn catalog(&self) -> Option<&'static HashMap<&'static str, &'static str>> {
+        static GERMAN_CATALOG: OnceLock<HashMap<&'static str, &'static str>> = OnceLock::new();
+        match self {
+            Language::English => None,
+            Language::German => Some(GERMAN_CATALOG.get_or_init(|| parse(GERMAN))),
+        }
+    }
+}
+
+fn parse(source: &'static str) -> HashMap<&'static str, &'static str> {
+    source
+        .lines()
+        .filter(|line| !line.trim().is_empty() && !line.starts_with('#'))
+        .filter_map(|line| line.split_once(" = "))
+        .map(|(english, translated)| (english.trim(), translated.trim()))
+        .collect()
+}
+
+pub fn set_language(language: Language) {
+    LANGUAGE.store(Language::ALL.iter().position(|l| *l == language).unwrap_or(0) as u8, Ordering::Relaxed);
+}
+
+pub fn language() -> Language {
+    Language::ALL.get(LANGUAGE.load(Ordering::Relaxed) as usize).copied().unwrap_or_default()
+}
+
+pub fn tr(text: &str) -> &str {
+    language().catalog().and_then(|catalog| catalog.get(text).copied()).unwrap_or(text)
+}
+
+pub fn trf(template: &str, args: &[&dyn std::fmt::Display]) -> String {
+    let mut out = String::new();
+    let mut parts = tr(template).split("{}");
+    if let Some(first) = parts.next() {
+        out.push_str(first);
+    }
+    for (index, part) in parts.enumerate() {
+        if let Some(arg) = args.get(index) {
+            out.push_str(&arg.to_string());
+        }
+        out.push_str(part);
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn german_keeps_placeholders() {
+        for (english, german) in parse(GERMAN) {
+            assert_eq!(english.matches("{}").count(), german.matches("{}").count(), "{}", english);
+        }
+    }
+}
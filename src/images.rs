@@ -0,0 +1,283 @@
+use eframe::egui;
+use std::collections::{HashMap, HashSet};
+use std::hash::{Hash, Hasher};
+use std::path::PathBuf;
+use uuid::Uuid;
+
+use crate::models::ImageAdjustments;
+
+pub const MAX_INSERT_SIZE: f32 = 800.0;
+const REDUCED_SIDE: u32 = 256;
+
+struct CachedTexture {
+    key: u64,
+    texture: Option<egui::TextureHandle>,
+    reduced: bool,
+    last_used: u64,
+}
+
+pub struct ImageCache {
+    textures: HashMap<Uuid, CachedTexture>,
+    frame: u64,
+    frame_hits: (u32, u32),
+}
+
+fn fingerprint(data: &[u8]) -> u64 {
+    let mut hasher = std::collections::hash_map::DefaultHasher::new();
+    data.len().hash(&mut hasher);
+    for byte in data.iter().step_by(data.len() / 4096 + 1) {
+        byte.hash(&mut hasher);
+    }
+    hasher.finish()
+}
+
+pub fn decode(data: &[u8]) -> Result<image::RgbaImage, String> {
+    image::load_from_memory(data)
+        .map(|img| img.to_rgba8())
+        .map_err(|e| format!("Failed to decode image: {}", e))
+}
+
+pub fn adjust(image: &mut image::RgbaImage, adjustments: &ImageAdjustments) {
+    if adjustments.is_identity() {
+        return;
+    }
+    let white_point = adjustments.white_point.clamp(0.05, 1.0);
+    let contrast = 1.0 + adjustments.contrast;
+    for pixel in image.pixels_mut() {
+        let mut rgb = [pixel[0], pixel[1], pixel[2]].map(|c| c as f32 / 255.0);
+        rgb = rgb.map(|c| (c / white_point).min(1.0));
+        if adjustments.grayscale {
+            let luma = 0.299 * rgb[0] + 0.587 * rgb[1] + 0.114 * rgb[2];
+            rgb = [luma; 3];
+        }
+        rgb = rgb.map(|c| ((c - 0.5) * contrast + 0.5 + adjustments.brightness).clamp(0.0, 1.0));
+        if adjustments.invert {
+            rgb = rgb.map(|c| 1.0 - c);
+        }
+        for (channel, value) in rgb.into_iter().enumerate() {
+            pixel[channel] = (value * 255.0).round() as u8;
+        }
+    }
+}
+
+pub fn dimensions(data: &[u8]) -> Option<[u32; 2]> {
+    image::ImageReader::new(std::io::Cursor::new(data))
+        .with_guessed_format()
+        .ok()?
+        .into_dimensions()
+        .ok()
+        .map(|(w, h)| [w, h])
+}
+
+pub fn insert_size(data: &[u8]) -> Option<[f32; 2]> {
+    let [width, height] = dimensions(data)?;
+    let scale = (MAX_INSERT_SIZE / width.max(height).max(1) as f32).min(1.0);
+    Some([width as f32 * scale, height as f32 * scale])
+}
+
+fn percent_decode(text: &str) -> String {
+    let bytes = text.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let hex = bytes.get(i + 1..i + 3).and_then(|hex| std::str::from_utf8(hex).ok()).and_then(|hex| u8::from_str_radix(hex, 16).ok());
+        match (bytes[i], hex) {
+            (b'%', Some(byte)) => {
+                decoded.push(byte);
+                i += 3;
+            }
+            (byte, _) => {
+                decoded.push(byte);
+                i += 1;
+            }
+        }
+    }
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+fn uri_path(line: &str) -> PathBuf {
+    let Some(rest) = line.strip_prefix("file://") else {
+        return PathBuf::from(line);
+    };
+    let path = percent_decode(rest.strip_prefix("localhost").unwrap_or(rest));
+    let drive = path.strip_prefix('/').filter(|rest| rest.as_bytes().get(1) == Some(&b':'));
+    PathBuf::from(drive.unwrap_or(&path))
+}
+
+pub fn image_paths(text: &str) -> Vec<PathBuf> {
+    text.lines()
+        .map(str::trim)
+        .map(uri_path)
+        .filter(|path| image::ImageFormat::from_path(path).is_ok() && path.is_file())
+        .collect()
+}
+
+pub struct FolderImage {
+    pub name: String,
+    pub data: Vec<u8>,
+}
+
+pub fn folder_images(folder: &str) -> Result<Vec<FolderImage>, Box<dyn std::error::Error>> {
+    let mut paths: Vec<PathBuf> = std::fs::read_dir(folder)?
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|path| path.is_file() && image::ImageFormat::from_path(path).is_ok())
+        .collect();
+    paths.sort();
+    let mut found = Vec::new();
+    for path in paths {
+        let name = path.file_stem().map_or_else(String::new, |stem| stem.to_string_lossy().into_owned());
+        found.push(FolderImage { name, data: std::fs::read(&path)? });
+    }
+    if found.is_empty() {
+        return Err(format!("No images found in {}", folder).into());
+    }
+    Ok(found)
+}
+
+pub fn clipboard_image() -> Result<Vec<u8>, String> {
+    let mut clipboard = arboard::Clipboard::new().map_err(|e| format!("Clipboard unavailable: {}", e))?;
+    let data = clipboard.get_image().map_err(|_| "The clipboard holds no image".to_string())?;
+    let image = image::RgbaImage::from_raw(data.width as u32, data.height as u32, data.bytes.into_owned())
+        .ok_or("Clipboard image has an unexpected size")?;
+    let mut bytes = std::io::Cursor::new(Vec::new());
+    image.write_to(&mut bytes, image::ImageFormat::Png).map_err(|e| format!("Failed to encode image: {}", e))?;
+    Ok(bytes.into_inner())
+}
+
+fn encode_like(original: &[u8], image: &image::RgbaImage) -> Result<Vec<u8>, String> {
+    let mut bytes = std::io::Cursor::new(Vec::new());
+    let result = match image::guess_format(original) {
+        Ok(image::ImageFormat::Jpeg) => image::DynamicImage::ImageRgba8(image.clone())
+            .to_rgb8()
+            .write_to(&mut bytes, image::ImageFormat::Jpeg),
+        _ => image.write_to(&mut bytes, image::ImageFormat::Png),
+    };
+    result.map_err(|e| format!("Failed to encode image: {}", e))?;
+    Ok(bytes.into_inner())
+}
+
+fn pixel_region(image: &image::RgbaImage, from: [f32; 2], to: [f32; 2]) -> (u32, u32, u32, u32) {
+    let (width, height) = image.dimensions();
+    let x0 = (from[0].clamp(0.0, 1.0) * width as f32).round() as u32;
+    let y0 = (from[1].clamp(0.0, 1.0) * height as f32).round() as u32;
+    let x1 = (to[0].clamp(0.0, 1.0) * width as f32).round() as u32;
+    let y1 = (to[1].clamp(0.0, 1.0) * height as f32).round() as u32;
+    (x0, y0, x1.saturating_sub(x0).max(1), y1.saturating_sub(y0).max(1))
+}
+
+pub fn crop(data: &[u8], from: [f32; 2], to: [f32; 2]) -> Result<Vec<u8>, String> {
+    let image = decode(data)?;
+    let (x, y, width, height) = pixel_region(&image, from, to);
+    let cropped = image::imageops::crop_imm(&image, x, y, width, height).to_image();
+    encode_like(data, &cropped)
+}
+
+pub fn split(data: &[u8], columns: u32, rows: u32) -> Result<Vec<Vec<u8>>, String> {
+    let image = decode(data)?;
+    let mut tiles = Vec::new();
+    for row in 0..rows {
+        for column in 0..columns {
+            let from = [column as f32 / columns as f32, row as f32 / rows as f32];
+            let to = [(column + 1) as f32 / columns as f32, (row + 1) as f32 / rows as f32];
+            let (x, y, width, height) = pixel_region(&image, from, to);
+            let tile = image::imageops::crop_imm(&image, x, y, width, height).to_image();
+            tiles.push(encode_like(data, &tile)?);
+        }
+    }
+    Ok(tiles)
+}
+
+impl ImageCache {
+    pub fn new() -> Self {
+        Self {
+            textures: HashMap::new(),
+            frame: 0,
+            frame_hits: (0, 0),
+        }
+    }
+
+    pub fn next_frame(&mut self) {
+        self.frame += 1;
+        self.frame_hits = (0, 0);
+    }
+
+    pub fn frame_hits(&self) -> (u32, u32) {
+        self.frame_hits
+    }
+
+    pub fn get_or_create_texture(&mut self, ctx: &egui::Context, id: Uuid, data: &[u8], adjustments: &ImageAdjustments) -> Option<egui::TextureHandle> {
+        let mut hasher = std::collections::hash_map::DefaultHasher::new();
+        fingerprint(data).hash(&mut hasher);
+        adjustments.invert.hash(&mut hasher);
+        adjustments.grayscale.hash(&mut hasher);
+        for value in [adjustments.brightness, adjustments.contrast, adjustments.white_point] {
+            value.to_bits().hash(&mut hasher);
+        }
+        let key = hasher.finish();
+        if let Some(cached) = self.textures.get_mut(&id) {
+            if cached.key == key && !cached.reduced {
+                cached.last_used = self.frame;
+                self.frame_hits.0 += 1;
+                return cached.texture.clone();
+            }
+        }
+        self.frame_hits.1 += 1;
+
+        let texture = match decode(data) {
+            Ok(mut rgba) => {
+                adjust(&mut rgba, adjustments);
+                let size = [rgba.width() as usize, rgba.height() as usize];
+                let image = egui::ColorImage::from_rgba_unmultiplied(size, rgba.as_raw());
+                Some(ctx.load_texture(format!("image_{}", id), image, egui::TextureOptions::LINEAR))
+            }
+            Err(e) => {
+                eprintln!("Error loading image {}: {}", id, e);
+                None
+            }
+        };
+
+        self.textures.insert(id, CachedTexture { key, texture: texture.clone(), reduced: false, last_used: self.frame });
+        texture
+    }
+
+    pub fn memory_bytes(&self) -> usize {
+        self.textures.values().filter_map(|cached| cached.texture.as_ref()).map(egui::TextureHandle::byte_size).sum()
+    }
+
+    pub fn downsample<'a>(&mut self, excess: usize, source: impl Fn(Uuid) -> Option<(&'a [u8], &'a ImageAdjustments)>) -> usize {
+        let mut offscreen: Vec<(u64, Uuid)> = self.textures
+            .iter()
+            .filter(|(_, cached)| !cached.reduced && cached.last_used < self.frame && cached.texture.is_some())
+            .map(|(id, cached)| (cached.last_used, *id))
+            .collect();
+        offscreen.sort_unstable();
+        let mut freed = 0;
+        for (_, id) in offscreen {
+            if freed >= excess {
+                break;
+            }
+            let (Some(cached), Some((data, adjustments))) = (self.textures.get_mut(&id), source(id)) else {
+                continue;
+            };
+            let Some(texture) = cached.texture.as_mut() else {
+                continue;
+            };
+            let Ok(mut rgba) = decode(data) else {
+                continue;
+            };
+            adjust(&mut rgba, adjustments);
+            let ratio = (REDUCED_SIDE as f32 / rgba.width().max(rgba.height()) as f32).min(1.0);
+            let small = image::imageops::thumbnail(&rgba, ((rgba.width() as f32 * ratio) as u32).max(1), ((rgba.height() as f32 * ratio) as u32).max(1));
+            let before = texture.byte_size();
+            let size = [small.width() as usize, small.height() as usize];
+            texture.set(egui::ColorImage::from_rgba_unmultiplied(size, small.as_raw()), egui::TextureOptions::LINEAR);
+            freed += before.saturating_sub(texture.byte_size());
+            cached.reduced = true;
+        }
+        freed
+    }
+
+    pub fn retain(&mut self, live: &HashSet<Uuid>) {
+        self.textures.retain(|id, _| live.contains(id));
+    }
+}
@@ -1,10 +1,171 @@
 use eframe::egui;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
+use std::sync::mpsc::{self, Receiver, Sender};
 use std::sync::Arc;
 
+pub const DISPLAY_SCALE: f32 = 3.0;
+const TEXT_FONT_SIZE: f32 = 12.0;
+
+struct Job {
+    generation: u64,
+    key: String,
+    source: String,
+    font: Option<String>,
+    color: [u8; 4],
+    scale_factor: Option<f32>,
+    font_db: Option<Arc<usvg::fontdb::Database>>,
+    reply: Sender<Finished>,
+    ctx: Option<egui::Context>,
+}
+
+struct Rendered {
+    size: [f32; 2],
+    image: Option<Arc<egui::ColorImage>>,
+}
+
+struct Finished {
+    generation: u64,
+    key: String,
+    result: Result<Rendered, String>,
+}
+
 pub struct LatexRenderer {
     cache: HashMap<String, Arc<egui::ColorImage>>,
     textures: HashMap<String, egui::TextureHandle>,
+    sizes: HashMap<String, [f32; 2]>,
+    scale_factor: f32,
+    font_db: Option<Arc<usvg::fontdb::Database>>,
+    font_families: Option<Vec<String>>,
+    worker: Option<Sender<Job>>,
+    blocking_worker: Option<Sender<Job>>,
+    finished: (Sender<Finished>, Receiver<Finished>),
+    pending: HashSet<String>,
+    failed: HashSet<String>,
+    generation: u64,
+    last_used: HashMap<String, u64>,
+    frame: u64,
+    frame_hits: (u32, u32),
+}
+
+fn escape_xml(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&apos;")
+}
+
+fn text_svg(text: &str, family: &str, color: [u8; 4], width: f32) -> String {
+    format!(
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{w}\" height=\"{h}\" viewBox=\"0 0 {w} {h}\">\
+         <text x=\"0\" y=\"{baseline}\" font-family=\"{family}\" font-size=\"{size}\" fill=\"#{r:02X}{g:02X}{b:02X}\" xml:space=\"preserve\">{text}</text></svg>",
+        w = width,
+        h = TEXT_FONT_SIZE * 1.4,
+        baseline = TEXT_FONT_SIZE * 1.1,
+        family = escape_xml(family),
+        size = TEXT_FONT_SIZE,
+        r = color[0],
+        g = color[1],
+        b = color[2],
+        text = escape_xml(text),
+    )
+}
+
+fn parse_svg(formula: &str, color: [u8; 4]) -> Result<usvg::Tree, String> {
+    let mut svg_string = match mathjax_svg::convert_to_svg(formula) {
+        Ok(svg) => svg,
+        Err(e) => return Err(format!("Failed to render LaTeX: {}", e)),
+    };
+    
+    let color_hex = format!("#{:02X}{:02X}{:02X}", color[0], color[1], color[2]);
+    svg_string = svg_string.replace("currentColor", &color_hex);
+    svg_string = svg_string.replace("fill=\"#000\"", &format!("fill=\"{}\"", color_hex));
+    svg_string = svg_string.replace("fill=\"#000000\"", &format!("fill=\"{}\"", color_hex));
+    svg_string = svg_string.replace("fill=\"black\"", &format!("fill=\"{}\"", color_hex));
+    svg_string = svg_string.replace("stroke=\"#000\"", &format!("stroke=\"{}\"", color_hex));
+    svg_string = svg_string.replace("stroke=\"#000000\"", &format!("stroke=\"{}\"", color_hex));
+    svg_string = svg_string.replace("stroke=\"black\"", &format!("stroke=\"{}\"", color_hex));
+
+    let opt = usvg::Options::default();
+    usvg::Tree::from_str(&svg_string, &opt).map_err(|e| format!("Failed to parse SVG: {}", e))
+}
+
+fn parse(source: &str, font: Option<&str>, color: [u8; 4], font_db: Option<Arc<usvg::fontdb::Database>>) -> Result<usvg::Tree, String> {
+    let (Some(family), Some(fontdb)) = (font, font_db) else {
+        return parse_svg(source, color);
+    };
+    let options = usvg::Options {
+        fontdb,
+        ..Default::default()
+    };
+    let parse = |width: f32| {
+        usvg::Tree::from_str(&text_svg(source, family, color, width), &options)
+            .map_err(|e| format!("Failed to lay out text: {}", e))
+    };
+    let draft = parse((source.chars().count() as f32 + 1.0) * TEXT_FONT_SIZE * 1.5)?;
+    let right = draft.root().abs_bounding_box().right();
+    if right <= 0.0 {
+        return Err(format!("Font \"{}\" has no glyphs for this text", family));
+    }
+    parse(right.ceil() + 1.0)
+}
+
+fn rasterize(tree: &usvg::Tree, scale_factor: f32) -> Result<egui::ColorImage, String> {
+    let size = tree.size();
+    let width = (size.width() * scale_factor) as u32;
+    let height = (size.height() * scale_factor) as u32;
+
+    if width == 0 || height == 0 {
+        return Err("Invalid image dimensions".to_string());
+    }
+
+    let mut pixmap = tiny_skia::Pixmap::new(width, height)
+        .ok_or("Failed to create pixmap")?;
+
+    let transform = tiny_skia::Transform::from_scale(scale_factor, scale_factor);
+    resvg::render(tree, transform, &mut pixmap.as_mut());
+
+    let mut image_data = Vec::with_capacity((width * height) as usize);
+    for pixel in pixmap.pixels() {
+        image_data.push(egui::Color32::from_rgba_premultiplied(
+            pixel.red(),
+            pixel.green(),
+            pixel.blue(),
+            pixel.alpha(),
+        ));
+    }
+
+    Ok(egui::ColorImage {
+        size: [width as usize, height as usize],
+        pixels: image_data,
+    })
+}
+
+fn run_job(job: &Job) -> Result<Rendered, String> {
+    let tree = parse(&job.source, job.font.as_deref(), job.color, job.font_db.clone())?;
+    let size = tree.size();
+    let image = match job.scale_factor {
+        Some(scale_factor) => Some(Arc::new(rasterize(&tree, scale_factor)?)),
+        None => None,
+    };
+    Ok(Rendered {
+        size: [size.width() * DISPLAY_SCALE, size.height() * DISPLAY_SCALE],
+        image,
+    })
+}
+
+fn spawn_worker() -> Sender<Job> {
+    let (sender, receiver) = mpsc::channel::<Job>();
+    std::thread::spawn(move || {
+        for job in receiver {
+            let result = run_job(&job);
+            let _ = job.reply.send(Finished { generation: job.generation, key: job.key, result });
+            if let Some(ctx) = job.ctx {
+                ctx.request_repaint();
+            }
+        }
+    });
+    sender
 }
 
 impl LatexRenderer {
@@ -12,87 +173,308 @@ impl LatexRenderer {
         Self {
             cache: HashMap::new(),
             textures: HashMap::new(),
+            sizes: HashMap::new(),
+            scale_factor: DISPLAY_SCALE,
+            font_db: None,
+            font_families: None,
+            worker: None,
+            blocking_worker: None,
+            finished: mpsc::channel(),
+            pending: HashSet::new(),
+            failed: HashSet::new(),
+            generation: 0,
+            last_used: HashMap::new(),
+            frame: 0,
+            frame_hits: (0, 0),
         }
     }
 
-    pub fn render_to_image(&mut self, formula: &str, color: [u8; 4]) -> Result<Arc<egui::ColorImage>, String> {
-        let cache_key = format!("{}_{}_{}_{}", formula, color[0], color[1], color[2]);
-        
-        if let Some(cached) = self.cache.get(&cache_key) {
-            return Ok(cached.clone());
+    pub fn next_frame(&mut self) {
+        self.frame += 1;
+        self.frame_hits = (0, 0);
+        self.collect_finished();
+    }
+
+    pub fn frame_hits(&self) -> (u32, u32) {
+        self.frame_hits
+    }
+
+    pub fn memory_bytes(&self) -> usize {
+        let images: usize = self.cache.values().map(|image| image.pixels.len() * 4).sum();
+        let textures: usize = self.textures.values().map(egui::TextureHandle::byte_size).sum();
+        images + textures
+    }
+
+    pub fn trim(&mut self, excess: usize) -> usize {
+        let mut stale: Vec<(u64, String)> = self.cache
+            .keys()
+            .chain(self.textures.keys().filter(|key| !self.cache.contains_key(*key)))
+            .map(|key| (self.last_used.get(key).copied().unwrap_or(0), key.clone()))
+            .filter(|(used, _)| *used < self.frame)
+            .collect();
+        stale.sort_unstable();
+        let mut freed = 0;
+        for (_, key) in stale {
+            if freed >= excess {
+                break;
+            }
+            freed += self.cache.remove(&key).map_or(0, |image| image.pixels.len() * 4);
+            freed += self.textures.remove(&key).map_or(0, |texture| texture.byte_size());
+            self.last_used.remove(&key);
         }
+        freed
+    }
 
-        let mut svg_string = match mathjax_svg::convert_to_svg(formula) {
-            Ok(svg) => svg,
-            Err(e) => return Err(format!("Failed to render LaTeX: {}", e)),
-        };
-        
-        let color_hex = format!("#{:02X}{:02X}{:02X}", color[0], color[1], color[2]);
-        svg_string = svg_string.replace("currentColor", &color_hex);
-        svg_string = svg_string.replace("fill=\"#000\"", &format!("fill=\"{}\"", color_hex));
-        svg_string = svg_string.replace("fill=\"#000000\"", &format!("fill=\"{}\"", color_hex));
-        svg_string = svg_string.replace("fill=\"black\"", &format!("fill=\"{}\"", color_hex));
-        svg_string = svg_string.replace("stroke=\"#000\"", &format!("stroke=\"{}\"", color_hex));
-        svg_string = svg_string.replace("stroke=\"#000000\"", &format!("stroke=\"{}\"", color_hex));
-        svg_string = svg_string.replace("stroke=\"black\"", &format!("stroke=\"{}\"", color_hex));
-
-        let opt = usvg::Options::default();
-        let tree = match usvg::Tree::from_str(&svg_string, &opt) {
-            Ok(tree) => tree,
-            Err(e) => return Err(format!("Failed to parse SVG: {}", e)),
+    fn invalidate(&mut self) {
+        self.generation += 1;
+        self.cache.clear();
+        self.textures.clear();
+        self.last_used.clear();
+        self.failed.clear();
+    }
+
+    fn font_db(&mut self) -> Arc<usvg::fontdb::Database> {
+        self.font_db
+            .get_or_insert_with(|| {
+                let mut db = usvg::fontdb::Database::new();
+                db.load_system_fonts();
+                let fallback = db.faces().find_map(|face| face.families.first().map(|(name, _)| name.clone()));
+                if let Some(fallback) = fallback {
+                    let generics = [usvg::fontdb::Family::Serif, usvg::fontdb::Family::SansSerif];
+                    for generic in generics {
+                        let query = usvg::fontdb::Query { families: &[generic], ..Default::default() };
+                        if db.query(&query).is_none() {
+                            match generic {
+                                usvg::fontdb::Family::Serif => db.set_serif_family(fallback.clone()),
+                                _ => db.set_sans_serif_family(fallback.clone()),
+                            }
+                        }
+                    }
+                }
+                Arc::new(db)
+            })
+            .clone()
+    }
+
+    pub fn font_families(&mut self) -> &[String] {
+        if self.font_families.is_none() {
+            let db = self.font_db();
+            let mut families: Vec<String> = db
+                .faces()
+                .filter_map(|face| face.families.first().map(|(name, _)| name.clone()))
+                .collect();
+            families.sort();
+            families.dedup();
+            self.font_families = Some(families);
+        }
+        self.font_families.as_deref().unwrap_or_default()
+    }
+
+    pub fn add_font_file(&mut self, path: &str) -> Result<Vec<String>, String> {
+        self.font_db();
+        let Some(db) = self.font_db.as_mut() else {
+            return Err("Font database unavailable".to_string());
         };
+        let before = db.len();
+        Arc::make_mut(db)
+            .load_font_file(path)
+            .map_err(|e| format!("Failed to load font {}: {}", path, e))?;
+        let mut added: Vec<String> = db
+            .faces()
+            .skip(before)
+            .filter_map(|face| face.families.first().map(|(name, _)| name.clone()))
+            .collect();
+        added.dedup();
+        if added.is_empty() {
+            return Err(format!("No usable font faces in {}", path));
+        }
+        self.font_families = None;
+        self.invalidate();
+        self.sizes.clear();
+        Ok(added)
+    }
 
-        let size = tree.size();
-        let scale_factor = 3.0;
-        let width = (size.width() * scale_factor) as u32;
-        let height = (size.height() * scale_factor) as u32;
+    fn job(&mut self, key: String, source: &str, font: Option<&str>, color: [u8; 4], scale_factor: Option<f32>) -> Job {
+        Job {
+            generation: self.generation,
+            key,
+            source: source.to_string(),
+            font: font.map(str::to_string),
+            color,
+            scale_factor,
+            font_db: font.map(|_| self.font_db()),
+            reply: self.finished.0.clone(),
+            ctx: None,
+        }
+    }
 
-        if width == 0 || height == 0 {
-            return Err("Invalid image dimensions".to_string());
+    fn submit(&mut self, job: Job) -> Result<(), String> {
+        self.worker
+            .get_or_insert_with(spawn_worker)
+            .send(job)
+            .map_err(|_| "LaTeX worker stopped".to_string())
+    }
+
+    fn run_blocking(&mut self, mut job: Job) -> Result<Rendered, String> {
+        let (reply, receiver) = mpsc::channel();
+        job.reply = reply;
+        self.blocking_worker
+            .get_or_insert_with(spawn_worker)
+            .send(job)
+            .map_err(|_| "LaTeX worker stopped".to_string())?;
+        receiver
+            .recv()
+            .map_err(|_| "LaTeX worker stopped".to_string())?
+            .result
+    }
+
+    fn collect_finished(&mut self) {
+        while let Ok(finished) = self.finished.1.try_recv() {
+            self.pending.remove(&finished.key);
+            if finished.generation != self.generation {
+                continue;
+            }
+            match finished.result {
+                Ok(Rendered { image: Some(image), .. }) => {
+                    self.cache.insert(finished.key, image);
+                }
+                Ok(Rendered { image: None, size }) => {
+                    self.sizes.insert(finished.key, size);
+                }
+                Err(e) => {
+                    eprintln!("Error rendering LaTeX: {}", e);
+                    self.failed.insert(finished.key);
+                }
+            }
         }
+    }
 
-        let mut pixmap = tiny_skia::Pixmap::new(width, height)
-            .ok_or("Failed to create pixmap")?;
+    pub fn is_pending(&self, formula: &str, font: Option<&str>, color: [u8; 4]) -> bool {
+        self.pending.contains(&self.cache_key(formula, font, color))
+    }
 
-        let transform = tiny_skia::Transform::from_scale(scale_factor, scale_factor);
-        resvg::render(&tree, transform, &mut pixmap.as_mut());
+    pub fn pending_count(&self) -> usize {
+        self.pending.len()
+    }
 
-        let mut image_data = Vec::with_capacity((width * height) as usize);
-        for pixel in pixmap.pixels() {
-            image_data.push(egui::Color32::from_rgba_premultiplied(
-                pixel.red(),
-                pixel.green(),
-                pixel.blue(),
-                pixel.alpha(),
-            ));
+    pub fn set_scale_factor(&mut self, scale_factor: f32) {
+        if self.scale_factor != scale_factor {
+            self.scale_factor = scale_factor;
+            self.invalidate();
         }
+    }
 
-        let color_image = Arc::new(egui::ColorImage {
-            size: [width as usize, height as usize],
-            pixels: image_data,
-        });
+    pub fn display_size(&self, image_size: [usize; 2]) -> [f32; 2] {
+        let ratio = DISPLAY_SCALE / self.scale_factor;
+        [image_size[0] as f32 * ratio, image_size[1] as f32 * ratio]
+    }
 
+    fn cache_key(&self, formula: &str, font: Option<&str>, color: [u8; 4]) -> String {
+        format!("{}_{}_{}_{}@{}:{}", formula, color[0], color[1], color[2], self.scale_factor, font.unwrap_or("latex"))
+    }
+
+    pub fn render_to_image(&mut self, formula: &str, font: Option<&str>, color: [u8; 4]) -> Result<Arc<egui::ColorImage>, String> {
+        let cache_key = self.cache_key(formula, font, color);
+        
+        self.collect_finished();
+        if let Some(cached) = self.cache.get(&cache_key) {
+            return Ok(cached.clone());
+        }
+
+        let job = self.job(cache_key.clone(), formula, font, color, Some(self.scale_factor));
+        let color_image = self.run_blocking(job)?.image.ok_or("Formula was not rasterized")?;
         self.cache.insert(cache_key, color_image.clone());
         Ok(color_image)
     }
 
-    pub fn get_or_create_texture(&mut self, ctx: &egui::Context, formula: &str, color: [u8; 4]) -> Option<egui::TextureHandle> {
-        let texture_key = format!("{}_{}_{}_{}", formula, color[0], color[1], color[2]);
-        
-        if let Some(texture) = self.textures.get(&texture_key) {
-            return Some(texture.clone());
+    pub fn vector_svg(&mut self, formula: &str, font: Option<&str>, color: [u8; 4], id_prefix: &str) -> Result<(String, [f32; 2]), String> {
+        let font_db = font.map(|_| self.font_db());
+        let tree = parse(formula, font, color, font_db)?;
+        let options = usvg::WriteOptions {
+            id_prefix: Some(id_prefix.to_string()),
+            ..Default::default()
+        };
+        let size = tree.size();
+        Ok((tree.to_string(&options), [size.width(), size.height()]))
+    }
+
+    fn size_key(formula: &str, font: Option<&str>) -> String {
+        format!("{}:{}", font.unwrap_or("latex"), formula)
+    }
+
+    pub fn is_measuring(&self, formula: &str, font: Option<&str>) -> bool {
+        self.pending.contains(&Self::size_key(formula, font))
+    }
+
+    pub fn request_size(&mut self, ctx: &egui::Context, formula: &str, font: Option<&str>) -> Option<[f32; 2]> {
+        if formula.is_empty() {
+            return None;
+        }
+        let size_key = Self::size_key(formula, font);
+        if let Some(size) = self.sizes.get(&size_key) {
+            return Some(*size);
         }
+        if !self.pending.contains(&size_key) && !self.failed.contains(&size_key) {
+            let mut job = self.job(size_key.clone(), formula, font, [0, 0, 0, 255], None);
+            job.ctx = Some(ctx.clone());
+            match self.submit(job) {
+                Ok(()) => {
+                    self.pending.insert(size_key);
+                }
+                Err(e) => eprintln!("Error measuring LaTeX '{}': {}", formula, e),
+            }
+        }
+        None
+    }
 
-        let image = match self.render_to_image(formula, color) {
-            Ok(img) => img,
+    pub fn measure(&mut self, formula: &str, font: Option<&str>) -> Option<[f32; 2]> {
+        if formula.is_empty() {
+            return None;
+        }
+        let size_key = Self::size_key(formula, font);
+        if let Some(size) = self.sizes.get(&size_key) {
+            return Some(*size);
+        }
+
+        let job = self.job(size_key.clone(), formula, font, [0, 0, 0, 255], None);
+        let size = match self.run_blocking(job) {
+            Ok(rendered) => rendered.size,
             Err(e) => {
-                eprintln!("Error rendering LaTeX '{}': {}", formula, e);
+                eprintln!("Error measuring LaTeX '{}': {}", formula, e);
                 return None;
             }
         };
+        self.sizes.insert(size_key, size);
+        Some(size)
+    }
+
+    pub fn get_or_create_texture(&mut self, ctx: &egui::Context, formula: &str, font: Option<&str>, color: [u8; 4]) -> Option<egui::TextureHandle> {
+        let texture_key = self.cache_key(formula, font, color);
+        self.last_used.insert(texture_key.clone(), self.frame);
+
+        if let Some(texture) = self.textures.get(&texture_key) {
+            self.frame_hits.0 += 1;
+            return Some(texture.clone());
+        }
+        self.frame_hits.1 += 1;
+
+        self.collect_finished();
+        let Some(image) = self.cache.get(&texture_key).cloned() else {
+            if !self.pending.contains(&texture_key) && !self.failed.contains(&texture_key) {
+                let mut job = self.job(texture_key.clone(), formula, font, color, Some(self.scale_factor));
+                job.ctx = Some(ctx.clone());
+                match self.submit(job) {
+                    Ok(()) => {
+                        self.pending.insert(texture_key);
+                    }
+                    Err(e) => eprintln!("Error rendering LaTeX '{}': {}", formula, e),
+                }
+            }
+            return None;
+        };
 
         let texture = ctx.load_texture(
-            format!("latex_{}_{}_{}_{}", formula, color[0], color[1], color[2]),
+            format!("latex_{}", texture_key),
             image.as_ref().clone(),
             egui::TextureOptions::LINEAR,
         );
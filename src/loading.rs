@@ -0,0 +1,104 @@
+use std::fs;
+use std::io::{BufReader, Read};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::mpsc::{self, Receiver, TryRecvError};
+use std::sync::Arc;
+
+use crate::models::{self, DrawObject, WhiteboardState};
+
+pub const LARGE_DOCUMENT_BYTES: u64 = 2 * 1024 * 1024;
+pub const OBJECTS_PER_FRAME: usize = 2000;
+
+struct CountingReader<R> {
+    inner: R,
+    count: Arc<AtomicU64>,
+}
+
+impl<R: Read> Read for CountingReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let read = self.inner.read(buf)?;
+        self.count.fetch_add(read as u64, Ordering::Relaxed);
+        Ok(read)
+    }
+}
+
+pub struct DocumentLoad {
+    pub path: String,
+    total_bytes: u64,
+    read_bytes: Arc<AtomicU64>,
+    receiver: Receiver<Result<WhiteboardState, String>>,
+}
+
+impl DocumentLoad {
+    pub fn start(path: &str) -> Result<Self, Box<dyn std::error::Error>> {
+        let file = fs::File::open(path)?;
+        let total_bytes = file.metadata()?.len();
+        let read_bytes = Arc::new(AtomicU64::new(0));
+        let reader = CountingReader { inner: file, count: read_bytes.clone() };
+        let (sender, receiver) = mpsc::channel();
+        std::thread::spawn(move || {
+            let result = serde_json::from_reader(BufReader::new(reader)).map_err(|e| e.to_string());
+            let _ = sender.send(result);
+        });
+        Ok(Self { path: path.to_string(), total_bytes, read_bytes, receiver })
+    }
+
+    pub fn fraction(&self) -> f32 {
+        if self.total_bytes == 0 {
+            return 0.0;
+        }
+        (self.read_bytes.load(Ordering::Relaxed) as f64 / self.total_bytes as f64).min(1.0) as f32
+    }
+
+    pub fn poll(&self) -> Option<Result<WhiteboardState, String>> {
+        match self.receiver.try_recv() {
+            Ok(result) => Some(result),
+            Err(TryRecvError::Empty) => None,
+            Err(TryRecvError::Disconnected) => Some(Err("Loading stopped unexpectedly".to_string())),
+        }
+    }
+}
+
+pub struct ObjectFeed {
+    pending: Vec<(usize, DrawObject)>,
+    order: Vec<usize>,
+    pub total: usize,
+}
+
+impl ObjectFeed {
+    pub fn new(objects: Vec<DrawObject>, focus: [f32; 2]) -> Self {
+        let mut keyed: Vec<(f32, usize, DrawObject)> = objects
+            .into_iter()
+            .enumerate()
+            .map(|(index, obj)| {
+                let (min, max) = obj.bounds();
+                let distance = models::distance([(min[0] + max[0]) / 2.0, (min[1] + max[1]) / 2.0], focus);
+                (if distance.is_finite() { distance } else { f32::MAX }, index, obj)
+            })
+            .collect();
+        keyed.sort_by(|a, b| b.0.total_cmp(&a.0));
+        let pending: Vec<(usize, DrawObject)> = keyed.into_iter().map(|(_, index, obj)| (index, obj)).collect();
+        Self { total: pending.len(), pending, order: Vec::new() }
+    }
+
+    pub fn loaded(&self) -> usize {
+        self.total - self.pending.len()
+    }
+
+    pub fn is_done(&self) -> bool {
+        self.pending.is_empty()
+    }
+
+    pub fn feed(&mut self, objects: &mut Vec<DrawObject>, count: usize) {
+        let batch = self.pending.split_off(self.pending.len().saturating_sub(count));
+        for (index, obj) in batch.into_iter().rev() {
+            self.order.push(index);
+            objects.push(obj);
+        }
+        if self.is_done() {
+            let mut placed: Vec<(usize, DrawObject)> = std::mem::take(&mut self.order).into_iter().zip(std::mem::take(objects)).collect();
+            placed.sort_unstable_by_key(|(index, _)| *index);
+            objects.extend(placed.into_iter().map(|(_, obj)| obj));
+        }
+    }
+}
@@ -5,13 +5,40 @@ mod canvas;
 mod latex;
 mod selection;
 mod file_io;
+mod settings;
+mod palette;
+mod clock;
+mod export;
+mod pdf;
+mod svg;
+mod pdf_import;
+mod images;
+mod guides;
+mod symbols;
+mod find;
+mod ocr;
+mod session;
+mod history;
+mod spatial;
+mod clipboard;
+mod templates;
+mod recipes;
+mod hotkey;
+mod loading;
+mod memory;
+mod profiler;
+mod ops;
+mod i18n;
 mod app;
 
 use app::WhiteboardApp;
 
 fn main() -> Result<(), eframe::Error> {
+    let mut app = WhiteboardApp::new();
+    let viewport = app.restore_session(egui::ViewportBuilder::default().with_inner_size([1280.0, 720.0]).with_transparent(true));
+    app.apply_launch_args(std::env::args().skip(1));
     let options = eframe::NativeOptions {
-        viewport: egui::ViewportBuilder::default().with_inner_size([1280.0, 720.0]),
+        viewport,
         ..Default::default()
     };
     
@@ -20,7 +47,7 @@ fn main() -> Result<(), eframe::Error> {
         options,
         Box::new(|cc| {
             egui_extras::install_image_loaders(&cc.egui_ctx);
-            Ok(Box::new(WhiteboardApp::default()))
+            Ok(Box::new(app))
         }),
     )
 }
@@ -0,0 +1,42 @@
+use crate::models::{DrawObject, StrokePoint};
+
+pub const MEGABYTE: usize = 1024 * 1024;
+pub const DEFAULT_BUDGET_MB: u32 = 512;
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct MemoryUsage {
+    pub latex: usize,
+    pub images: usize,
+    pub history: usize,
+    pub document: usize,
+}
+
+impl MemoryUsage {
+    pub fn trimmable(&self) -> usize {
+        self.latex + self.images + self.history
+    }
+
+    pub fn total(&self) -> usize {
+        self.trimmable() + self.document
+    }
+}
+
+pub fn object_bytes(obj: &DrawObject) -> usize {
+    std::mem::size_of::<DrawObject>()
+        + match obj {
+            DrawObject::Stroke { points, .. } => points.len() * std::mem::size_of::<StrokePoint>(),
+            DrawObject::Image { data, .. } => data.len(),
+            DrawObject::LatexFormula { formula, .. } => formula.len(),
+            DrawObject::ScaleBar { label, .. } => label.len(),
+            DrawObject::Instance { members, .. } => members.iter().map(object_bytes).sum(),
+            _ => 0,
+        }
+}
+
+pub fn format_bytes(bytes: usize) -> String {
+    if bytes >= MEGABYTE {
+        format!("{:.1} MB", bytes as f64 / MEGABYTE as f64)
+    } else {
+        format!("{:.0} KB", bytes as f64 / 1024.0)
+    }
+}
@@ -1,8 +1,12 @@
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 use uuid::Uuid;
 
-#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+use crate::session::Viewport;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub enum Tool {
+    #[serde(alias = "Shading")]
     Brush,
     Line,
     Circle,
@@ -10,6 +14,8 @@ pub enum Tool {
     Eraser,
     Select,
     Text,
+    Frame,
+    Highlighter,
 }
 
 #[derive(Debug, Clone, Copy, PartialEq)]
@@ -21,6 +27,13 @@ pub enum SelectionMode {
     Rotating,
 }
 
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SelectionCombine {
+    Replace,
+    Add,
+    Subtract,
+}
+
 #[derive(Debug, Clone, Copy, PartialEq)]
 pub enum SelectionHandle {
     TopLeft,
@@ -34,18 +47,191 @@ pub enum SelectionHandle {
     Rotate,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum GuideAxis {
+    Horizontal,
+    Vertical,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Guide {
+    pub axis: GuideAxis,
+    pub position: f32,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct StrokePoint {
     pub pos: [f32; 2],
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub width: Option<f32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub opacity: Option<f32>,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+impl StrokePoint {
+    pub fn new(pos: [f32; 2]) -> Self {
+        Self { pos, width: None, opacity: None }
+    }
+}
+
+pub fn stroke_max_width(points: &[StrokePoint], width: f32) -> f32 {
+    points.iter().filter_map(|p| p.width).fold(width, f32::max)
+}
+
+mod base64_bytes {
+    use base64::engine::general_purpose::STANDARD;
+    use base64::Engine;
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(bytes: &[u8], serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&STANDARD.encode(bytes))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<u8>, D::Error> {
+        let encoded = String::deserialize(deserializer)?;
+        STANDARD.decode(encoded).map_err(serde::de::Error::custom)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ObjectProps {
+    pub opacity: f32,
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub hidden: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub reveal_step: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub group: Option<Uuid>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub layer: Option<Uuid>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub style: Option<Uuid>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub created: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub modified: Option<u64>,
+    #[serde(skip_serializing_if = "HashMap::is_empty")]
+    pub metadata: HashMap<String, String>,
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub highlight: bool,
+}
+
+impl Default for ObjectProps {
+    fn default() -> Self {
+        Self {
+            opacity: 1.0,
+            hidden: false,
+            reveal_step: None,
+            group: None,
+            layer: None,
+            style: None,
+            created: None,
+            modified: None,
+            metadata: HashMap::new(),
+            highlight: false,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum GridStyle {
+    Dots,
+    Lines,
+    Blank,
+}
+
+impl GridStyle {
+    pub const ALL: [GridStyle; 3] = [GridStyle::Dots, GridStyle::Lines, GridStyle::Blank];
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            GridStyle::Dots => "Dots",
+            GridStyle::Lines => "Lines",
+            GridStyle::Blank => "Blank",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Layer {
+    pub id: Uuid,
+    pub name: String,
+    pub visible: bool,
+    pub locked: bool,
+    pub opacity: f32,
+}
+
+impl Layer {
+    pub fn new(name: impl Into<String>) -> Self {
+        Self { id: Uuid::new_v4(), name: name.into(), visible: true, locked: false, opacity: 1.0 }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ColorStyle {
+    pub id: Uuid,
+    pub name: String,
+    pub color: [u8; 4],
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ImageAdjustments {
+    pub invert: bool,
+    pub grayscale: bool,
+    pub brightness: f32,
+    pub contrast: f32,
+    pub white_point: f32,
+}
+
+impl Default for ImageAdjustments {
+    fn default() -> Self {
+        Self { invert: false, grayscale: false, brightness: 0.0, contrast: 0.0, white_point: 1.0 }
+    }
+}
+
+impl ImageAdjustments {
+    pub fn is_identity(&self) -> bool {
+        *self == Self::default()
+    }
+}
+
+pub const SCALE_BAR_TICK: f32 = 8.0;
+pub const SCALE_BAR_LABEL_HEIGHT: f32 = 24.0;
+
+pub fn scale_bar_segments(pos: [f32; 2], length: f32) -> [([f32; 2], [f32; 2]); 4] {
+    let [x, y] = pos;
+    let mid = x + length / 2.0;
+    [
+        ([x, y], [x + length, y]),
+        ([x, y - SCALE_BAR_TICK], [x, y + SCALE_BAR_TICK / 2.0]),
+        ([mid, y - SCALE_BAR_TICK / 2.0], [mid, y]),
+        ([x + length, y - SCALE_BAR_TICK], [x + length, y + SCALE_BAR_TICK / 2.0]),
+    ]
+}
+
+fn unit_scale() -> [f32; 2] {
+    [1.0, 1.0]
+}
+
+fn is_zero(value: &f32) -> bool {
+    *value == 0.0
+}
+
+fn is_unit_scale(scale: &[f32; 2]) -> bool {
+    *scale == unit_scale()
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub enum DrawObject {
     Stroke {
         id: Uuid,
         points: Vec<StrokePoint>,
         color: [u8; 4],
         width: f32,
+        #[serde(flatten)]
+        props: ObjectProps,
     },
     Line {
         id: Uuid,
@@ -53,6 +239,8 @@ pub enum DrawObject {
         end: [f32; 2],
         color: [u8; 4],
         width: f32,
+        #[serde(flatten)]
+        props: ObjectProps,
     },
     Circle {
         id: Uuid,
@@ -61,6 +249,8 @@ pub enum DrawObject {
         color: [u8; 4],
         width: f32,
         filled: bool,
+        #[serde(flatten)]
+        props: ObjectProps,
     },
     Rectangle {
         id: Uuid,
@@ -69,14 +259,73 @@ pub enum DrawObject {
         color: [u8; 4],
         width: f32,
         filled: bool,
+        #[serde(flatten)]
+        props: ObjectProps,
     },
     LatexFormula {
         id: Uuid,
         pos: [f32; 2],
         formula: String,
         color: [u8; 4],
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        size: Option<[f32; 2]>,
+        #[serde(default = "unit_scale", skip_serializing_if = "is_unit_scale")]
+        scale: [f32; 2],
+        #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+        auto_color: bool,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        font: Option<String>,
+        #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+        pin_size: bool,
+        #[serde(flatten)]
+        props: ObjectProps,
+    },
+    Frame {
+        id: Uuid,
+        min: [f32; 2],
+        max: [f32; 2],
+        name: String,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        background: Option<[u8; 4]>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        grid: Option<GridStyle>,
+        #[serde(flatten)]
+        props: ObjectProps,
+    },
+    Image {
+        id: Uuid,
+        min: [f32; 2],
+        max: [f32; 2],
+        #[serde(with = "base64_bytes")]
+        data: Vec<u8>,
+        #[serde(default, skip_serializing_if = "ImageAdjustments::is_identity")]
+        adjustments: ImageAdjustments,
+        #[serde(flatten)]
+        props: ObjectProps,
+    },
+    ScaleBar {
+        id: Uuid,
+        pos: [f32; 2],
+        length: f32,
+        label: String,
+        color: [u8; 4],
+        #[serde(flatten)]
+        props: ObjectProps,
+    },
+    Instance {
+        id: Uuid,
+        source: Uuid,
+        origin: [f32; 2],
+        #[serde(default = "unit_scale", skip_serializing_if = "is_unit_scale")]
+        scale: [f32; 2],
+        #[serde(default, skip_serializing_if = "is_zero")]
+        rotation: f32,
         #[serde(skip)]
-        cached_size: Option<[f32; 2]>,
+        members: Vec<DrawObject>,
+        #[serde(skip)]
+        synced: Vec<DrawObject>,
+        #[serde(flatten)]
+        props: ObjectProps,
     },
 }
 
@@ -88,6 +337,75 @@ impl DrawObject {
             DrawObject::Circle { id, .. } => *id,
             DrawObject::Rectangle { id, .. } => *id,
             DrawObject::LatexFormula { id, .. } => *id,
+            DrawObject::Frame { id, .. } => *id,
+            DrawObject::Image { id, .. } => *id,
+            DrawObject::ScaleBar { id, .. } => *id,
+            DrawObject::Instance { id, .. } => *id,
+        }
+    }
+
+    pub fn props(&self) -> &ObjectProps {
+        match self {
+            DrawObject::Stroke { props, .. }
+            | DrawObject::Line { props, .. }
+            | DrawObject::Circle { props, .. }
+            | DrawObject::Rectangle { props, .. }
+            | DrawObject::LatexFormula { props, .. }
+            | DrawObject::Frame { props, .. }
+            | DrawObject::Image { props, .. }
+            | DrawObject::ScaleBar { props, .. }
+            | DrawObject::Instance { props, .. } => props,
+        }
+    }
+
+    pub fn props_mut(&mut self) -> &mut ObjectProps {
+        match self {
+            DrawObject::Stroke { props, .. }
+            | DrawObject::Line { props, .. }
+            | DrawObject::Circle { props, .. }
+            | DrawObject::Rectangle { props, .. }
+            | DrawObject::LatexFormula { props, .. }
+            | DrawObject::Frame { props, .. }
+            | DrawObject::Image { props, .. }
+            | DrawObject::ScaleBar { props, .. }
+            | DrawObject::Instance { props, .. } => props,
+        }
+    }
+
+    pub fn color_mut(&mut self) -> Option<&mut [u8; 4]> {
+        match self {
+            DrawObject::Stroke { color, .. }
+            | DrawObject::Line { color, .. }
+            | DrawObject::Circle { color, .. }
+            | DrawObject::Rectangle { color, .. }
+            | DrawObject::LatexFormula { color, .. }
+            | DrawObject::ScaleBar { color, .. } => Some(color),
+            DrawObject::Frame { .. } | DrawObject::Image { .. } | DrawObject::Instance { .. } => None,
+        }
+    }
+
+    pub fn with_new_id(&self) -> DrawObject {
+        let mut copy = self.clone();
+        copy.set_id(Uuid::new_v4());
+        if let DrawObject::Instance { synced, .. } = &mut copy {
+            synced.clear();
+        }
+        copy.props_mut().created = None;
+        copy.props_mut().modified = None;
+        copy
+    }
+
+    pub fn set_id(&mut self, new: Uuid) {
+        match self {
+            DrawObject::Stroke { id, .. }
+            | DrawObject::Line { id, .. }
+            | DrawObject::Circle { id, .. }
+            | DrawObject::Rectangle { id, .. }
+            | DrawObject::LatexFormula { id, .. }
+            | DrawObject::Frame { id, .. }
+            | DrawObject::Image { id, .. }
+            | DrawObject::ScaleBar { id, .. }
+            | DrawObject::Instance { id, .. } => *id = new,
         }
     }
 
@@ -107,7 +425,7 @@ impl DrawObject {
                     max_x = max_x.max(p.pos[0]);
                     max_y = max_y.max(p.pos[1]);
                 }
-                let half_width = width / 2.0;
+                let half_width = stroke_max_width(points, *width) / 2.0;
                 ([min_x - half_width, min_y - half_width], [max_x + half_width, max_y + half_width])
             }
             DrawObject::Line { start, end, width, .. } => {
@@ -126,10 +444,98 @@ impl DrawObject {
                 let half_width = width / 2.0;
                 ([min[0] - half_width, min[1] - half_width], [max[0] + half_width, max[1] + half_width])
             }
-            DrawObject::LatexFormula { pos, cached_size, .. } => {
-                let size = cached_size.unwrap_or([100.0, 40.0]);
-                (*pos, [pos[0] + size[0], pos[1] + size[1]])
+            DrawObject::LatexFormula { pos, size, scale, .. } => {
+                let size = size.unwrap_or([100.0, 40.0]);
+                (*pos, [pos[0] + size[0] * scale[0], pos[1] + size[1] * scale[1]])
+            }
+            DrawObject::Frame { min, max, .. } | DrawObject::Image { min, max, .. } => (*min, *max),
+            DrawObject::ScaleBar { pos, length, .. } => {
+                ([pos[0] - 1.0, pos[1] - SCALE_BAR_TICK - 1.0], [pos[0] + length + 1.0, pos[1] + SCALE_BAR_LABEL_HEIGHT])
+            }
+            DrawObject::Instance { origin, members, .. } => members
+                .iter()
+                .map(|member| member.bounds())
+                .reduce(|(amin, amax), (bmin, bmax)| {
+                    ([amin[0].min(bmin[0]), amin[1].min(bmin[1])], [amax[0].max(bmax[0]), amax[1].max(bmax[1])])
+                })
+                .unwrap_or((*origin, *origin)),
+        }
+    }
+
+    pub fn view_bounds(&self, zoom: f32) -> ([f32; 2], [f32; 2]) {
+        match self {
+            DrawObject::LatexFormula { pos, size, scale, pin_size: true, .. } => {
+                let size = size.unwrap_or([100.0, 40.0]);
+                (*pos, [pos[0] + size[0] * scale[0] / zoom, pos[1] + size[1] * scale[1] / zoom])
+            }
+            _ => self.bounds(),
+        }
+    }
+
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            DrawObject::Stroke { .. } => "Stroke",
+            DrawObject::Line { .. } => "Line",
+            DrawObject::Circle { .. } => "Circle",
+            DrawObject::Rectangle { .. } => "Rectangle",
+            DrawObject::LatexFormula { .. } => "Formula",
+            DrawObject::Frame { .. } => "Frame",
+            DrawObject::Image { .. } => "Image",
+            DrawObject::ScaleBar { .. } => "Scale bar",
+            DrawObject::Instance { .. } => "Linked instance",
+        }
+    }
+
+    pub fn describe(&self) -> String {
+        match self {
+            DrawObject::Stroke { points, .. } => {
+                let (min, max) = self.bounds();
+                format!(
+                    "Handwritten stroke with {} points, about {:.0} by {:.0} units, near ({:.0}, {:.0})",
+                    points.len(),
+                    max[0] - min[0],
+                    max[1] - min[1],
+                    (min[0] + max[0]) / 2.0,
+                    (min[1] + max[1]) / 2.0,
+                )
+            }
+            DrawObject::Line { start, end, .. } => format!(
+                "Line from ({:.0}, {:.0}) to ({:.0}, {:.0})",
+                start[0], start[1], end[0], end[1],
+            ),
+            DrawObject::Circle { center, radius, filled, .. } => format!(
+                "{} circle of radius {:.0} centered at ({:.0}, {:.0})",
+                if *filled { "Filled" } else { "Outlined" },
+                radius, center[0], center[1],
+            ),
+            DrawObject::Rectangle { min, max, filled, .. } => format!(
+                "{} rectangle {:.0} by {:.0} at ({:.0}, {:.0})",
+                if *filled { "Filled" } else { "Outlined" },
+                max[0] - min[0], max[1] - min[1], min[0], min[1],
+            ),
+            DrawObject::LatexFormula { pos, formula, .. } => {
+                if formula.is_empty() {
+                    format!("Empty formula at ({:.0}, {:.0})", pos[0], pos[1])
+                } else {
+                    format!("Formula \"{}\" at ({:.0}, {:.0})", formula, pos[0], pos[1])
+                }
             }
+            DrawObject::Frame { min, max, name, .. } => format!(
+                "Frame \"{}\" {:.0} by {:.0} at ({:.0}, {:.0})",
+                name, max[0] - min[0], max[1] - min[1], min[0], min[1],
+            ),
+            DrawObject::Image { min, max, .. } => format!(
+                "Image {:.0} by {:.0} at ({:.0}, {:.0})",
+                max[0] - min[0], max[1] - min[1], min[0], min[1],
+            ),
+            DrawObject::ScaleBar { pos, label, .. } => format!(
+                "Scale bar of {} at ({:.0}, {:.0})",
+                label, pos[0], pos[1],
+            ),
+            DrawObject::Instance { origin, members, .. } => format!(
+                "Linked instance of a group of {} objects at ({:.0}, {:.0})",
+                members.len(), origin[0], origin[1],
+            ),
         }
     }
 
@@ -137,9 +543,239 @@ impl DrawObject {
         let (min, max) = self.bounds();
         point[0] >= min[0] && point[0] <= max[0] && point[1] >= min[1] && point[1] <= max[1]
     }
+
+    pub fn hit_test(&self, point: [f32; 2], tolerance: f32) -> bool {
+        match self {
+            DrawObject::Stroke { points, width, .. } => {
+                let reach = stroke_max_width(points, *width) / 2.0 + tolerance;
+                match points.as_slice() {
+                    [] => false,
+                    [only] => distance(point, only.pos) <= reach,
+                    _ => points.windows(2).any(|w| distance_to_segment(point, w[0].pos, w[1].pos) <= reach),
+                }
+            }
+            DrawObject::Line { start, end, width, .. } => {
+                distance_to_segment(point, *start, *end) <= width / 2.0 + tolerance
+            }
+            DrawObject::Circle { center, radius, width, filled, .. } => {
+                let d = distance(point, *center);
+                if *filled {
+                    d <= radius + width / 2.0 + tolerance
+                } else {
+                    (d - radius).abs() <= width / 2.0 + tolerance
+                }
+            }
+            DrawObject::Rectangle { min, max, width, filled, .. } => {
+                let band = width / 2.0 + tolerance;
+                let inside = |inset: f32| {
+                    point[0] >= min[0] + inset && point[0] <= max[0] - inset &&
+                    point[1] >= min[1] + inset && point[1] <= max[1] - inset
+                };
+                inside(-band) && (*filled || !inside(band))
+            }
+            DrawObject::Frame { min, max, .. } => {
+                let inside = |inset: f32| {
+                    point[0] >= min[0] + inset && point[0] <= max[0] - inset &&
+                    point[1] >= min[1] + inset && point[1] <= max[1] - inset
+                };
+                inside(-tolerance) && !inside(tolerance)
+            }
+            DrawObject::LatexFormula { .. } | DrawObject::Image { .. } | DrawObject::ScaleBar { .. } => self.contains_point(point),
+            DrawObject::Instance { members, .. } => members.iter().any(|member| member.hit_test(point, tolerance)),
+        }
+    }
+
+    pub fn hit_test_segment(&self, a: [f32; 2], b: [f32; 2], tolerance: f32) -> bool {
+        match self {
+            DrawObject::Stroke { points, width, .. } => {
+                let reach = stroke_max_width(points, *width) / 2.0 + tolerance;
+                match points.as_slice() {
+                    [] => false,
+                    [only] => distance_to_segment(only.pos, a, b) <= reach,
+                    _ => points.windows(2).any(|w| segment_distance(a, b, w[0].pos, w[1].pos) <= reach),
+                }
+            }
+            DrawObject::Line { start, end, width, .. } => {
+                segment_distance(a, b, *start, *end) <= width / 2.0 + tolerance
+            }
+            DrawObject::Circle { center, radius, width, filled, .. } => {
+                let reach = width / 2.0 + tolerance;
+                let nearest = distance_to_segment(*center, a, b);
+                let farthest = distance(*center, a).max(distance(*center, b));
+                nearest <= radius + reach && (*filled || farthest >= radius - reach)
+            }
+            DrawObject::Rectangle { min, max, width, filled, .. } => {
+                segment_hits_rect(a, b, *min, *max, width / 2.0 + tolerance, *filled)
+            }
+            DrawObject::Frame { min, max, .. } => segment_hits_rect(a, b, *min, *max, tolerance, false),
+            DrawObject::LatexFormula { .. } | DrawObject::Image { .. } | DrawObject::ScaleBar { .. } => {
+                let (min, max) = self.bounds();
+                segment_hits_rect(a, b, min, max, tolerance, true)
+            }
+            DrawObject::Instance { members, .. } => members.iter().any(|member| member.hit_test_segment(a, b, tolerance)),
+        }
+    }
+}
+
+impl DrawObject {
+    pub fn erase_along(&self, a: [f32; 2], b: [f32; 2], radius: f32) -> Option<Vec<DrawObject>> {
+        let DrawObject::Stroke { id, points, color, width, props } = self else {
+            return None;
+        };
+        let erased = |p: &StrokePoint| distance_to_segment(p.pos, a, b) <= radius + p.width.unwrap_or(*width) / 2.0;
+        if !points.iter().any(erased) {
+            return None;
+        }
+        let pieces = points
+            .split(erased)
+            .filter(|piece| piece.len() > 1)
+            .enumerate()
+            .map(|(index, piece)| DrawObject::Stroke {
+                id: if index == 0 { *id } else { Uuid::new_v4() },
+                points: piece.to_vec(),
+                color: *color,
+                width: *width,
+                props: props.clone(),
+            })
+            .collect();
+        Some(pieces)
+    }
+}
+
+fn cross(o: [f32; 2], a: [f32; 2], b: [f32; 2]) -> f32 {
+    (a[0] - o[0]) * (b[1] - o[1]) - (a[1] - o[1]) * (b[0] - o[0])
+}
+
+pub fn segment_distance(a: [f32; 2], b: [f32; 2], c: [f32; 2], d: [f32; 2]) -> f32 {
+    let d1 = cross(a, b, c);
+    let d2 = cross(a, b, d);
+    let d3 = cross(c, d, a);
+    let d4 = cross(c, d, b);
+    if d1 * d2 < 0.0 && d3 * d4 < 0.0 {
+        return 0.0;
+    }
+    distance_to_segment(a, c, d)
+        .min(distance_to_segment(b, c, d))
+        .min(distance_to_segment(c, a, b))
+        .min(distance_to_segment(d, a, b))
+}
+
+fn segment_hits_rect(a: [f32; 2], b: [f32; 2], min: [f32; 2], max: [f32; 2], reach: f32, filled: bool) -> bool {
+    let inside = |p: [f32; 2]| p[0] >= min[0] && p[0] <= max[0] && p[1] >= min[1] && p[1] <= max[1];
+    if filled && (inside(a) || inside(b)) {
+        return true;
+    }
+    let corners = [min, [max[0], min[1]], max, [min[0], max[1]]];
+    (0..4).any(|i| segment_distance(a, b, corners[i], corners[(i + 1) % 4]) <= reach)
+}
+
+pub fn distance(a: [f32; 2], b: [f32; 2]) -> f32 {
+    ((a[0] - b[0]).powi(2) + (a[1] - b[1]).powi(2)).sqrt()
+}
+
+pub fn distance_to_segment(point: [f32; 2], a: [f32; 2], b: [f32; 2]) -> f32 {
+    let ab = [b[0] - a[0], b[1] - a[1]];
+    let length_sq = ab[0] * ab[0] + ab[1] * ab[1];
+    if length_sq == 0.0 {
+        return distance(point, a);
+    }
+    let t = (((point[0] - a[0]) * ab[0] + (point[1] - a[1]) * ab[1]) / length_sq).clamp(0.0, 1.0);
+    distance(point, [a[0] + ab[0] * t, a[1] + ab[1] * t])
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Snapshot {
+    pub name: String,
+    pub timestamp: u64,
+    pub objects: Vec<DrawObject>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub board: Option<Uuid>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct UnitScale {
+    pub canvas_units: f32,
+    pub unit: String,
+}
+
+impl Default for UnitScale {
+    fn default() -> Self {
+        Self { canvas_units: 50.0, unit: "cm".to_string() }
+    }
+}
+
+impl UnitScale {
+    pub fn to_units(&self, canvas_length: f32) -> f32 {
+        canvas_length / self.canvas_units.max(f32::EPSILON)
+    }
+
+    pub fn format(&self, canvas_length: f32) -> String {
+        let value = self.to_units(canvas_length);
+        let number = if value.abs() >= 10.0 {
+            format!("{:.0}", value)
+        } else {
+            format!("{:.2}", value).trim_end_matches('0').trim_end_matches('.').to_string()
+        };
+        format!("{} {}", number, self.unit)
+    }
+
+    pub fn nice_length(&self, approx_canvas_length: f32) -> f32 {
+        let units = self.to_units(approx_canvas_length).max(f32::EPSILON);
+        let magnitude = 10f32.powf(units.log10().floor());
+        let step = [1.0, 2.0, 5.0, 10.0]
+            .into_iter()
+            .map(|factor| factor * magnitude)
+            .rfind(|step| *step <= units)
+            .unwrap_or(magnitude);
+        step * self.canvas_units
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Board {
+    pub id: Uuid,
+    pub name: String,
+    #[serde(default)]
+    pub objects: Vec<DrawObject>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub background: Option<[u8; 4]>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub camera: Option<Viewport>,
+}
+
+impl Board {
+    pub fn new(name: impl Into<String>) -> Self {
+        Self { id: Uuid::new_v4(), name: name.into(), objects: Vec::new(), background: None, camera: None }
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct DocumentMetadata {
+    pub title: String,
+    pub author: String,
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub read_only: bool,
 }
 
 #[derive(Serialize, Deserialize)]
 pub struct WhiteboardState {
     pub objects: Vec<DrawObject>,
+    #[serde(default)]
+    pub snapshots: Vec<Snapshot>,
+    #[serde(default)]
+    pub metadata: DocumentMetadata,
+    #[serde(default)]
+    pub guides: Vec<Guide>,
+    #[serde(default)]
+    pub layers: Vec<Layer>,
+    #[serde(default)]
+    pub styles: Vec<ColorStyle>,
+    #[serde(default)]
+    pub unit_scale: UnitScale,
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub export_names: HashMap<Uuid, String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub boards: Vec<Board>,
+    #[serde(default)]
+    pub active_board: usize,
 }
@@ -0,0 +1,95 @@
+use std::process::Command;
+use std::sync::mpsc::{self, Receiver, TryRecvError};
+
+use uuid::Uuid;
+
+pub struct OcrLine {
+    pub text: String,
+    pub min: [u32; 2],
+    pub max: [u32; 2],
+}
+
+pub trait OcrBackend: Send {
+    fn recognize(&self, image_data: &[u8]) -> Result<Vec<OcrLine>, String>;
+}
+
+pub struct TesseractCli {
+    pub command: String,
+    pub language: String,
+}
+
+impl OcrBackend for TesseractCli {
+    fn recognize(&self, image_data: &[u8]) -> Result<Vec<OcrLine>, String> {
+        let path = std::env::temp_dir().join(format!("ocr-{}.img", Uuid::new_v4()));
+        std::fs::write(&path, image_data).map_err(|e| format!("Failed to write OCR input: {}", e))?;
+        let output = Command::new(&self.command)
+            .arg(&path)
+            .args(["stdout", "-l", &self.language, "tsv"])
+            .output();
+        let _ = std::fs::remove_file(&path);
+        let output = output.map_err(|e| format!("Failed to run {}: {}", self.command, e))?;
+        if !output.status.success() {
+            return Err(format!("{} failed: {}", self.command, String::from_utf8_lossy(&output.stderr).trim()));
+        }
+        Ok(parse_tsv(&String::from_utf8_lossy(&output.stdout)))
+    }
+}
+
+pub struct OcrJob {
+    pub image: Uuid,
+    receiver: Receiver<Result<Vec<OcrLine>, String>>,
+}
+
+impl OcrJob {
+    pub fn start(backend: Box<dyn OcrBackend>, image: Uuid, data: Vec<u8>) -> Self {
+        let (sender, receiver) = mpsc::channel();
+        std::thread::spawn(move || {
+            let _ = sender.send(backend.recognize(&data));
+        });
+        Self { image, receiver }
+    }
+
+    pub fn poll(&self) -> Option<Result<Vec<OcrLine>, String>> {
+        match self.receiver.try_recv() {
+            Ok(result) => Some(result),
+            Err(TryRecvError::Empty) => None,
+            Err(TryRecvError::Disconnected) => Some(Err("Text recognition stopped unexpectedly".to_string())),
+        }
+    }
+}
+
+fn parse_tsv(tsv: &str) -> Vec<OcrLine> {
+    let mut lines: Vec<([u32; 3], OcrLine)> = Vec::new();
+    for row in tsv.lines().skip(1) {
+        let fields: Vec<&str> = row.split('\t').collect();
+        if fields.len() < 12 || fields[0] != "5" {
+            continue;
+        }
+        let word = fields[11].trim();
+        let Ok(numbers) = fields[1..10].iter().map(|field| field.parse::<u32>()).collect::<Result<Vec<_>, _>>() else {
+            continue;
+        };
+        if word.is_empty() {
+            continue;
+        }
+        let key = [numbers[1], numbers[2], numbers[3]];
+        let (left, top, width, height) = (numbers[5], numbers[6], numbers[7], numbers[8]);
+        match lines.last_mut() {
+            Some((line_key, line)) if *line_key == key => {
+                line.text.push(' ');
+                line.text.push_str(word);
+                line.min = [line.min[0].min(left), line.min[1].min(top)];
+                line.max = [line.max[0].max(left + width), line.max[1].max(top + height)];
+            }
+            _ => lines.push((
+                key,
+                OcrLine {
+                    text: word.to_string(),
+                    min: [left, top],
+                    max: [left + width, top + height],
+                },
+            )),
+        }
+    }
+    lines.into_iter().map(|(_, line)| line).collect()
+}
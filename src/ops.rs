@@ -0,0 +1,229 @@
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::models::DrawObject;
+use crate::selection;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ZOrder {
+    Front,
+    Forward,
+    Backward,
+    Back,
+}
+
+impl ZOrder {
+    pub const ALL: [ZOrder; 4] = [ZOrder::Front, ZOrder::Forward, ZOrder::Backward, ZOrder::Back];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            ZOrder::Front => "Bring to front (Ctrl+Shift+])",
+            ZOrder::Forward => "Bring forward (Ctrl+])",
+            ZOrder::Backward => "Send backward (Ctrl+[)",
+            ZOrder::Back => "Send to back (Ctrl+Shift+[)",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "op", rename_all = "snake_case")]
+pub enum DocumentOp {
+    Add {
+        objects: Vec<DrawObject>,
+    },
+    Erase {
+        ids: Vec<Uuid>,
+    },
+    Transform {
+        ids: Vec<Uuid>,
+        #[serde(default = "unit_scale")]
+        scale: [f32; 2],
+        #[serde(default)]
+        rotation: f32,
+        #[serde(default)]
+        translation: [f32; 2],
+        #[serde(default)]
+        pivot: [f32; 2],
+    },
+    Group {
+        ids: Vec<Uuid>,
+        group: Option<Uuid>,
+    },
+    Reorder {
+        ids: Vec<Uuid>,
+        order: ZOrder,
+    },
+    Replace {
+        ids: Vec<Uuid>,
+        objects: Vec<DrawObject>,
+    },
+    Update {
+        objects: Vec<DrawObject>,
+    },
+    Clear,
+}
+
+fn unit_scale() -> [f32; 2] {
+    [1.0, 1.0]
+}
+
+pub fn apply(objects: &mut Vec<DrawObject>, op: &DocumentOp) {
+    match op {
+        DocumentOp::Add { objects: added } => objects.extend(added.iter().cloned()),
+        DocumentOp::Erase { ids } => objects.retain(|obj| !ids.contains(&obj.id())),
+        DocumentOp::Transform { ids, scale, rotation, translation, pivot } => {
+            selection::transform_objects(objects, ids, *scale, *rotation, *translation, *pivot);
+        }
+        DocumentOp::Group { ids, group } => {
+            for obj in objects.iter_mut().filter(|obj| ids.contains(&obj.id())) {
+                obj.props_mut().group = *group;
+            }
+        }
+        DocumentOp::Reorder { ids, order } => reorder(objects, ids, *order),
+        DocumentOp::Replace { ids, objects: replacements } => {
+            let index = objects.iter().position(|obj| ids.contains(&obj.id())).unwrap_or(objects.len());
+            objects.retain(|obj| !ids.contains(&obj.id()));
+            objects.splice(index..index, replacements.iter().cloned());
+        }
+        DocumentOp::Update { objects: updated } => {
+            for obj in updated {
+                if let Some(current) = objects.iter_mut().find(|current| current.id() == obj.id()) {
+                    *current = obj.clone();
+                }
+            }
+        }
+        DocumentOp::Clear => objects.clear(),
+    }
+}
+
+fn reorder(objects: &mut Vec<DrawObject>, ids: &[Uuid], order: ZOrder) {
+    let selected = |obj: &DrawObject| ids.contains(&obj.id());
+    match order {
+        ZOrder::Front | ZOrder::Back => {
+            let (mut moved, rest): (Vec<DrawObject>, Vec<DrawObject>) = std::mem::take(objects).into_iter().partition(selected);
+            if order == ZOrder::Front {
+                objects.extend(rest);
+                objects.append(&mut moved);
+            } else {
+                objects.append(&mut moved);
+                objects.extend(rest);
+            }
+        }
+        ZOrder::Forward | ZOrder::Backward => {
+            let Some((min, max)) = selection::get_selection_bounds(objects, ids) else {
+                return;
+            };
+            let layers: Vec<Option<Uuid>> = objects.iter().filter(|obj| selected(obj)).map(|obj| obj.props().layer).collect();
+            let blocks = |obj: &DrawObject| {
+                let (omin, omax) = obj.bounds();
+                !selected(obj)
+                    && layers.contains(&obj.props().layer)
+                    && omax[0] >= min[0] && omin[0] <= max[0] && omax[1] >= min[1] && omin[1] <= max[1]
+            };
+            let Some(target) = (if order == ZOrder::Forward {
+                let top = objects.iter().rposition(selected).unwrap_or(0);
+                objects[top..].iter().position(blocks).map(|offset| top + offset)
+            } else {
+                let bottom = objects.iter().position(selected).unwrap_or(0);
+                objects[..bottom].iter().rposition(blocks)
+            }) else {
+                return;
+            };
+            let target = objects[target].id();
+            let (mut moved, mut rest): (Vec<DrawObject>, Vec<DrawObject>) = std::mem::take(objects).into_iter().partition(selected);
+            let index = rest.iter().position(|obj| obj.id() == target).map_or(rest.len(), |index| {
+                if order == ZOrder::Forward { index + 1 } else { index }
+            });
+            let tail = rest.split_off(index);
+            objects.append(&mut rest);
+            objects.append(&mut moved);
+            objects.extend(tail);
+        }
+    }
+}
+
+pub fn load_ops(path: &str) -> Result<Vec<DocumentOp>, Box<dyn std::error::Error>> {
+    let json = std::fs::read_to_string(path)?;
+    Ok(serde_json::from_str(&json)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::Value;
+    use std::path::Path;
+
+    const TOLERANCE: f64 = 1e-3;
+
+    fn replay(objects: &mut Vec<DrawObject>, ops: &[DocumentOp]) {
+        for op in ops {
+            apply(objects, op);
+        }
+    }
+
+    fn close(expected: &Value, actual: &Value, at: &str) -> Result<(), String> {
+        match (expected, actual) {
+            (Value::Number(a), Value::Number(b)) => {
+                let (a, b) = (a.as_f64().unwrap_or(f64::NAN), b.as_f64().unwrap_or(f64::NAN));
+                if (a - b).abs() <= TOLERANCE {
+                    Ok(())
+                } else {
+                    Err(format!("{}: expected {}, got {}", at, a, b))
+                }
+            }
+            (Value::Array(a), Value::Array(b)) if a.len() == b.len() => {
+                a.iter().zip(b).enumerate().try_for_each(|(i, (a, b))| close(a, b, &format!("{}[{}]", at, i)))
+            }
+            (Value::Object(a), Value::Object(b)) if a.len() == b.len() => a.iter().try_for_each(|(key, a)| {
+                let b = b.get(key).ok_or_else(|| format!("{}.{}: missing", at, key))?;
+                close(a, b, &format!("{}.{}", at, key))
+            }),
+            _ if expected == actual => Ok(()),
+            _ => Err(format!("{}: expected {}, got {}", at, expected, actual)),
+        }
+    }
+
+    #[test]
+    fn golden_files() {
+        let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/golden");
+        let update = std::env::var_os("UPDATE_GOLDEN").is_some();
+        let mut cases: Vec<_> = std::fs::read_dir(&dir)
+            .expect("tests/golden exists")
+            .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+            .filter(|path| path.to_string_lossy().ends_with(".ops.json"))
+            .collect();
+        cases.sort();
+        assert!(!cases.is_empty(), "no golden cases in {}", dir.display());
+        for case in cases {
+            let name = case.to_string_lossy().trim_end_matches(".ops.json").to_string();
+            let ops = load_ops(&case.to_string_lossy()).unwrap_or_else(|e| panic!("{}: {}", case.display(), e));
+            let mut objects = Vec::new();
+            replay(&mut objects, &ops);
+            let actual = serde_json::to_value(&objects).expect("objects serialize");
+            let expected_path = format!("{}.expected.json", name);
+            if update {
+                let json = serde_json::to_string_pretty(&actual).expect("objects serialize");
+                std::fs::write(&expected_path, json + "\n").expect("golden file writable");
+                continue;
+            }
+            let expected: Value = std::fs::read_to_string(&expected_path)
+                .ok()
+                .and_then(|json| serde_json::from_str(&json).ok())
+                .unwrap_or_else(|| panic!("missing or invalid {}; run with UPDATE_GOLDEN=1", expected_path));
+            if let Err(e) = close(&expected, &actual, "objects") {
+                panic!("{}: {}", name, e);
+            }
+        }
+    }
+
+    #[test]
+    fn replay_is_deterministic() {
+        let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/golden/transform.ops.json");
+        let ops = load_ops(&dir.to_string_lossy()).expect("golden ops load");
+        let (mut first, mut second) = (Vec::new(), Vec::new());
+        replay(&mut first, &ops);
+        replay(&mut second, &ops);
+        assert_eq!(first, second);
+    }
+}
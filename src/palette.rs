@@ -0,0 +1,127 @@
+use eframe::egui;
+
+pub struct Palette {
+    pub name: &'static str,
+    pub colors: &'static [(&'static str, [u8; 3])],
+}
+
+pub const OKABE_ITO: Palette = Palette {
+    name: "Okabe-Ito (color-blind safe)",
+    colors: &[
+        ("Black", [0, 0, 0]),
+        ("Orange", [230, 159, 0]),
+        ("Sky Blue", [86, 180, 233]),
+        ("Bluish Green", [0, 158, 115]),
+        ("Yellow", [240, 228, 66]),
+        ("Blue", [0, 114, 178]),
+        ("Vermillion", [213, 94, 0]),
+        ("Reddish Purple", [204, 121, 167]),
+    ],
+};
+
+pub const TOL_BRIGHT: Palette = Palette {
+    name: "Tol Bright (color-blind safe)",
+    colors: &[
+        ("Blue", [68, 119, 170]),
+        ("Cyan", [102, 204, 238]),
+        ("Green", [34, 136, 51]),
+        ("Yellow", [204, 187, 68]),
+        ("Red", [238, 102, 119]),
+        ("Purple", [170, 51, 119]),
+        ("Grey", [187, 187, 187]),
+    ],
+};
+
+pub const HIGH_CONTRAST: Palette = Palette {
+    name: "High contrast",
+    colors: &[
+        ("Black", [0, 0, 0]),
+        ("White", [255, 255, 255]),
+        ("Yellow", [255, 255, 0]),
+        ("Cyan", [0, 255, 255]),
+        ("Magenta", [255, 0, 255]),
+        ("Dark Blue", [0, 0, 160]),
+    ],
+};
+
+pub const PALETTES: [Palette; 3] = [OKABE_ITO, TOL_BRIGHT, HIGH_CONTRAST];
+
+pub const MIN_INK_CONTRAST: f32 = 3.0;
+
+fn linear_channel(c: u8) -> f32 {
+    let c = c as f32 / 255.0;
+    if c <= 0.04045 {
+        c / 12.92
+    } else {
+        ((c + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+pub fn relative_luminance(color: egui::Color32) -> f32 {
+    0.2126 * linear_channel(color.r()) + 0.7152 * linear_channel(color.g()) + 0.0722 * linear_channel(color.b())
+}
+
+pub fn contrast_ratio(a: egui::Color32, b: egui::Color32) -> f32 {
+    let la = relative_luminance(a);
+    let lb = relative_luminance(b);
+    (la.max(lb) + 0.05) / (la.min(lb) + 0.05)
+}
+
+pub fn is_dark(color: egui::Color32) -> bool {
+    relative_luminance(color) < 0.18
+}
+
+pub fn auto_ink(background: egui::Color32) -> [u8; 4] {
+    if is_dark(background) {
+        [235, 235, 235, 255]
+    } else {
+        [20, 20, 20, 255]
+    }
+}
+
+pub fn invert_lightness(color: [u8; 4]) -> [u8; 4] {
+    let max = color[0].max(color[1]).max(color[2]) as f32;
+    let min = color[0].min(color[1]).min(color[2]) as f32;
+    let shift = 255.0 - (max + min);
+    let channel = |c: u8| (c as f32 + shift).round().clamp(0.0, 255.0) as u8;
+    [channel(color[0]), channel(color[1]), channel(color[2]), color[3]]
+}
+
+pub fn high_contrast_visuals(dark: bool) -> egui::Visuals {
+    let (mut visuals, fg, bg) = if dark {
+        (egui::Visuals::dark(), egui::Color32::WHITE, egui::Color32::BLACK)
+    } else {
+        (egui::Visuals::light(), egui::Color32::BLACK, egui::Color32::WHITE)
+    };
+    let accent = if dark {
+        egui::Color32::from_rgb(255, 255, 0)
+    } else {
+        egui::Color32::from_rgb(0, 0, 160)
+    };
+
+    visuals.override_text_color = Some(fg);
+    visuals.panel_fill = bg;
+    visuals.window_fill = bg;
+    visuals.extreme_bg_color = bg;
+    visuals.faint_bg_color = bg;
+    visuals.window_stroke = egui::Stroke::new(2.0, fg);
+    visuals.selection.bg_fill = accent;
+    visuals.selection.stroke = egui::Stroke::new(2.0, bg);
+    visuals.hyperlink_color = accent;
+
+    for widget in [
+        &mut visuals.widgets.noninteractive,
+        &mut visuals.widgets.inactive,
+        &mut visuals.widgets.hovered,
+        &mut visuals.widgets.active,
+        &mut visuals.widgets.open,
+    ] {
+        widget.bg_fill = bg;
+        widget.weak_bg_fill = bg;
+        widget.fg_stroke = egui::Stroke::new(1.5, fg);
+        widget.bg_stroke = egui::Stroke::new(1.5, fg);
+    }
+    visuals.widgets.hovered.bg_stroke = egui::Stroke::new(2.5, accent);
+    visuals.widgets.active.bg_stroke = egui::Stroke::new(3.0, accent);
+    visuals
+}
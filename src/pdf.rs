@@ -0,0 +1,185 @@
+use std::fs;
+
+pub struct PdfPage {
+    pub jpeg: Vec<u8>,
+    pub pixel_size: [u32; 2],
+    pub image_size_pt: [f32; 2],
+    pub header: Option<String>,
+    pub footer: Option<String>,
+}
+
+const MARGIN_PT: f32 = 36.0;
+const TEXT_SIZE_PT: f32 = 10.0;
+
+fn escape_text(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '(' | ')' | '\\' => {
+                escaped.push('\\');
+                escaped.push(c);
+            }
+            c if c.is_ascii() && !c.is_ascii_control() => escaped.push(c),
+            _ => escaped.push('?'),
+        }
+    }
+    escaped
+}
+
+struct PdfWriter {
+    buffer: Vec<u8>,
+    offsets: Vec<usize>,
+}
+
+impl PdfWriter {
+    fn new() -> Self {
+        let mut buffer = Vec::new();
+        buffer.extend_from_slice(b"%PDF-1.4\n%\xE2\xE3\xCF\xD3\n");
+        Self { buffer, offsets: Vec::new() }
+    }
+
+    fn reserve(&mut self) -> usize {
+        self.offsets.push(0);
+        self.offsets.len()
+    }
+
+    fn begin_object(&mut self, id: usize) {
+        self.offsets[id - 1] = self.buffer.len();
+        self.buffer.extend_from_slice(format!("{} 0 obj\n", id).as_bytes());
+    }
+
+    fn object(&mut self, id: usize, body: &str) {
+        self.begin_object(id);
+        self.buffer.extend_from_slice(body.as_bytes());
+        self.buffer.extend_from_slice(b"\nendobj\n");
+    }
+
+    fn stream(&mut self, id: usize, dict: &str, data: &[u8]) {
+        self.begin_object(id);
+        self.buffer.extend_from_slice(format!("<< {} /Length {} >>\nstream\n", dict, data.len()).as_bytes());
+        self.buffer.extend_from_slice(data);
+        self.buffer.extend_from_slice(b"\nendstream\nendobj\n");
+    }
+
+    fn finish(mut self, root: usize, info: usize) -> Vec<u8> {
+        let xref_offset = self.buffer.len();
+        self.buffer.extend_from_slice(format!("xref\n0 {}\n", self.offsets.len() + 1).as_bytes());
+        self.buffer.extend_from_slice(b"0000000000 65535 f \n");
+        for offset in &self.offsets {
+            self.buffer.extend_from_slice(format!("{:010} 00000 n \n", offset).as_bytes());
+        }
+        self.buffer.extend_from_slice(
+            format!(
+                "trailer\n<< /Size {} /Root {} 0 R /Info {} 0 R >>\nstartxref\n{}\n%%EOF\n",
+                self.offsets.len() + 1,
+                root,
+                info,
+                xref_offset,
+            )
+            .as_bytes(),
+        );
+        self.buffer
+    }
+}
+
+pub fn encode_jpeg(pixmap: &tiny_skia::Pixmap, quality: u8) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+    let rgb: Vec<u8> = pixmap
+        .pixels()
+        .iter()
+        .flat_map(|p| {
+            let c = p.demultiply();
+            [c.red(), c.green(), c.blue()]
+        })
+        .collect();
+    let mut jpeg = Vec::new();
+    image::codecs::jpeg::JpegEncoder::new_with_quality(&mut jpeg, quality).encode(
+        &rgb,
+        pixmap.width(),
+        pixmap.height(),
+        image::ExtendedColorType::Rgb8,
+    )?;
+    Ok(jpeg)
+}
+
+pub fn write_pdf(pages: &[PdfPage], title: &str, author: &str, path: &str) -> Result<(), Box<dyn std::error::Error>> {
+    if pages.is_empty() {
+        return Err("No pages to export".into());
+    }
+
+    let mut writer = PdfWriter::new();
+    let catalog = writer.reserve();
+    let pages_root = writer.reserve();
+    let font = writer.reserve();
+    let info = writer.reserve();
+
+    let mut kids = Vec::new();
+    for page in pages {
+        let page_id = writer.reserve();
+        let content_id = writer.reserve();
+        let image_id = writer.reserve();
+        kids.push(page_id);
+
+        let page_width = page.image_size_pt[0] + 2.0 * MARGIN_PT;
+        let page_height = page.image_size_pt[1] + 2.0 * MARGIN_PT;
+
+        let mut content = format!(
+            "q\n{:.2} 0 0 {:.2} {:.2} {:.2} cm\n/Im0 Do\nQ\n",
+            page.image_size_pt[0], page.image_size_pt[1], MARGIN_PT, MARGIN_PT,
+        );
+        if let Some(header) = &page.header {
+            content.push_str(&format!(
+                "BT\n/F1 {} Tf\n{:.2} {:.2} Td\n({}) Tj\nET\n",
+                TEXT_SIZE_PT,
+                MARGIN_PT,
+                page_height - MARGIN_PT + (MARGIN_PT - TEXT_SIZE_PT) / 2.0,
+                escape_text(header),
+            ));
+        }
+        if let Some(footer) = &page.footer {
+            let approx_width = footer.chars().count() as f32 * TEXT_SIZE_PT * 0.5;
+            content.push_str(&format!(
+                "BT\n/F1 {} Tf\n{:.2} {:.2} Td\n({}) Tj\nET\n",
+                TEXT_SIZE_PT,
+                (page_width - approx_width) / 2.0,
+                (MARGIN_PT - TEXT_SIZE_PT) / 2.0,
+                escape_text(footer),
+            ));
+        }
+
+        writer.object(
+            page_id,
+            &format!(
+                "<< /Type /Page /Parent {} 0 R /MediaBox [0 0 {:.2} {:.2}] /Resources << /Font << /F1 {} 0 R >> /XObject << /Im0 {} 0 R >> >> /Contents {} 0 R >>",
+                pages_root, page_width, page_height, font, image_id, content_id,
+            ),
+        );
+        writer.stream(content_id, "", content.as_bytes());
+        writer.stream(
+            image_id,
+            &format!(
+                "/Type /XObject /Subtype /Image /Width {} /Height {} /ColorSpace /DeviceRGB /BitsPerComponent 8 /Filter /DCTDecode",
+                page.pixel_size[0], page.pixel_size[1],
+            ),
+            &page.jpeg,
+        );
+    }
+
+    let kids_list: Vec<String> = kids.iter().map(|id| format!("{} 0 R", id)).collect();
+    writer.object(catalog, &format!("<< /Type /Catalog /Pages {} 0 R >>", pages_root));
+    writer.object(
+        pages_root,
+        &format!("<< /Type /Pages /Kids [{}] /Count {} >>", kids_list.join(" "), kids.len()),
+    );
+    writer.object(font, "<< /Type /Font /Subtype /Type1 /BaseFont /Helvetica /Encoding /WinAnsiEncoding >>");
+    writer.object(
+        info,
+        &format!(
+            "<< /Title ({}) /Author ({}) /Producer (Math Workspace) >>",
+            escape_text(title),
+            escape_text(author),
+        ),
+    );
+
+    fs::write(path, writer.finish(catalog, info))?;
+    Ok(())
+}
@@ -0,0 +1,254 @@
+use std::collections::HashMap;
+
+pub struct ImportedPage {
+    pub jpeg: Option<Vec<u8>>,
+}
+
+struct PdfReader<'a> {
+    bytes: &'a [u8],
+    offsets: HashMap<u32, usize>,
+}
+
+fn find(haystack: &[u8], needle: &[u8], from: usize) -> Option<usize> {
+    if from >= haystack.len() {
+        return None;
+    }
+    haystack[from..]
+        .windows(needle.len())
+        .position(|w| w == needle)
+        .map(|p| p + from)
+}
+
+fn rfind(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack.windows(needle.len()).rposition(|w| w == needle)
+}
+
+fn skip_whitespace(bytes: &[u8], mut pos: usize) -> usize {
+    while pos < bytes.len() && bytes[pos].is_ascii_whitespace() {
+        pos += 1;
+    }
+    pos
+}
+
+fn parse_number(bytes: &[u8], pos: usize) -> Option<(u32, usize)> {
+    let start = skip_whitespace(bytes, pos);
+    let mut end = start;
+    while end < bytes.len() && bytes[end].is_ascii_digit() {
+        end += 1;
+    }
+    let value = std::str::from_utf8(&bytes[start..end]).ok()?.parse().ok()?;
+    Some((value, end))
+}
+
+fn parse_ref(bytes: &[u8], pos: usize) -> Option<(u32, usize)> {
+    let (id, pos) = parse_number(bytes, pos)?;
+    let (_generation, pos) = parse_number(bytes, pos)?;
+    let pos = skip_whitespace(bytes, pos);
+    (bytes.get(pos) == Some(&b'R')).then_some((id, pos + 1))
+}
+
+fn parse_refs(bytes: &[u8]) -> Vec<u32> {
+    let mut refs = Vec::new();
+    let mut pos = 0;
+    while pos < bytes.len() {
+        if bytes[pos].is_ascii_digit() && (pos == 0 || !bytes[pos - 1].is_ascii_digit()) {
+            if let Some((id, end)) = parse_ref(bytes, pos) {
+                refs.push(id);
+                pos = end;
+                continue;
+            }
+        }
+        pos += 1;
+    }
+    refs
+}
+
+fn dict_end(bytes: &[u8], start: usize) -> Option<usize> {
+    let mut depth = 0;
+    let mut pos = start;
+    while pos + 1 < bytes.len() {
+        match &bytes[pos..pos + 2] {
+            b"<<" => {
+                depth += 1;
+                pos += 2;
+            }
+            b">>" => {
+                depth -= 1;
+                pos += 2;
+                if depth == 0 {
+                    return Some(pos);
+                }
+            }
+            _ => pos += 1,
+        }
+    }
+    None
+}
+
+fn key_value(dict: &[u8], key: &[u8]) -> Option<usize> {
+    let mut from = 0;
+    while let Some(pos) = find(dict, key, from) {
+        let end = pos + key.len();
+        if dict.get(end).is_none_or(|c| !c.is_ascii_alphanumeric()) {
+            return Some(skip_whitespace(dict, end));
+        }
+        from = end;
+    }
+    None
+}
+
+fn has_name(dict: &[u8], key: &[u8], name: &[u8]) -> bool {
+    key_value(dict, key).is_some_and(|pos| dict[pos..].starts_with(name))
+}
+
+impl<'a> PdfReader<'a> {
+    fn new(bytes: &'a [u8]) -> Self {
+        let mut offsets = HashMap::new();
+        let mut from = 0;
+        while let Some(pos) = find(bytes, b" obj", from) {
+            from = pos + 4;
+            let mut start = pos;
+            let mut spaces = 0;
+            while start > 0 && (bytes[start - 1].is_ascii_digit() || (bytes[start - 1] == b' ' && spaces == 0)) {
+                if bytes[start - 1] == b' ' {
+                    spaces += 1;
+                }
+                start -= 1;
+            }
+            if let Some((id, _)) = parse_number(bytes, start) {
+                offsets.insert(id, pos + 4);
+            }
+        }
+        Self { bytes, offsets }
+    }
+
+    fn object(&self, id: u32) -> Option<&'a [u8]> {
+        let start = *self.offsets.get(&id)?;
+        let end = find(self.bytes, b"endobj", start).unwrap_or(self.bytes.len());
+        Some(&self.bytes[start..end])
+    }
+
+    fn dict_value(&self, dict: &'a [u8], key: &[u8]) -> Option<&'a [u8]> {
+        let pos = key_value(dict, key)?;
+        if dict[pos..].starts_with(b"<<") {
+            let end = dict_end(dict, pos)?;
+            return Some(&dict[pos..end]);
+        }
+        let (id, _) = parse_ref(dict, pos)?;
+        let object = self.object(id)?;
+        let start = find(object, b"<<", 0)?;
+        let end = dict_end(object, start)?;
+        Some(&object[start..end])
+    }
+
+    fn number_value(&self, dict: &[u8], key: &[u8]) -> Option<usize> {
+        let pos = key_value(dict, key)?;
+        if let Some((id, _)) = parse_ref(dict, pos) {
+            return parse_number(self.object(id)?, 0).map(|(n, _)| n as usize);
+        }
+        parse_number(dict, pos).map(|(n, _)| n as usize)
+    }
+
+    fn stream_data(&self, object: &'a [u8]) -> Option<&'a [u8]> {
+        let dict_start = find(object, b"<<", 0)?;
+        let dict_stop = dict_end(object, dict_start)?;
+        let mut start = find(object, b"stream", dict_stop)? + 6;
+        if object.get(start) == Some(&b'\r') {
+            start += 1;
+        }
+        if object.get(start) == Some(&b'\n') {
+            start += 1;
+        }
+        let length = self.number_value(&object[dict_start..dict_stop], b"/Length");
+        match length {
+            Some(length) if start + length <= object.len() => Some(&object[start..start + length]),
+            _ => {
+                let end = find(object, b"endstream", start)?;
+                Some(&object[start..end])
+            }
+        }
+    }
+
+    fn jpeg_image(&self, id: u32) -> Option<&'a [u8]> {
+        let object = self.object(id)?;
+        let dict_start = find(object, b"<<", 0)?;
+        let dict = &object[dict_start..dict_end(object, dict_start)?];
+        if !has_name(dict, b"/Subtype", b"/Image") || find(dict, b"/DCTDecode", 0).is_none() {
+            return None;
+        }
+        self.stream_data(object)
+    }
+
+    fn collect_pages(&self, id: u32, inherited: Option<&'a [u8]>, pages: &mut Vec<Option<&'a [u8]>>, depth: usize) {
+        let Some(object) = self.object(id) else {
+            return;
+        };
+        if depth > 32 {
+            return;
+        }
+        let resources = self.dict_value(object, b"/Resources").or(inherited);
+        if has_name(object, b"/Type", b"/Pages") || key_value(object, b"/Kids").is_some() {
+            let Some(kids_start) = key_value(object, b"/Kids") else {
+                return;
+            };
+            let kids_end = find(object, b"]", kids_start).unwrap_or(object.len());
+            for kid in parse_refs(&object[kids_start..kids_end]) {
+                self.collect_pages(kid, resources, pages, depth + 1);
+            }
+        } else {
+            pages.push(resources);
+        }
+    }
+
+    fn pages(&self) -> Option<Vec<ImportedPage>> {
+        let root_pos = rfind(self.bytes, b"/Root")?;
+        let (catalog, _) = parse_ref(self.bytes, root_pos + 5)?;
+        let catalog = self.object(catalog)?;
+        let (pages_root, _) = parse_ref(catalog, key_value(catalog, b"/Pages")?)?;
+
+        let mut pages = Vec::new();
+        self.collect_pages(pages_root, None, &mut pages, 0);
+        if pages.is_empty() {
+            return None;
+        }
+
+        Some(
+            pages
+                .into_iter()
+                .map(|resources| {
+                    let jpeg = resources
+                        .and_then(|r| self.dict_value(r, b"/XObject"))
+                        .map(parse_refs)
+                        .unwrap_or_default()
+                        .into_iter()
+                        .filter_map(|id| self.jpeg_image(id))
+                        .max_by_key(|data| data.len())
+                        .map(|data| data.to_vec());
+                    ImportedPage { jpeg }
+                })
+                .collect(),
+        )
+    }
+}
+
+pub fn read_pdf_pages(path: &str) -> Result<Vec<ImportedPage>, Box<dyn std::error::Error>> {
+    let bytes = std::fs::read(path)?;
+    if !bytes.starts_with(b"%PDF") {
+        return Err("Not a PDF file".into());
+    }
+
+    if find(&bytes, b"/Encrypt", 0).is_some() {
+        return Err("Encrypted or password-protected PDFs cannot be imported".into());
+    }
+
+    let reader = PdfReader::new(&bytes);
+    let compressed = find(&bytes, b"/ObjStm", 0).is_some();
+    let pages = reader.pages().unwrap_or_default();
+    if pages.iter().all(|page| page.jpeg.is_none()) {
+        if compressed {
+            return Err("This PDF keeps its pages in compressed object streams, which the importer cannot read; re-save it as PDF 1.4 or export the pages as images".into());
+        }
+        return Err("No JPEG page images found; only scanned PDFs can be imported, not text or vector PDFs".into());
+    }
+    Ok(pages)
+}
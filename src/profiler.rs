@@ -0,0 +1,85 @@
+use std::cell::Cell;
+use std::collections::VecDeque;
+use std::time::Duration;
+
+const WINDOW: usize = 120;
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct FrameSample {
+    pub update_ms: f32,
+    pub paint_ms: f32,
+    pub index_ms: f32,
+    pub render_ms: f32,
+    pub hit_test_ms: f32,
+    pub objects_drawn: usize,
+    pub objects_total: usize,
+    pub latex_cache: (u32, u32),
+    pub image_cache: (u32, u32),
+}
+
+#[derive(Default)]
+pub struct Profiler {
+    samples: VecDeque<FrameSample>,
+    pub current: FrameSample,
+    hit_test: Cell<Duration>,
+    last_update_ms: f32,
+}
+
+fn millis(duration: Duration) -> f32 {
+    duration.as_secs_f32() * 1000.0
+}
+
+impl Profiler {
+    pub fn add_hit_test(&self, elapsed: Duration) {
+        self.hit_test.set(self.hit_test.get() + elapsed);
+    }
+
+    pub fn finish_frame(&mut self, update: Duration, cpu_usage: Option<f32>) {
+        let mut sample = std::mem::take(&mut self.current);
+        sample.update_ms = millis(update);
+        sample.hit_test_ms = millis(self.hit_test.take());
+        sample.paint_ms = cpu_usage.map_or(0.0, |cpu| (cpu * 1000.0 - self.last_update_ms).max(0.0));
+        self.last_update_ms = sample.update_ms;
+        if self.samples.len() >= WINDOW {
+            self.samples.pop_front();
+        }
+        self.samples.push_back(sample);
+    }
+
+    fn timing(&self, value: impl Fn(&FrameSample) -> f32) -> String {
+        let count = self.samples.len().max(1) as f32;
+        let average = self.samples.iter().map(&value).sum::<f32>() / count;
+        let max = self.samples.iter().map(&value).fold(0.0, f32::max);
+        format!("{:.2} ms (max {:.2})", average, max)
+    }
+
+    fn hit_rate(&self, value: impl Fn(&FrameSample) -> (u32, u32)) -> String {
+        let (hits, misses) = self.samples.iter().map(value).fold((0, 0), |(h, m), (hits, misses)| (h + hits, m + misses));
+        if hits + misses == 0 {
+            return "–".to_string();
+        }
+        format!("{:.0}% of {}", hits as f32 / (hits + misses) as f32 * 100.0, hits + misses)
+    }
+
+    pub fn rows(&self) -> Vec<(&'static str, String)> {
+        let last = self.samples.back().copied().unwrap_or_default();
+        vec![
+            ("Update", self.timing(|s| s.update_ms)),
+            ("Tessellate + paint", self.timing(|s| s.paint_ms)),
+            ("Spatial index", self.timing(|s| s.index_ms)),
+            ("Render objects", self.timing(|s| s.render_ms)),
+            ("Hit tests", self.timing(|s| s.hit_test_ms)),
+            ("Objects drawn", format!("{} / {}", last.objects_drawn, last.objects_total)),
+            ("LaTeX cache hits", self.hit_rate(|s| s.latex_cache)),
+            ("Image cache hits", self.hit_rate(|s| s.image_cache)),
+        ]
+    }
+
+    pub fn report(&self) -> String {
+        let mut report = format!("Last {} frames\n", self.samples.len());
+        for (label, value) in self.rows() {
+            report.push_str(&format!("{}: {}\n", label, value));
+        }
+        report
+    }
+}
@@ -0,0 +1,276 @@
+use std::path::PathBuf;
+use std::process::{Child, Command};
+
+use eframe::egui;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::clock;
+use crate::export::{self, ExportFraming};
+use crate::latex::LatexRenderer;
+use crate::models::{DocumentMetadata, DrawObject};
+use crate::pdf::{self, PdfPage};
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum RecipeFormat {
+    Png,
+    Pdf,
+}
+
+impl RecipeFormat {
+    pub const ALL: [RecipeFormat; 2] = [RecipeFormat::Png, RecipeFormat::Pdf];
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            RecipeFormat::Png => "PNG",
+            RecipeFormat::Pdf => "PDF",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum RecipeRegion {
+    All,
+    Selection,
+    Frame,
+}
+
+impl RecipeRegion {
+    pub const ALL: [RecipeRegion; 3] = [RecipeRegion::All, RecipeRegion::Selection, RecipeRegion::Frame];
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            RecipeRegion::All => "Whole board",
+            RecipeRegion::Selection => "Selection",
+            RecipeRegion::Frame => "Frame",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum RecipeBackground {
+    Transparent,
+    Board,
+    Color,
+}
+
+impl RecipeBackground {
+    pub const ALL: [RecipeBackground; 3] = [RecipeBackground::Transparent, RecipeBackground::Board, RecipeBackground::Color];
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            RecipeBackground::Transparent => "Transparent",
+            RecipeBackground::Board => "Board color",
+            RecipeBackground::Color => "Custom color",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum PostCommand {
+    Nothing,
+    Open,
+    CopyPath,
+    Command,
+}
+
+impl PostCommand {
+    pub const ALL: [PostCommand; 4] = [PostCommand::Nothing, PostCommand::Open, PostCommand::CopyPath, PostCommand::Command];
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            PostCommand::Nothing => "Nothing",
+            PostCommand::Open => "Open with default app",
+            PostCommand::CopyPath => "Copy path",
+            PostCommand::Command => "Run command",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ExportRecipe {
+    pub name: String,
+    pub format: RecipeFormat,
+    pub scale: f32,
+    pub background: RecipeBackground,
+    pub background_color: [u8; 4],
+    pub region: RecipeRegion,
+    pub frame: String,
+    pub path: String,
+    pub after: PostCommand,
+    pub command: String,
+    pub shortcut: String,
+}
+
+impl Default for ExportRecipe {
+    fn default() -> Self {
+        Self {
+            name: "New recipe".to_string(),
+            format: RecipeFormat::Png,
+            scale: 2.0,
+            background: RecipeBackground::Transparent,
+            background_color: [255, 255, 255, 255],
+            region: RecipeRegion::All,
+            frame: String::new(),
+            path: "export-{date}-{time}.png".to_string(),
+            after: PostCommand::Nothing,
+            command: String::new(),
+            shortcut: String::new(),
+        }
+    }
+}
+
+pub fn parse_shortcut(text: &str) -> Option<egui::KeyboardShortcut> {
+    let mut parts: Vec<&str> = text.split('+').map(str::trim).collect();
+    let key = egui::Key::from_name(parts.pop()?)?;
+    let mut modifiers = egui::Modifiers::NONE;
+    for part in parts {
+        match part.to_ascii_lowercase().as_str() {
+            "ctrl" | "cmd" | "command" => modifiers = modifiers | egui::Modifiers::COMMAND,
+            "alt" | "option" => modifiers = modifiers | egui::Modifiers::ALT,
+            "shift" => modifiers = modifiers | egui::Modifiers::SHIFT,
+            _ => return None,
+        }
+    }
+    Some(egui::KeyboardShortcut::new(modifiers, key))
+}
+
+fn expand_path(template: &str, frame: &str, time: u64) -> PathBuf {
+    let path = template
+        .replace("{frame}", &export::file_stem(frame))
+        .replace("{date}", &clock::date_stamp(time))
+        .replace("{time}", &clock::time_stamp(time));
+    match (path.strip_prefix("~/"), std::env::var_os("HOME")) {
+        (Some(rest), Some(home)) => PathBuf::from(home).join(rest),
+        _ => PathBuf::from(path),
+    }
+}
+
+struct Region {
+    objects: Vec<DrawObject>,
+    bounds: ([f32; 2], [f32; 2]),
+    label: String,
+}
+
+fn region(
+    recipe: &ExportRecipe,
+    objects: &[DrawObject],
+    selected: &[Uuid],
+    framing: &ExportFraming,
+) -> Result<Region, Box<dyn std::error::Error>> {
+    let padded = |bounds| framing.around(bounds, recipe.scale);
+    match recipe.region {
+        RecipeRegion::All => {
+            let bounds = export::content_bounds(objects).ok_or("Nothing to export")?;
+            Ok(Region { objects: objects.to_vec(), bounds: padded(bounds), label: "board".to_string() })
+        }
+        RecipeRegion::Selection => {
+            let chosen: Vec<DrawObject> = objects.iter().filter(|obj| selected.contains(&obj.id())).cloned().collect();
+            let bounds = export::content_bounds(&chosen).ok_or("Nothing is selected")?;
+            Ok(Region { objects: chosen, bounds: padded(bounds), label: "selection".to_string() })
+        }
+        RecipeRegion::Frame => {
+            let wanted = recipe.frame.trim();
+            let (min, max, name) = objects
+                .iter()
+                .find_map(|obj| match obj {
+                    DrawObject::Frame { min, max, name, .. } if name.trim() == wanted => Some((*min, *max, name.clone())),
+                    _ => None,
+                })
+                .ok_or_else(|| format!("No frame named \"{}\"", wanted))?;
+            let visible: Vec<DrawObject> = objects
+                .iter()
+                .filter(|obj| {
+                    let (omin, omax) = obj.bounds();
+                    omax[0] >= min[0] && omin[0] <= max[0] && omax[1] >= min[1] && omin[1] <= max[1]
+                })
+                .cloned()
+                .collect();
+            let bounds = framing.frame(&visible, (min, max), recipe.scale);
+            Ok(Region { objects: visible, bounds, label: name })
+        }
+    }
+}
+
+pub fn run(
+    recipe: &ExportRecipe,
+    objects: &[DrawObject],
+    selected: &[Uuid],
+    board_background: [u8; 4],
+    metadata: &DocumentMetadata,
+    framing: &ExportFraming,
+    latex_renderer: &mut LatexRenderer,
+) -> Result<PathBuf, Box<dyn std::error::Error>> {
+    let Region { objects, bounds: (min, max), label } = region(recipe, objects, selected, framing)?;
+    let background = match recipe.background {
+        RecipeBackground::Transparent => None,
+        RecipeBackground::Board => Some(board_background),
+        RecipeBackground::Color => Some(recipe.background_color),
+    };
+    let path = expand_path(&recipe.path, &label, clock::now_secs());
+    if let Some(parent) = path.parent().filter(|parent| !parent.as_os_str().is_empty()) {
+        std::fs::create_dir_all(parent)?;
+    }
+
+    match recipe.format {
+        RecipeFormat::Png => {
+            let pixmap = export::render_to_pixmap(&objects, (min, max), recipe.scale, background, latex_renderer)?;
+            pixmap.save_png(&path)?;
+        }
+        RecipeFormat::Pdf => {
+            let pixmap = export::render_to_pixmap(
+                &objects,
+                (min, max),
+                recipe.scale,
+                Some(background.unwrap_or([255, 255, 255, 255])),
+                latex_renderer,
+            )?;
+            let page = PdfPage {
+                jpeg: pdf::encode_jpeg(&pixmap, 90)?,
+                pixel_size: [pixmap.width(), pixmap.height()],
+                image_size_pt: [(max[0] - min[0]) * 0.75, (max[1] - min[1]) * 0.75],
+                header: None,
+                footer: None,
+            };
+            pdf::write_pdf(&[page], &metadata.title, &metadata.author, &path.to_string_lossy())?;
+        }
+    }
+    Ok(path)
+}
+
+pub fn open_with_default_app(path: &std::path::Path) -> Result<(), String> {
+    let mut command = if cfg!(target_os = "windows") {
+        let mut command = Command::new("cmd");
+        command.args(["/C", "start", ""]);
+        command
+    } else if cfg!(target_os = "macos") {
+        Command::new("open")
+    } else {
+        Command::new("xdg-open")
+    };
+    command.arg(path).spawn().map(reap).map_err(|e| format!("Failed to open {}: {}", path.display(), e))
+}
+
+fn reap(mut child: Child) {
+    std::thread::spawn(move || {
+        let _ = child.wait();
+    });
+}
+
+pub fn run_command(template: &str, path: &std::path::Path) -> Result<(), String> {
+    let mut command = if cfg!(target_os = "windows") {
+        let mut command = Command::new("cmd");
+        command.arg("/C").arg(template.replace("{path}", "\"%EXPORT_PATH%\""));
+        command
+    } else {
+        let mut command = Command::new("sh");
+        command.arg("-c").arg(template.replace("{path}", "\"$EXPORT_PATH\""));
+        command
+    };
+    command
+        .env("EXPORT_PATH", path)
+        .spawn()
+        .map(reap)
+        .map_err(|e| format!("Failed to run {}: {}", template, e))
+}
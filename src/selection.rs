@@ -1,7 +1,13 @@
-use crate::models::{DrawObject, SelectionHandle};
+use std::collections::HashMap;
+
+use crate::models::{DrawObject, GridStyle, SelectionHandle};
 use uuid::Uuid;
 
 pub fn get_selection_bounds(objects: &[DrawObject], selected_objects: &[Uuid]) -> Option<([f32; 2], [f32; 2])> {
+    get_view_bounds(objects, selected_objects, 1.0)
+}
+
+pub fn get_view_bounds(objects: &[DrawObject], selected_objects: &[Uuid], zoom: f32) -> Option<([f32; 2], [f32; 2])> {
     if selected_objects.is_empty() {
         return None;
     }
@@ -13,7 +19,7 @@ pub fn get_selection_bounds(objects: &[DrawObject], selected_objects: &[Uuid]) -
 
     for obj_id in selected_objects {
         if let Some(obj) = objects.iter().find(|o| o.id() == *obj_id) {
-            let (obj_min, obj_max) = obj.bounds();
+            let (obj_min, obj_max) = obj.view_bounds(zoom);
             min_x = min_x.min(obj_min[0]);
             min_y = min_y.min(obj_min[1]);
             max_x = max_x.max(obj_max[0]);
@@ -131,7 +137,7 @@ pub fn transform_objects(objects: &mut [DrawObject], selected_objects: &[Uuid],
                     circle_center[1] = y + center[1] + translation[1];
                     *radius *= scale[0].max(scale[1]);
                 }
-                DrawObject::Rectangle { min, max, .. } => {
+                DrawObject::Rectangle { min, max, .. } | DrawObject::Frame { min, max, .. } | DrawObject::Image { min, max, .. } => {
                     for pos in [min, max] {
                         let mut x = pos[0] - center[0];
                         let mut y = pos[1] - center[1];
@@ -152,7 +158,28 @@ pub fn transform_objects(objects: &mut [DrawObject], selected_objects: &[Uuid],
                         pos[1] = y + center[1] + translation[1];
                     }
                 }
-                DrawObject::LatexFormula { pos, .. } => {
+                DrawObject::LatexFormula { pos, scale: formula_scale, .. } => {
+                    let mut x = pos[0] - center[0];
+                    let mut y = pos[1] - center[1];
+                    
+                    if rotation != 0.0 {
+                        let cos_r = rotation.cos();
+                        let sin_r = rotation.sin();
+                        let new_x = x * cos_r - y * sin_r;
+                        let new_y = x * sin_r + y * cos_r;
+                        x = new_x;
+                        y = new_y;
+                    }
+                    
+                    x *= scale[0];
+                    y *= scale[1];
+                    
+                    pos[0] = x + center[0] + translation[0];
+                    pos[1] = y + center[1] + translation[1];
+                    formula_scale[0] *= scale[0];
+                    formula_scale[1] *= scale[1];
+                }
+                DrawObject::ScaleBar { pos, length, .. } => {
                     let mut x = pos[0] - center[0];
                     let mut y = pos[1] - center[1];
                     
@@ -170,8 +197,257 @@ pub fn transform_objects(objects: &mut [DrawObject], selected_objects: &[Uuid],
                     
                     pos[0] = x + center[0] + translation[0];
                     pos[1] = y + center[1] + translation[1];
+                    *length *= scale[0];
+                }
+                DrawObject::Instance { origin, scale: instance_scale, rotation: instance_rotation, members, synced, .. } => {
+                    let mut x = origin[0] - center[0];
+                    let mut y = origin[1] - center[1];
+                    
+                    if rotation != 0.0 {
+                        let cos_r = rotation.cos();
+                        let sin_r = rotation.sin();
+                        let new_x = x * cos_r - y * sin_r;
+                        let new_y = x * sin_r + y * cos_r;
+                        x = new_x;
+                        y = new_y;
+                    }
+                    
+                    x *= scale[0];
+                    y *= scale[1];
+                    
+                    origin[0] = x + center[0] + translation[0];
+                    origin[1] = y + center[1] + translation[1];
+                    instance_scale[0] *= scale[0];
+                    instance_scale[1] *= scale[1];
+                    *instance_rotation += rotation;
+                    let member_ids: Vec<Uuid> = members.iter().map(|member| member.id()).collect();
+                    transform_objects(members, &member_ids, scale, rotation, translation, center);
+                    synced.clear();
+                }
+            }
+        }
+    }
+}
+
+pub fn arrange_in_grid(objects: &mut [DrawObject], selected_objects: &[Uuid], columns: usize, spacing: f32) {
+    let Some((origin, _)) = get_selection_bounds(objects, selected_objects) else {
+        return;
+    };
+    let mut items: Vec<_> = selected_objects
+        .iter()
+        .filter_map(|id| objects.iter().find(|o| o.id() == *id).map(|o| (*id, o.bounds())))
+        .collect();
+    items.sort_by(|a, b| a.1 .0[1].total_cmp(&b.1 .0[1]).then(a.1 .0[0].total_cmp(&b.1 .0[0])));
+
+    let cell_width = items.iter().map(|(_, (min, max))| max[0] - min[0]).fold(0.0, f32::max);
+    let cell_height = items.iter().map(|(_, (min, max))| max[1] - min[1]).fold(0.0, f32::max);
+    let columns = columns.max(1);
+
+    for (index, (id, (min, _))) in items.into_iter().enumerate() {
+        let target = [
+            origin[0] + (index % columns) as f32 * (cell_width + spacing),
+            origin[1] + (index / columns) as f32 * (cell_height + spacing),
+        ];
+        let translation = [target[0] - min[0], target[1] - min[1]];
+        transform_objects(objects, &[id], [1.0, 1.0], 0.0, translation, [0.0, 0.0]);
+    }
+}
+
+pub fn tidy_handwriting(objects: &mut [DrawObject], selected_objects: &[Uuid]) {
+    const BASELINE_TOLERANCE: f32 = 0.35;
+    const SPACING_BLEND: f32 = 0.5;
+    const WORD_GAP: f32 = 2.5;
+
+    let mut strokes: Vec<_> = objects
+        .iter()
+        .filter(|obj| matches!(obj, DrawObject::Stroke { .. }) && selected_objects.contains(&obj.id()))
+        .map(|obj| (obj.id(), obj.bounds()))
+        .collect();
+    if strokes.len() < 2 {
+        return;
+    }
+    strokes.sort_by(|a, b| (a.1 .0[1] + a.1 .1[1]).total_cmp(&(b.1 .0[1] + b.1 .1[1])));
+
+    let mut lines: Vec<Vec<_>> = Vec::new();
+    let mut line_range = [f32::MAX, f32::MIN];
+    for stroke in strokes {
+        let center = (stroke.1 .0[1] + stroke.1 .1[1]) / 2.0;
+        match lines.last_mut() {
+            Some(line) if center >= line_range[0] && center <= line_range[1] => {
+                line_range[1] = line_range[1].max(stroke.1 .1[1]);
+                line.push(stroke);
+            }
+            _ => {
+                line_range = [stroke.1 .0[1], stroke.1 .1[1]];
+                lines.push(vec![stroke]);
+            }
+        }
+    }
+
+    for mut line in lines {
+        let mut clusters: Vec<(Vec<Uuid>, [f32; 2], [f32; 2])> = Vec::new();
+        line.sort_by(|a, b| a.1 .0[0].total_cmp(&b.1 .0[0]));
+        for (id, (min, max)) in line {
+            match clusters.last_mut() {
+                Some((ids, cluster_min, cluster_max)) if min[0] <= cluster_max[0] => {
+                    ids.push(id);
+                    *cluster_min = [cluster_min[0].min(min[0]), cluster_min[1].min(min[1])];
+                    *cluster_max = [cluster_max[0].max(max[0]), cluster_max[1].max(max[1])];
+                }
+                _ => clusters.push((vec![id], min, max)),
+            }
+        }
+        if clusters.len() < 2 {
+            continue;
+        }
+
+        let median = |mut values: Vec<f32>| {
+            values.sort_by(f32::total_cmp);
+            values[values.len() / 2]
+        };
+        let baseline = median(clusters.iter().map(|(_, _, max)| max[1]).collect());
+        let height = median(clusters.iter().map(|(_, min, max)| max[1] - min[1]).collect());
+        let gaps: Vec<f32> = clusters.windows(2).map(|pair| pair[1].1[0] - pair[0].2[0]).collect();
+        let typical_gap = median(gaps.iter().map(|gap| gap.max(0.0)).collect());
+
+        let mut shift_x = 0.0;
+        for (index, (ids, _, max)) in clusters.iter().enumerate() {
+            if index > 0 {
+                let gap = gaps[index - 1];
+                if gap >= 0.0 && gap <= typical_gap * WORD_GAP {
+                    shift_x += (typical_gap - gap) * SPACING_BLEND;
                 }
             }
+            let offset_y = baseline - max[1];
+            let shift_y = if offset_y.abs() <= height * BASELINE_TOLERANCE { offset_y } else { 0.0 };
+            transform_objects(objects, ids, [1.0, 1.0], 0.0, [shift_x, shift_y], [0.0, 0.0]);
+        }
+    }
+}
+
+fn masters(objects: &[DrawObject]) -> HashMap<Uuid, Vec<&DrawObject>> {
+    let mut masters: HashMap<Uuid, Vec<&DrawObject>> = HashMap::new();
+    for obj in objects {
+        if let (Some(group), false) = (obj.props().group, matches!(obj, DrawObject::Instance { .. })) {
+            masters.entry(group).or_default().push(obj);
         }
     }
+    masters
+}
+
+pub fn member_id(instance: Uuid, master: Uuid) -> Uuid {
+    Uuid::from_u128(instance.as_u128() ^ master.as_u128().rotate_left(1))
+}
+
+pub fn sync_instances(objects: &mut [DrawObject]) {
+    if !objects.iter().any(|obj| matches!(obj, DrawObject::Instance { .. })) {
+        return;
+    }
+
+    let masters = masters(objects);
+    let stale: Vec<(usize, Vec<DrawObject>)> = objects
+        .iter()
+        .enumerate()
+        .filter_map(|(index, obj)| {
+            let DrawObject::Instance { source, synced, .. } = obj else {
+                return None;
+            };
+            let master = masters.get(source)?;
+            let unchanged = synced.len() == master.len() && synced.iter().zip(master).all(|(a, b)| a == *b);
+            (!unchanged).then(|| (index, master.iter().map(|obj| (*obj).clone()).collect()))
+        })
+        .collect();
+
+    for (index, master) in stale {
+        let DrawObject::Instance { id, origin, scale, rotation, members, synced, .. } = &mut objects[index] else {
+            continue;
+        };
+        let ids: Vec<Uuid> = master.iter().map(|member| member.id()).collect();
+        let Some((master_min, _)) = get_selection_bounds(&master, &ids) else {
+            continue;
+        };
+        let translation = [origin[0] - master_min[0], origin[1] - master_min[1]];
+        let mut copies = master.clone();
+        transform_objects(&mut copies, &ids, *scale, *rotation, translation, master_min);
+        for copy in &mut copies {
+            let derived = member_id(*id, copy.id());
+            copy.set_id(derived);
+            copy.props_mut().group = None;
+        }
+        *members = copies;
+        *synced = master;
+    }
+}
+
+pub fn orphaned_instances(objects: &[DrawObject]) -> Vec<Uuid> {
+    let masters = masters(objects);
+    objects
+        .iter()
+        .filter_map(|obj| match obj {
+            DrawObject::Instance { id, source, .. } if !masters.contains_key(source) => Some(*id),
+            _ => None,
+        })
+        .collect()
+}
+
+pub fn page_of(objects: &[DrawObject], obj: &DrawObject) -> Option<Uuid> {
+    if let DrawObject::Frame { id, .. } = obj {
+        return Some(*id);
+    }
+    let (min, max) = obj.bounds();
+    page_at(objects, [(min[0] + max[0]) / 2.0, (min[1] + max[1]) / 2.0])
+}
+
+pub struct PageOverride {
+    pub min: [f32; 2],
+    pub max: [f32; 2],
+    pub background: Option<[u8; 4]>,
+    pub grid: Option<GridStyle>,
+}
+
+impl PageOverride {
+    pub fn contains(&self, point: [f32; 2]) -> bool {
+        point[0] >= self.min[0] && point[0] <= self.max[0] && point[1] >= self.min[1] && point[1] <= self.max[1]
+    }
+}
+
+pub fn page_overrides(objects: &[DrawObject]) -> Vec<PageOverride> {
+    objects
+        .iter()
+        .filter_map(|obj| match obj {
+            DrawObject::Frame { min, max, background, grid, props, .. } if !props.hidden && (background.is_some() || grid.is_some()) => {
+                Some(PageOverride { min: *min, max: *max, background: *background, grid: *grid })
+            }
+            _ => None,
+        })
+        .collect()
+}
+
+pub type PageBackground = ([f32; 2], [f32; 2], [u8; 4]);
+
+pub fn page_backgrounds(objects: &[DrawObject]) -> Vec<PageBackground> {
+    objects
+        .iter()
+        .filter_map(|frame| match frame {
+            DrawObject::Frame { min, max, background: Some(background), props, .. } if !props.hidden => Some((*min, *max, *background)),
+            _ => None,
+        })
+        .collect()
+}
+
+pub fn background_in(backgrounds: &[PageBackground], point: [f32; 2]) -> Option<[u8; 4]> {
+    backgrounds.iter().rev().find_map(|(min, max, background)| {
+        (point[0] >= min[0] && point[0] <= max[0] && point[1] >= min[1] && point[1] <= max[1]).then_some(*background)
+    })
+}
+
+pub fn page_at(objects: &[DrawObject], point: [f32; 2]) -> Option<Uuid> {
+    objects.iter().find_map(|frame| match frame {
+        DrawObject::Frame { id, min, max, .. }
+            if point[0] >= min[0] && point[0] <= max[0] && point[1] >= min[1] && point[1] <= max[1] =>
+        {
+            Some(*id)
+        }
+        _ => None,
+    })
 }
@@ -0,0 +1,38 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+pub const SESSION_PATH: &str = "session.json";
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Viewport {
+    pub offset: [f32; 2],
+    pub zoom: f32,
+    pub rotation: f32,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct WindowLayout {
+    pub position: Option<[f32; 2]>,
+    pub size: [f32; 2],
+    pub maximized: bool,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PanelLayout {
+    pub toolbar: bool,
+    pub snapshots: bool,
+    pub guides: bool,
+    pub reveal_steps: bool,
+    pub layers: bool,
+    pub rulers: bool,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Session {
+    pub document: Option<String>,
+    pub viewports: HashMap<String, Viewport>,
+    pub window: Option<WindowLayout>,
+    pub panels: Option<PanelLayout>,
+}
@@ -0,0 +1,235 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+use crate::export::ExportFraming;
+use crate::i18n::Language;
+use crate::models::Tool;
+use crate::recipes::ExportRecipe;
+
+pub const SETTINGS_PATH: &str = "settings.json";
+
+pub const SIZE_PRESET_NAMES: [&str; 4] = ["Fine", "Medium", "Bold", "Marker"];
+pub const DEFAULT_SIZE_PRESETS: [f32; 4] = [1.0, 2.5, 5.0, 12.0];
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum ToolbarDock {
+    Top,
+    Bottom,
+    Left,
+    Right,
+    Floating,
+}
+
+impl ToolbarDock {
+    pub const ALL: [ToolbarDock; 5] = [
+        ToolbarDock::Top,
+        ToolbarDock::Bottom,
+        ToolbarDock::Left,
+        ToolbarDock::Right,
+        ToolbarDock::Floating,
+    ];
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            ToolbarDock::Top => "Top",
+            ToolbarDock::Bottom => "Bottom",
+            ToolbarDock::Left => "Left",
+            ToolbarDock::Right => "Right",
+            ToolbarDock::Floating => "Floating",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum PageOutline {
+    Off,
+    A4Portrait,
+    A4Landscape,
+    LetterPortrait,
+    LetterLandscape,
+}
+
+impl PageOutline {
+    pub const ALL: [PageOutline; 5] = [
+        PageOutline::Off,
+        PageOutline::A4Portrait,
+        PageOutline::A4Landscape,
+        PageOutline::LetterPortrait,
+        PageOutline::LetterLandscape,
+    ];
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            PageOutline::Off => "Off",
+            PageOutline::A4Portrait => "A4 portrait",
+            PageOutline::A4Landscape => "A4 landscape",
+            PageOutline::LetterPortrait => "Letter portrait",
+            PageOutline::LetterLandscape => "Letter landscape",
+        }
+    }
+
+    pub fn canvas_size(&self) -> Option<[f32; 2]> {
+        let points = match self {
+            PageOutline::Off => return None,
+            PageOutline::A4Portrait => [595.0, 842.0],
+            PageOutline::A4Landscape => [842.0, 595.0],
+            PageOutline::LetterPortrait => [612.0, 792.0],
+            PageOutline::LetterLandscape => [792.0, 612.0],
+        };
+        Some([points[0] / 0.75, points[1] / 0.75])
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum LatexQuality {
+    Draft,
+    Normal,
+    High,
+}
+
+impl LatexQuality {
+    pub const ALL: [LatexQuality; 3] = [
+        LatexQuality::Draft,
+        LatexQuality::Normal,
+        LatexQuality::High,
+    ];
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            LatexQuality::Draft => "Draft",
+            LatexQuality::Normal => "Normal",
+            LatexQuality::High => "High",
+        }
+    }
+
+    pub fn scale_factor(&self) -> f32 {
+        match self {
+            LatexQuality::Draft => 1.5,
+            LatexQuality::Normal => 3.0,
+            LatexQuality::High => 6.0,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum PressureMapping {
+    Width,
+    Opacity,
+    Both,
+}
+
+impl PressureMapping {
+    pub const ALL: [PressureMapping; 3] = [
+        PressureMapping::Width,
+        PressureMapping::Opacity,
+        PressureMapping::Both,
+    ];
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            PressureMapping::Width => "Width",
+            PressureMapping::Opacity => "Opacity",
+            PressureMapping::Both => "Width and opacity",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum EraserMode {
+    Objects,
+    Partial,
+}
+
+impl EraserMode {
+    pub const ALL: [EraserMode; 2] = [EraserMode::Objects, EraserMode::Partial];
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            EraserMode::Objects => "Whole objects",
+            EraserMode::Partial => "Partial strokes",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Settings {
+    pub toolbar_dock: ToolbarDock,
+    pub high_contrast_ui: bool,
+    pub warn_low_contrast: bool,
+    pub page_outline: PageOutline,
+    pub remap_ink_on_background: bool,
+    pub size_presets: HashMap<Tool, [f32; 4]>,
+    pub stroke_prediction_ms: f32,
+    #[serde(alias = "shading_pressure")]
+    pub highlighter_pressure: PressureMapping,
+    pub latex_quality: LatexQuality,
+    pub font_files: Vec<String>,
+    pub ocr_command: String,
+    pub ocr_language: String,
+    pub restore_session: bool,
+    pub eraser_hold_key: String,
+    pub limit_panning: bool,
+    pub overview_when_zoomed_out: bool,
+    pub backup_dir: String,
+    pub backup_every_saves: u32,
+    pub backup_daily: bool,
+    pub export_recipes: Vec<ExportRecipe>,
+    pub export_framing: ExportFraming,
+    pub highlighter_width: f32,
+    pub highlighter_color: [u8; 4],
+    pub stay_resident: bool,
+    pub quick_capture_hotkey: String,
+    pub scratchpad_on_summon: bool,
+    pub click_through_hotkey: String,
+    pub overlay_tint: f32,
+    pub eraser_mode: EraserMode,
+    pub eraser_radius: f32,
+    pub memory_budget_mb: u32,
+    pub language: Language,
+}
+
+impl Settings {
+    pub fn size_presets_for(&self, tool: Tool) -> [f32; 4] {
+        self.size_presets.get(&tool).copied().unwrap_or(DEFAULT_SIZE_PRESETS)
+    }
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            toolbar_dock: ToolbarDock::Top,
+            high_contrast_ui: false,
+            warn_low_contrast: true,
+            page_outline: PageOutline::Off,
+            remap_ink_on_background: false,
+            size_presets: HashMap::new(),
+            stroke_prediction_ms: 16.0,
+            highlighter_pressure: PressureMapping::Width,
+            latex_quality: LatexQuality::Normal,
+            font_files: Vec::new(),
+            ocr_command: "tesseract".to_string(),
+            ocr_language: "eng".to_string(),
+            restore_session: true,
+            eraser_hold_key: "E".to_string(),
+            limit_panning: false,
+            overview_when_zoomed_out: false,
+            backup_dir: "backups".to_string(),
+            backup_every_saves: 10,
+            backup_daily: true,
+            export_recipes: Vec::new(),
+            export_framing: ExportFraming::default(),
+            highlighter_width: 18.0,
+            highlighter_color: [255, 221, 0, 96],
+            stay_resident: false,
+            quick_capture_hotkey: crate::hotkey::DEFAULT_HOTKEY.to_string(),
+            scratchpad_on_summon: true,
+            click_through_hotkey: crate::hotkey::DEFAULT_CLICK_THROUGH_HOTKEY.to_string(),
+            overlay_tint: 0.05,
+            eraser_mode: EraserMode::Objects,
+            eraser_radius: 10.0,
+            memory_budget_mb: crate::memory::DEFAULT_BUDGET_MB,
+            language: Language::English,
+        }
+    }
+}
@@ -0,0 +1,129 @@
+use std::collections::HashMap;
+
+use crate::models::DrawObject;
+use uuid::Uuid;
+
+const CELL_SIZE: f32 = 256.0;
+const MAX_CELLS_PER_OBJECT: i64 = 256;
+
+type Bounds = ([f32; 2], [f32; 2]);
+
+#[derive(Default)]
+pub struct SpatialIndex {
+    ids: Vec<Uuid>,
+    bounds: Vec<Bounds>,
+    cells: HashMap<(i32, i32), Vec<usize>>,
+    unbounded: Vec<usize>,
+}
+
+fn cell_range((min, max): Bounds) -> ((i32, i32), (i32, i32)) {
+    let cell = |v: f32| (v / CELL_SIZE).floor().clamp(i32::MIN as f32, i32::MAX as f32) as i32;
+    ((cell(min[0]), cell(min[1])), (cell(max[0]), cell(max[1])))
+}
+
+fn overlaps((a_min, a_max): Bounds, (b_min, b_max): Bounds) -> bool {
+    a_max[0] >= b_min[0] && a_min[0] <= b_max[0] && a_max[1] >= b_min[1] && a_min[1] <= b_max[1]
+}
+
+fn indexed_bounds(obj: &DrawObject) -> Bounds {
+    let bounds = obj.bounds();
+    let pinned = matches!(obj, DrawObject::LatexFormula { pin_size: true, .. });
+    if pinned || !bounds.0.iter().chain(&bounds.1).all(|v| v.is_finite()) {
+        return ([f32::NEG_INFINITY; 2], [f32::INFINITY; 2]);
+    }
+    bounds
+}
+
+fn cells_for(bounds: Bounds) -> Option<((i32, i32), (i32, i32))> {
+    if !bounds.0.iter().chain(&bounds.1).all(|v| v.is_finite()) {
+        return None;
+    }
+    let ((x0, y0), (x1, y1)) = cell_range(bounds);
+    ((x1 as i64 - x0 as i64 + 1) * (y1 as i64 - y0 as i64 + 1) <= MAX_CELLS_PER_OBJECT).then_some(((x0, y0), (x1, y1)))
+}
+
+impl SpatialIndex {
+    fn rebuild(&mut self, objects: &[DrawObject]) {
+        self.ids.clear();
+        self.bounds.clear();
+        self.cells.clear();
+        self.unbounded.clear();
+        for (index, obj) in objects.iter().enumerate() {
+            self.ids.push(obj.id());
+            self.bounds.push(indexed_bounds(obj));
+            self.insert(index);
+        }
+    }
+
+    pub fn update(&mut self, objects: &[DrawObject]) {
+        if self.ids.len() != objects.len() || !self.ids.iter().zip(objects).all(|(id, obj)| *id == obj.id()) {
+            self.rebuild(objects);
+            return;
+        }
+        for (index, obj) in objects.iter().enumerate() {
+            let bounds = indexed_bounds(obj);
+            if bounds == self.bounds[index] {
+                continue;
+            }
+            self.remove(index);
+            self.bounds[index] = bounds;
+            self.insert(index);
+        }
+    }
+
+    fn insert(&mut self, index: usize) {
+        let Some(((x0, y0), (x1, y1))) = cells_for(self.bounds[index]) else {
+            self.unbounded.push(index);
+            return;
+        };
+        for x in x0..=x1 {
+            for y in y0..=y1 {
+                self.cells.entry((x, y)).or_default().push(index);
+            }
+        }
+    }
+
+    fn remove(&mut self, index: usize) {
+        let Some(((x0, y0), (x1, y1))) = cells_for(self.bounds[index]) else {
+            self.unbounded.retain(|&i| i != index);
+            return;
+        };
+        for x in x0..=x1 {
+            for y in y0..=y1 {
+                if let Some(indices) = self.cells.get_mut(&(x, y)) {
+                    indices.retain(|&i| i != index);
+                    if indices.is_empty() {
+                        self.cells.remove(&(x, y));
+                    }
+                }
+            }
+        }
+    }
+
+    pub fn query(&self, min: [f32; 2], max: [f32; 2], object_count: usize) -> Vec<usize> {
+        let area = (min, max);
+        let ((x0, y0), (x1, y1)) = cell_range(area);
+        let mut found: Vec<usize> = self.unbounded.iter().copied().filter(|&i| overlaps(self.bounds[i], area)).collect();
+        let cell_count = (x1 as i64 - x0 as i64 + 1) * (y1 as i64 - y0 as i64 + 1);
+        if cell_count > self.cells.len() as i64 {
+            for ((x, y), indices) in &self.cells {
+                if (x0..=x1).contains(x) && (y0..=y1).contains(y) {
+                    found.extend(indices.iter().copied().filter(|&i| overlaps(self.bounds[i], area)));
+                }
+            }
+        } else {
+            for x in x0..=x1 {
+                for y in y0..=y1 {
+                    if let Some(indices) = self.cells.get(&(x, y)) {
+                        found.extend(indices.iter().copied().filter(|&i| overlaps(self.bounds[i], area)));
+                    }
+                }
+            }
+        }
+        found.extend(self.bounds.len()..object_count);
+        found.retain(|&i| i < object_count);
+        found.sort_unstable();
+        found.dedup();
+        found
+    }
+}
@@ -0,0 +1,190 @@
+use std::fmt::Write;
+
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+
+use crate::latex::{LatexRenderer, DISPLAY_SCALE};
+use crate::models::{self, DrawObject};
+use crate::selection::{self, PageBackground};
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;").replace('"', "&quot;")
+}
+
+fn paint(color: [u8; 4], opacity: f32) -> (String, f32) {
+    (
+        format!("#{:02x}{:02x}{:02x}", color[0], color[1], color[2]),
+        color[3] as f32 / 255.0 * opacity.clamp(0.0, 1.0),
+    )
+}
+
+fn stroke_attrs(color: [u8; 4], opacity: f32, width: f32) -> String {
+    let (color, alpha) = paint(color, opacity);
+    format!(
+        "fill=\"none\" stroke=\"{}\" stroke-opacity=\"{:.3}\" stroke-width=\"{:.2}\" stroke-linecap=\"round\" stroke-linejoin=\"round\"",
+        color, alpha, width
+    )
+}
+
+fn fill_attrs(color: [u8; 4], opacity: f32) -> String {
+    let (color, alpha) = paint(color, opacity);
+    format!("fill=\"{}\" fill-opacity=\"{:.3}\"", color, alpha)
+}
+
+fn png_data_uri(image: &image::RgbaImage) -> Option<String> {
+    let mut png = Vec::new();
+    image.write_to(&mut std::io::Cursor::new(&mut png), image::ImageFormat::Png).ok()?;
+    Some(format!("data:image/png;base64,{}", STANDARD.encode(png)))
+}
+
+fn image_element(out: &mut String, uri: &str, min: [f32; 2], size: [f32; 2], opacity: f32) {
+    let _ = writeln!(
+        out,
+        "<image x=\"{:.2}\" y=\"{:.2}\" width=\"{:.2}\" height=\"{:.2}\" opacity=\"{:.3}\" preserveAspectRatio=\"none\" href=\"{}\"/>",
+        min[0], min[1], size[0], size[1], opacity, uri
+    );
+}
+
+fn write_object(out: &mut String, obj: &DrawObject, backgrounds: &[PageBackground], latex_renderer: &mut LatexRenderer) {
+    let opacity = obj.props().opacity;
+    match obj {
+        DrawObject::Stroke { points, color, width, .. } => {
+            if points.len() < 2 {
+                return;
+            }
+            if points.iter().any(|p| p.width.is_some() || p.opacity.is_some()) {
+                for pair in points.windows(2) {
+                    let (segment_width, segment_opacity) = crate::canvas::segment_ink(&pair[0], &pair[1], *width);
+                    let _ = writeln!(
+                        out,
+                        "<line x1=\"{:.2}\" y1=\"{:.2}\" x2=\"{:.2}\" y2=\"{:.2}\" {}/>",
+                        pair[0].pos[0], pair[0].pos[1], pair[1].pos[0], pair[1].pos[1],
+                        stroke_attrs(*color, opacity * segment_opacity, segment_width)
+                    );
+                }
+                return;
+            }
+            let path: Vec<String> = points
+                .iter()
+                .enumerate()
+                .map(|(i, p)| format!("{}{:.2} {:.2}", if i == 0 { "M" } else { "L" }, p.pos[0], p.pos[1]))
+                .collect();
+            let _ = writeln!(out, "<path d=\"{}\" {}/>", path.join(" "), stroke_attrs(*color, opacity, *width));
+        }
+        DrawObject::Line { start, end, color, width, .. } => {
+            let _ = writeln!(
+                out,
+                "<line x1=\"{:.2}\" y1=\"{:.2}\" x2=\"{:.2}\" y2=\"{:.2}\" {}/>",
+                start[0], start[1], end[0], end[1], stroke_attrs(*color, opacity, *width)
+            );
+        }
+        DrawObject::Circle { center, radius, color, width, filled, .. } => {
+            let attrs = if *filled { fill_attrs(*color, opacity) } else { stroke_attrs(*color, opacity, *width) };
+            let _ = writeln!(out, "<circle cx=\"{:.2}\" cy=\"{:.2}\" r=\"{:.2}\" {}/>", center[0], center[1], radius, attrs);
+        }
+        DrawObject::Rectangle { min, max, color, width, filled, .. } => {
+            let attrs = if *filled { fill_attrs(*color, opacity) } else { stroke_attrs(*color, opacity, *width) };
+            let _ = writeln!(
+                out,
+                "<rect x=\"{:.2}\" y=\"{:.2}\" width=\"{:.2}\" height=\"{:.2}\" {}/>",
+                min[0], min[1], max[0] - min[0], max[1] - min[1], attrs
+            );
+        }
+        DrawObject::LatexFormula { pos, formula, color, size, scale, auto_color, font, .. } => {
+            if formula.is_empty() {
+                return;
+            }
+            let color = if *auto_color {
+                let natural = size.or_else(|| latex_renderer.measure(formula, font.as_deref())).unwrap_or_default();
+                let center = [pos[0] + natural[0] * scale[0] / 2.0, pos[1] + natural[1] * scale[1] / 2.0];
+                let background = selection::background_in(backgrounds, center).unwrap_or([255, 255, 255, 255]);
+                crate::palette::auto_ink(eframe::egui::Color32::from_rgba_unmultiplied(background[0], background[1], background[2], background[3]))
+            } else {
+                *color
+            };
+            let prefix = format!("f{}-", obj.id().simple());
+            let Ok((markup, own)) = latex_renderer.vector_svg(formula, font.as_deref(), color, &prefix) else {
+                return;
+            };
+            let target = size.unwrap_or([own[0] * DISPLAY_SCALE, own[1] * DISPLAY_SCALE]);
+            let _ = writeln!(
+                out,
+                "<g transform=\"translate({:.2} {:.2}) scale({:.4} {:.4})\" opacity=\"{:.3}\">",
+                pos[0],
+                pos[1],
+                target[0] * scale[0] / own[0].max(f32::EPSILON),
+                target[1] * scale[1] / own[1].max(f32::EPSILON),
+                opacity
+            );
+            let _ = writeln!(out, "{}", markup.trim_end());
+            out.push_str("</g>\n");
+        }
+        DrawObject::Image { min, max, data, adjustments, .. } => {
+            let uri = if adjustments.is_identity() {
+                let mime = match image::guess_format(data) {
+                    Ok(image::ImageFormat::Jpeg) => "image/jpeg",
+                    Ok(image::ImageFormat::Gif) => "image/gif",
+                    Ok(image::ImageFormat::WebP) => "image/webp",
+                    _ => "image/png",
+                };
+                Some(format!("data:{};base64,{}", mime, STANDARD.encode(data)))
+            } else {
+                crate::images::decode(data).ok().and_then(|mut rgba| {
+                    crate::images::adjust(&mut rgba, adjustments);
+                    png_data_uri(&rgba)
+                })
+            };
+            if let Some(uri) = uri {
+                image_element(out, &uri, *min, [max[0] - min[0], max[1] - min[1]], opacity);
+            }
+        }
+        DrawObject::ScaleBar { pos, length, label, color, .. } => {
+            for (start, end) in models::scale_bar_segments(*pos, *length) {
+                let _ = writeln!(
+                    out,
+                    "<line x1=\"{:.2}\" y1=\"{:.2}\" x2=\"{:.2}\" y2=\"{:.2}\" {}/>",
+                    start[0], start[1], end[0], end[1], stroke_attrs(*color, opacity, 2.0)
+                );
+            }
+            let _ = writeln!(
+                out,
+                "<text x=\"{:.2}\" y=\"{:.2}\" text-anchor=\"middle\" dominant-baseline=\"hanging\" font-family=\"sans-serif\" font-size=\"14\" {}>{}</text>",
+                pos[0] + length / 2.0, pos[1] + 4.0, fill_attrs(*color, opacity), escape(label)
+            );
+        }
+        DrawObject::Frame { min, max, background: Some(color), .. } => {
+            let _ = writeln!(
+                out,
+                "<rect x=\"{:.2}\" y=\"{:.2}\" width=\"{:.2}\" height=\"{:.2}\" {}/>",
+                min[0], min[1], max[0] - min[0], max[1] - min[1], fill_attrs(*color, 1.0)
+            );
+        }
+        DrawObject::Frame { .. } => {}
+        DrawObject::Instance { members, .. } => {
+            for member in members.iter().filter(|member| !member.props().hidden) {
+                write_object(out, member, backgrounds, latex_renderer);
+            }
+        }
+    }
+}
+
+pub fn render_svg(objects: &[DrawObject], bounds: ([f32; 2], [f32; 2]), latex_renderer: &mut LatexRenderer) -> String {
+    let (min, max) = bounds;
+    let (width, height) = (max[0] - min[0], max[1] - min[1]);
+    let mut out = format!(
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{w:.2}\" height=\"{h:.2}\" viewBox=\"{x:.2} {y:.2} {w:.2} {h:.2}\">\n",
+        x = min[0],
+        y = min[1],
+        w = width,
+        h = height,
+    );
+    let visible = objects.iter().filter(|obj| !obj.props().hidden);
+    let (frames, mut others): (Vec<&DrawObject>, Vec<&DrawObject>) = visible.partition(|obj| matches!(obj, DrawObject::Frame { .. }));
+    others.sort_by_key(|obj| !obj.props().highlight);
+    let backgrounds = selection::page_backgrounds(objects);
+    for obj in frames.into_iter().chain(others) {
+        write_object(&mut out, obj, &backgrounds, latex_renderer);
+    }
+    out.push_str("</svg>\n");
+    out
+}
@@ -0,0 +1,142 @@
+pub struct Symbol {
+    pub glyph: &'static str,
+    pub name: &'static str,
+    pub latex: Option<&'static str>,
+}
+
+pub struct SymbolCategory {
+    pub name: &'static str,
+    pub symbols: &'static [Symbol],
+}
+
+const fn sym(glyph: &'static str, name: &'static str, latex: Option<&'static str>) -> Symbol {
+    Symbol { glyph, name, latex }
+}
+
+pub const GREEK: SymbolCategory = SymbolCategory {
+    name: "Greek",
+    symbols: &[
+        sym("α", "alpha", Some("\\alpha")),
+        sym("β", "beta", Some("\\beta")),
+        sym("γ", "gamma", Some("\\gamma")),
+        sym("δ", "delta", Some("\\delta")),
+        sym("ε", "epsilon", Some("\\varepsilon")),
+        sym("ζ", "zeta", Some("\\zeta")),
+        sym("η", "eta", Some("\\eta")),
+        sym("θ", "theta", Some("\\theta")),
+        sym("ι", "iota", Some("\\iota")),
+        sym("κ", "kappa", Some("\\kappa")),
+        sym("λ", "lambda", Some("\\lambda")),
+        sym("μ", "mu", Some("\\mu")),
+        sym("ν", "nu", Some("\\nu")),
+        sym("ξ", "xi", Some("\\xi")),
+        sym("π", "pi", Some("\\pi")),
+        sym("ρ", "rho", Some("\\rho")),
+        sym("σ", "sigma", Some("\\sigma")),
+        sym("τ", "tau", Some("\\tau")),
+        sym("φ", "phi", Some("\\varphi")),
+        sym("χ", "chi", Some("\\chi")),
+        sym("ψ", "psi", Some("\\psi")),
+        sym("ω", "omega", Some("\\omega")),
+        sym("Γ", "capital gamma", Some("\\Gamma")),
+        sym("Δ", "capital delta", Some("\\Delta")),
+        sym("Θ", "capital theta", Some("\\Theta")),
+        sym("Λ", "capital lambda", Some("\\Lambda")),
+        sym("Π", "capital pi", Some("\\Pi")),
+        sym("Σ", "capital sigma", Some("\\Sigma")),
+        sym("Φ", "capital phi", Some("\\Phi")),
+        sym("Ψ", "capital psi", Some("\\Psi")),
+        sym("Ω", "capital omega", Some("\\Omega")),
+    ],
+};
+
+pub const ARROWS: SymbolCategory = SymbolCategory {
+    name: "Arrows",
+    symbols: &[
+        sym("→", "right arrow", Some("\\to")),
+        sym("←", "left arrow", Some("\\leftarrow")),
+        sym("↑", "up arrow", Some("\\uparrow")),
+        sym("↓", "down arrow", Some("\\downarrow")),
+        sym("↔", "left right arrow", Some("\\leftrightarrow")),
+        sym("⇒", "implies", Some("\\Rightarrow")),
+        sym("⇐", "implied by", Some("\\Leftarrow")),
+        sym("⇔", "if and only if", Some("\\Leftrightarrow")),
+        sym("↦", "maps to", Some("\\mapsto")),
+        sym("⟶", "long right arrow", Some("\\longrightarrow")),
+    ],
+};
+
+pub const SETS: SymbolCategory = SymbolCategory {
+    name: "Sets and logic",
+    symbols: &[
+        sym("∈", "element of", Some("\\in")),
+        sym("∉", "not element of", Some("\\notin")),
+        sym("⊂", "subset", Some("\\subset")),
+        sym("⊆", "subset or equal", Some("\\subseteq")),
+        sym("⊃", "superset", Some("\\supset")),
+        sym("∪", "union", Some("\\cup")),
+        sym("∩", "intersection", Some("\\cap")),
+        sym("∅", "empty set", Some("\\emptyset")),
+        sym("∀", "for all", Some("\\forall")),
+        sym("∃", "exists", Some("\\exists")),
+        sym("¬", "not", Some("\\neg")),
+        sym("∧", "and", Some("\\land")),
+        sym("∨", "or", Some("\\lor")),
+        sym("ℕ", "naturals", Some("\\mathbb{N}")),
+        sym("ℤ", "integers", Some("\\mathbb{Z}")),
+        sym("ℚ", "rationals", Some("\\mathbb{Q}")),
+        sym("ℝ", "reals", Some("\\mathbb{R}")),
+        sym("ℂ", "complex numbers", Some("\\mathbb{C}")),
+    ],
+};
+
+pub const OPERATORS: SymbolCategory = SymbolCategory {
+    name: "Operators",
+    symbols: &[
+        sym("±", "plus minus", Some("\\pm")),
+        sym("×", "times", Some("\\times")),
+        sym("÷", "divide", Some("\\div")),
+        sym("·", "dot", Some("\\cdot")),
+        sym("≠", "not equal", Some("\\neq")),
+        sym("≈", "approximately", Some("\\approx")),
+        sym("≡", "equivalent", Some("\\equiv")),
+        sym("≤", "less or equal", Some("\\leq")),
+        sym("≥", "greater or equal", Some("\\geq")),
+        sym("∞", "infinity", Some("\\infty")),
+        sym("∂", "partial", Some("\\partial")),
+        sym("∇", "nabla", Some("\\nabla")),
+        sym("∑", "sum", Some("\\sum")),
+        sym("∏", "product", Some("\\prod")),
+        sym("∫", "integral", Some("\\int")),
+        sym("√", "square root", Some("\\surd")),
+    ],
+};
+
+pub const MARKS: SymbolCategory = SymbolCategory {
+    name: "Stars and marks",
+    symbols: &[
+        sym("★", "star filled", Some("\\bigstar")),
+        sym("☆", "star outline", None),
+        sym("✓", "check mark", Some("\\checkmark")),
+        sym("✗", "cross mark", None),
+        sym("•", "bullet", Some("\\bullet")),
+        sym("†", "dagger", Some("\\dagger")),
+        sym("‼", "double exclamation", None),
+        sym("⚠", "warning", None),
+        sym("☐", "empty box", Some("\\square")),
+        sym("☑", "checked box", None),
+        sym("♥", "heart", Some("\\heartsuit")),
+        sym("☺", "smiley", None),
+    ],
+};
+
+pub const CATEGORIES: [SymbolCategory; 5] = [GREEK, ARROWS, SETS, OPERATORS, MARKS];
+
+impl Symbol {
+    pub fn matches(&self, query: &str) -> bool {
+        query.is_empty()
+            || self.glyph == query
+            || self.name.contains(query)
+            || self.latex.is_some_and(|latex| latex.contains(query))
+    }
+}
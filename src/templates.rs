@@ -0,0 +1,158 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use uuid::Uuid;
+
+use crate::models::{DrawObject, ObjectProps};
+use crate::selection;
+
+pub const TEMPLATES_PATH: &str = "templates.json";
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Template {
+    pub name: String,
+    pub objects: Vec<DrawObject>,
+    #[serde(skip)]
+    pub builtin: bool,
+}
+
+impl Template {
+    pub fn from_objects(name: String, objects: &[DrawObject]) -> Self {
+        let group = Uuid::new_v4();
+        let mut objects: Vec<DrawObject> = objects.iter().map(DrawObject::with_new_id).collect();
+        for obj in &mut objects {
+            let props = obj.props_mut();
+            props.group = Some(group);
+            props.layer = None;
+            props.style = None;
+        }
+        let ids: Vec<Uuid> = objects.iter().map(|obj| obj.id()).collect();
+        if let Some((min, _)) = selection::get_selection_bounds(&objects, &ids) {
+            selection::transform_objects(&mut objects, &ids, [1.0, 1.0], 0.0, [-min[0], -min[1]], [0.0, 0.0]);
+        }
+        Self { name, objects, builtin: false }
+    }
+
+    pub fn placeholders(&self) -> Vec<String> {
+        let mut names = Vec::new();
+        for obj in &self.objects {
+            if let DrawObject::LatexFormula { formula, .. } = obj {
+                for name in placeholder_names(formula) {
+                    if !names.contains(&name) {
+                        names.push(name);
+                    }
+                }
+            }
+        }
+        names
+    }
+
+    pub fn fill(&self, values: &HashMap<String, String>) -> Vec<DrawObject> {
+        let mut objects = self.objects.clone();
+        for obj in &mut objects {
+            if let DrawObject::LatexFormula { formula, size, .. } = obj {
+                let filled = fill_placeholders(formula, values);
+                if filled != *formula {
+                    *formula = filled;
+                    *size = None;
+                }
+            }
+        }
+        objects
+    }
+}
+
+fn placeholder_spans(text: &str) -> Vec<(usize, usize, &str)> {
+    let mut spans = Vec::new();
+    let mut start = 0;
+    while let Some(open) = text[start..].find("{{").map(|i| start + i) {
+        let Some(close) = text[open + 2..].find("}}").map(|i| open + 2 + i) else {
+            break;
+        };
+        let name = text[open + 2..close].trim();
+        if !name.is_empty() && !name.contains(['{', '}']) {
+            spans.push((open, close + 2, name));
+            start = close + 2;
+        } else {
+            start = open + 1;
+        }
+    }
+    spans
+}
+
+pub fn placeholder_names(text: &str) -> Vec<String> {
+    placeholder_spans(text).into_iter().map(|(_, _, name)| name.to_string()).collect()
+}
+
+pub fn fill_placeholders(text: &str, values: &HashMap<String, String>) -> String {
+    let mut filled = String::with_capacity(text.len());
+    let mut last = 0;
+    for (start, end, name) in placeholder_spans(text) {
+        filled.push_str(&text[last..start]);
+        match values.get(name).filter(|value| !value.trim().is_empty()) {
+            Some(value) => filled.push_str(value),
+            None => filled.push_str(&text[start..end]),
+        }
+        last = end;
+    }
+    filled.push_str(&text[last..]);
+    filled
+}
+
+fn text_slot(pos: [f32; 2], formula: &str, group: Uuid) -> DrawObject {
+    DrawObject::LatexFormula {
+        id: Uuid::new_v4(),
+        pos,
+        formula: formula.to_string(),
+        color: [20, 20, 20, 255],
+        size: None,
+        scale: [1.0, 1.0],
+        auto_color: true,
+        font: None,
+        pin_size: false,
+        props: ObjectProps { group: Some(group), ..ObjectProps::default() },
+    }
+}
+
+pub fn builtin_templates() -> Vec<Template> {
+    let group = Uuid::new_v4();
+    let card = vec![
+        DrawObject::Rectangle {
+            id: Uuid::new_v4(),
+            min: [0.0, 0.0],
+            max: [560.0, 380.0],
+            color: [128, 128, 128, 255],
+            width: 2.0,
+            filled: false,
+            props: ObjectProps { group: Some(group), ..ObjectProps::default() },
+        },
+        text_slot([20.0, 20.0], "\\textbf{Given:}\\ {{Given}}", group),
+        text_slot([20.0, 100.0], "\\textbf{Find:}\\ {{Find}}", group),
+        text_slot([20.0, 180.0], "\\textbf{Solution:}\\ {{Solution}}", group),
+    ];
+    vec![Template { name: "Problem card (Given / Find / Solution)".to_string(), objects: card, builtin: true }]
+}
+
+pub struct TemplateFill {
+    pub template: Template,
+    pub placeholders: Vec<String>,
+    pub values: Vec<String>,
+    pub step: usize,
+    pub center: [f32; 2],
+}
+
+impl TemplateFill {
+    pub fn new(template: Template, center: [f32; 2]) -> Self {
+        let placeholders = template.placeholders();
+        let values = vec![String::new(); placeholders.len()];
+        Self { template, placeholders, values, step: 0, center }
+    }
+
+    pub fn is_last_step(&self) -> bool {
+        self.step + 1 >= self.placeholders.len()
+    }
+
+    pub fn objects(&self) -> Vec<DrawObject> {
+        let values: HashMap<String, String> = self.placeholders.iter().cloned().zip(self.values.iter().cloned()).collect();
+        self.template.fill(&values)
+    }
+}